@@ -20,15 +20,14 @@
 // 2. Update the backend component to produce a config in a standard location.
 // 3. Update the KvRouter to read the config from the backend component.
 
-use std::sync::Arc;
+use std::{sync::Arc, time::Duration};
 
 use clap::Parser;
 
 use dynamo_llm::kv_router::{
-    protocols::WorkerSelectionResult,
-    scheduler::{DefaultWorkerSelector, KvSchedulerError, SchedulingRequest},
-    scoring::ProcessedEndpoints,
-    KvRouter, WorkerSelector,
+    selector::WorkerSelectorKind,
+    snapshot::{KvSnapshotConfig, SnapshotTarget},
+    KvRouter, KvRouterConfig,
 };
 use dynamo_runtime::{
     logging, pipeline::network::Ingress, DistributedRuntime, Result, Runtime, Worker,
@@ -48,6 +47,42 @@ struct Args {
     /// Block size for the router
     #[arg(long)]
     block_size: usize,
+
+    /// Worker selection policy.
+    /// One of softmax, power-of-two, least-outstanding-tokens, cache-affinity
+    #[arg(long, default_value = "softmax")]
+    kv_selector: WorkerSelectorKind,
+
+    /// Weight for overlap score in worker selection (softmax selector)
+    #[arg(long)]
+    kv_overlap_score_weight: Option<f64>,
+
+    /// Weight for GPU cache usage in worker selection (softmax selector)
+    #[arg(long)]
+    kv_gpu_cache_usage_weight: Option<f64>,
+
+    /// Weight for waiting requests in worker selection (softmax selector)
+    #[arg(long)]
+    kv_waiting_requests_weight: Option<f64>,
+
+    /// Softmax temperature over worker logits, 0 always picks the best worker (softmax selector)
+    #[arg(long)]
+    kv_router_temperature: Option<f64>,
+
+    /// KV cache usage fraction above which a worker is no longer preferred
+    /// for its cached prefix (cache-affinity selector)
+    #[arg(long)]
+    kv_max_gpu_cache_usage: Option<f64>,
+
+    /// Persist the radix tree so it survives router restarts.
+    /// Either a file path, which gets the component added to its name, or `etcd` to store it in
+    /// the runtime's etcd
+    #[arg(long)]
+    kv_snapshot: Option<SnapshotTarget>,
+
+    /// How often, in seconds, the radix tree is snapshotted
+    #[arg(long)]
+    kv_snapshot_interval: Option<u64>,
 }
 
 fn main() -> Result<()> {
@@ -64,9 +99,25 @@ async fn app(runtime: Runtime) -> Result<()> {
         .namespace(&args.namespace)?
         .component(&args.component)?;
 
-    let selector = Box::new(CustomWorkerSelector::default());
+    let config = KvRouterConfig::new(
+        args.kv_overlap_score_weight,
+        args.kv_gpu_cache_usage_weight,
+        args.kv_waiting_requests_weight,
+    )
+    .with_selector(
+        args.kv_selector,
+        args.kv_router_temperature,
+        args.kv_max_gpu_cache_usage,
+    )
+    .with_snapshot(args.kv_snapshot.map(|target| {
+        KvSnapshotConfig::new(target, args.kv_snapshot_interval.map(Duration::from_secs))
+    }));
+    tracing::info!("KV router using the {} worker selector", config.selector);
+    let selector = config.worker_selector();
 
-    let router = KvRouter::new(component.clone(), args.block_size, Some(selector)).await?;
+    let router =
+        KvRouter::new_with_config(component.clone(), args.block_size, Some(selector), &config)
+            .await?;
     let router = Ingress::for_engine(Arc::new(router))?;
 
     component
@@ -79,19 +130,3 @@ async fn app(runtime: Runtime) -> Result<()> {
         .start()
         .await
 }
-
-#[derive(Default)]
-pub struct CustomWorkerSelector(DefaultWorkerSelector);
-
-impl WorkerSelector for CustomWorkerSelector {
-    fn select_worker(
-        &self,
-        workers: &ProcessedEndpoints,
-        request: &SchedulingRequest,
-        block_size: usize,
-    ) -> Result<WorkerSelectionResult, KvSchedulerError> {
-        // customize logic here
-        // F12 into [DefaultWorkerSelector] to see the original logic
-        self.0.select_worker(workers, request, block_size)
-    }
-}
@@ -32,7 +32,7 @@ from vllm.entrypoints.openai.api_server import (
 )
 from vllm.inputs import TokensPrompt
 from vllm.remote_prefill import RemotePrefillParams, RemotePrefillRequest
-from vllm.sampling_params import RequestOutputKind
+from vllm.sampling_params import GuidedDecodingParams, RequestOutputKind
 
 from dynamo.llm import ModelType, WorkerMetricsPublisher, register_llm
 from dynamo.sdk import async_on_start, depends, dynamo_context, endpoint, service
@@ -40,6 +40,19 @@ from dynamo.sdk import async_on_start, depends, dynamo_context, endpoint, servic
 logger = logging.getLogger(__name__)
 
 
+def to_guided_decoding_params(guided_decoding) -> GuidedDecodingParams:
+    """Map the guided decoding options of a PreprocessedRequest to vLLM"""
+    if guided_decoding == "json_object":
+        return GuidedDecodingParams(json_object=True)
+    if "json_schema" in guided_decoding:
+        return GuidedDecodingParams(json=guided_decoding["json_schema"])
+    if "regex" in guided_decoding:
+        return GuidedDecodingParams(regex=guided_decoding["regex"])
+    if "grammar" in guided_decoding:
+        return GuidedDecodingParams(grammar=guided_decoding["grammar"])
+    raise ValueError(f"Unknown guided decoding options {guided_decoding}")
+
+
 @service(
     dynamo={
         "namespace": "dynamo",
@@ -245,6 +258,14 @@ class VllmWorker:
         sampling_params.max_tokens = request.stop_conditions.max_tokens
         if request.stop_conditions.ignore_eos:
             sampling_params.ignore_eos = request.stop_conditions.ignore_eos
+        num_top_logprobs = request.output_options.logprobs
+        if num_top_logprobs is not None:
+            # vLLM always includes the sampled token, so ask for at least one
+            sampling_params.logprobs = max(num_top_logprobs, 1)
+        if request.guided_decoding is not None:
+            sampling_params.guided_decoding = to_guided_decoding_params(
+                request.guided_decoding
+            )
 
         async for response in self.engine_client.generate(
             prompt=TokensPrompt(prompt_token_ids=request.token_ids),
@@ -262,6 +283,24 @@ class VllmWorker:
 
             output = response.outputs[0]
             out = {"token_ids": output.token_ids}
+            if num_top_logprobs is not None and output.logprobs:
+                out["log_probs"] = [
+                    logprobs[token_id].logprob
+                    for token_id, logprobs in zip(output.token_ids, output.logprobs)
+                ]
+                out["top_logprobs"] = [
+                    [
+                        {
+                            "token_id": token_id,
+                            "token": logprob.decoded_token,
+                            "logprob": logprob.logprob,
+                        }
+                        for token_id, logprob in sorted(
+                            logprobs.items(), key=lambda item: -item[1].logprob
+                        )[:num_top_logprobs]
+                    ]
+                    for logprobs in output.logprobs
+                ]
             if output.finish_reason:
                 out["finish_reason"] = output.finish_reason
             if output.stop_reason:
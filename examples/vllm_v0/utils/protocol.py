@@ -13,7 +13,7 @@
 # See the License for the specific language governing permissions and
 # limitations under the License.
 
-from typing import List, Optional
+from typing import Any, Dict, List, Optional, Union
 
 from pydantic import BaseModel, Field
 
@@ -45,10 +45,20 @@ class SamplingOptions(BaseModel):
     seed: Optional[int] = None
 
 
+class OutputOptions(BaseModel):
+    logprobs: Optional[int] = None
+    prompt_logprobs: Optional[int] = None
+    skip_special_tokens: Optional[bool] = None
+    formatted_prompt: Optional[bool] = None
+
+
 class PreprocessedRequest(BaseModel):
     token_ids: List[TokenIdType]
     stop_conditions: StopConditions
     sampling_options: SamplingOptions
+    output_options: OutputOptions = Field(default_factory=OutputOptions)
+    # "json_object", or one of {"json_schema": ...}, {"regex": ...}, {"grammar": ...}
+    guided_decoding: Optional[Union[str, Dict[str, Any]]] = None
     eos_token_ids: List[TokenIdType] = Field(default_factory=list)
     mdc_sum: Optional[str] = None
     annotations: List[str] = Field(default_factory=list)
@@ -15,9 +15,20 @@
 
 use std::collections::HashMap;
 use std::path::PathBuf;
+use std::time::Duration;
 
+use anyhow::Context as _;
 use clap::ValueEnum;
-use dynamo_llm::kv_router::KvRouterConfig;
+use dynamo_llm::http::service::{
+    auth::ApiKeysSource,
+    rate_limit::{RateLimitConfig, RateLimits},
+    tls::TlsConfig,
+};
+use dynamo_llm::kv_router::{
+    selector::WorkerSelectorKind,
+    snapshot::{KvSnapshotConfig, SnapshotTarget},
+    KvRouterConfig,
+};
 use dynamo_runtime::pipeline::RouterMode as RuntimeRouterMode;
 
 /// Required options depend on the in and out choices
@@ -121,6 +132,42 @@ pub struct Flags {
     #[arg(long)]
     pub kv_waiting_requests_weight: Option<f64>,
 
+    /// KV Router: Worker selection policy.
+    /// One of softmax, power-of-two, least-outstanding-tokens, cache-affinity. Default: softmax
+    #[arg(long, default_value = "softmax")]
+    pub kv_selector: WorkerSelectorKind,
+
+    /// KV Router: Softmax temperature over worker logits (softmax selector).
+    /// Lower values are greedier, 0 always picks the best worker. Default: 1.0
+    #[arg(long)]
+    pub kv_router_temperature: Option<f64>,
+
+    /// KV Router: KV cache usage fraction above which a worker is no longer preferred
+    /// for its cached prefix (cache-affinity selector). Default: 0.9
+    #[arg(long)]
+    pub kv_max_gpu_cache_usage: Option<f64>,
+
+    /// KV Router: Persist the router's radix tree so it survives restarts.
+    /// Either a file path, which gets the component and model added to its name for each
+    /// model, or `etcd` to store it in the runtime's etcd. Default: not persisted
+    #[arg(long)]
+    pub kv_snapshot: Option<SnapshotTarget>,
+
+    /// KV Router: How often, in seconds, the radix tree is snapshotted. Default: 30
+    #[arg(long)]
+    pub kv_snapshot_interval: Option<u64>,
+
+    /// KV Router: Serve `POST /debug/kv_router`, which shows the worker ids, their load and the
+    /// selector's scores for a request. `in=http` with `--router-mode kv` only. Default: off
+    #[arg(long)]
+    pub enable_kv_router_debug: bool,
+
+    /// Send a request again, continued from the tokens generated so far, when the stream of the
+    /// worker serving it ends before the response is complete, e.g. because the worker died.
+    /// This many times at most per request. `out=dyn` only. Default: 0, not migrated
+    #[arg(long, default_value = "0")]
+    pub migration_limit: u32,
+
     /// Max model context length. Reduce this if you don't have enough VRAM for the full model
     /// context length (e.g. Llama 4).
     /// Defaults to the model's max, which is usually model_max_length in tokenizer_config.json.
@@ -131,6 +178,21 @@ pub struct Flags {
     #[arg(long)]
     pub kv_cache_block_size: Option<usize>,
 
+    /// Parser of the tool calls in the model output: hermes, llama3_json, mistral or
+    /// deepseek_v3. Defaults to the one matching the model name, if any.
+    #[arg(long)]
+    pub tool_call_parser: Option<String>,
+
+    /// Format of the reasoning in the output of thinking models: deepseek_r1 or qwen3. Defaults
+    /// to the one matching the model name, if any.
+    #[arg(long)]
+    pub reasoning_parser: Option<String>,
+
+    /// Serve the model as this LoRA adapter. KV aware routing then only counts the blocks workers
+    /// cached for the same adapter. Default: the base model
+    #[arg(long)]
+    pub lora_id: Option<u64>,
+
     /// Additional engine-specific arguments from a JSON file.
     /// Contains a mapping of parameter names to values.
     #[arg(long)]
@@ -147,6 +209,62 @@ pub struct Flags {
     #[arg(long)]
     pub request_template: Option<PathBuf>,
 
+    /// Send an SSE keep-alive comment on chat and completion streams that have been idle for this
+    /// many seconds, for example during a long prefill, so load balancers do not time them out.
+    /// `in=http` only. Default: no keep-alive
+    #[arg(long)]
+    pub sse_keep_alive: Option<u64>,
+
+    /// Require an API key on the OpenAI endpoints. `in=http` only.
+    /// Either the path of a JSON file listing the keys, or `etcd` to read them from the
+    /// `http_api_keys` bucket in etcd (`out=dyn` only). Default: no authentication
+    /// Example file contents:
+    /// [
+    ///     {"key": "sk-team-a", "name": "team-a", "models": ["Llama-3.2-3B-Instruct"]},
+    ///     {"key": "sk-admin", "name": "admin"}
+    /// ]
+    #[arg(long)]
+    pub api_keys: Option<ApiKeysSource>,
+
+    /// HTTP rate limit: Sustained requests per second per tenant. `in=http` only.
+    /// A tenant is an API key, or the value of `--rate-limit-key-header`. Default: unlimited
+    #[arg(long)]
+    pub rate_limit_rps: Option<f64>,
+
+    /// HTTP rate limit: Input plus output tokens per minute per tenant. `in=http` only.
+    /// Default: unlimited
+    #[arg(long, value_parser = clap::value_parser!(u64).range(1..))]
+    pub rate_limit_tpm: Option<u64>,
+
+    /// HTTP rate limit: Identify tenants by this request header instead of their API key.
+    /// Clients can set it to anything, so it must be set by a trusted proxy.
+    #[arg(long)]
+    pub rate_limit_key_header: Option<String>,
+
+    /// HTTP rate limit: Path to a JSON file of per-tenant limits, which replace the
+    /// `--rate-limit-*` limits for those tenants.
+    /// Example file contents:
+    /// {
+    ///     "sk-team-a": {"requests_per_second": 50, "tokens_per_minute": 1000000},
+    ///     "sk-batch": {"tokens_per_minute": 100000}
+    /// }
+    #[arg(long)]
+    pub rate_limit_tenants: Option<PathBuf>,
+
+    /// Serve HTTPS with this PEM certificate chain. `in=http` only. Requires `--tls-key-path`.
+    /// The certificate and key are reloaded when the files change. Default: plain HTTP
+    #[arg(long, requires = "tls_key_path")]
+    pub tls_cert_path: Option<PathBuf>,
+
+    /// PEM private key of `--tls-cert-path`.
+    #[arg(long, requires = "tls_cert_path")]
+    pub tls_key_path: Option<PathBuf>,
+
+    /// Require HTTPS clients to present a certificate signed by one of the CAs in this PEM file
+    /// (mutual TLS). Requires `--tls-cert-path`.
+    #[arg(long, requires = "tls_cert_path")]
+    pub tls_client_ca_path: Option<PathBuf>,
+
     /// Everything after a `--`.
     /// These are the command line arguments to the python engine when using `pystr` or `pytok`.
     #[arg(index = 2, last = true, hide = true, allow_hyphen_values = true)]
@@ -161,6 +279,14 @@ impl Flags {
             self.kv_gpu_cache_usage_weight,
             self.kv_waiting_requests_weight,
         )
+        .with_selector(
+            self.kv_selector,
+            self.kv_router_temperature,
+            self.kv_max_gpu_cache_usage,
+        )
+        .with_snapshot(self.kv_snapshot.clone().map(|target| {
+            KvSnapshotConfig::new(target, self.kv_snapshot_interval.map(Duration::from_secs))
+        }))
     }
 
     /// Convert the flags back to a command line. Including only the non-null values, but
@@ -216,6 +342,54 @@ impl Flags {
         out
     }
 
+    /// HTTP rate limiting configuration, or `None` if no limit was set.
+    pub fn rate_limit_config(&self) -> anyhow::Result<Option<RateLimitConfig>> {
+        let tenant_limits: HashMap<String, RateLimits> = match &self.rate_limit_tenants {
+            Some(path) => {
+                let file_content = std::fs::read_to_string(path)?;
+                let tenant_limits: HashMap<String, RateLimits> =
+                    serde_json::from_str(&file_content)?;
+                for limits in tenant_limits.values() {
+                    // the tenant is usually an API key, so it is not in the error
+                    limits
+                        .validate()
+                        .with_context(|| format!("Invalid tenant limits in {path:?}"))?;
+                }
+                tenant_limits
+            }
+            None => HashMap::new(),
+        };
+        if self.rate_limit_rps.is_none()
+            && self.rate_limit_tpm.is_none()
+            && tenant_limits.is_empty()
+        {
+            return Ok(None);
+        }
+        let limits = RateLimits {
+            requests_per_second: self.rate_limit_rps,
+            tokens_per_minute: self.rate_limit_tpm,
+        };
+        limits
+            .validate()
+            .context("Invalid --rate-limit-rps or --rate-limit-tpm")?;
+        Ok(Some(
+            RateLimitConfig::new(limits)
+                .with_key_header(self.rate_limit_key_header.clone())
+                .with_tenant_limits(tenant_limits),
+        ))
+    }
+
+    /// HTTP TLS configuration, or `None` to serve plain HTTP.
+    pub fn tls_config(&self) -> Option<TlsConfig> {
+        let (Some(cert_path), Some(key_path)) = (&self.tls_cert_path, &self.tls_key_path) else {
+            return None;
+        };
+        Some(
+            TlsConfig::new(cert_path.clone(), key_path.clone())
+                .with_client_ca_path(self.tls_client_ca_path.clone()),
+        )
+    }
+
     /// Load extra engine arguments from a JSON file
     /// Returns a HashMap of parameter names to values
     pub fn load_extra_engine_args(
@@ -250,3 +424,26 @@ impl From<RouterMode> for RuntimeRouterMode {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::Parser;
+
+    #[test]
+    fn test_rate_limits_must_be_positive() {
+        for rps in ["0", "-1"] {
+            let arg = format!("--rate-limit-rps={rps}");
+            let flags = Flags::try_parse_from(["dynamo-run", arg.as_str()]).unwrap();
+            assert!(
+                flags.rate_limit_config().is_err(),
+                "{rps} should be rejected"
+            );
+        }
+        assert!(Flags::try_parse_from(["dynamo-run", "--rate-limit-tpm", "0"]).is_err());
+
+        let flags = Flags::try_parse_from(["dynamo-run", "--rate-limit-rps", "2.5"]).unwrap();
+        let config = flags.rate_limit_config().unwrap().unwrap();
+        assert_eq!(config.limits.requests_per_second, Some(2.5));
+    }
+}
@@ -2,6 +2,7 @@
 // SPDX-License-Identifier: Apache-2.0
 
 use std::sync::Arc;
+use std::time::Duration;
 
 use crate::input::common;
 use crate::{EngineConfig, Flags};
@@ -9,7 +10,10 @@ use dynamo_llm::kv_router::KvRouterConfig;
 use dynamo_llm::{
     discovery::{ModelManager, ModelWatcher, MODEL_ROOT_PATH},
     engines::StreamingEngineAdapter,
-    http::service::service_v2,
+    http::service::{
+        auth::{self, ApiKeys, ApiKeysSource, DEFAULT_API_KEYS_REFRESH_INTERVAL},
+        service_v2,
+    },
     request_template::RequestTemplate,
     types::{
         openai::chat_completions::{
@@ -19,6 +23,7 @@ use dynamo_llm::{
     },
 };
 use dynamo_runtime::pipeline::RouterMode;
+use dynamo_runtime::storage::key_value_store::EtcdStorage;
 use dynamo_runtime::transports::etcd;
 use dynamo_runtime::{DistributedRuntime, Runtime};
 
@@ -29,11 +34,24 @@ pub async fn run(
     engine_config: EngineConfig,
     template: Option<RequestTemplate>,
 ) -> anyhow::Result<()> {
+    let api_keys = match &flags.api_keys {
+        Some(ApiKeysSource::File(path)) => Some(Arc::new(ApiKeys::from_file(path)?)),
+        // filled in once we are connected to etcd
+        Some(ApiKeysSource::Etcd) => Some(Arc::new(ApiKeys::default())),
+        None => None,
+    };
     let http_service = service_v2::HttpService::builder()
         .port(flags.http_port)
         .enable_chat_endpoints(true)
         .enable_cmpl_endpoints(true)
         .enable_embeddings_endpoints(true)
+        .enable_kv_router_debug_endpoints(
+            flags.enable_kv_router_debug && RouterMode::from(flags.router_mode).is_kv_routing(),
+        )
+        .rate_limit(flags.rate_limit_config()?)
+        .api_keys(api_keys.clone())
+        .tls(flags.tls_config())
+        .sse_keep_alive(flags.sse_keep_alive.map(Duration::from_secs))
         .with_request_template(template)
         .build()?;
     match engine_config {
@@ -41,6 +59,19 @@ pub async fn run(
             let distributed_runtime = DistributedRuntime::from_settings(runtime.clone()).await?;
             match distributed_runtime.etcd_client() {
                 Some(etcd_client) => {
+                    if let (Some(ApiKeysSource::Etcd), Some(api_keys)) =
+                        (&flags.api_keys, &api_keys)
+                    {
+                        let store = EtcdStorage::new(etcd_client.clone());
+                        api_keys.reload(&store).await?;
+                        tracing::info!(keys = api_keys.len(), "Loaded API keys from etcd");
+                        tokio::spawn(auth::refresh_loop(
+                            api_keys.clone(),
+                            Box::new(store),
+                            DEFAULT_API_KEYS_REFRESH_INTERVAL,
+                            runtime.primary_token(),
+                        ));
+                    }
                     // Listen for models registering themselves in etcd, add them to HTTP service
                     run_watcher(
                         distributed_runtime,
@@ -49,14 +80,25 @@ pub async fn run(
                         MODEL_ROOT_PATH,
                         flags.router_mode.into(),
                         Some(flags.kv_router_config()),
+                        flags.migration_limit,
                     )
                     .await?;
                 }
                 None => {
                     // Static endpoints don't need discovery
+                    if flags.api_keys == Some(ApiKeysSource::Etcd) {
+                        anyhow::bail!(
+                            "--api-keys etcd needs etcd, which this runtime does not use"
+                        );
+                    }
                 }
             }
         }
+        EngineConfig::StaticFull { .. } | EngineConfig::StaticCore { .. }
+            if flags.api_keys == Some(ApiKeysSource::Etcd) =>
+        {
+            anyhow::bail!("--api-keys etcd is only supported with out=dyn");
+        }
         EngineConfig::StaticFull { engine, model } => {
             let engine = Arc::new(StreamingEngineAdapter::new(engine));
             let manager = http_service.model_manager();
@@ -106,8 +148,10 @@ async fn run_watcher(
     network_prefix: &str,
     router_mode: RouterMode,
     kv_router_config: Option<KvRouterConfig>,
+    migration_limit: u32,
 ) -> anyhow::Result<()> {
-    let watch_obj = ModelWatcher::new(runtime, model_manager, router_mode, kv_router_config);
+    let watch_obj = ModelWatcher::new(runtime, model_manager, router_mode, kv_router_config)
+        .with_migration_limit(migration_limit);
     tracing::info!("Watching for remote model at {network_prefix}");
     let models_watcher = etcd_client.kv_get_and_watch_prefix(network_prefix).await?;
     let (_prefix, _watcher, receiver) = models_watcher.dissolve();
@@ -93,6 +93,15 @@ pub async fn run(
     if let Some(context_length) = flags.context_length {
         local_model.set_context_length(context_length);
     }
+    if let Some(tool_call_parser) = flags.tool_call_parser.clone() {
+        local_model.set_tool_call_parser(tool_call_parser);
+    }
+    if let Some(reasoning_parser) = flags.reasoning_parser.clone() {
+        local_model.set_reasoning_parser(reasoning_parser);
+    }
+    if let Some(lora_id) = flags.lora_id {
+        local_model.set_lora_id(lora_id);
+    }
     // Always set, there is no engine provided default
     local_model.set_kv_cache_block_size(
         flags
@@ -137,6 +146,12 @@ pub async fn run(
             if flags.kv_cache_block_size.is_some() {
                 anyhow::bail!("'--kv-cache-block-size' flag should only be used on the worker node, not on the ingress");
             }
+            if flags.tool_call_parser.is_some() {
+                anyhow::bail!("'--tool-call-parser' flag should only be used on the worker node, not on the ingress");
+            }
+            if flags.reasoning_parser.is_some() {
+                anyhow::bail!("'--reasoning-parser' flag should only be used on the worker node, not on the ingress");
+            }
             EngineConfig::Dynamic
         }
         Output::EchoFull => EngineConfig::StaticFull {
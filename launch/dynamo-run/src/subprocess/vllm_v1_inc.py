@@ -262,7 +262,7 @@ async def init(runtime: DistributedRuntime, config: Config):
         worker_id=generate_endpoint.lease_id(), kv_block_size=engine_args.block_size
     )
 
-    _ = ZmqKvEventPublisher(component=component, config=zmq_config)
+    kv_publisher = ZmqKvEventPublisher(component=component, config=zmq_config)
 
     handler = RequestHandler(component, engine_client, default_sampling_params)
 
@@ -270,6 +270,7 @@ async def init(runtime: DistributedRuntime, config: Config):
         await asyncio.gather(
             generate_endpoint.serve_endpoint(handler.generate),
             clear_endpoint.serve_endpoint(handler.clear_kv_blocks),
+            kv_publisher.create_state_endpoint(component),
         )
     except Exception as e:
         logger.error(f"Failed to serve endpoints: {e}")
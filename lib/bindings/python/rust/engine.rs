@@ -201,7 +201,22 @@ where
             let mut stream = stream;
             let mut count = 0;
 
-            while let Some(item) = stream.next().await {
+            loop {
+                let item = tokio::select! {
+                    biased;
+
+                    // the request was cancelled, e.g. the client disconnected; dropping the
+                    // stream closes the async generator, so the engine can free its KV blocks
+                    _ = ctx.stopped() => {
+                        tracing::debug!(request_id, "request stopped; closing python async generator");
+                        break;
+                    }
+
+                    item = stream.next() => match item {
+                        Some(item) => item,
+                        None => break,
+                    },
+                };
                 count += 1;
                 tracing::trace!(
                     request_id,
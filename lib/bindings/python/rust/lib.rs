@@ -97,7 +97,7 @@ fn log_message(level: &str, message: &str, module: &str, file: &str, line: u32)
 }
 
 #[pyfunction]
-#[pyo3(signature = (model_type, endpoint, model_path, model_name=None, context_length=None, kv_cache_block_size=None))]
+#[pyo3(signature = (model_type, endpoint, model_path, model_name=None, context_length=None, kv_cache_block_size=None, lora_id=None))]
 fn register_llm<'p>(
     py: Python<'p>,
     model_type: ModelType,
@@ -106,6 +106,7 @@ fn register_llm<'p>(
     model_name: Option<&str>,
     context_length: Option<usize>,
     kv_cache_block_size: Option<usize>,
+    lora_id: Option<u64>,
 ) -> PyResult<Bound<'p, PyAny>> {
     let model_type_obj = match model_type {
         ModelType::Chat => llm_rs::model_type::ModelType::Chat,
@@ -128,6 +129,9 @@ fn register_llm<'p>(
         if let Some(kv_cache_block_size) = kv_cache_block_size {
             local_model.set_kv_cache_block_size(kv_cache_block_size);
         }
+        if let Some(lora_id) = lora_id {
+            local_model.set_lora_id(lora_id);
+        }
 
         // Advertise ourself on etcd so ingress can find us
         local_model
@@ -68,12 +68,17 @@ impl KvRouter {
 }
 
 #[pyfunction]
-pub fn compute_block_hash_for_seq_py(tokens: Vec<u32>, kv_block_size: usize) -> PyResult<Vec<u64>> {
+#[pyo3(signature = (tokens, kv_block_size, lora_id=0))]
+pub fn compute_block_hash_for_seq_py(
+    tokens: Vec<u32>,
+    kv_block_size: usize,
+    lora_id: u64,
+) -> PyResult<Vec<u64>> {
     if kv_block_size == 0 {
         return Err(to_pyerr(anyhow::anyhow!("kv_block_size cannot be 0")));
     }
 
-    let hashes = compute_block_hash_for_seq(&tokens, kv_block_size);
+    let hashes = compute_block_hash_for_seq(&tokens, kv_block_size, lora_id);
     Ok(hashes.into_iter().map(|h| h.0).collect())
 }
 
@@ -201,6 +206,23 @@ impl ZmqKvEventPublisher {
         Ok(Self { inner })
     }
 
+    /// Serve the blocks announced so far, so routers that lost events can resync this worker.
+    #[pyo3(signature = (component))]
+    fn create_state_endpoint<'p>(
+        &self,
+        py: Python<'p>,
+        component: Component,
+    ) -> PyResult<Bound<'p, PyAny>> {
+        let state = self.inner.state_endpoint();
+        pyo3_async_runtimes::tokio::future_into_py(py, async move {
+            state
+                .create_endpoint(component.inner)
+                .await
+                .map_err(to_pyerr)?;
+            Ok(())
+        })
+    }
+
     fn shutdown(&mut self) {
         self.inner.shutdown()
     }
@@ -305,8 +327,25 @@ impl KvEventPublisher {
         })
     }
 
+    /// Serve the blocks announced so far, so routers that lost events can resync this worker.
+    #[pyo3(signature = (component))]
+    fn create_state_endpoint<'p>(
+        &self,
+        py: Python<'p>,
+        component: Component,
+    ) -> PyResult<Bound<'p, PyAny>> {
+        let state = self.inner.state_endpoint();
+        pyo3_async_runtimes::tokio::future_into_py(py, async move {
+            state
+                .create_endpoint(component.inner)
+                .await
+                .map_err(to_pyerr)?;
+            Ok(())
+        })
+    }
+
     #[allow(clippy::too_many_arguments)]
-    #[pyo3(signature = (event_id, token_ids, num_block_tokens, block_hashes, lora_id, parent_hash=None))]
+    #[pyo3(signature = (event_id, token_ids, num_block_tokens, block_hashes, lora_id, parent_hash=None, media_hashes=None))]
     fn publish_stored(
         &mut self,
         _py: Python,
@@ -316,7 +355,9 @@ impl KvEventPublisher {
         block_hashes: Vec<i64>,
         lora_id: u64,
         parent_hash: Option<i64>,
+        media_hashes: Option<Vec<u64>>,
     ) -> PyResult<()> {
+        let media_hashes = media_hashes.unwrap_or_default();
         let event = KvCacheEvent {
             event_id,
             data: KvCacheEventData::Stored(KvCacheStoreData {
@@ -327,8 +368,11 @@ impl KvEventPublisher {
                     &num_block_tokens,
                     &block_hashes,
                     lora_id,
+                    &media_hashes,
                     &self.warning_count,
                 ),
+                lora_id: (lora_id != 0).then_some(lora_id),
+                media_hashes,
             }),
         };
 
@@ -503,12 +547,12 @@ impl KvIndexer {
         &self,
         py: Python<'p>,
         token_ids: Vec<u32>,
-        _lora_id: u64,
+        lora_id: u64,
     ) -> PyResult<Bound<'p, PyAny>> {
         let indexer = self.inner.clone();
         pyo3_async_runtimes::tokio::future_into_py(py, async move {
             let rs_overlap_scores = indexer
-                .find_matches_for_request(token_ids.as_slice())
+                .find_matches_for_request(token_ids.as_slice(), lora_id)
                 .await
                 .map_err(to_pyerr)?;
             Ok(OverlapScores {
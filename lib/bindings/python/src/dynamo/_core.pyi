@@ -344,13 +344,16 @@ class DisaggregatedRouter:
         """
         ...
 
-def compute_block_hash_for_seq_py(tokens: List[int], kv_block_size: int) -> List[int]:
+def compute_block_hash_for_seq_py(
+    tokens: List[int], kv_block_size: int, lora_id: int = 0
+) -> List[int]:
     """
     Compute block hashes for a sequence of tokens
 
     Args:
         tokens: List of token IDs
         kv_block_size: Size of each KV cache block
+        lora_id: LoRA adapter the blocks belong to, 0 for the base model
 
     Returns:
         List of block hashes as integers
@@ -674,9 +677,13 @@ class KvEventPublisher:
         block_hashes: List[int],
         lora_id: int,
         parent_hash: Optional[int] = None,
+        media_hashes: Optional[List[int]] = None,
     ) -> None:
         """
         Publish a KV stored event.
+
+        media_hashes are the `hash`es of the media of the request the blocks belong to, so the
+        router does not match them with prompts of the same tokens and other media.
         """
         ...
 
@@ -686,6 +693,12 @@ class KvEventPublisher:
         """
         ...
 
+    async def create_state_endpoint(self, component: Component) -> None:
+        """
+        Serve the KV blocks published so far, so a router that lost events can resync this worker.
+        """
+        ...
+
 class ZmqKvEventPublisherConfig:
     def __init__(
         self,
@@ -714,6 +727,12 @@ class ZmqKvEventPublisher:
         """
         ...
 
+    async def create_state_endpoint(self, component: Component) -> None:
+        """
+        Serve the KV blocks published so far, so a router that lost events can resync this worker.
+        """
+        ...
+
     def shutdown(self) -> None:
         """
         Shuts down the event publisher, stopping any background tasks.
@@ -748,8 +767,9 @@ class ModelType:
     """What type of request this model needs: Chat, Component or Backend (pre-processed)"""
     ...
 
-async def register_llm(model_type: ModelType, endpoint: Endpoint, model_path: str, model_name: Optional[str] = None, context_length: Optional[int] = None, kv_cache_block_size: Optional[int] = None) -> None:
-    """Attach the model at path to the given endpoint, and advertise it as model_type"""
+async def register_llm(model_type: ModelType, endpoint: Endpoint, model_path: str, model_name: Optional[str] = None, context_length: Optional[int] = None, kv_cache_block_size: Optional[int] = None, lora_id: Optional[int] = None) -> None:
+    """Attach the model at path to the given endpoint, and advertise it as model_type.
+    Set lora_id when the endpoint serves the model as a LoRA adapter."""
     ...
 
 class NatsQueue:
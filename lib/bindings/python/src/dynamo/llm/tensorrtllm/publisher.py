@@ -136,8 +136,21 @@ class Publisher:
         self.kv_event_publisher = KvEventPublisher(
             self.kv_listener, self.worker_id, self.kv_block_size
         )
+        task = asyncio.create_task(
+            self.kv_event_publisher.create_state_endpoint(self.kv_listener)
+        )
+        task.add_done_callback(self._on_state_endpoint_done)
         self._init_publish_kv_cache_events_thread()
 
+    def _on_state_endpoint_done(self, task: asyncio.Task):
+        # without the endpoint the router cannot resync this worker after lost events
+        if task.cancelled():
+            logging.warning("Creating the kv state endpoint was cancelled")
+        elif task.exception() is not None:
+            logging.error(f"Failed to create the kv state endpoint: {task.exception()}")
+        else:
+            logging.debug("kv state endpoint created")
+
     def _init_publish_metrics_thread(self):
         # Need to publish stats once so that worker can be selected.
         # Publishing some dummy values...
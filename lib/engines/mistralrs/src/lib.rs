@@ -23,6 +23,7 @@ use dynamo_runtime::pipeline::error as pipeline_error;
 use dynamo_runtime::pipeline::{Error, ManyOut, SingleIn};
 use dynamo_runtime::protocols::annotated::Annotated;
 
+use dynamo_llm::protocols::common::{GuidedDecodingOptions, GuidedDecodingOptionsProvider};
 use dynamo_llm::protocols::openai::{
     chat_completions::{NvCreateChatCompletionRequest, NvCreateChatCompletionStreamResponse},
     completions::{prompt_to_string, NvCreateCompletionRequest, NvCreateCompletionResponse},
@@ -273,6 +274,7 @@ impl
         let (request, context) = request.transfer(());
         let ctx = context.context();
         let (tx, mut rx) = channel(10_000);
+        let constraint = to_constraint(request.extract_guided_decoding_options()?);
 
         let mut messages = vec![];
         for m in request.inner.messages {
@@ -349,7 +351,7 @@ impl
             response: tx,
             return_logprobs: request.inner.logprobs.unwrap_or_default(),
             is_streaming: true,
-            constraint: Constraint::None,
+            constraint,
             suffix: None,
             tools: None,
             tool_choice: None,
@@ -413,7 +415,10 @@ impl
                             system_fingerprint: Some(c.system_fingerprint),
                             service_tier: None,
                         };
-                        let delta = NvCreateChatCompletionStreamResponse{inner};
+                        let delta = NvCreateChatCompletionStreamResponse {
+                            inner,
+                            reasoning_content: HashMap::new(),
+                        };
                         let ann = Annotated{
                             id: None,
                             data: Some(delta),
@@ -443,6 +448,19 @@ fn to_stop_tokens(t: async_openai::types::Stop) -> StopTokens {
     }
 }
 
+/// guided decoding options to a mistralrs constraint, grammars are in its Lark syntax
+fn to_constraint(options: Option<GuidedDecodingOptions>) -> Constraint {
+    match options {
+        None => Constraint::None,
+        Some(GuidedDecodingOptions::JsonObject) => {
+            Constraint::JsonSchema(serde_json::json!({"type": "object"}))
+        }
+        Some(GuidedDecodingOptions::JsonSchema(schema)) => Constraint::JsonSchema(schema),
+        Some(GuidedDecodingOptions::Regex(pattern)) => Constraint::Regex(pattern),
+        Some(GuidedDecodingOptions::Grammar(grammar)) => Constraint::Lark(grammar),
+    }
+}
+
 /// openai logit bias (strings/json) to mistralrs (u32/f32)
 /// I think the input looks like this: {"3721": -100, "17765": 100}
 fn to_logit_bias(lb: HashMap<String, serde_json::Value>) -> HashMap<u32, f32> {
@@ -482,6 +500,7 @@ impl
         let ctx = context.context();
         let (tx, mut rx) = channel(10_000);
         let response_generator = request.response_generator();
+        let constraint = to_constraint(request.extract_guided_decoding_options()?);
 
         let messages = RequestMessage::Completion {
             text: prompt_to_string(&request.inner.prompt),
@@ -543,7 +562,7 @@ impl
             response: tx,
             return_logprobs: false,
             is_streaming: true,
-            constraint: Constraint::None,
+            constraint,
             suffix: None,
             tools: None,
             tool_choice: None,
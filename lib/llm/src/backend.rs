@@ -33,7 +33,9 @@ use anyhow::{Error, Result};
 use futures::stream::{self, StreamExt};
 use tracing as log;
 
+use crate::guided_decoding::OutputValidator;
 use crate::model_card::model::{ModelDeploymentCard, TokenizerKind};
+use crate::reasoning::{ReasoningFormat, ReasoningParser};
 use dynamo_runtime::{
     pipeline::{
         async_trait, AsyncEngineContextProvider, ManyOut, Operator, ResponseStream,
@@ -44,8 +46,10 @@ use dynamo_runtime::{
 
 use crate::protocols::{
     common::{
-        llm_backend::{BackendOutput, FinishReason, LLMEngineOutput, PreprocessedRequest},
-        StopConditions,
+        llm_backend::{
+            BackendOutput, FinishReason, LLMEngineOutput, PreprocessedRequest, TopLogProbs,
+        },
+        ReasoningMode, StopConditions,
     },
     TokenIdType,
 };
@@ -63,6 +67,7 @@ pub type ExecutionContext = ServerStreamingEngine<PreprocessedRequest, Execution
 pub struct Backend {
     pub tokenizer: Option<Tokenizer>, // Handles token encoding/decoding
     validate_engine_decode: bool,     // Enable validation of engine decoding
+    reasoning_format: Option<ReasoningFormat>, // How a thinking model marks its reasoning
 }
 
 /// Internal state for managing token decoding and stream processing
@@ -81,10 +86,12 @@ impl Backend {
         Ok(Arc::new(Self {
             tokenizer: Some(tokenizer),
             validate_engine_decode: false,
+            reasoning_format: None,
         }))
     }
 
     pub async fn from_mdc(mdc: ModelDeploymentCard) -> Result<Arc<Self>> {
+        let reasoning_format = reasoning_format(&mdc);
         let tokenizer = match &mdc.tokenizer {
             Some(TokenizerKind::HfTokenizerJson(file)) => {
                 HfTokenizer::from_file(file).map_err(Error::msg)?
@@ -94,10 +101,16 @@ impl Backend {
                 return Ok(Arc::new(Self {
                     tokenizer: None,
                     validate_engine_decode: false,
+                    reasoning_format,
                 }));
             }
         };
-        Self::from_tokenizer(tokenizer).await
+        let tokenizer = HuggingFaceTokenizer::from_tokenizer(tokenizer);
+        Ok(Arc::new(Self {
+            tokenizer: Some(Tokenizer::from(Arc::new(tokenizer))),
+            validate_engine_decode: false,
+            reasoning_format,
+        }))
     }
 
     fn decoder(
@@ -133,6 +146,14 @@ impl
         next: ServerStreamingEngine<PreprocessedRequest, Annotated<LLMEngineOutput>>,
     ) -> Result<ManyOut<Annotated<BackendOutput>>> {
         let stop_conditions = request.stop_conditions.clone();
+        let validator = match &request.guided_decoding {
+            Some(options) => OutputValidator::new(options),
+            None => None,
+        };
+        let mut reasoning = match (request.output_options.reasoning, &self.reasoning_format) {
+            (Some(mode), Some(format)) => Some((ReasoningParser::new(format.clone()), mode)),
+            _ => None,
+        };
         let next_stream = next.generate(request).await?;
 
         let context = next_stream.context();
@@ -178,6 +199,16 @@ impl
                         state.stream.context().stop_generating();
                     }
 
+                    // the decoder stops at a stop condition: drop the logprobs of the tokens
+                    // after it, and of a hidden stop token
+                    let mut emitted = result.tokens.len();
+                    if matches!(
+                        result.stop_trigger,
+                        Some(StopTrigger::HiddenStopTokenDetected(_))
+                    ) {
+                        emitted -= 1;
+                    }
+
                     let text = result.text;
                     let tokens = result.tokens;
 
@@ -202,6 +233,12 @@ impl
                     data.finish_reason = finish_reason;
                     data.text = text;
                     data.tokens = Some(tokens);
+                    if let Some(log_probs) = &mut data.log_probs {
+                        log_probs.truncate(emitted);
+                    }
+                    if let Some(top_logprobs) = &mut data.top_logprobs {
+                        top_logprobs.truncate(emitted);
+                    }
 
                     output.data = Some(data);
 
@@ -214,17 +251,62 @@ impl
 
         // convert stream of processed Annotated<LLMEngineOutput> to Annotated<BackendOutput>
         //let mdcsum = self.mdcsum.clone();
+        let tokenizer = self.tokenizer.clone();
+        let mut guided_output = validator.map(|validator| (validator, String::new()));
         let stream = processed_stream.map(move |output| {
-            output.map_data(|data| {
+            output.map_data(|mut data| {
+                if let (Some(tokenizer), Some(top_logprobs)) = (&tokenizer, &mut data.top_logprobs)
+                {
+                    decode_top_logprobs(tokenizer, top_logprobs);
+                }
+                // separate the reasoning before the content is validated
+                let mut reasoning_text = None;
+                let mut reasoning_tokens = 0;
+                if let Some((parser, mode)) = &mut reasoning {
+                    let in_reasoning = parser.in_reasoning();
+                    let mut output = parser.parse(data.text.as_deref().unwrap_or_default());
+                    if data.finish_reason.is_some() {
+                        let rest = parser.finish();
+                        output.content.push_str(&rest.content);
+                        output.reasoning.push_str(&rest.reasoning);
+                    }
+                    // tokens are counted by the part of the output their delta starts in
+                    if in_reasoning || (!output.reasoning.is_empty() && output.content.is_empty()) {
+                        reasoning_tokens = data.token_ids.len() as u32;
+                    }
+                    data.text = (!output.content.is_empty()).then_some(output.content);
+                    if *mode == ReasoningMode::Separate && !output.reasoning.is_empty() {
+                        reasoning_text = Some(output.reasoning);
+                    }
+                }
+                if let Some((validator, text)) = &mut guided_output {
+                    if let Some(delta) = &data.text {
+                        text.push_str(delta);
+                    }
+                    // outputs cut short by max tokens or a cancellation are returned as they are
+                    if matches!(
+                        data.finish_reason,
+                        Some(FinishReason::Stop | FinishReason::EoS)
+                    ) {
+                        if let Err(err) = validator.validate(text) {
+                            data.finish_reason = Some(FinishReason::Error(format!(
+                                "Output does not match the guided decoding options: {err:#}"
+                            )));
+                        }
+                    }
+                }
                 Ok(BackendOutput {
                     token_ids: data.token_ids,
                     tokens: data.tokens.unwrap_or_default(),
                     text: data.text,
                     cum_log_probs: data.cum_log_probs,
                     log_probs: data.log_probs,
+                    top_logprobs: data.top_logprobs,
                     finish_reason: data.finish_reason,
                     //mdcsum: mdcsum.clone(),
                     index: data.index,
+                    reasoning_text,
+                    reasoning_tokens,
                 })
             })
         });
@@ -233,6 +315,32 @@ impl
     }
 }
 
+/// The reasoning format named in the model card, or else the one matching the model name
+fn reasoning_format(mdc: &ModelDeploymentCard) -> Option<ReasoningFormat> {
+    let name = match &mdc.reasoning_parser {
+        Some(name) => name.as_str(),
+        None => ReasoningFormat::detect(&mdc.display_name)?,
+    };
+    let format = ReasoningFormat::from_name(name);
+    if format.is_none() {
+        tracing::warn!(
+            model = %mdc.display_name,
+            parser = name,
+            "Unknown reasoning parser, the reasoning will be returned as content"
+        );
+    }
+    format
+}
+
+/// Decode the alternative tokens the engine did not detokenize
+fn decode_top_logprobs(tokenizer: &Tokenizer, top_logprobs: &mut [TopLogProbs]) {
+    for top in top_logprobs.iter_mut().flatten() {
+        if top.token.is_none() {
+            top.token = tokenizer.decode(&[top.token_id], false).ok();
+        }
+    }
+}
+
 // todo - add visible stop conditions
 // visible_stop_ids: HashSet<TokenIdType>,
 // visible_stop_sequences: Vec<String>,
@@ -28,6 +28,7 @@ pub mod layout;
 pub mod metrics;
 pub mod offload;
 pub mod pool;
+pub mod prefetch;
 pub mod storage;
 
 pub use crate::common::dtype::DType;
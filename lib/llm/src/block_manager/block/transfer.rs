@@ -15,7 +15,7 @@
 
 mod context;
 mod cuda;
-mod memcpy;
+pub(crate) mod memcpy;
 mod nixl;
 mod strategy;
 
@@ -0,0 +1,437 @@
+// SPDX-FileCopyrightText: Copyright (c) 2024-2025 NVIDIA CORPORATION & AFFILIATES. All rights reserved.
+// SPDX-License-Identifier: Apache-2.0
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! # Prefix Prefetch
+//! The KV router attaches to each request the prompt blocks past the routed worker's cached prefix
+//! that other workers have cached, as [`PeerBlock`]s. Rather than computing those blocks again, the
+//! [`PrefixPrefetcher`] fetches them from the peers through a [`PeerBlockSource`], copies them into
+//! blocks of the local pool and registers them, so the prompt is matched as if it were cached.
+//!
+//! The router names a [`PeerBlock`] by its [`compute_block_hash_for_seq`] hash, while pools know
+//! blocks by their [`SequenceHash`]; [`peer_token_blocks`] maps the peer blocks of a request onto
+//! the worker's own [`TokenBlock`]s of the prompt.
+//!
+//! The [`LocalPeerBlockSource`] serves the pools of peers living in the same process, which lets
+//! the whole path run on [`SystemStorage`](super::storage::SystemStorage) with memcpy transfers.
+
+use super::block::{
+    transfer::{memcpy, TransferStrategy, WriteTo, WriteToStrategy},
+    BlockExt, BlockMetadata, ImmutableBlock, MutableBlock, ReadableBlock, TransferContext,
+    WritableBlock,
+};
+use super::pool::BlockPool;
+use super::storage::Storage;
+use crate::kv_router::indexer::{compute_block_hash_for_seq, compute_block_hash_salt};
+use crate::protocols::common::preprocessor::{PeerBlock, PreprocessedRequest};
+use crate::tokens::{SequenceHash, TokenBlock};
+
+use anyhow::Result;
+use async_trait::async_trait;
+use nixl_sys::NixlDescriptor;
+use std::{collections::HashMap, sync::Arc};
+
+/// Where the blocks cached by other workers are fetched from
+#[async_trait]
+pub trait PeerBlockSource<S: Storage, M: BlockMetadata>: Send + Sync {
+    /// Fetch the blocks of `sequence_hashes` that the worker `worker_id` has cached.
+    /// Returns the blocks of the longest prefix of `sequence_hashes` the worker still has.
+    async fn fetch_blocks(
+        &self,
+        worker_id: i64,
+        sequence_hashes: &[SequenceHash],
+    ) -> Result<Vec<ImmutableBlock<S, M>>>;
+}
+
+/// A [`PeerBlockSource`] over the block pools of peers in this process
+pub struct LocalPeerBlockSource<S: Storage, M: BlockMetadata> {
+    pools: HashMap<i64, Arc<BlockPool<S, M>>>,
+}
+
+impl<S: Storage, M: BlockMetadata> Default for LocalPeerBlockSource<S, M> {
+    fn default() -> Self {
+        Self {
+            pools: HashMap::new(),
+        }
+    }
+}
+
+impl<S: Storage, M: BlockMetadata> LocalPeerBlockSource<S, M> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Serve the blocks of `pool` as those of the worker `worker_id`
+    pub fn add_peer(&mut self, worker_id: i64, pool: Arc<BlockPool<S, M>>) {
+        self.pools.insert(worker_id, pool);
+    }
+}
+
+#[async_trait]
+impl<S: Storage, M: BlockMetadata> PeerBlockSource<S, M> for LocalPeerBlockSource<S, M> {
+    async fn fetch_blocks(
+        &self,
+        worker_id: i64,
+        sequence_hashes: &[SequenceHash],
+    ) -> Result<Vec<ImmutableBlock<S, M>>> {
+        let pool = self
+            .pools
+            .get(&worker_id)
+            .ok_or_else(|| anyhow::anyhow!("Unknown peer worker {worker_id}"))?;
+        Ok(pool.match_sequence_hashes(sequence_hashes).await?)
+    }
+}
+
+/// Fills a pool with prompt blocks cached by other workers
+pub struct PrefixPrefetcher<S: Storage, M: BlockMetadata> {
+    pool: Arc<BlockPool<S, M>>,
+    source: Arc<dyn PeerBlockSource<S, M>>,
+    transfer_ctx: Option<Arc<TransferContext>>,
+}
+
+impl<S, M> PrefixPrefetcher<S, M>
+where
+    S: Storage + NixlDescriptor,
+    M: BlockMetadata,
+    ImmutableBlock<S, M>: ReadableBlock<StorageType = S> + WriteToStrategy<MutableBlock<S, M>>,
+    MutableBlock<S, M>: WritableBlock<StorageType = S>,
+    Vec<Arc<ImmutableBlock<S, M>>>: WriteTo<MutableBlock<S, M>>,
+{
+    /// Prefetch into `pool` from `source`. Only memcpy transfers are possible until a
+    /// [`TransferContext`] is given with [`PrefixPrefetcher::with_transfer_context`].
+    pub fn new(pool: Arc<BlockPool<S, M>>, source: Arc<dyn PeerBlockSource<S, M>>) -> Self {
+        Self {
+            pool,
+            source,
+            transfer_ctx: None,
+        }
+    }
+
+    /// Use `transfer_ctx` for the transfers memcpy can't do, e.g. into device storage
+    pub fn with_transfer_context(mut self, transfer_ctx: Arc<TransferContext>) -> Self {
+        self.transfer_ctx = Some(transfer_ctx);
+        self
+    }
+
+    /// Fetch `token_blocks` from the workers caching them, `peers[i]` caching `token_blocks[i]`,
+    /// and register them in the pool. Stops at the first block its peer no longer has, since only
+    /// a prefix of the prompt can be reused. Returns the registered blocks, in prompt order.
+    pub async fn prefetch(
+        &self,
+        token_blocks: &[TokenBlock],
+        peers: &[i64],
+    ) -> Result<Vec<ImmutableBlock<S, M>>> {
+        if token_blocks.len() != peers.len() {
+            anyhow::bail!(
+                "Mismatched prefetch counts: {} blocks, {} peers",
+                token_blocks.len(),
+                peers.len()
+            );
+        }
+
+        let mut prefetched = Vec::with_capacity(token_blocks.len());
+        let mut start = 0;
+
+        // Fetch each run of consecutive blocks cached by the same peer at once
+        while start < token_blocks.len() {
+            let peer = peers[start];
+            let end = peers[start..]
+                .iter()
+                .position(|&worker_id| worker_id != peer)
+                .map_or(peers.len(), |len| start + len);
+            let run = &token_blocks[start..end];
+
+            let sequence_hashes: Vec<SequenceHash> =
+                run.iter().map(|block| block.sequence_hash()).collect();
+            let sources = self.source.fetch_blocks(peer, &sequence_hashes).await?;
+            if sources.len() > run.len() {
+                anyhow::bail!(
+                    "Peer {peer} returned {} blocks for {} requested",
+                    sources.len(),
+                    run.len()
+                );
+            }
+            for (source, sequence_hash) in sources.iter().zip(&sequence_hashes) {
+                if source.sequence_hash()? != *sequence_hash {
+                    anyhow::bail!("Peer {peer} returned a block of another sequence");
+                }
+            }
+            if sources.is_empty() {
+                break;
+            }
+
+            let mut targets = self.pool.allocate_blocks(sources.len()).await?;
+            for (target, token_block) in targets.iter_mut().zip(run) {
+                target.apply_token_block(token_block.clone())?;
+            }
+            let fetched = sources.len();
+            self.transfer(sources, &mut targets).await?;
+            prefetched.extend(self.pool.register_blocks(targets).await?);
+            tracing::debug!(peer, blocks = fetched, "Prefetched prefix blocks");
+
+            if fetched < run.len() {
+                break;
+            }
+            start = end;
+        }
+
+        Ok(prefetched)
+    }
+
+    /// Prefetch the [`PeerBlock`]s the KV router attached to `request`. `token_blocks` are the
+    /// worker's blocks of the request's prompt.
+    pub async fn prefetch_request(
+        &self,
+        request: &PreprocessedRequest,
+        token_blocks: &[TokenBlock],
+    ) -> Result<Vec<ImmutableBlock<S, M>>> {
+        let media_hashes: Vec<u64> = request.media.iter().map(|media| media.hash).collect();
+        let (token_blocks, peers) = peer_token_blocks(
+            token_blocks,
+            request.estimated_prefix_hit_num_blocks.unwrap_or(0) as usize,
+            &request.peer_blocks,
+            compute_block_hash_salt(request.lora_id.unwrap_or(0), &media_hashes),
+        );
+        if token_blocks.is_empty() {
+            return Ok(Vec::new());
+        }
+        self.prefetch(&token_blocks, &peers).await
+    }
+
+    async fn transfer(
+        &self,
+        sources: Vec<ImmutableBlock<S, M>>,
+        targets: &mut Vec<MutableBlock<S, M>>,
+    ) -> Result<()> {
+        let strategy =
+            <ImmutableBlock<S, M> as WriteToStrategy<MutableBlock<S, M>>>::write_to_strategy();
+
+        match (strategy, &self.transfer_ctx) {
+            (TransferStrategy::Memcpy, _) => {
+                for (source, target) in sources.iter().zip(targets.iter_mut()) {
+                    memcpy::copy_block(source, target)?;
+                }
+            }
+            (TransferStrategy::Invalid, _) => {
+                anyhow::bail!("Blocks can't be transferred between these storages");
+            }
+            (_, Some(transfer_ctx)) => {
+                let sources: Vec<_> = sources.into_iter().map(Arc::new).collect();
+                let notify = sources
+                    .write_to(targets, true, transfer_ctx.clone())?
+                    .ok_or_else(|| {
+                        anyhow::anyhow!("write_to returned None when notify was true")
+                    })?;
+                notify.await?;
+            }
+            (_, None) => {
+                anyhow::bail!("A transfer context is needed for {strategy:?} transfers");
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The prompt blocks of `peer_blocks`, and the workers caching them, for
+/// [`PrefixPrefetcher::prefetch`]. The peer blocks follow the `cached_blocks` blocks of the prompt
+/// the router found on the routed worker; each is checked against the router's hash of the tokens
+/// of the prompt block it maps to, and the mapping stops at the first one which does not match,
+/// e.g. when the router uses another block size. `salt` is the request's LoRA adapter, or a
+/// [`compute_block_hash_salt`] of it and the request's media.
+pub fn peer_token_blocks(
+    token_blocks: &[TokenBlock],
+    cached_blocks: usize,
+    peer_blocks: &[PeerBlock],
+    salt: u64,
+) -> (Vec<TokenBlock>, Vec<i64>) {
+    token_blocks
+        .iter()
+        .skip(cached_blocks)
+        .zip(peer_blocks)
+        .map_while(|(token_block, peer_block)| {
+            let tokens = token_block.tokens();
+            let router_hash = compute_block_hash_for_seq(tokens, tokens.len(), salt);
+            (router_hash.first().map(|hash| hash.0) == Some(peer_block.block_hash))
+                .then(|| (token_block.clone(), peer_block.worker_id))
+        })
+        .unzip()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::block_manager::{
+        block::{BasicMetadata, BlockDataExt, Blocks},
+        layout::FullyContiguous,
+        storage::{SystemAllocator, SystemStorage},
+        DType, LayoutConfig,
+    };
+    use crate::protocols::common::{SamplingOptions, StopConditions};
+    use crate::tokens::{TokenBlockSequence, Tokens};
+
+    const BLOCK_SIZE: usize = 4;
+
+    type SystemPool = Arc<BlockPool<SystemStorage, BasicMetadata>>;
+
+    fn make_pool(num_blocks: usize) -> Result<SystemPool> {
+        let config = LayoutConfig {
+            num_blocks,
+            num_layers: 2,
+            outer_dim: 2,
+            page_size: BLOCK_SIZE,
+            inner_dim: 16,
+            alignment: 1,
+            dtype: DType::FP16,
+        };
+        let layout = FullyContiguous::allocate(config, &SystemAllocator)?;
+        let blocks = Blocks::<_, BasicMetadata>::new(layout, 42, 0)?.into_blocks()?;
+        Ok(Arc::new(BlockPool::builder().blocks(blocks).build()?))
+    }
+
+    /// Register `token_blocks` in `pool`, filling block `i` with the byte `fill + i`.
+    async fn cache_blocks(
+        pool: &SystemPool,
+        token_blocks: &[TokenBlock],
+        fill: u8,
+    ) -> Result<Vec<ImmutableBlock<SystemStorage, BasicMetadata>>> {
+        let mut blocks = pool.allocate_blocks(token_blocks.len()).await?;
+        for (i, (block, token_block)) in blocks.iter_mut().zip(token_blocks).enumerate() {
+            block.apply_token_block(token_block.clone())?;
+            let mut view = block.block_view_mut()?;
+            unsafe { std::ptr::write_bytes(view.as_mut_ptr(), fill + i as u8, view.size()) };
+        }
+        Ok(pool.register_blocks(blocks).await?)
+    }
+
+    fn is_filled_with(block: &ImmutableBlock<SystemStorage, BasicMetadata>, fill: u8) -> bool {
+        let view = block.block_view().unwrap();
+        let bytes = unsafe { std::slice::from_raw_parts(view.as_ptr(), view.size()) };
+        bytes.iter().all(|&byte| byte == fill)
+    }
+
+    #[tokio::test]
+    async fn test_prefetch_from_peers() -> Result<()> {
+        let tokens = Tokens::from((0..4 * BLOCK_SIZE as u32).collect::<Vec<_>>());
+        let sequence = TokenBlockSequence::new(tokens, BLOCK_SIZE, None);
+        let token_blocks = sequence.blocks();
+
+        // Peer 1 has the first three blocks of the prompt, peer 2 all four
+        let peer_1 = make_pool(8)?;
+        let peer_2 = make_pool(8)?;
+        let _peer_1_blocks = cache_blocks(&peer_1, &token_blocks[..3], 10).await?;
+        let _peer_2_blocks = cache_blocks(&peer_2, token_blocks, 20).await?;
+
+        let mut source = LocalPeerBlockSource::new();
+        source.add_peer(1, peer_1);
+        source.add_peer(2, peer_2);
+
+        // The local worker has the first block and fetches the next three
+        let local = make_pool(8)?;
+        let _local_blocks = cache_blocks(&local, &token_blocks[..1], 0).await?;
+        let prefetcher = PrefixPrefetcher::new(local.clone(), Arc::new(source));
+        let prefetched = prefetcher.prefetch(&token_blocks[1..], &[1, 1, 2]).await?;
+
+        assert_eq!(prefetched.len(), 3);
+        assert!(is_filled_with(&prefetched[0], 11));
+        assert!(is_filled_with(&prefetched[1], 12));
+        assert!(is_filled_with(&prefetched[2], 23));
+
+        // The whole prompt now matches in the local pool
+        let sequence_hashes: Vec<SequenceHash> = token_blocks
+            .iter()
+            .map(|block| block.sequence_hash())
+            .collect();
+        let matched = local.match_sequence_hashes(&sequence_hashes).await?;
+        assert_eq!(matched.len(), 4);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_prefetch_stops_at_missing_block() -> Result<()> {
+        let tokens = Tokens::from((0..3 * BLOCK_SIZE as u32).collect::<Vec<_>>());
+        let sequence = TokenBlockSequence::new(tokens, BLOCK_SIZE, None);
+        let token_blocks = sequence.blocks();
+
+        // Peer 1 evicted the second block since the router saw it
+        let peer_1 = make_pool(8)?;
+        let _peer_1_blocks = cache_blocks(&peer_1, &token_blocks[..1], 10).await?;
+        let peer_2 = make_pool(8)?;
+        let _peer_2_blocks = cache_blocks(&peer_2, token_blocks, 20).await?;
+
+        let mut source = LocalPeerBlockSource::new();
+        source.add_peer(1, peer_1);
+        source.add_peer(2, peer_2);
+
+        let local = make_pool(8)?;
+        let prefetcher = PrefixPrefetcher::new(local, Arc::new(source));
+        let prefetched = prefetcher.prefetch(token_blocks, &[1, 1, 2]).await?;
+
+        // The third block can't be used without the second one
+        assert_eq!(prefetched.len(), 1);
+        assert_eq!(
+            prefetched[0].sequence_hash()?,
+            token_blocks[0].sequence_hash()
+        );
+
+        // Unknown peers are an error
+        assert!(prefetcher.prefetch(&token_blocks[..1], &[3]).await.is_err());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_prefetch_request() -> Result<()> {
+        let token_ids: Vec<u32> = (0..3 * BLOCK_SIZE as u32).collect();
+        let sequence = TokenBlockSequence::new(Tokens::from(token_ids.clone()), BLOCK_SIZE, None);
+        let token_blocks = sequence.blocks();
+
+        let peer = make_pool(8)?;
+        let _peer_blocks = cache_blocks(&peer, token_blocks, 10).await?;
+        let mut source = LocalPeerBlockSource::new();
+        source.add_peer(7, peer);
+
+        // The router found the first block on the routed worker and the other two on peer 7
+        let router_hashes = compute_block_hash_for_seq(&token_ids, BLOCK_SIZE, 0);
+        let peer_blocks: Vec<PeerBlock> = router_hashes[1..]
+            .iter()
+            .map(|hash| PeerBlock {
+                worker_id: 7,
+                block_hash: hash.0,
+            })
+            .collect();
+        let request = PreprocessedRequest::builder()
+            .token_ids(token_ids.clone())
+            .stop_conditions(StopConditions::default())
+            .sampling_options(SamplingOptions::default())
+            .estimated_prefix_hit_num_blocks(Some(1))
+            .peer_blocks(peer_blocks.clone())
+            .build()?;
+
+        let local = make_pool(8)?;
+        let _local_blocks = cache_blocks(&local, &token_blocks[..1], 0).await?;
+        let prefetcher = PrefixPrefetcher::new(local, Arc::new(source));
+        let prefetched = prefetcher.prefetch_request(&request, token_blocks).await?;
+        assert_eq!(prefetched.len(), 2);
+        assert!(is_filled_with(&prefetched[0], 11));
+        assert!(is_filled_with(&prefetched[1], 12));
+
+        // Blocks hashed for another adapter don't map onto the prompt
+        let (blocks, peers) = peer_token_blocks(token_blocks, 1, &peer_blocks, 3);
+        assert!(blocks.is_empty() && peers.is_empty());
+
+        Ok(())
+    }
+}
@@ -5,9 +5,10 @@ use dynamo_runtime::component::Component;
 
 use crate::discovery::ModelEntry;
 
-use crate::kv_router::{scheduler::DefaultWorkerSelector, KvRouterConfig};
+use crate::kv_router::KvRouterConfig;
 use crate::{
     kv_router::KvRouter,
+    preprocessor::OpenAIPreprocessor,
     types::openai::{
         chat_completions::OpenAIChatCompletionsStreamingEngine,
         completions::OpenAICompletionsStreamingEngine, embeddings::OpenAIEmbeddingsStreamingEngine,
@@ -39,6 +40,7 @@ pub struct ModelManager {
     // These two are Mutex because we read and write rarely and equally
     entries: Mutex<HashMap<String, ModelEntry>>,
     kv_choosers: Mutex<HashMap<String, Arc<KvRouter>>>,
+    preprocessors: Mutex<HashMap<String, Arc<OpenAIPreprocessor>>>,
 }
 
 impl Default for ModelManager {
@@ -55,6 +57,7 @@ impl ModelManager {
             embeddings_engines: RwLock::new(ModelEngines::default()),
             entries: Mutex::new(HashMap::new()),
             kv_choosers: Mutex::new(HashMap::new()),
+            preprocessors: Mutex::new(HashMap::new()),
         }
     }
 
@@ -176,6 +179,23 @@ impl ModelManager {
         self.entries.lock().unwrap().remove(key)
     }
 
+    /// Keep the preprocessor of a model we pre-process requests for, so its tokenizer can be
+    /// used outside of the model's engines.
+    pub fn add_preprocessor(&self, model: &str, preprocessor: Arc<OpenAIPreprocessor>) {
+        self.preprocessors
+            .lock()
+            .unwrap()
+            .insert(model.to_string(), preprocessor);
+    }
+
+    pub fn remove_preprocessor(&self, model: &str) -> Option<Arc<OpenAIPreprocessor>> {
+        self.preprocessors.lock().unwrap().remove(model)
+    }
+
+    pub fn get_preprocessor(&self, model: &str) -> Option<Arc<OpenAIPreprocessor>> {
+        self.preprocessors.lock().unwrap().get(model).cloned()
+    }
+
     pub async fn kv_chooser_for(
         &self,
         model_name: &str,
@@ -200,7 +220,7 @@ impl ModelManager {
             .await
     }
 
-    fn get_kv_chooser(&self, model_name: &str) -> Option<Arc<KvRouter>> {
+    pub fn get_kv_chooser(&self, model_name: &str) -> Option<Arc<KvRouter>> {
         self.kv_choosers.lock().unwrap().get(model_name).cloned()
     }
 
@@ -212,8 +232,19 @@ impl ModelManager {
         kv_cache_block_size: usize,
         kv_router_config: Option<KvRouterConfig>,
     ) -> anyhow::Result<Arc<KvRouter>> {
-        let selector = Box::new(DefaultWorkerSelector::new(kv_router_config));
-        let chooser = KvRouter::new(component.clone(), kv_cache_block_size, Some(selector)).await?;
+        let kv_router_config = kv_router_config.unwrap_or_default();
+        let selector = kv_router_config.worker_selector();
+        let snapshot = kv_router_config
+            .snapshot
+            .clone()
+            .map(|snapshot| snapshot.with_model_name(model_name));
+        let chooser = KvRouter::new_with_config(
+            component.clone(),
+            kv_cache_block_size,
+            Some(selector),
+            &kv_router_config.with_snapshot(snapshot),
+        )
+        .await?;
         let new_kv_chooser = Arc::new(chooser);
         self.kv_choosers
             .lock()
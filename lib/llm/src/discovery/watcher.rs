@@ -19,6 +19,7 @@ use dynamo_runtime::{
 use crate::{
     backend::Backend,
     kv_router::{KvPushRouter, KvRouterConfig},
+    migration::Migration,
     model_type::ModelType,
     preprocessor::{OpenAIPreprocessor, PreprocessedRequest},
     protocols::common::llm_backend::LLMEngineOutput,
@@ -37,6 +38,7 @@ pub struct ModelWatcher {
     router_mode: RouterMode,
     notify_on_model: Notify,
     kv_router_config: Option<KvRouterConfig>,
+    migration_limit: u32,
 }
 
 impl ModelWatcher {
@@ -52,9 +54,17 @@ impl ModelWatcher {
             router_mode,
             notify_on_model: Notify::new(),
             kv_router_config,
+            migration_limit: 0,
         }
     }
 
+    /// Send requests whose worker stream ends early to another worker, up to `migration_limit`
+    /// times per request. Default: 0, requests are not migrated.
+    pub fn with_migration_limit(mut self, migration_limit: u32) -> Self {
+        self.migration_limit = migration_limit;
+        self
+    }
+
     /// Wait until we have at least one chat completions model and return it's name.
     pub async fn wait_for_chat_model(&self) -> String {
         // Loop in case it gets added and immediately deleted
@@ -153,6 +163,7 @@ impl ModelWatcher {
         let _ = self.manager.remove_chat_completions_model(&model_name);
         let _ = self.manager.remove_completions_model(&model_name);
         let _ = self.manager.remove_embeddings_model(&model_name);
+        self.manager.remove_preprocessor(&model_name);
 
         Ok(Some(model_name))
     }
@@ -201,8 +212,10 @@ impl ModelWatcher {
                     SingleIn<NvCreateChatCompletionRequest>,
                     ManyOut<Annotated<NvCreateChatCompletionStreamResponse>>,
                 >::new();
-                let preprocessor = OpenAIPreprocessor::new(card.clone()).await?.into_operator();
+                let chat_preprocessor = OpenAIPreprocessor::new(card.clone()).await?;
+                let preprocessor = chat_preprocessor.into_operator();
                 let backend = Backend::from_mdc(card.clone()).await?.into_operator();
+                let migration = Migration::new(self.migration_limit).into_operator();
                 let router =
                     PushRouter::<PreprocessedRequest, Annotated<LLMEngineOutput>>::from_client(
                         client.clone(),
@@ -231,12 +244,16 @@ impl ModelWatcher {
                 let chat_engine = frontend
                     .link(preprocessor.forward_edge())?
                     .link(backend.forward_edge())?
+                    .link(migration.forward_edge())?
                     .link(service_backend)?
+                    .link(migration.backward_edge())?
                     .link(backend.backward_edge())?
                     .link(preprocessor.backward_edge())?
                     .link(frontend)?;
                 self.manager
                     .add_chat_completions_model(&model_entry.name, chat_engine)?;
+                self.manager
+                    .add_preprocessor(&model_entry.name, chat_preprocessor);
 
                 let frontend = SegmentSource::<
                     SingleIn<NvCreateCompletionRequest>,
@@ -244,6 +261,7 @@ impl ModelWatcher {
                 >::new();
                 let preprocessor = OpenAIPreprocessor::new(card.clone()).await?.into_operator();
                 let backend = Backend::from_mdc(card.clone()).await?.into_operator();
+                let migration = Migration::new(self.migration_limit).into_operator();
                 let router =
                     PushRouter::<PreprocessedRequest, Annotated<LLMEngineOutput>>::from_client(
                         client,
@@ -272,7 +290,9 @@ impl ModelWatcher {
                 let completions_engine = frontend
                     .link(preprocessor.forward_edge())?
                     .link(backend.forward_edge())?
+                    .link(migration.forward_edge())?
                     .link(service_backend)?
+                    .link(migration.backward_edge())?
                     .link(backend.backward_edge())?
                     .link(preprocessor.backward_edge())?
                     .link(frontend)?;
@@ -114,6 +114,7 @@ fn delta_core(tok: u32) -> Annotated<LLMEngineOutput> {
         text: None,
         cum_log_probs: None,
         log_probs: None,
+        top_logprobs: None,
         finish_reason: None,
         index: None,
     };
@@ -202,6 +203,7 @@ impl
                 let inner = deltas.create_choice(0, Some(c.to_string()), None, None);
                 let response = NvCreateChatCompletionStreamResponse {
                     inner,
+                    reasoning_content: Default::default(),
                 };
                 yield Annotated{ id: Some(id.to_string()), data: Some(response), event: None, comment: None };
                 id += 1;
@@ -210,6 +212,7 @@ impl
             let inner = deltas.create_choice(0, None, Some(async_openai::types::FinishReason::Stop), None);
             let response = NvCreateChatCompletionStreamResponse {
                 inner,
+                reasoning_content: Default::default(),
             };
             yield Annotated { id: Some(id.to_string()), data: Some(response), event: None, comment: None };
         };
@@ -0,0 +1,102 @@
+// SPDX-FileCopyrightText: Copyright (c) 2024-2025 NVIDIA CORPORATION & AFFILIATES. All rights reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+//! Guided decoding constrains the output of a request to a format, see [`GuidedDecodingOptions`].
+//!
+//! The constraint is applied by the engine: mistral.rs and the engines in other processes get
+//! the options, in the request or in the
+//! [`crate::protocols::common::preprocessor::PreprocessedRequest`], and use their own guided
+//! decoding. The [`crate::backend::Backend`] checks the complete output with an
+//! [`OutputValidator`], so an engine which ignores the options fails the request instead of
+//! returning an unconstrained output. Regexes the `regex` crate cannot compile, such as ones with
+//! look-around or backreferences, are left to the engine.
+
+pub mod json_schema;
+
+use anyhow::{bail, Context, Result};
+
+use crate::protocols::common::GuidedDecodingOptions;
+
+/// Checks the complete output of a request against its guided decoding options
+pub enum OutputValidator {
+    /// Any JSON object, or a JSON document of the schema
+    Json(Option<serde_json::Value>),
+    Regex(regex::Regex),
+}
+
+impl OutputValidator {
+    /// Grammars are not validated, they are only enforced by the engine. Neither are regexes
+    /// the `regex` crate cannot compile: engines support syntax it lacks, such as look-around.
+    pub fn new(options: &GuidedDecodingOptions) -> Option<Self> {
+        match options {
+            GuidedDecodingOptions::JsonObject => Some(Self::Json(None)),
+            GuidedDecodingOptions::JsonSchema(schema) => Some(Self::Json(Some(schema.clone()))),
+            GuidedDecodingOptions::Regex(pattern) => {
+                match regex::Regex::new(&format!("^(?:{pattern})$")) {
+                    Ok(regex) => Some(Self::Regex(regex)),
+                    Err(err) => {
+                        tracing::warn!(
+                            pattern,
+                            %err,
+                            "Guided decoding regex is not supported, the output is not validated"
+                        );
+                        None
+                    }
+                }
+            }
+            GuidedDecodingOptions::Grammar(_) => None,
+        }
+    }
+
+    pub fn validate(&self, output: &str) -> Result<()> {
+        match self {
+            Self::Json(schema) => {
+                let value: serde_json::Value =
+                    serde_json::from_str(output).context("Output is not valid JSON")?;
+                match schema {
+                    Some(schema) => json_schema::validate(schema, &value)?,
+                    None if !value.is_object() => bail!("Output is not a JSON object"),
+                    None => {}
+                }
+            }
+            Self::Regex(regex) => {
+                if !regex.is_match(output) {
+                    bail!("Output does not match the regex {}", regex.as_str());
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_grammar_is_not_validated() {
+        let options = GuidedDecodingOptions::Grammar("start: \"a\"".to_string());
+        assert!(OutputValidator::new(&options).is_none());
+    }
+
+    #[test]
+    fn test_output_validator() {
+        let validator = OutputValidator::new(&GuidedDecodingOptions::JsonObject).unwrap();
+        assert!(validator.validate(r#"{"a": 1}"#).is_ok());
+        assert!(validator.validate("[1]").is_err());
+        assert!(validator.validate("{").is_err());
+
+        let validator =
+            OutputValidator::new(&GuidedDecodingOptions::Regex("[0-9]+".to_string())).unwrap();
+        assert!(validator.validate("42").is_ok());
+        assert!(validator.validate("42a").is_err());
+    }
+
+    #[test]
+    fn test_unsupported_regex_is_not_validated() {
+        let lookahead = GuidedDecodingOptions::Regex("(?=[a-z]*[0-9])[a-z0-9]+".to_string());
+        assert!(OutputValidator::new(&lookahead).is_none());
+        let backreference = GuidedDecodingOptions::Regex(r"(a)\1".to_string());
+        assert!(OutputValidator::new(&backreference).is_none());
+    }
+}
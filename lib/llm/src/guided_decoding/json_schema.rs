@@ -0,0 +1,187 @@
+// SPDX-FileCopyrightText: Copyright (c) 2024-2025 NVIDIA CORPORATION & AFFILIATES. All rights reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+//! Validates JSON documents against a JSON schema.
+//!
+//! Only a subset of JSON schema is checked: `type`, `const`, `enum`, `anyOf`, `oneOf`, `allOf`,
+//! the `properties`, `required` and `additionalProperties` of objects, the `items`, `minItems`
+//! and `maxItems` of arrays, the `pattern`, `minLength` and `maxLength` of strings and the
+//! `minimum` and `maximum` of numbers.
+
+use anyhow::{bail, Context, Result};
+use serde_json::{Map, Value};
+
+/// Check `value` against the supported subset of `schema`. Unsupported keywords are ignored.
+pub fn validate(schema: &Value, value: &Value) -> Result<()> {
+    validate_at(schema, value, "$")
+}
+
+fn validate_at(schema: &Value, value: &Value, path: &str) -> Result<()> {
+    let schema = match schema {
+        Value::Bool(true) => return Ok(()),
+        Value::Bool(false) => bail!("{path}: no value is allowed"),
+        Value::Object(schema) => schema,
+        _ => bail!("Invalid JSON schema: {schema}"),
+    };
+
+    if let Some(expected) = schema.get("const") {
+        if value != expected {
+            bail!("{path}: expected {expected}");
+        }
+    }
+    if let Some(Value::Array(values)) = schema.get("enum") {
+        if !values.contains(value) {
+            bail!("{path}: {value} is not one of the enum values");
+        }
+    }
+    for key in ["anyOf", "oneOf"] {
+        if let Some(Value::Array(schemas)) = schema.get(key) {
+            if !schemas
+                .iter()
+                .any(|schema| validate_at(schema, value, path).is_ok())
+            {
+                bail!("{path}: does not match any of the {key} schemas");
+            }
+        }
+    }
+    if let Some(Value::Array(schemas)) = schema.get("allOf") {
+        for schema in schemas {
+            validate_at(schema, value, path)?;
+        }
+    }
+
+    match schema.get("type") {
+        Some(Value::String(ty)) if !has_type(ty, value) => {
+            bail!("{path}: expected a value of type {ty}")
+        }
+        Some(Value::Array(types))
+            if !types
+                .iter()
+                .any(|ty| ty.as_str().is_some_and(|ty| has_type(ty, value))) =>
+        {
+            bail!(
+                "{path}: expected a value of one of the types {}",
+                Value::Array(types.clone())
+            )
+        }
+        _ => {}
+    }
+
+    match value {
+        Value::String(string) => {
+            let length = string.chars().count();
+            if get_usize(schema, "minLength")?.is_some_and(|min| length < min) {
+                bail!("{path}: string is shorter than minLength");
+            }
+            if get_usize(schema, "maxLength")?.is_some_and(|max| length > max) {
+                bail!("{path}: string is longer than maxLength");
+            }
+            if let Some(Value::String(pattern)) = schema.get("pattern") {
+                let regex = regex::Regex::new(pattern)
+                    .with_context(|| format!("Invalid JSON schema pattern {pattern}"))?;
+                if !regex.is_match(string) {
+                    bail!("{path}: string does not match the pattern {pattern}");
+                }
+            }
+        }
+        Value::Number(number) => {
+            let number = number.as_f64().unwrap_or(f64::NAN);
+            if let Some(minimum) = schema.get("minimum").and_then(Value::as_f64) {
+                if number < minimum {
+                    bail!("{path}: {number} is less than the minimum {minimum}");
+                }
+            }
+            if let Some(maximum) = schema.get("maximum").and_then(Value::as_f64) {
+                if number > maximum {
+                    bail!("{path}: {number} is greater than the maximum {maximum}");
+                }
+            }
+        }
+        Value::Array(items) => {
+            if get_usize(schema, "minItems")?.is_some_and(|min| items.len() < min) {
+                bail!("{path}: array has fewer items than minItems");
+            }
+            if get_usize(schema, "maxItems")?.is_some_and(|max| items.len() > max) {
+                bail!("{path}: array has more items than maxItems");
+            }
+            if let Some(item_schema) = schema.get("items") {
+                for (index, item) in items.iter().enumerate() {
+                    validate_at(item_schema, item, &format!("{path}[{index}]"))?;
+                }
+            }
+        }
+        Value::Object(object) => {
+            if let Some(Value::Array(required)) = schema.get("required") {
+                for name in required.iter().filter_map(Value::as_str) {
+                    if !object.contains_key(name) {
+                        bail!("{path}: missing required property {name}");
+                    }
+                }
+            }
+            let properties = schema.get("properties").and_then(Value::as_object);
+            for (name, value) in object {
+                let property_path = format!("{path}.{name}");
+                match properties.and_then(|properties| properties.get(name)) {
+                    Some(property_schema) => validate_at(property_schema, value, &property_path)?,
+                    None => match schema.get("additionalProperties") {
+                        Some(Value::Bool(false)) => {
+                            bail!("{path}: additional property {name} is not allowed")
+                        }
+                        Some(additional) => validate_at(additional, value, &property_path)?,
+                        None => {}
+                    },
+                }
+            }
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+fn has_type(ty: &str, value: &Value) -> bool {
+    match ty {
+        "string" => value.is_string(),
+        "integer" => {
+            value.is_i64() || value.is_u64() || value.as_f64().is_some_and(|n| n.fract() == 0.0)
+        }
+        "number" => value.is_number(),
+        "boolean" => value.is_boolean(),
+        "null" => value.is_null(),
+        "array" => value.is_array(),
+        "object" => value.is_object(),
+        _ => false,
+    }
+}
+
+fn get_usize(schema: &Map<String, Value>, key: &str) -> Result<Option<usize>> {
+    schema
+        .get(key)
+        .map(|value| {
+            value
+                .as_u64()
+                .map(|value| value as usize)
+                .with_context(|| format!("JSON schema {key} must be a non-negative integer"))
+        })
+        .transpose()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_validate() {
+        let schema = json!({
+            "type": "object",
+            "properties": {"n": {"type": "integer", "minimum": 0}},
+            "required": ["n"],
+            "additionalProperties": false
+        });
+        assert!(validate(&schema, &json!({"n": 3})).is_ok());
+        assert!(validate(&schema, &json!({"n": -3})).is_err());
+        assert!(validate(&schema, &json!({"n": "3"})).is_err());
+        assert!(validate(&schema, &json!({})).is_err());
+        assert!(validate(&schema, &json!({"n": 3, "m": 4})).is_err());
+    }
+}
@@ -20,10 +20,15 @@
 
 mod openai;
 
+pub mod auth;
 pub mod error;
 pub mod health;
+pub mod kv_router_debug;
 pub mod metrics;
+pub mod rate_limit;
+pub mod response_store;
 pub mod service_v2;
+pub mod tls;
 
 pub use axum;
 pub use metrics::Metrics;
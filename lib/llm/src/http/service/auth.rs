@@ -0,0 +1,286 @@
+// SPDX-FileCopyrightText: Copyright (c) 2024-2025 NVIDIA CORPORATION & AFFILIATES. All rights reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+//! Bearer token authentication for the OpenAI endpoints.
+//!
+//! Every API key has a name, used in logs and metrics instead of the key itself, and optionally
+//! the list of models it may use. Keys are loaded from a JSON file, or from the
+//! [`API_KEYS_BUCKET`] bucket of a [`KeyValueStore`], which is re-read periodically so keys can be
+//! added and revoked without a restart.
+
+use std::{
+    collections::{HashMap, HashSet},
+    path::{Path, PathBuf},
+    str::FromStr,
+    sync::{Arc, RwLock},
+    time::Duration,
+};
+
+use anyhow::Context;
+use axum::{
+    extract::{Request, State},
+    http::header::AUTHORIZATION,
+    middleware::Next,
+    response::{IntoResponse, Response},
+    Extension,
+};
+use dynamo_runtime::storage::key_value_store::KeyValueStore;
+use serde::{Deserialize, Serialize};
+use tokio_util::sync::CancellationToken;
+
+use super::openai::ErrorResponse;
+
+/// The key-value store bucket API keys are read from, one [`ApiKey`] per entry.
+pub const API_KEYS_BUCKET: &str = "http_api_keys";
+
+/// How often API keys are re-read from the key-value store by default.
+pub const DEFAULT_API_KEYS_REFRESH_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Value of the `auth` metrics label when authentication is disabled.
+pub const AUTH_LABEL_NONE: &str = "none";
+
+/// Where API keys are loaded from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ApiKeysSource {
+    /// A JSON file with a list of [`ApiKey`].
+    File(PathBuf),
+    /// The [`API_KEYS_BUCKET`] bucket of the runtime's etcd.
+    Etcd,
+}
+
+impl FromStr for ApiKeysSource {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "" => anyhow::bail!("API keys source cannot be empty"),
+            "etcd" => Ok(ApiKeysSource::Etcd),
+            path => Ok(ApiKeysSource::File(PathBuf::from(path))),
+        }
+    }
+}
+
+/// A key allowed to use the HTTP service.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ApiKey {
+    /// The bearer token clients send
+    pub key: String,
+
+    /// Identifies the key in logs and metrics
+    pub name: String,
+
+    /// The models this key may use. `None` allows every model.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub models: Option<HashSet<String>>,
+}
+
+impl ApiKey {
+    pub fn allows(&self, model: &str) -> bool {
+        self.models
+            .as_ref()
+            .is_none_or(|models| models.contains(model))
+    }
+}
+
+/// The set of valid API keys. Can be replaced while the service runs.
+#[derive(Debug, Default)]
+pub struct ApiKeys {
+    keys: RwLock<HashMap<String, Arc<ApiKey>>>,
+}
+
+impl ApiKeys {
+    pub fn new(keys: Vec<ApiKey>) -> Self {
+        let this = Self::default();
+        this.replace(keys);
+        this
+    }
+
+    /// Load keys from a JSON file containing a list of [`ApiKey`].
+    pub fn from_file(path: &Path) -> anyhow::Result<Self> {
+        let json = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed reading API keys from {path:?}"))?;
+        let keys: Vec<ApiKey> = serde_json::from_str(&json)
+            .with_context(|| format!("Failed parsing API keys in {path:?}"))?;
+        Ok(Self::new(keys))
+    }
+
+    /// Replace all keys with those in the [`API_KEYS_BUCKET`] bucket of `store`.
+    pub async fn reload(&self, store: &dyn KeyValueStore) -> anyhow::Result<()> {
+        let mut keys = Vec::new();
+        if let Some(bucket) = store.get_bucket(API_KEYS_BUCKET).await? {
+            for (entry, json) in bucket.entries().await? {
+                match serde_json::from_slice::<ApiKey>(&json) {
+                    Ok(key) => keys.push(key),
+                    Err(err) => tracing::warn!(entry, %err, "Ignoring invalid API key entry"),
+                }
+            }
+        }
+        self.replace(keys);
+        Ok(())
+    }
+
+    pub fn replace(&self, keys: Vec<ApiKey>) {
+        let keys = keys
+            .into_iter()
+            .map(|key| (key.key.clone(), Arc::new(key)))
+            .collect();
+        *self.keys.write().unwrap() = keys;
+    }
+
+    pub fn get(&self, key: &str) -> Option<Arc<ApiKey>> {
+        self.keys.read().unwrap().get(key).cloned()
+    }
+
+    pub fn len(&self) -> usize {
+        self.keys.read().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+/// Periodically re-read the keys from `store` until `cancel` is triggered.
+pub async fn refresh_loop(
+    keys: Arc<ApiKeys>,
+    store: Box<dyn KeyValueStore>,
+    interval: Duration,
+    cancel: CancellationToken,
+) {
+    let mut ticker = tokio::time::interval(interval);
+    loop {
+        tokio::select! {
+            _ = cancel.cancelled() => return,
+            _ = ticker.tick() => {}
+        }
+        if let Err(err) = keys.reload(store.as_ref()).await {
+            // keep serving with the keys we have
+            tracing::warn!(%err, "Failed to reload API keys");
+        }
+    }
+}
+
+/// The metrics `auth` label of a request: the name of its API key.
+pub fn auth_label(api_key: &Option<Extension<Arc<ApiKey>>>) -> &str {
+    match api_key {
+        Some(Extension(api_key)) => &api_key.name,
+        None => AUTH_LABEL_NONE,
+    }
+}
+
+/// Reject requests without a valid API key with a 401. The key of admitted requests is added
+/// to their extensions.
+pub async fn auth_middleware(
+    State(keys): State<Arc<ApiKeys>>,
+    mut request: Request,
+    next: Next,
+) -> Response {
+    let token = request
+        .headers()
+        .get(AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "))
+        .map(str::trim);
+    let Some(token) = token else {
+        return ErrorResponse::unauthorized("Missing API key").into_response();
+    };
+    let Some(api_key) = keys.get(token) else {
+        return ErrorResponse::unauthorized("Invalid API key").into_response();
+    };
+    tracing::trace!(api_key = api_key.name, "Authenticated request");
+    request.extensions_mut().insert(api_key);
+    next.run(request).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use dynamo_runtime::storage::key_value_store::MemoryStorage;
+
+    fn api_key(key: &str, name: &str, models: Option<&[&str]>) -> ApiKey {
+        ApiKey {
+            key: key.to_string(),
+            name: name.to_string(),
+            models: models.map(|models| models.iter().map(|m| m.to_string()).collect()),
+        }
+    }
+
+    #[test]
+    fn test_api_keys_source_from_str() {
+        assert_eq!(
+            "etcd".parse::<ApiKeysSource>().unwrap(),
+            ApiKeysSource::Etcd
+        );
+        assert_eq!(
+            "keys.json".parse::<ApiKeysSource>().unwrap(),
+            ApiKeysSource::File(PathBuf::from("keys.json"))
+        );
+        assert!("".parse::<ApiKeysSource>().is_err());
+    }
+
+    #[test]
+    fn test_api_key_allows() {
+        let keys = ApiKeys::new(vec![
+            api_key("sk-1", "all", None),
+            api_key("sk-2", "llama-only", Some(&["llama"])),
+        ]);
+        assert!(keys.get("sk-3").is_none());
+
+        let all = keys.get("sk-1").unwrap();
+        assert!(all.allows("llama") && all.allows("mistral"));
+
+        let llama_only = keys.get("sk-2").unwrap();
+        assert_eq!(llama_only.name, "llama-only");
+        assert!(llama_only.allows("llama"));
+        assert!(!llama_only.allows("mistral"));
+    }
+
+    #[test]
+    fn test_api_keys_from_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("keys.json");
+        std::fs::write(
+            &path,
+            r#"[{"key": "sk-1", "name": "team-a", "models": ["llama"]}, {"key": "sk-2", "name": "admin"}]"#,
+        )
+        .unwrap();
+
+        let keys = ApiKeys::from_file(&path).unwrap();
+        assert_eq!(keys.len(), 2);
+        assert_eq!(
+            *keys.get("sk-1").unwrap(),
+            api_key("sk-1", "team-a", Some(&["llama"]))
+        );
+        assert!(keys.get("sk-2").unwrap().models.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_api_keys_reload() {
+        let storage = MemoryStorage::new();
+        let keys = ApiKeys::new(vec![api_key("sk-old", "old", None)]);
+
+        let bucket = storage
+            .get_or_create_bucket(API_KEYS_BUCKET, None)
+            .await
+            .unwrap();
+        let team_a = api_key("sk-1", "team-a", None);
+        bucket
+            .insert(
+                "team-a".to_string(),
+                serde_json::to_string(&team_a).unwrap(),
+                0,
+            )
+            .await
+            .unwrap();
+        bucket
+            .insert("broken".to_string(), "not json".to_string(), 0)
+            .await
+            .unwrap();
+
+        keys.reload(&storage).await.unwrap();
+        // revoked keys are dropped and invalid entries skipped
+        assert_eq!(keys.len(), 1);
+        assert!(keys.get("sk-old").is_none());
+        assert_eq!(*keys.get("sk-1").unwrap(), team_a);
+    }
+}
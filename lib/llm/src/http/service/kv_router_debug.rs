@@ -0,0 +1,131 @@
+// SPDX-FileCopyrightText: Copyright (c) 2024-2025 NVIDIA CORPORATION & AFFILIATES. All rights reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+//! Debug route explaining where the KV router would send a request, without sending it.
+
+use std::sync::Arc;
+
+use axum::{
+    http::{Method, StatusCode},
+    response::IntoResponse,
+    routing::post,
+    Extension, Json, Router,
+};
+use serde::{Deserialize, Serialize};
+
+use super::{
+    auth::ApiKey,
+    error::HttpError,
+    openai::{check_model_access, ErrorResponse},
+    service_v2, RouteDoc,
+};
+use crate::kv_router::protocols::RoutingDecision;
+
+/// Either `prompt` or `token_ids` must be set. A prompt is tokenized with the model's tokenizer
+/// as is, without applying the chat template.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct KvRouterDebugRequest {
+    pub model: String,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub prompt: Option<String>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub token_ids: Option<Vec<u32>>,
+
+    /// The LoRA adapter to explain the routing for; `None` for the adapter of the model's card, as
+    /// requests to the model are routed with.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub lora_id: Option<u64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct KvRouterDebugResponse {
+    pub model: String,
+
+    /// Number of tokens in the request
+    pub isl_tokens: usize,
+
+    #[serde(flatten)]
+    pub decision: RoutingDecision,
+}
+
+pub fn kv_router_debug_router(
+    state: Arc<service_v2::State>,
+    path: Option<String>,
+) -> (Vec<RouteDoc>, Router) {
+    let path = path.unwrap_or_else(|| "/debug/kv_router".to_string());
+
+    let docs: Vec<RouteDoc> = vec![RouteDoc::new(Method::POST, &path)];
+
+    let router = Router::new()
+        .route(&path, post(kv_router_debug_handler))
+        .with_state(state);
+
+    (docs, router)
+}
+
+async fn kv_router_debug_handler(
+    axum::extract::State(state): axum::extract::State<Arc<service_v2::State>>,
+    api_key: Option<Extension<Arc<ApiKey>>>,
+    Json(request): Json<KvRouterDebugRequest>,
+) -> Result<impl IntoResponse, (StatusCode, Json<ErrorResponse>)> {
+    check_model_access(&api_key, &request.model)?;
+
+    let manager = state.manager();
+    let Some(kv_router) = manager.get_kv_chooser(&request.model) else {
+        if manager.has_model_any(&request.model) {
+            return Err(bad_request(format!(
+                "Model {} is not routed by the KV router",
+                request.model
+            )));
+        }
+        return Err(ErrorResponse::model_not_found());
+    };
+
+    let preprocessor = manager.get_preprocessor(&request.model);
+    let token_ids = match (request.prompt, request.token_ids) {
+        (Some(_), Some(_)) => {
+            return Err(bad_request("Only one of prompt or token_ids can be set"));
+        }
+        (None, None) => return Err(bad_request("One of prompt or token_ids must be set")),
+        (None, Some(token_ids)) => token_ids,
+        (Some(prompt), None) => {
+            let Some(preprocessor) = &preprocessor else {
+                return Err(bad_request(format!(
+                    "No tokenizer for model {}; pass token_ids instead",
+                    request.model
+                )));
+            };
+            preprocessor
+                .tokenize(&prompt)
+                .map_err(|err| ErrorResponse::from_anyhow(err, "Failed to tokenize prompt"))?
+                .token_ids
+        }
+    };
+    if token_ids.is_empty() {
+        return Err(bad_request("The request has no tokens"));
+    }
+
+    let lora_id = request
+        .lora_id
+        .or_else(|| preprocessor.and_then(|preprocessor| preprocessor.lora_id()))
+        .unwrap_or(0);
+    let decision = kv_router
+        .explain(&token_ids, lora_id)
+        .await
+        .map_err(|err| ErrorResponse::from_anyhow(err, "Failed to explain routing decision"))?;
+
+    Ok(Json(KvRouterDebugResponse {
+        model: request.model,
+        isl_tokens: token_ids.len(),
+        decision,
+    }))
+}
+
+fn bad_request(message: impl Into<String>) -> (StatusCode, Json<ErrorResponse>) {
+    ErrorResponse::from_http_error(HttpError {
+        code: 400,
+        message: message.into(),
+    })
+}
@@ -2,7 +2,9 @@
 // SPDX-License-Identifier: Apache-2.0
 
 use axum::{extract::State, http::StatusCode, response::IntoResponse, routing::get, Router};
-use prometheus::{Encoder, HistogramOpts, HistogramVec, IntCounterVec, IntGaugeVec, Opts};
+use prometheus::{
+    core::Collector, Encoder, HistogramOpts, HistogramVec, IntCounterVec, IntGaugeVec, Opts,
+};
 use std::{
     sync::Arc,
     time::{Duration, Instant},
@@ -10,7 +12,7 @@ use std::{
 
 pub use prometheus::Registry;
 
-use super::RouteDoc;
+use super::{rate_limit::TokenQuota, RouteDoc};
 
 /// Value for the `status` label in the request counter for successful requests
 pub const REQUEST_STATUS_SUCCESS: &str = "success";
@@ -27,11 +29,13 @@ pub const REQUEST_TYPE_UNARY: &str = "unary";
 pub struct Metrics {
     request_counter: IntCounterVec,
     inflight_gauge: IntGaugeVec,
+    client_disconnects: IntCounterVec,
     request_duration: HistogramVec,
     input_sequence_length: HistogramVec,
     output_sequence_length: HistogramVec,
     time_to_first_token: HistogramVec,
     inter_token_latency: HistogramVec,
+    token_counter: IntCounterVec,
 }
 
 /// RAII object for inflight gauge and request counters
@@ -41,6 +45,7 @@ pub struct Metrics {
 pub struct InflightGuard {
     metrics: Arc<Metrics>,
     model: String,
+    auth: String,
     endpoint: Endpoint,
     request_type: RequestType,
     status: Status,
@@ -58,6 +63,9 @@ pub enum Endpoint {
 
     /// OAI Embeddings
     Embeddings,
+
+    /// OAI Responses
+    Responses,
 }
 
 /// Metrics for the HTTP service
@@ -69,6 +77,18 @@ pub enum RequestType {
     Stream,
 }
 
+/// The tokens of the requests are counted by type
+pub enum TokenType {
+    /// The tokens of the prompts
+    Prompt,
+
+    /// The generated tokens
+    Completion,
+
+    /// The tokens of the prompts the KV router expected the workers to have cached
+    CachedPrompt,
+}
+
 /// Status
 pub enum Status {
     Success,
@@ -86,7 +106,11 @@ pub struct ResponseMetricCollector {
     // we track the last response time so that ITL for the newly returned tokens can
     // be computed.
     last_response_time: Option<Duration>,
+    isl: usize,
     osl: usize,
+    cached_tokens: usize,
+    // charged with the request's ISL + OSL on drop, when the tenant has a token limit
+    token_quota: Option<TokenQuota>,
 }
 
 impl Default for Metrics {
@@ -100,18 +124,20 @@ impl Metrics {
     /// The following metrics will be created:
     /// - `{prefix}_http_service_requests_total` - IntCounterVec for the total number of requests processed
     /// - `{prefix}_http_service_inflight_requests` - IntGaugeVec for the number of inflight requests
+    /// - `{prefix}_http_service_client_disconnects_total` - IntCounterVec for the number of requests cancelled because the client disconnected
     /// - `{prefix}_http_service_request_duration_seconds` - HistogramVec for the duration of requests
     /// - `{prefix}_http_service_input_sequence_tokens` - HistogramVec for input sequence length in tokens
     /// - `{prefix}_http_service_output_sequence_tokens` - HistogramVec for output sequence length in tokens
     /// - `{prefix}_http_service_time_to_first_token_seconds` - HistogramVec for time to first token in seconds
     /// - `{prefix}_http_service_inter_token_latency_seconds` - HistogramVec for inter-token latency in seconds
+    /// - `{prefix}_http_service_tokens_total` - IntCounterVec for the number of prompt, completion and cached prompt tokens
     pub fn new(prefix: &str) -> Self {
         let request_counter = IntCounterVec::new(
             Opts::new(
                 format!("{}_http_service_requests_total", prefix),
                 "Total number of LLM requests processed",
             ),
-            &["model", "endpoint", "request_type", "status"],
+            &["model", "endpoint", "request_type", "status", "auth"],
         )
         .unwrap();
 
@@ -120,7 +146,16 @@ impl Metrics {
                 format!("{}_http_service_inflight_requests", prefix),
                 "Number of inflight requests",
             ),
-            &["model"],
+            &["model", "auth"],
+        )
+        .unwrap();
+
+        let client_disconnects = IntCounterVec::new(
+            Opts::new(
+                format!("{}_http_service_client_disconnects_total", prefix),
+                "Number of requests cancelled because the client disconnected",
+            ),
+            &["model", "endpoint"],
         )
         .unwrap();
 
@@ -186,18 +221,29 @@ impl Metrics {
         )
         .unwrap();
 
+        let token_counter = IntCounterVec::new(
+            Opts::new(
+                format!("{}_http_service_tokens_total", prefix),
+                "Number of tokens of the requests, by type",
+            ),
+            &["model", "type"],
+        )
+        .unwrap();
+
         Metrics {
             request_counter,
             inflight_gauge,
+            client_disconnects,
             request_duration,
             input_sequence_length,
             output_sequence_length,
             time_to_first_token,
             inter_token_latency,
+            token_counter,
         }
     }
 
-    /// Get the number of successful requests for the given dimensions:
+    /// Get the number of successful requests for the given dimensions, across all API keys:
     /// - model
     /// - endpoint (completions/chat_completions)
     /// - request type (unary/stream)
@@ -209,14 +255,15 @@ impl Metrics {
         request_type: &RequestType,
         status: &Status,
     ) -> u64 {
-        self.request_counter
-            .with_label_values(&[
-                model,
-                endpoint.as_str(),
-                request_type.as_str(),
-                status.as_str(),
-            ])
-            .get()
+        let labels = [
+            ("model", model),
+            ("endpoint", endpoint.as_str()),
+            ("request_type", request_type.as_str()),
+            ("status", status.as_str()),
+        ];
+        sum_matching(&self.request_counter, &labels, |m| {
+            m.get_counter().get_value()
+        }) as u64
     }
 
     /// Increment the counter for requests for the given dimensions:
@@ -224,12 +271,14 @@ impl Metrics {
     /// - endpoint (completions/chat_completions)
     /// - request type (unary/stream)
     /// - status (success/error)
+    /// - auth (the name of the API key, or `none` without authentication)
     fn inc_request_counter(
         &self,
         model: &str,
         endpoint: &Endpoint,
         request_type: &RequestType,
         status: &Status,
+        auth: &str,
     ) {
         self.request_counter
             .with_label_values(&[
@@ -237,36 +286,62 @@ impl Metrics {
                 endpoint.as_str(),
                 request_type.as_str(),
                 status.as_str(),
+                auth,
             ])
             .inc()
     }
 
-    /// Get the number if inflight requests for the given model
+    /// Get the number if inflight requests for the given model, across all API keys
     pub fn get_inflight_count(&self, model: &str) -> i64 {
-        self.inflight_gauge.with_label_values(&[model]).get()
+        sum_matching(&self.inflight_gauge, &[("model", model)], |m| {
+            m.get_gauge().get_value()
+        }) as i64
+    }
+
+    /// Get the number of requests to the given model and endpoint that were cancelled because
+    /// the client disconnected
+    pub fn get_client_disconnects(&self, model: &str, endpoint: &Endpoint) -> u64 {
+        self.client_disconnects
+            .with_label_values(&[model, endpoint.as_str()])
+            .get()
+    }
+
+    /// Get the number of tokens of the given type of the requests to the given model
+    pub fn get_token_counter(&self, model: &str, token_type: &TokenType) -> u64 {
+        self.token_counter
+            .with_label_values(&[model, token_type.as_str()])
+            .get()
     }
 
-    fn inc_inflight_gauge(&self, model: &str) {
-        self.inflight_gauge.with_label_values(&[model]).inc()
+    fn inc_token_counter(&self, model: &str, token_type: &TokenType, tokens: usize) {
+        self.token_counter
+            .with_label_values(&[model, token_type.as_str()])
+            .inc_by(tokens as u64)
     }
 
-    fn dec_inflight_gauge(&self, model: &str) {
-        self.inflight_gauge.with_label_values(&[model]).dec()
+    fn inc_inflight_gauge(&self, model: &str, auth: &str) {
+        self.inflight_gauge.with_label_values(&[model, auth]).inc()
+    }
+
+    fn dec_inflight_gauge(&self, model: &str, auth: &str) {
+        self.inflight_gauge.with_label_values(&[model, auth]).dec()
     }
 
     pub fn register(&self, registry: &Registry) -> Result<(), prometheus::Error> {
         registry.register(Box::new(self.request_counter.clone()))?;
         registry.register(Box::new(self.inflight_gauge.clone()))?;
+        registry.register(Box::new(self.client_disconnects.clone()))?;
         registry.register(Box::new(self.request_duration.clone()))?;
         registry.register(Box::new(self.input_sequence_length.clone()))?;
         registry.register(Box::new(self.output_sequence_length.clone()))?;
         registry.register(Box::new(self.time_to_first_token.clone()))?;
         registry.register(Box::new(self.inter_token_latency.clone()))?;
+        registry.register(Box::new(self.token_counter.clone()))?;
         Ok(())
     }
 
     /// Create a new [`InflightGuard`] for the given model and annotate if its a streaming request,
-    /// and the kind of endpoint that was hit
+    /// the kind of endpoint that was hit and the API key it was made with
     ///
     /// The [`InflightGuard`] is an RAII object will handle incrementing the inflight gauge and
     /// request counters.
@@ -275,6 +350,7 @@ impl Metrics {
         model: &str,
         endpoint: Endpoint,
         streaming: bool,
+        auth: &str,
     ) -> InflightGuard {
         let request_type = if streaming {
             RequestType::Stream
@@ -285,6 +361,7 @@ impl Metrics {
         InflightGuard::new(
             self.clone(),
             model.to_string().to_lowercase(),
+            auth.to_string(),
             endpoint,
             request_type,
         )
@@ -300,6 +377,7 @@ impl InflightGuard {
     fn new(
         metrics: Arc<Metrics>,
         model: String,
+        auth: String,
         endpoint: Endpoint,
         request_type: RequestType,
     ) -> Self {
@@ -307,12 +385,13 @@ impl InflightGuard {
         let timer = Instant::now();
 
         // Increment the inflight gauge when the guard is created
-        metrics.inc_inflight_gauge(&model);
+        metrics.inc_inflight_gauge(&model, &auth);
 
         // Return the RAII Guard
         InflightGuard {
             metrics,
             model,
+            auth,
             endpoint,
             request_type,
             status: Status::Error,
@@ -323,12 +402,21 @@ impl InflightGuard {
     pub(crate) fn mark_ok(&mut self) {
         self.status = Status::Success;
     }
+
+    /// The client disconnected before the response was complete, so the request was cancelled.
+    /// It is still counted as an error.
+    pub(crate) fn mark_disconnected(&mut self) {
+        self.metrics
+            .client_disconnects
+            .with_label_values(&[&self.model, self.endpoint.as_str()])
+            .inc();
+    }
 }
 
 impl Drop for InflightGuard {
     fn drop(&mut self) {
         // Decrement the gauge when the guard is dropped
-        self.metrics.dec_inflight_gauge(&self.model);
+        self.metrics.dec_inflight_gauge(&self.model, &self.auth);
 
         // the frequency on incrementing the full request counter is relatively low
         // if we were incrementing the counter on every forward pass, we'd use static CounterVec or
@@ -338,6 +426,7 @@ impl Drop for InflightGuard {
             &self.endpoint,
             &self.request_type,
             &self.status,
+            &self.auth,
         );
 
         // Record the duration of the request
@@ -354,6 +443,7 @@ impl std::fmt::Display for Endpoint {
             Endpoint::Completions => write!(f, "completions"),
             Endpoint::ChatCompletions => write!(f, "chat_completions"),
             Endpoint::Embeddings => write!(f, "embeddings"),
+            Endpoint::Responses => write!(f, "responses"),
         }
     }
 }
@@ -364,6 +454,7 @@ impl Endpoint {
             Endpoint::Completions => "completions",
             Endpoint::ChatCompletions => "chat_completions",
             Endpoint::Embeddings => "embeddings",
+            Endpoint::Responses => "responses",
         }
     }
 }
@@ -377,6 +468,16 @@ impl RequestType {
     }
 }
 
+impl TokenType {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TokenType::Prompt => "prompt",
+            TokenType::Completion => "completion",
+            TokenType::CachedPrompt => "cached_prompt",
+        }
+    }
+}
+
 impl Status {
     pub fn as_str(&self) -> &'static str {
         match self {
@@ -394,20 +495,35 @@ impl ResponseMetricCollector {
             is_first_token: true,
             last_response_time: None,
             start_time: Instant::now(),
+            isl: 0,
             osl: 0,
+            cached_tokens: 0,
+            token_quota: None,
         }
     }
 
+    /// Charge the request's input and output tokens to this quota once the response is done
+    pub fn with_token_quota(mut self, token_quota: Option<TokenQuota>) -> Self {
+        self.token_quota = token_quota;
+        self
+    }
+
     /// Observe the current output sequence length
     pub fn observe_current_osl(&mut self, osl: usize) {
         self.osl = osl;
     }
 
+    /// Observe the number of input tokens the worker was expected to have cached
+    pub fn observe_cached_tokens(&mut self, cached_tokens: usize) {
+        self.cached_tokens = cached_tokens;
+    }
+
     /// Observe a response with input sequence length and number of new tokens
     pub fn observe_response(&mut self, isl: usize, num_tokens: usize) {
         if num_tokens == 0 {
             return;
         }
+        self.isl = isl;
 
         if self.is_first_token {
             // NOTE: when there are multiple tokens in the first response,
@@ -453,9 +569,43 @@ impl Drop for ResponseMetricCollector {
             .output_sequence_length
             .with_label_values(&[&self.model])
             .observe(self.osl as f64);
+
+        // Count the tokens of the request
+        self.metrics
+            .inc_token_counter(&self.model, &TokenType::Prompt, self.isl);
+        self.metrics
+            .inc_token_counter(&self.model, &TokenType::Completion, self.osl);
+        self.metrics
+            .inc_token_counter(&self.model, &TokenType::CachedPrompt, self.cached_tokens);
+
+        if let Some(token_quota) = self.token_quota.take() {
+            token_quota.charge(self.isl + self.osl);
+        }
     }
 }
 
+/// Sum a metric over the series whose labels match all of `labels`, ignoring other labels
+fn sum_matching(
+    collector: &impl Collector,
+    labels: &[(&str, &str)],
+    value: impl Fn(&prometheus::proto::Metric) -> f64,
+) -> f64 {
+    collector
+        .collect()
+        .iter()
+        .flat_map(|family| family.get_metric())
+        .filter(|metric| {
+            labels.iter().all(|(name, expected)| {
+                metric
+                    .get_label()
+                    .iter()
+                    .any(|label| label.get_name() == *name && label.get_value() == *expected)
+            })
+        })
+        .map(value)
+        .sum()
+}
+
 /// Create a new router with the given path
 pub fn router(registry: Registry, path: Option<String>) -> (Vec<RouteDoc>, Router) {
     let registry = Arc::new(registry);
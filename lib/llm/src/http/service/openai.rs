@@ -2,14 +2,14 @@
 // SPDX-License-Identifier: Apache-2.0
 
 use axum::{
-    extract::State,
-    http::StatusCode,
+    extract::{Path, State},
+    http::{header::RETRY_AFTER, HeaderMap, StatusCode},
     response::{
         sse::{Event, KeepAlive, Sse},
         IntoResponse, Response,
     },
     routing::{get, post},
-    Json, Router,
+    Extension, Json, Router,
 };
 use futures::{Stream, StreamExt};
 use serde::{Deserialize, Serialize};
@@ -22,15 +22,21 @@ use std::{
 use tokio_stream::wrappers::ReceiverStream;
 
 use super::{
+    auth::{auth_label, ApiKey},
     error::HttpError,
     metrics::{Endpoint, InflightGuard, ResponseMetricCollector},
+    rate_limit::{RateLimitExceeded, TokenQuota},
+    response_store::{ResponseStore, StoredResponse},
     service_v2, RouteDoc,
 };
 
-use crate::preprocessor::LLMMetricAnnotation;
+use crate::preprocessor::{ContextLengthExceeded, LLMMetricAnnotation};
 use crate::protocols::openai::embeddings::{NvCreateEmbeddingRequest, NvCreateEmbeddingResponse};
 use crate::protocols::openai::{
-    chat_completions::NvCreateChatCompletionResponse, completions::NvCreateCompletionResponse,
+    chat_completions::NvCreateChatCompletionResponse,
+    completions::NvCreateCompletionResponse,
+    nvext::NvExt,
+    responses::{NvCreateResponseRequest, ResponseGenerator},
 };
 use crate::request_template::RequestTemplate;
 use crate::types::{
@@ -40,11 +46,18 @@ use crate::types::{
     Annotated,
 };
 
+use dynamo_runtime::logging::TraceContext;
 use dynamo_runtime::pipeline::{AsyncEngineContext, Context};
 
+/// The header with the Unix time, in milliseconds, by which a request must be complete
+const REQUEST_DEADLINE_HEADER: &str = "x-request-deadline";
+
 #[derive(Serialize, Deserialize)]
 pub(crate) struct ErrorResponse {
     error: String,
+    /// Machine readable reason of the error, for the errors the OpenAI API has a code for
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    code: Option<String>,
 }
 
 impl ErrorResponse {
@@ -54,6 +67,18 @@ impl ErrorResponse {
             StatusCode::NOT_FOUND,
             Json(ErrorResponse {
                 error: "Model not found".to_string(),
+                code: None,
+            }),
+        )
+    }
+
+    /// Not Found Error for a stored response of the Responses API
+    pub fn response_not_found(id: &str) -> (StatusCode, Json<ErrorResponse>) {
+        (
+            StatusCode::NOT_FOUND,
+            Json(ErrorResponse {
+                error: format!("Response {id} not found"),
+                code: None,
             }),
         )
     }
@@ -65,10 +90,38 @@ impl ErrorResponse {
             StatusCode::SERVICE_UNAVAILABLE,
             Json(ErrorResponse {
                 error: "Service is not ready".to_string(),
+                code: None,
             }),
         )
     }
 
+    /// Unauthorized
+    /// Return this error when a request has no valid API key.
+    pub fn unauthorized(msg: &str) -> (StatusCode, Json<ErrorResponse>) {
+        (
+            StatusCode::UNAUTHORIZED,
+            Json(ErrorResponse {
+                error: msg.to_string(),
+                code: None,
+            }),
+        )
+    }
+
+    /// Too Many Requests
+    /// Return this error when a tenant is over its rate limit. `Retry-After` tells the client
+    /// when to try again.
+    pub fn rate_limited(err: &RateLimitExceeded) -> Response {
+        (
+            StatusCode::TOO_MANY_REQUESTS,
+            [(RETRY_AFTER, err.retry_after_secs().to_string())],
+            Json(ErrorResponse {
+                error: err.to_string(),
+                code: None,
+            }),
+        )
+            .into_response()
+    }
+
     /// Internal Service Error
     /// Return this error when the service encounters an internal error.
     /// We should return a generic message to the client instead of the real error.
@@ -79,6 +132,21 @@ impl ErrorResponse {
             StatusCode::INTERNAL_SERVER_ERROR,
             Json(ErrorResponse {
                 error: msg.to_string(),
+                code: None,
+            }),
+        )
+    }
+
+    /// Bad Request
+    /// Return this error when the prompt of a request does not fit in the context of the model.
+    pub fn context_length_exceeded(
+        err: &ContextLengthExceeded,
+    ) -> (StatusCode, Json<ErrorResponse>) {
+        (
+            StatusCode::BAD_REQUEST,
+            Json(ErrorResponse {
+                error: err.to_string(),
+                code: Some("context_length_exceeded".to_string()),
             }),
         )
     }
@@ -88,6 +156,9 @@ impl ErrorResponse {
     /// If successful, it will return the [`HttpError`] as an [`ErrorResponse::internal_server_error`]
     /// with the details of the error.
     pub fn from_anyhow(err: anyhow::Error, alt_msg: &str) -> (StatusCode, Json<ErrorResponse>) {
+        if let Some(err) = err.downcast_ref::<ContextLengthExceeded>() {
+            return ErrorResponse::context_length_exceeded(err);
+        }
         match err.downcast::<HttpError>() {
             Ok(http_error) => ErrorResponse::from_http_error(http_error),
             Err(err) => ErrorResponse::internal_server_error(&format!("{alt_msg}: {err}")),
@@ -100,7 +171,13 @@ impl ErrorResponse {
             return ErrorResponse::internal_server_error(&err.message);
         }
         match StatusCode::from_u16(err.code) {
-            Ok(code) => (code, Json(ErrorResponse { error: err.message })),
+            Ok(code) => (
+                code,
+                Json(ErrorResponse {
+                    error: err.message,
+                    code: None,
+                }),
+            ),
             Err(_) => ErrorResponse::internal_server_error(&err.message),
         }
     }
@@ -108,7 +185,10 @@ impl ErrorResponse {
 
 impl From<HttpError> for ErrorResponse {
     fn from(err: HttpError) -> Self {
-        ErrorResponse { error: err.message }
+        ErrorResponse {
+            error: err.message,
+            code: None,
+        }
     }
 }
 
@@ -123,20 +203,38 @@ impl From<HttpError> for ErrorResponse {
 #[tracing::instrument(skip_all)]
 async fn completions(
     State(state): State<Arc<service_v2::State>>,
-    Json(request): Json<NvCreateCompletionRequest>,
+    api_key: Option<Extension<Arc<ApiKey>>>,
+    quota: Option<Extension<TokenQuota>>,
+    headers: HeaderMap,
+    Json(mut request): Json<NvCreateCompletionRequest>,
 ) -> Result<Response, (StatusCode, Json<ErrorResponse>)> {
     // return a 503 if the service is not ready
     check_ready(&state)?;
 
+    apply_deadline_header(&headers, &mut request.nvext)?;
+
     // todo - extract distributed tracing id and context id from headers
     let request_id = uuid::Uuid::new_v4().to_string();
 
     // todo - decide on default
     let streaming = request.inner.stream.unwrap_or(false);
 
+    // with `best_of`, the `n` choices to return are only known once every choice is complete
+    let best_of_n = match (request.inner.best_of, request.inner.n.unwrap_or(1)) {
+        (Some(best_of), n) if best_of > n => Some(n as usize),
+        _ => None,
+    };
+    if streaming && best_of_n.is_some() {
+        return Err(ErrorResponse::from_http_error(HttpError {
+            code: 400,
+            message: "best_of greater than n is not supported when streaming".to_string(),
+        }));
+    }
+
     // update the request to always stream
     let inner = async_openai::types::CreateCompletionRequest {
         stream: Some(true),
+        stream_options: stream_options(streaming, request.inner.stream_options),
         ..request.inner
     };
 
@@ -149,22 +247,30 @@ async fn completions(
     // todo - when optional, if none, apply a default
     let model = &request.inner.model;
 
+    check_model_access(&api_key, model)?;
+
     // todo - error handling should be more robust
     let engine = state
         .manager()
         .get_completions_engine(model)
         .map_err(|_| ErrorResponse::model_not_found())?;
 
-    let mut inflight_guard =
-        state
-            .metrics_clone()
-            .create_inflight_guard(model, Endpoint::Completions, streaming);
+    let inflight_guard = state.metrics_clone().create_inflight_guard(
+        model,
+        Endpoint::Completions,
+        streaming,
+        auth_label(&api_key),
+    );
 
-    let mut response_collector = state.metrics_clone().create_response_collector(model);
+    let mut response_collector = state
+        .metrics_clone()
+        .create_response_collector(model)
+        .with_token_quota(quota.map(|Extension(quota)| quota));
 
     // setup context
     // todo - inherit request_id from distributed trace details
-    let request = Context::with_id(request, request_id.clone());
+    let mut request = Context::with_id(request, request_id.clone());
+    request.set_trace_context(trace_context(&headers));
 
     // issue the generate call on the engine
     let stream = engine
@@ -182,6 +288,9 @@ async fn completions(
         let stream = stream.map(move |response| {
             process_event_converter(EventConverter::from(response), &mut response_collector)
         });
+        let stream = stream.chain(futures::stream::once(async {
+            Ok(Event::default().data("[DONE]"))
+        }));
         let stream = monitor_for_disconnects(stream.boxed(), ctx, inflight_guard).await;
 
         let mut sse_stream = Sse::new(stream);
@@ -192,17 +301,26 @@ async fn completions(
 
         Ok(sse_stream.into_response())
     } else {
-        // TODO: report ISL/OSL for non-streaming requests
-        let response = NvCreateCompletionResponse::from_annotated_stream(stream.into())
-            .await
-            .map_err(|e| {
-                tracing::error!(
-                    "Failed to fold completions stream for {}: {:?}",
-                    request_id,
-                    e
-                );
-                ErrorResponse::internal_server_error("Failed to fold completions stream")
-            })?;
+        let stream = stream.map(move |response| {
+            observe_response_metrics(&response, &mut response_collector);
+            response
+        });
+        let disconnect_guard = DisconnectGuard::new(ctx, inflight_guard);
+        let response = match best_of_n {
+            Some(n) => {
+                NvCreateCompletionResponse::from_annotated_stream_best_of(Box::pin(stream), n).await
+            }
+            None => NvCreateCompletionResponse::from_annotated_stream(Box::pin(stream)).await,
+        };
+        let mut inflight_guard = disconnect_guard.disarm();
+        let response = response.map_err(|e| {
+            tracing::error!(
+                "Failed to fold completions stream for {}: {:?}",
+                request_id,
+                e
+            );
+            ErrorResponse::internal_server_error("Failed to fold completions stream")
+        })?;
 
         inflight_guard.mark_ok();
         Ok(Json(response).into_response())
@@ -212,6 +330,9 @@ async fn completions(
 #[tracing::instrument(skip_all)]
 async fn embeddings(
     State(state): State<Arc<service_v2::State>>,
+    api_key: Option<Extension<Arc<ApiKey>>>,
+    quota: Option<Extension<TokenQuota>>,
+    headers: HeaderMap,
     Json(request): Json<NvCreateEmbeddingRequest>,
 ) -> Result<Response, (StatusCode, Json<ErrorResponse>)> {
     // return a 503 if the service is not ready
@@ -227,6 +348,8 @@ async fn embeddings(
     // todo - when optional, if none, apply a default
     let model = &request.inner.model;
 
+    check_model_access(&api_key, model)?;
+
     // todo - error handling should be more robust
     let engine = state
         .manager()
@@ -234,14 +357,17 @@ async fn embeddings(
         .map_err(|_| ErrorResponse::model_not_found())?;
 
     // this will increment the inflight gauge for the model
-    let mut inflight =
-        state
-            .metrics_clone()
-            .create_inflight_guard(model, Endpoint::Embeddings, streaming);
+    let mut inflight = state.metrics_clone().create_inflight_guard(
+        model,
+        Endpoint::Embeddings,
+        streaming,
+        auth_label(&api_key),
+    );
 
     // setup context
     // todo - inherit request_id from distributed trace details
-    let request = Context::with_id(request, request_id.clone());
+    let mut request = Context::with_id(request, request_id.clone());
+    request.set_trace_context(trace_context(&headers));
 
     // issue the generate call on the engine
     let stream = engine
@@ -262,6 +388,10 @@ async fn embeddings(
             ErrorResponse::internal_server_error("Failed to fold embeddings stream")
         })?;
 
+    if let Some(Extension(quota)) = quota {
+        quota.charge(response.inner.usage.total_tokens as usize);
+    }
+
     inflight.mark_ok();
     Ok(Json(response).into_response())
 }
@@ -277,11 +407,16 @@ async fn embeddings(
 #[tracing::instrument(skip_all)]
 async fn chat_completions(
     State((state, template)): State<(Arc<service_v2::State>, Option<RequestTemplate>)>,
+    api_key: Option<Extension<Arc<ApiKey>>>,
+    quota: Option<Extension<TokenQuota>>,
+    headers: HeaderMap,
     Json(mut request): Json<NvCreateChatCompletionRequest>,
 ) -> Result<Response, (StatusCode, Json<ErrorResponse>)> {
     // return a 503 if the service is not ready
     check_ready(&state)?;
 
+    apply_deadline_header(&headers, &mut request.nvext)?;
+
     // Apply template values if present
     if let Some(template) = template {
         if request.inner.model.is_empty() {
@@ -305,6 +440,7 @@ async fn chat_completions(
     // update the request to always stream
     let inner_request = async_openai::types::CreateChatCompletionRequest {
         stream: Some(true),
+        stream_options: stream_options(streaming, request.inner.stream_options),
         ..request.inner
     };
 
@@ -320,21 +456,29 @@ async fn chat_completions(
     // todo - determine the proper error code for when a request model is not present
     tracing::trace!("Getting chat completions engine for model: {}", model);
 
+    check_model_access(&api_key, model)?;
+
     let engine = state
         .manager()
         .get_chat_completions_engine(model)
         .map_err(|_| ErrorResponse::model_not_found())?;
 
-    let mut inflight_guard =
-        state
-            .metrics_clone()
-            .create_inflight_guard(model, Endpoint::ChatCompletions, streaming);
+    let inflight_guard = state.metrics_clone().create_inflight_guard(
+        model,
+        Endpoint::ChatCompletions,
+        streaming,
+        auth_label(&api_key),
+    );
 
-    let mut response_collector = state.metrics_clone().create_response_collector(model);
+    let mut response_collector = state
+        .metrics_clone()
+        .create_response_collector(model)
+        .with_token_quota(quota.map(|Extension(quota)| quota));
 
     // setup context
     // todo - inherit request_id from distributed trace details
-    let request = Context::with_id(request, request_id.clone());
+    let mut request = Context::with_id(request, request_id.clone());
+    request.set_trace_context(trace_context(&headers));
 
     tracing::trace!("Issuing generate call for chat completions");
 
@@ -354,6 +498,9 @@ async fn chat_completions(
         let stream = stream.map(move |response| {
             process_event_converter(EventConverter::from(response), &mut response_collector)
         });
+        let stream = stream.chain(futures::stream::once(async {
+            Ok(Event::default().data("[DONE]"))
+        }));
         let stream = monitor_for_disconnects(stream.boxed(), ctx, inflight_guard).await;
 
         let mut sse_stream = Sse::new(stream);
@@ -364,26 +511,266 @@ async fn chat_completions(
 
         Ok(sse_stream.into_response())
     } else {
-        // TODO: report ISL/OSL for non-streaming requests
-        let response = NvCreateChatCompletionResponse::from_annotated_stream(stream.into())
-            .await
-            .map_err(|e| {
-                tracing::error!(
-                    request_id,
-                    "Failed to fold chat completions stream for: {:?}",
-                    e
-                );
-                ErrorResponse::internal_server_error(&format!(
-                    "Failed to fold chat completions stream: {}",
-                    e
-                ))
-            })?;
+        let stream = stream.map(move |response| {
+            observe_response_metrics(&response, &mut response_collector);
+            response
+        });
+        let disconnect_guard = DisconnectGuard::new(ctx, inflight_guard);
+        let response =
+            NvCreateChatCompletionResponse::from_annotated_stream(Box::pin(stream)).await;
+        let mut inflight_guard = disconnect_guard.disarm();
+        let response = response.map_err(|e| {
+            tracing::error!(
+                request_id,
+                "Failed to fold chat completions stream for: {:?}",
+                e
+            );
+            ErrorResponse::internal_server_error(&format!(
+                "Failed to fold chat completions stream: {}",
+                e
+            ))
+        })?;
 
         inflight_guard.mark_ok();
         Ok(Json(response).into_response())
     }
 }
 
+/// OpenAI Responses Request Handler
+///
+/// This method will handle the incoming request for the `/v1/responses` endpoint. Responses are generated by the
+/// chat completions engine of the model: the conversation of `previous_response_id`, the instructions and the input
+/// are converted to chat messages, and the chat completion deltas are converted back to Responses output items and
+/// streaming events. Unless `store` is false, the response is stored so it can be retrieved and continued.
+#[tracing::instrument(skip_all)]
+async fn responses(
+    State((state, store, template)): State<(
+        Arc<service_v2::State>,
+        Arc<ResponseStore>,
+        Option<RequestTemplate>,
+    )>,
+    api_key: Option<Extension<Arc<ApiKey>>>,
+    quota: Option<Extension<TokenQuota>>,
+    headers: HeaderMap,
+    Json(mut request): Json<NvCreateResponseRequest>,
+) -> Result<Response, (StatusCode, Json<ErrorResponse>)> {
+    // return a 503 if the service is not ready
+    check_ready(&state)?;
+
+    // Apply template values if present
+    if let Some(template) = template {
+        if request.model.is_empty() {
+            request.model = template.model.clone();
+        }
+        if request.temperature.unwrap_or(0.0) == 0.0 {
+            request.temperature = Some(template.temperature);
+        }
+        if request.max_output_tokens.unwrap_or(0) == 0 {
+            request.max_output_tokens = Some(template.max_completion_tokens);
+        }
+    }
+    tracing::trace!("Received responses request: {:?}", request);
+
+    let request_id = uuid::Uuid::new_v4().to_string();
+    let streaming = request.stream.unwrap_or(false);
+    let model = request.model.clone();
+
+    check_model_access(&api_key, &model)?;
+
+    // the conversation so far, followed by the new input
+    let mut input = match &request.previous_response_id {
+        Some(id) => {
+            let previous = store
+                .get(id)
+                .await
+                .map_err(|e| ErrorResponse::from_anyhow(e, "Failed to load previous response"))?
+                .filter(|previous| previous.is_visible_to(api_key.as_deref().map(Arc::as_ref)))
+                .ok_or_else(|| {
+                    ErrorResponse::from_http_error(HttpError {
+                        code: 400,
+                        message: format!("Previous response {id} not found"),
+                    })
+                })?;
+            previous.conversation()
+        }
+        None => Vec::new(),
+    };
+    input.extend(request.input_items());
+
+    let mut chat_request = request.to_chat_request(&input).map_err(|e| {
+        ErrorResponse::from_http_error(HttpError {
+            code: 400,
+            message: format!("Invalid responses request: {e}"),
+        })
+    })?;
+    apply_deadline_header(&headers, &mut chat_request.nvext)?;
+
+    let engine = state
+        .manager()
+        .get_chat_completions_engine(&model)
+        .map_err(|_| ErrorResponse::model_not_found())?;
+
+    let inflight_guard = state.metrics_clone().create_inflight_guard(
+        &model,
+        Endpoint::Responses,
+        streaming,
+        auth_label(&api_key),
+    );
+
+    let mut response_collector = state
+        .metrics_clone()
+        .create_response_collector(&model)
+        .with_token_quota(quota.map(|Extension(quota)| quota));
+
+    let created_at = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs();
+    let response = request.response(
+        format!("resp_{}", uuid::Uuid::new_v4().simple()),
+        created_at,
+    );
+    let store_response = response.store;
+    let owner = api_key
+        .as_ref()
+        .map(|Extension(api_key)| api_key.name.clone());
+    let mut generator = ResponseGenerator::new(response);
+
+    let mut request = Context::with_id(chat_request, request_id.clone());
+    request.set_trace_context(trace_context(&headers));
+
+    tracing::trace!("Issuing generate call for responses");
+
+    let mut stream = engine
+        .generate(request)
+        .await
+        .map_err(|e| ErrorResponse::from_anyhow(e, "Failed to generate response"))?;
+
+    // capture the context to cancel the stream if the client disconnects
+    let ctx = stream.context();
+
+    if streaming {
+        let stream = async_stream::stream! {
+            for event in generator.start() {
+                yield event;
+            }
+            while let Some(response) = stream.next().await {
+                observe_response_metrics(&response, &mut response_collector);
+                match response.ok() {
+                    Ok(Annotated { data: Some(chunk), .. }) => {
+                        for event in generator.process(&chunk) {
+                            yield event;
+                        }
+                    }
+                    Ok(_) => {}
+                    Err(err) => {
+                        generator.fail(err);
+                        break;
+                    }
+                }
+            }
+            for event in generator.finish() {
+                yield event;
+            }
+            if store_response {
+                let stored = StoredResponse {
+                    input,
+                    response: generator.into_response(),
+                    owner,
+                };
+                if let Err(err) = store.put(&stored).await {
+                    tracing::error!(request_id, %err, "Failed to store response");
+                }
+            }
+        }
+        .map(|event| Event::default().event(event.kind.name()).json_data(event));
+        let stream = monitor_for_disconnects(stream.boxed(), ctx, inflight_guard).await;
+
+        let mut sse_stream = Sse::new(stream);
+
+        if let Some(keep_alive) = state.sse_keep_alive() {
+            sse_stream = sse_stream.keep_alive(KeepAlive::default().interval(keep_alive));
+        }
+
+        Ok(sse_stream.into_response())
+    } else {
+        let disconnect_guard = DisconnectGuard::new(ctx, inflight_guard);
+        while let Some(response) = stream.next().await {
+            observe_response_metrics(&response, &mut response_collector);
+            match response.ok() {
+                Ok(Annotated {
+                    data: Some(chunk), ..
+                }) => {
+                    generator.process(&chunk);
+                }
+                Ok(_) => {}
+                Err(err) => {
+                    generator.fail(err);
+                    break;
+                }
+            }
+        }
+        generator.finish();
+        let mut inflight_guard = disconnect_guard.disarm();
+
+        let response = generator.into_response();
+        if let Some(error) = &response.error {
+            tracing::error!(request_id, "Failed to generate response: {}", error.message);
+            return Err(ErrorResponse::internal_server_error(&format!(
+                "Failed to generate response: {}",
+                error.message
+            )));
+        }
+        let stored = StoredResponse {
+            input,
+            response,
+            owner,
+        };
+        if store_response {
+            store
+                .put(&stored)
+                .await
+                .map_err(|e| ErrorResponse::from_anyhow(e, "Failed to store response"))?;
+        }
+
+        inflight_guard.mark_ok();
+        Ok(Json(stored.response).into_response())
+    }
+}
+
+/// Retrieve a stored response of the `/v1/responses` endpoint
+async fn get_response(
+    State((state, store, _)): State<(
+        Arc<service_v2::State>,
+        Arc<ResponseStore>,
+        Option<RequestTemplate>,
+    )>,
+    api_key: Option<Extension<Arc<ApiKey>>>,
+    Path(id): Path<String>,
+) -> Result<Response, (StatusCode, Json<ErrorResponse>)> {
+    check_ready(&state)?;
+
+    let stored = store
+        .get(&id)
+        .await
+        .map_err(|e| ErrorResponse::from_anyhow(e, "Failed to load response"))?
+        .filter(|stored| stored.is_visible_to(api_key.as_deref().map(Arc::as_ref)))
+        .ok_or_else(|| ErrorResponse::response_not_found(&id))?;
+    Ok(Json(stored.response).into_response())
+}
+
+/// Requests with an API key may only use the models the key allows. Other models are reported
+/// as not found, so a key cannot discover models it has no access to.
+pub(crate) fn check_model_access(
+    api_key: &Option<Extension<Arc<ApiKey>>>,
+    model: &str,
+) -> Result<(), (StatusCode, Json<ErrorResponse>)> {
+    match api_key {
+        Some(Extension(api_key)) if !api_key.allows(model) => Err(ErrorResponse::model_not_found()),
+        _ => Ok(()),
+    }
+}
+
 // todo - abstract this to the top level lib.rs to be reused
 // todo - move the service_observer to its own state/arc
 fn check_ready(_state: &Arc<service_v2::State>) -> Result<(), (StatusCode, Json<ErrorResponse>)> {
@@ -408,6 +795,7 @@ fn check_ready(_state: &Arc<service_v2::State>) -> Result<(), (StatusCode, Json<
 /// }
 async fn list_models_openai(
     State(state): State<Arc<service_v2::State>>,
+    api_key: Option<Extension<Arc<ApiKey>>>,
 ) -> Result<Response, (StatusCode, Json<ErrorResponse>)> {
     check_ready(&state)?;
 
@@ -419,6 +807,9 @@ async fn list_models_openai(
 
     let models: HashSet<String> = state.manager().model_display_names();
     for model_name in models {
+        if check_model_access(&api_key, &model_name).is_err() {
+            continue;
+        }
         data.push(ModelListing {
             id: model_name.clone(),
             object: "object",
@@ -449,9 +840,13 @@ struct ModelListing {
 }
 
 /// This method will consume a stream of SSE events and forward them to a new stream defined by a tokio channel.
+/// The stream should end with the terminating event of its protocol, such as `[DONE]`.
 /// In this way, if the downstream is dropped, then the upstream will be unable to send any more events. This is
 /// how we can monitor for disconnects and stop the generation of completions.
 ///
+/// The channel is watched while waiting on the upstream too, so a client that disconnects during a long prefill
+/// is noticed straight away rather than when the first token arrives.
+///
 /// If a disconnect is detected, then the context will issue a `stop_generating` call to the context which will
 /// propagate the cancellation signal to the backend.
 async fn monitor_for_disconnects(
@@ -465,7 +860,23 @@ async fn monitor_for_disconnects(
 
     tokio::spawn(async move {
         let mut stream = stream;
-        while let Some(event) = stream.next().await {
+        loop {
+            let event = tokio::select! {
+                biased;
+
+                _ = tx.closed() => {
+                    tracing::trace!(request_id = context.id(), "Client disconnected; stopping generation");
+                    context.stop_generating();
+                    inflight_guard.mark_disconnected();
+                    return;
+                }
+
+                event = stream.next() => match event {
+                    Some(event) => event,
+                    None => break,
+                },
+            };
+
             let event = match event {
                 Ok(event) => Ok(event),
                 Err(err) => Ok(Event::default().event("error").comment(err.to_string())),
@@ -474,19 +885,54 @@ async fn monitor_for_disconnects(
             if (tx.send(event).await).is_err() {
                 tracing::trace!("Forwarding SSE stream was dropped; breaking loop");
                 context.stop_generating();
-                break;
+                inflight_guard.mark_disconnected();
+                return;
             }
         }
 
         // Stream completed successfully - mark as ok
-        if tx.send(Ok(Event::default().data("[DONE]"))).await.is_ok() {
-            inflight_guard.mark_ok();
-        }
+        inflight_guard.mark_ok();
     });
 
     ReceiverStream::new(rx)
 }
 
+/// Stops generation if the client of a unary request disconnects. axum drops the handler future
+/// when the connection closes, and with it this guard. A completed request disarms it first.
+struct DisconnectGuard {
+    context: Arc<dyn AsyncEngineContext>,
+    inflight_guard: Option<InflightGuard>,
+}
+
+impl DisconnectGuard {
+    fn new(context: Arc<dyn AsyncEngineContext>, inflight_guard: InflightGuard) -> Self {
+        Self {
+            context,
+            inflight_guard: Some(inflight_guard),
+        }
+    }
+
+    /// The response is complete, hand back the inflight guard
+    fn disarm(mut self) -> InflightGuard {
+        self.inflight_guard
+            .take()
+            .expect("the inflight guard is only taken once")
+    }
+}
+
+impl Drop for DisconnectGuard {
+    fn drop(&mut self) {
+        if let Some(mut inflight_guard) = self.inflight_guard.take() {
+            tracing::trace!(
+                request_id = self.context.id(),
+                "Client disconnected; stopping generation"
+            );
+            self.context.stop_generating();
+            inflight_guard.mark_disconnected();
+        }
+    }
+}
+
 struct EventConverter<T>(Annotated<T>);
 
 impl<T> From<Annotated<T>> for EventConverter<T> {
@@ -495,6 +941,77 @@ impl<T> From<Annotated<T>> for EventConverter<T> {
     }
 }
 
+/// The W3C trace context of the `traceparent` and `tracestate` headers of a request. The span
+/// of the handler joins the caller's trace, and the request carries it on to the workers.
+fn trace_context(headers: &HeaderMap) -> Option<TraceContext> {
+    let header = |name: &str| headers.get(name).and_then(|value| value.to_str().ok());
+    let trace_context = TraceContext::new(
+        header(TraceContext::TRACEPARENT_HEADER)?,
+        header(TraceContext::TRACESTATE_HEADER).map(str::to_string),
+    )?;
+    trace_context.attach(&tracing::Span::current());
+    Some(trace_context)
+}
+
+/// Move the deadline of a request to the Unix time, in milliseconds, of its
+/// [`REQUEST_DEADLINE_HEADER`] header, if that is earlier than the `deadline_ms` of its `nvext`.
+fn apply_deadline_header(
+    headers: &HeaderMap,
+    nvext: &mut Option<NvExt>,
+) -> Result<(), (StatusCode, Json<ErrorResponse>)> {
+    let Some(value) = headers.get(REQUEST_DEADLINE_HEADER) else {
+        return Ok(());
+    };
+    let deadline_ms: u64 = value
+        .to_str()
+        .ok()
+        .and_then(|value| value.trim().parse().ok())
+        .ok_or_else(|| {
+            ErrorResponse::from_http_error(HttpError {
+                code: 400,
+                message: format!(
+                    "Invalid {REQUEST_DEADLINE_HEADER} header, expected a Unix time in milliseconds"
+                ),
+            })
+        })?;
+    let nvext = nvext.get_or_insert_with(NvExt::default);
+    nvext.deadline_ms = Some(
+        nvext
+            .deadline_ms
+            .map_or(deadline_ms, |current| current.min(deadline_ms)),
+    );
+    Ok(())
+}
+
+/// The stream options of the streaming request a request is served with. Unary responses always
+/// have the usage, which they are folded with from the last response of the stream.
+fn stream_options(
+    streaming: bool,
+    options: Option<async_openai::types::ChatCompletionStreamOptions>,
+) -> Option<async_openai::types::ChatCompletionStreamOptions> {
+    if streaming {
+        options
+    } else {
+        Some(async_openai::types::ChatCompletionStreamOptions {
+            include_usage: true,
+        })
+    }
+}
+
+/// Record the token counts of a response, if it carries them. Returns whether it did.
+fn observe_response_metrics<T>(
+    annotated: &Annotated<T>,
+    response_collector: &mut ResponseMetricCollector,
+) -> bool {
+    let Ok(Some(metrics)) = LLMMetricAnnotation::from_annotation(annotated) else {
+        return false;
+    };
+    response_collector.observe_current_osl(metrics.output_tokens);
+    response_collector.observe_cached_tokens(metrics.cached_tokens);
+    response_collector.observe_response(metrics.input_tokens, metrics.chunk_tokens);
+    true
+}
+
 fn process_event_converter<T: Serialize>(
     annotated: EventConverter<T>,
     response_collector: &mut ResponseMetricCollector,
@@ -502,10 +1019,7 @@ fn process_event_converter<T: Serialize>(
     let mut annotated = annotated.0;
 
     // update metrics
-    if let Ok(Some(metrics)) = LLMMetricAnnotation::from_annotation(&annotated) {
-        response_collector.observe_current_osl(metrics.output_tokens);
-        response_collector.observe_response(metrics.input_tokens, metrics.chunk_tokens);
-
+    if observe_response_metrics(&annotated, response_collector) {
         // Chomp the LLMMetricAnnotation so it's not returned in the response stream
         // TODO: add a flag to control what is returned in the SSE stream
         if annotated.event.as_deref() == Some(crate::preprocessor::ANNOTATION_LLM_METRICS) {
@@ -568,6 +1082,27 @@ pub fn chat_completions_router(
     (vec![doc], router)
 }
 
+/// Create an Axum [`Router`] for the OpenAI API Responses endpoints
+/// If not path is provided, the default path is `/v1/responses`. Stored responses are served at `{path}/{id}`.
+pub fn responses_router(
+    state: Arc<service_v2::State>,
+    store: Arc<ResponseStore>,
+    template: Option<RequestTemplate>,
+    path: Option<String>,
+) -> (Vec<RouteDoc>, Router) {
+    let path = path.unwrap_or("/v1/responses".to_string());
+    let get_path = format!("{path}/{{id}}");
+    let docs = vec![
+        RouteDoc::new(axum::http::Method::POST, &path),
+        RouteDoc::new(axum::http::Method::GET, &get_path),
+    ];
+    let router = Router::new()
+        .route(&path, post(responses))
+        .route(&get_path, get(get_response))
+        .with_state((state, store, template));
+    (docs, router)
+}
+
 /// Create an Axum [`Router`] for the OpenAI API Embeddings endpoint
 /// If not path is provided, the default path is `/v1/embeddings`
 pub fn embeddings_router(
@@ -657,4 +1192,60 @@ mod tests {
             )
         );
     }
+
+    #[test]
+    fn test_context_length_exceeded_response_from_anyhow() {
+        let err = anyhow::Error::new(ContextLengthExceeded {
+            context_length: 8,
+            prompt_tokens: 10,
+        });
+        let (status, response) = ErrorResponse::from_anyhow(err, BACKUP_ERROR_MESSAGE);
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(response.code.as_deref(), Some("context_length_exceeded"));
+    }
+
+    #[test]
+    fn test_deadline_header() {
+        let mut headers = HeaderMap::new();
+        let mut nvext = None;
+        apply_deadline_header(&headers, &mut nvext).unwrap();
+        assert!(nvext.is_none());
+
+        headers.insert(REQUEST_DEADLINE_HEADER, "2000".parse().unwrap());
+        apply_deadline_header(&headers, &mut nvext).unwrap();
+        assert_eq!(nvext.as_ref().unwrap().deadline_ms, Some(2000));
+
+        // the earlier deadline wins
+        let mut nvext = Some(NvExt::builder().deadline_ms(1000).build().unwrap());
+        apply_deadline_header(&headers, &mut nvext).unwrap();
+        assert_eq!(nvext.unwrap().deadline_ms, Some(1000));
+
+        headers.insert(REQUEST_DEADLINE_HEADER, "soon".parse().unwrap());
+        let (status, _) = apply_deadline_header(&headers, &mut None).unwrap_err();
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+    }
+
+    #[test]
+    fn test_trace_context_headers() {
+        let mut headers = HeaderMap::new();
+        assert!(trace_context(&headers).is_none());
+
+        headers.insert(
+            TraceContext::TRACEPARENT_HEADER,
+            "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01"
+                .parse()
+                .unwrap(),
+        );
+        headers.insert(
+            TraceContext::TRACESTATE_HEADER,
+            "vendor=value".parse().unwrap(),
+        );
+        let context = trace_context(&headers).unwrap();
+        assert_eq!(context.trace_id(), "4bf92f3577b34da6a3ce929d0e0e4736");
+        assert_eq!(context.tracestate.as_deref(), Some("vendor=value"));
+
+        // a malformed traceparent is ignored
+        headers.insert(TraceContext::TRACEPARENT_HEADER, "00-xyz".parse().unwrap());
+        assert!(trace_context(&headers).is_none());
+    }
 }
@@ -0,0 +1,467 @@
+// SPDX-FileCopyrightText: Copyright (c) 2024-2025 NVIDIA CORPORATION & AFFILIATES. All rights reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+//! Per-tenant rate limits for the OpenAI endpoints.
+//!
+//! A tenant is identified by its API key (`Authorization: Bearer <key>`), or by the value of a
+//! configurable header. Each tenant gets two token buckets: one for requests per second, which
+//! is checked and charged when a request arrives, and one for tokens per minute. Token usage is
+//! only known once a response is complete, so a request is admitted while the tenant's token
+//! bucket is not empty and its input plus output tokens are charged afterwards. A large response
+//! can therefore push the bucket into debt, delaying the tenant's next requests.
+//!
+//! Tenants whose buckets have fully refilled are forgotten by [`sweep_loop`], so the number of
+//! tracked tenants stays bounded without scanning them on the request path.
+
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use axum::{
+    extract::{Request, State},
+    http::HeaderMap,
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use serde::{Deserialize, Serialize};
+use tokio_util::sync::CancellationToken;
+
+use super::{auth::ApiKey, openai::ErrorResponse};
+
+/// The tenant of requests that carry no key.
+pub const ANONYMOUS_TENANT: &str = "anonymous";
+
+/// How often [`sweep_loop`] forgets idle tenants by default.
+pub const DEFAULT_TENANT_SWEEP_INTERVAL: Duration = Duration::from_secs(60);
+
+/// The limits of one tenant. `None` is unlimited.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct RateLimits {
+    /// Sustained requests per second. Bursts of up to one second's worth are allowed.
+    #[serde(default)]
+    pub requests_per_second: Option<f64>,
+
+    /// Sustained input plus output tokens per minute. Bursts of up to one minute's worth are
+    /// allowed.
+    #[serde(default)]
+    pub tokens_per_minute: Option<u64>,
+}
+
+impl RateLimits {
+    /// Limits must be positive; a limit of zero would never refill.
+    pub fn validate(&self) -> anyhow::Result<()> {
+        if let Some(rps) = self.requests_per_second {
+            if !(rps.is_finite() && rps > 0.0) {
+                anyhow::bail!("requests_per_second must be a positive number, got {rps}");
+            }
+        }
+        if self.tokens_per_minute == Some(0) {
+            anyhow::bail!("tokens_per_minute must be positive, got 0");
+        }
+        Ok(())
+    }
+}
+
+/// Rate limiting configuration for the HTTP service.
+#[derive(Debug, Clone, Default)]
+pub struct RateLimitConfig {
+    /// The header identifying the tenant. `None` uses the API key of the `Authorization` header.
+    pub key_header: Option<String>,
+
+    /// The limits of tenants without their own entry in `tenant_limits`.
+    pub limits: RateLimits,
+
+    /// Limits of specific tenants, by key.
+    pub tenant_limits: HashMap<String, RateLimits>,
+}
+
+impl RateLimitConfig {
+    pub fn new(limits: RateLimits) -> Self {
+        Self {
+            limits,
+            ..Default::default()
+        }
+    }
+
+    pub fn with_key_header(mut self, key_header: Option<String>) -> Self {
+        self.key_header = key_header;
+        self
+    }
+
+    pub fn with_tenant_limits(mut self, tenant_limits: HashMap<String, RateLimits>) -> Self {
+        self.tenant_limits = tenant_limits;
+        self
+    }
+
+    fn limits_for(&self, tenant: &str) -> RateLimits {
+        self.tenant_limits
+            .get(tenant)
+            .copied()
+            .unwrap_or(self.limits)
+    }
+}
+
+/// Which of a tenant's limits rejected a request.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, strum::Display)]
+pub enum RateLimitKind {
+    #[strum(serialize = "requests per second")]
+    Requests,
+    #[strum(serialize = "tokens per minute")]
+    Tokens,
+}
+
+#[derive(Debug, Clone, PartialEq, thiserror::Error)]
+#[error("Rate limit reached for {kind}. Please try again in {}s.", self.retry_after_secs())]
+pub struct RateLimitExceeded {
+    pub kind: RateLimitKind,
+    pub retry_after: Duration,
+}
+
+impl RateLimitExceeded {
+    /// The value of the `Retry-After` header, in whole seconds.
+    pub fn retry_after_secs(&self) -> u64 {
+        self.retry_after.as_secs_f64().ceil().max(1.0) as u64
+    }
+}
+
+struct TokenBucket {
+    capacity: f64,
+    refill_per_sec: f64,
+    available: f64,
+    updated: Instant,
+}
+
+impl TokenBucket {
+    fn new(capacity: f64, refill_per_sec: f64, now: Instant) -> Self {
+        Self {
+            capacity,
+            refill_per_sec,
+            available: capacity,
+            updated: now,
+        }
+    }
+
+    fn refill(&mut self, now: Instant) {
+        let elapsed = now.saturating_duration_since(self.updated).as_secs_f64();
+        self.available = (self.available + elapsed * self.refill_per_sec).min(self.capacity);
+        self.updated = now;
+    }
+
+    /// How long until `amount` is available, or `None` if it already is. A bucket that does not
+    /// refill never has it.
+    fn wait_for(&mut self, amount: f64, now: Instant) -> Option<Duration> {
+        self.refill(now);
+        if self.available >= amount {
+            return None;
+        }
+        if self.refill_per_sec <= 0.0 {
+            return Some(Duration::MAX);
+        }
+        let missing = amount - self.available;
+        Some(Duration::try_from_secs_f64(missing / self.refill_per_sec).unwrap_or(Duration::MAX))
+    }
+
+    /// Take `amount` without checking, the balance may go negative.
+    fn charge(&mut self, amount: f64, now: Instant) {
+        self.refill(now);
+        self.available -= amount;
+    }
+
+    fn is_full(&mut self, now: Instant) -> bool {
+        self.refill(now);
+        self.available >= self.capacity
+    }
+}
+
+struct TenantBuckets {
+    requests: Option<TokenBucket>,
+    tokens: Option<TokenBucket>,
+}
+
+impl TenantBuckets {
+    fn new(limits: RateLimits, now: Instant) -> Self {
+        Self {
+            requests: limits
+                .requests_per_second
+                .map(|rps| TokenBucket::new(rps.max(1.0), rps, now)),
+            tokens: limits.tokens_per_minute.map(|tpm| {
+                let tpm = tpm as f64;
+                TokenBucket::new(tpm, tpm / 60.0, now)
+            }),
+        }
+    }
+
+    fn is_full(&mut self, now: Instant) -> bool {
+        self.requests.as_mut().is_none_or(|b| b.is_full(now))
+            && self.tokens.as_mut().is_none_or(|b| b.is_full(now))
+    }
+}
+
+/// Tracks the buckets of every tenant.
+pub struct RateLimiter {
+    config: RateLimitConfig,
+    tenants: Mutex<HashMap<String, TenantBuckets>>,
+}
+
+impl RateLimiter {
+    pub fn new(config: RateLimitConfig) -> Self {
+        Self {
+            config,
+            tenants: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// The tenant a request belongs to: the value of the key header if one is configured,
+    /// otherwise the API key the request was authenticated with. Without authentication clients
+    /// choose their bearer token freely, so their requests all share the anonymous tenant.
+    pub fn tenant(&self, headers: &HeaderMap, api_key: Option<&ApiKey>) -> String {
+        let key = match &self.config.key_header {
+            Some(header) => headers.get(header).and_then(|v| v.to_str().ok()),
+            None => api_key.map(|api_key| api_key.key.as_str()),
+        };
+        match key {
+            Some(key) if !key.is_empty() => key.to_string(),
+            _ => ANONYMOUS_TENANT.to_string(),
+        }
+    }
+
+    /// Admit a request of `tenant`, charging it against the tenant's request limit.
+    pub fn check(&self, tenant: &str) -> Result<(), RateLimitExceeded> {
+        self.check_at(tenant, Instant::now())
+    }
+
+    /// Charge `tokens` of a completed request against the tenant's token limit.
+    pub fn charge_tokens(&self, tenant: &str, tokens: usize) {
+        self.charge_tokens_at(tenant, tokens, Instant::now())
+    }
+
+    /// Forget the tenants whose buckets have fully refilled. They start from full buckets again
+    /// on their next request, so this changes no limit.
+    pub fn sweep(&self) {
+        self.sweep_at(Instant::now())
+    }
+
+    fn sweep_at(&self, now: Instant) {
+        self.tenants
+            .lock()
+            .unwrap()
+            .retain(|_, buckets| !buckets.is_full(now));
+    }
+
+    fn check_at(&self, tenant: &str, now: Instant) -> Result<(), RateLimitExceeded> {
+        let mut tenants = self.tenants.lock().unwrap();
+        let buckets = tenants
+            .entry(tenant.to_string())
+            .or_insert_with(|| TenantBuckets::new(self.config.limits_for(tenant), now));
+
+        // check both before charging either, so a rejected request costs nothing
+        if let Some(retry_after) = buckets.tokens.as_mut().and_then(|b| b.wait_for(1.0, now)) {
+            return Err(RateLimitExceeded {
+                kind: RateLimitKind::Tokens,
+                retry_after,
+            });
+        }
+        if let Some(requests) = buckets.requests.as_mut() {
+            if let Some(retry_after) = requests.wait_for(1.0, now) {
+                return Err(RateLimitExceeded {
+                    kind: RateLimitKind::Requests,
+                    retry_after,
+                });
+            }
+            requests.charge(1.0, now);
+        }
+        Ok(())
+    }
+
+    fn charge_tokens_at(&self, tenant: &str, tokens: usize, now: Instant) {
+        let mut tenants = self.tenants.lock().unwrap();
+        if let Some(bucket) = tenants
+            .get_mut(tenant)
+            .and_then(|buckets| buckets.tokens.as_mut())
+        {
+            bucket.charge(tokens as f64, now);
+        }
+    }
+}
+
+/// Periodically forget idle tenants of `limiter` until `cancel` is triggered.
+pub async fn sweep_loop(limiter: Arc<RateLimiter>, interval: Duration, cancel: CancellationToken) {
+    let mut ticker = tokio::time::interval(interval);
+    loop {
+        tokio::select! {
+            _ = cancel.cancelled() => return,
+            _ = ticker.tick() => {}
+        }
+        limiter.sweep();
+    }
+}
+
+/// A handle to charge the token usage of an admitted request to its tenant. Added to the
+/// extensions of every request admitted by [`rate_limit_middleware`].
+#[derive(Clone)]
+pub struct TokenQuota {
+    limiter: Arc<RateLimiter>,
+    tenant: String,
+}
+
+impl TokenQuota {
+    pub fn charge(&self, tokens: usize) {
+        self.limiter.charge_tokens(&self.tenant, tokens);
+    }
+}
+
+/// Reject requests of tenants over their limits with a 429.
+pub async fn rate_limit_middleware(
+    State(limiter): State<Arc<RateLimiter>>,
+    mut request: Request,
+    next: Next,
+) -> Response {
+    let api_key = request.extensions().get::<Arc<ApiKey>>();
+    let tenant = limiter.tenant(request.headers(), api_key.map(Arc::as_ref));
+    if let Err(err) = limiter.check(&tenant) {
+        // the tenant is usually an API key, so it is not logged
+        tracing::debug!(%err, "Rejected rate limited request");
+        return ErrorResponse::rate_limited(&err).into_response();
+    }
+    request
+        .extensions_mut()
+        .insert(TokenQuota { limiter, tenant });
+    next.run(request).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::HeaderValue;
+
+    fn limiter(requests_per_second: Option<f64>, tokens_per_minute: Option<u64>) -> RateLimiter {
+        RateLimiter::new(RateLimitConfig::new(RateLimits {
+            requests_per_second,
+            tokens_per_minute,
+        }))
+    }
+
+    #[test]
+    fn test_tenant_from_headers() {
+        let limiter = limiter(None, None);
+        let mut headers = HeaderMap::new();
+        assert_eq!(limiter.tenant(&headers, None), ANONYMOUS_TENANT);
+
+        // without authentication the bearer token is whatever the client sends
+        headers.insert("authorization", HeaderValue::from_static("Bearer sk-123"));
+        headers.insert("x-tenant", HeaderValue::from_static("team-a"));
+        assert_eq!(limiter.tenant(&headers, None), ANONYMOUS_TENANT);
+
+        let api_key = ApiKey {
+            key: "sk-123".to_string(),
+            name: "team-a".to_string(),
+            models: None,
+        };
+        assert_eq!(limiter.tenant(&headers, Some(&api_key)), "sk-123");
+
+        let limiter = RateLimiter::new(
+            RateLimitConfig::default().with_key_header(Some("x-tenant".to_string())),
+        );
+        assert_eq!(limiter.tenant(&headers, Some(&api_key)), "team-a");
+    }
+
+    #[test]
+    fn test_requests_per_second() {
+        let limiter = limiter(Some(2.0), None);
+        let now = Instant::now();
+
+        limiter.check_at("a", now).unwrap();
+        limiter.check_at("a", now).unwrap();
+        let err = limiter.check_at("a", now).unwrap_err();
+        assert_eq!(err.kind, RateLimitKind::Requests);
+        assert_eq!(err.retry_after, Duration::from_millis(500));
+        assert_eq!(err.retry_after_secs(), 1);
+
+        // tenants are limited separately
+        limiter.check_at("b", now).unwrap();
+
+        limiter
+            .check_at("a", now + Duration::from_millis(500))
+            .unwrap();
+    }
+
+    #[test]
+    fn test_tokens_per_minute() {
+        let limiter = limiter(None, Some(600));
+        let now = Instant::now();
+
+        limiter.check_at("a", now).unwrap();
+        // a large response puts the tenant 600 tokens into debt
+        limiter.charge_tokens_at("a", 1200, now);
+        let err = limiter.check_at("a", now).unwrap_err();
+        assert_eq!(err.kind, RateLimitKind::Tokens);
+        // 601 tokens at 10 tokens per second
+        assert_eq!(err.retry_after_secs(), 61);
+
+        limiter
+            .check_at("a", now + Duration::from_secs(61))
+            .unwrap();
+    }
+
+    #[test]
+    fn test_validate_limits() {
+        assert!(RateLimits::default().validate().is_ok());
+        for rps in [0.0, -1.0, f64::NAN, f64::INFINITY] {
+            let limits = RateLimits {
+                requests_per_second: Some(rps),
+                tokens_per_minute: None,
+            };
+            assert!(limits.validate().is_err(), "{rps} should be rejected");
+        }
+        let limits = RateLimits {
+            requests_per_second: None,
+            tokens_per_minute: Some(0),
+        };
+        assert!(limits.validate().is_err());
+    }
+
+    #[test]
+    fn test_zero_refill_never_admits() {
+        let now = Instant::now();
+        let mut bucket = TokenBucket::new(1.0, 0.0, now);
+        assert_eq!(bucket.wait_for(1.0, now), None);
+        bucket.charge(1.0, now);
+        assert_eq!(bucket.wait_for(1.0, now), Some(Duration::MAX));
+    }
+
+    #[test]
+    fn test_sweep_forgets_idle_tenants() {
+        let limiter = limiter(Some(1.0), None);
+        let now = Instant::now();
+
+        limiter.check_at("a", now).unwrap();
+        limiter
+            .check_at("b", now + Duration::from_millis(500))
+            .unwrap();
+
+        // a has refilled, b has not
+        limiter.sweep_at(now + Duration::from_secs(1));
+        let tenants = limiter.tenants.lock().unwrap();
+        assert!(!tenants.contains_key("a"));
+        assert!(tenants.contains_key("b"));
+    }
+
+    #[test]
+    fn test_tenant_limits() {
+        let config = RateLimitConfig::new(RateLimits {
+            requests_per_second: Some(1.0),
+            tokens_per_minute: None,
+        })
+        .with_tenant_limits(HashMap::from([("vip".to_string(), RateLimits::default())]));
+        let limiter = RateLimiter::new(config);
+        let now = Instant::now();
+
+        limiter.check_at("a", now).unwrap();
+        assert!(limiter.check_at("a", now).is_err());
+        for _ in 0..100 {
+            limiter.check_at("vip", now).unwrap();
+        }
+    }
+}
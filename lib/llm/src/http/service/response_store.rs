@@ -0,0 +1,173 @@
+// SPDX-FileCopyrightText: Copyright (c) 2024-2025 NVIDIA CORPORATION & AFFILIATES. All rights reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+//! Storage of the responses of the `/v1/responses` endpoint, so they can be retrieved by id and
+//! continued with `previous_response_id`.
+//!
+//! Responses are kept in the [`RESPONSES_BUCKET`] bucket of a [`KeyValueStore`], together with
+//! the input that produced them. Only the most recent responses stored by this process are kept.
+
+use std::collections::VecDeque;
+use std::sync::Mutex;
+
+use dynamo_runtime::storage::key_value_store::{KeyValueStore, MemoryStorage};
+use serde::{Deserialize, Serialize};
+
+use super::auth::ApiKey;
+use crate::protocols::openai::responses::{NvResponse, ResponseItem};
+
+/// The key-value store bucket responses are stored in, one [`StoredResponse`] per entry.
+pub const RESPONSES_BUCKET: &str = "http_responses";
+
+/// How many responses are kept by default.
+pub const DEFAULT_RESPONSE_STORE_CAPACITY: usize = 10_000;
+
+/// A response and the input items of its request
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct StoredResponse {
+    /// The input of the request, including the conversation of the previous responses
+    pub input: Vec<ResponseItem>,
+
+    pub response: NvResponse,
+
+    /// The name of the API key that created the response, if the request was authenticated
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub owner: Option<String>,
+}
+
+impl StoredResponse {
+    /// With authentication a response is only visible to the key that created it, and only
+    /// while that key may use the response's model.
+    pub fn is_visible_to(&self, api_key: Option<&ApiKey>) -> bool {
+        match api_key {
+            Some(api_key) => {
+                self.owner.as_deref() == Some(api_key.name.as_str())
+                    && api_key.allows(&self.response.model)
+            }
+            None => true,
+        }
+    }
+
+    /// The conversation up to and including this response, to continue it
+    pub fn conversation(&self) -> Vec<ResponseItem> {
+        self.input
+            .iter()
+            .chain(self.response.output.iter())
+            .cloned()
+            .collect()
+    }
+}
+
+pub struct ResponseStore {
+    store: Box<dyn KeyValueStore>,
+    capacity: usize,
+    /// Ids of the stored responses, oldest first
+    ids: Mutex<VecDeque<String>>,
+}
+
+impl ResponseStore {
+    pub fn new(store: Box<dyn KeyValueStore>, capacity: usize) -> Self {
+        ResponseStore {
+            store,
+            capacity,
+            ids: Mutex::new(VecDeque::new()),
+        }
+    }
+
+    /// A store local to this process
+    pub fn in_memory() -> Self {
+        Self::new(
+            Box::new(MemoryStorage::new()),
+            DEFAULT_RESPONSE_STORE_CAPACITY,
+        )
+    }
+
+    pub async fn get(&self, id: &str) -> anyhow::Result<Option<StoredResponse>> {
+        let Some(bucket) = self.store.get_bucket(RESPONSES_BUCKET).await? else {
+            return Ok(None);
+        };
+        match bucket.get(id).await? {
+            Some(json) => Ok(Some(serde_json::from_slice(&json)?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Store a response, evicting the oldest ones beyond the capacity
+    pub async fn put(&self, stored: &StoredResponse) -> anyhow::Result<()> {
+        let bucket = self
+            .store
+            .get_or_create_bucket(RESPONSES_BUCKET, None)
+            .await?;
+        let id = stored.response.id.clone();
+        bucket
+            .insert(id.clone(), serde_json::to_string(stored)?, 0)
+            .await?;
+
+        let evicted: Vec<String> = {
+            let mut ids = self.ids.lock().unwrap();
+            ids.push_back(id);
+            let excess = ids.len().saturating_sub(self.capacity);
+            ids.drain(..excess).collect()
+        };
+        for id in evicted {
+            bucket.delete(&id).await?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocols::openai::responses::NvCreateResponseRequest;
+
+    fn stored(id: &str) -> StoredResponse {
+        let request: NvCreateResponseRequest =
+            serde_json::from_value(serde_json::json!({"model": "llama", "input": "Hi"})).unwrap();
+        StoredResponse {
+            input: request.input_items(),
+            response: request.response(id.to_string(), 0),
+            owner: None,
+        }
+    }
+
+    fn api_key(name: &str) -> ApiKey {
+        ApiKey {
+            key: format!("sk-{name}"),
+            name: name.to_string(),
+            models: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_put_get_evict() {
+        let store = ResponseStore::new(Box::new(MemoryStorage::new()), 2);
+        assert!(store.get("resp_1").await.unwrap().is_none());
+
+        for id in ["resp_1", "resp_2", "resp_3"] {
+            store.put(&stored(id)).await.unwrap();
+        }
+
+        assert!(store.get("resp_1").await.unwrap().is_none());
+        let resp_3 = store.get("resp_3").await.unwrap().unwrap();
+        assert_eq!(resp_3.response.id, "resp_3");
+        assert_eq!(resp_3.conversation().len(), 1);
+        assert!(store.get("resp_2").await.unwrap().is_some());
+    }
+
+    #[test]
+    fn test_visible_to_owner() {
+        let mut stored = stored("resp_1");
+        assert!(stored.is_visible_to(None));
+        // stored without authentication
+        assert!(!stored.is_visible_to(Some(&api_key("team-a"))));
+
+        stored.owner = Some("team-a".to_string());
+        assert!(stored.is_visible_to(Some(&api_key("team-a"))));
+        assert!(!stored.is_visible_to(Some(&api_key("team-b"))));
+
+        let mut restricted = api_key("team-a");
+        restricted.models = Some(["mistral".to_string()].into());
+        assert!(!stored.is_visible_to(Some(&restricted)));
+    }
+}
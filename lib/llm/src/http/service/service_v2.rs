@@ -4,7 +4,13 @@
 use std::sync::Arc;
 use std::time::Duration;
 
+use super::auth::{auth_middleware, ApiKeys};
 use super::metrics;
+use super::rate_limit::{
+    rate_limit_middleware, sweep_loop, RateLimitConfig, RateLimiter, DEFAULT_TENANT_SWEEP_INTERVAL,
+};
+use super::response_store::ResponseStore;
+use super::tls::{TlsConfig, TlsListener};
 use super::Metrics;
 use super::RouteDoc;
 use crate::discovery::ModelManager;
@@ -18,6 +24,7 @@ use tokio_util::sync::CancellationToken;
 pub struct State {
     metrics: Arc<Metrics>,
     manager: Arc<ModelManager>,
+    sse_keep_alive: Option<Duration>,
 }
 
 impl State {
@@ -25,9 +32,15 @@ impl State {
         Self {
             manager,
             metrics: Arc::new(Metrics::default()),
+            sse_keep_alive: None,
         }
     }
 
+    pub fn with_sse_keep_alive(mut self, sse_keep_alive: Option<Duration>) -> Self {
+        self.sse_keep_alive = sse_keep_alive;
+        self
+    }
+
     /// Get the Prometheus [`Metrics`] object which tracks request counts and inflight requests
     pub fn metrics_clone(&self) -> Arc<Metrics> {
         self.metrics.clone()
@@ -41,9 +54,9 @@ impl State {
         self.manager.clone()
     }
 
-    // TODO
+    /// How long a stream may be idle before a keep-alive comment is sent, if at all
     pub fn sse_keep_alive(&self) -> Option<Duration> {
-        None
+        self.sse_keep_alive
     }
 }
 
@@ -55,6 +68,8 @@ pub struct HttpService {
     router: axum::Router,
     port: u16,
     host: String,
+    tls: Option<TlsConfig>,
+    rate_limiter: Option<Arc<RateLimiter>>,
     route_docs: Vec<RouteDoc>,
 }
 
@@ -78,8 +93,41 @@ pub struct HttpServiceConfig {
     #[builder(default = "true")]
     enable_embeddings_endpoints: bool,
 
+    /// Serve the OpenAI Responses API, `POST /v1/responses` and `GET /v1/responses/{id}`,
+    /// with the chat completions engines.
+    #[builder(default = "true")]
+    enable_responses_endpoints: bool,
+
+    /// Where responses are stored for `previous_response_id`. Default: in memory
+    #[builder(default = "None")]
+    response_store: Option<Arc<ResponseStore>>,
+
+    /// Serve `POST /debug/kv_router`, which explains KV router decisions without routing.
+    #[builder(default = "false")]
+    enable_kv_router_debug_endpoints: bool,
+
     #[builder(default = "None")]
     request_template: Option<RequestTemplate>,
+
+    /// Send an SSE comment on streams that have been idle this long, for example during a long
+    /// prefill, so proxies do not time them out. Default: no keep-alive
+    #[builder(default = "None")]
+    sse_keep_alive: Option<Duration>,
+
+    /// Per-tenant limits on the chat, completions, embeddings and responses endpoints.
+    /// Default: unlimited
+    #[builder(default = "None")]
+    rate_limit: Option<RateLimitConfig>,
+
+    /// Require one of these bearer tokens on every route except health and metrics.
+    /// Default: no authentication
+    #[builder(default = "None")]
+    api_keys: Option<Arc<ApiKeys>>,
+
+    /// Serve HTTPS with this certificate, optionally requiring client certificates.
+    /// Default: plain HTTP
+    #[builder(default = "None")]
+    tls: Option<TlsConfig>,
 }
 
 impl HttpService {
@@ -106,7 +154,8 @@ impl HttpService {
 
     pub async fn run(&self, cancel_token: CancellationToken) -> Result<()> {
         let address = format!("{}:{}", self.host, self.port);
-        tracing::info!(address, "Starting HTTP service on: {address}");
+        let scheme = if self.tls.is_some() { "https" } else { "http" };
+        tracing::info!(address, scheme, "Starting HTTP service on: {address}");
 
         let listener = tokio::net::TcpListener::bind(address.as_str())
             .await
@@ -115,10 +164,29 @@ impl HttpService {
         let router = self.router.clone();
         let observer = cancel_token.child_token();
 
-        axum::serve(listener, router)
-            .with_graceful_shutdown(observer.cancelled_owned())
-            .await
-            .inspect_err(|_| cancel_token.cancel())?;
+        if let Some(limiter) = &self.rate_limiter {
+            tokio::spawn(sweep_loop(
+                limiter.clone(),
+                DEFAULT_TENANT_SWEEP_INTERVAL,
+                observer.clone(),
+            ));
+        }
+
+        match &self.tls {
+            Some(tls) => {
+                let listener = TlsListener::new(listener, tls.clone())?;
+                axum::serve(listener, router)
+                    .with_graceful_shutdown(observer.cancelled_owned())
+                    .await
+                    .inspect_err(|_| cancel_token.cancel())?;
+            }
+            None => {
+                axum::serve(listener, router)
+                    .with_graceful_shutdown(observer.cancelled_owned())
+                    .await
+                    .inspect_err(|_| cancel_token.cancel())?;
+            }
+        }
 
         Ok(())
     }
@@ -133,8 +201,13 @@ impl HttpServiceConfigBuilder {
     pub fn build(self) -> Result<HttpService, anyhow::Error> {
         let config: HttpServiceConfig = self.build_internal()?;
 
+        // fail now rather than when the service starts
+        if let Some(tls) = &config.tls {
+            tls.server_config()?;
+        }
+
         let model_manager = Arc::new(ModelManager::new());
-        let state = Arc::new(State::new(model_manager));
+        let state = Arc::new(State::new(model_manager).with_sse_keep_alive(config.sse_keep_alive));
 
         // enable prometheus metrics
         let registry = metrics::Registry::new();
@@ -146,24 +219,75 @@ impl HttpServiceConfigBuilder {
 
         let mut routes = vec![
             metrics::router(registry, None),
-            super::openai::list_models_router(state.clone(), None),
             super::health::health_check_router(state.clone(), None),
         ];
 
+        // the routes that need an API key when authentication is enabled
+        let mut authenticated_routes = vec![super::openai::list_models_router(state.clone(), None)];
+
+        // the routes that serve inference, and so are subject to rate limits
+        let mut inference_routes = Vec::new();
+
         if config.enable_chat_endpoints {
-            routes.push(super::openai::chat_completions_router(
+            inference_routes.push(super::openai::chat_completions_router(
+                state.clone(),
+                config.request_template.clone(),
+                None,
+            ));
+        }
+
+        if config.enable_responses_endpoints {
+            let store = config
+                .response_store
+                .unwrap_or_else(|| Arc::new(ResponseStore::in_memory()));
+            inference_routes.push(super::openai::responses_router(
                 state.clone(),
+                store,
                 config.request_template,
                 None,
             ));
         }
 
         if config.enable_cmpl_endpoints {
-            routes.push(super::openai::completions_router(state.clone(), None));
+            inference_routes.push(super::openai::completions_router(state.clone(), None));
         }
 
         if config.enable_embeddings_endpoints {
-            routes.push(super::openai::embeddings_router(state.clone(), None));
+            inference_routes.push(super::openai::embeddings_router(state.clone(), None));
+        }
+
+        let rate_limiter = config
+            .rate_limit
+            .map(|rate_limit| Arc::new(RateLimiter::new(rate_limit)));
+        match &rate_limiter {
+            Some(limiter) => {
+                authenticated_routes.extend(inference_routes.into_iter().map(|(docs, route)| {
+                    let layer = axum::middleware::from_fn_with_state(
+                        limiter.clone(),
+                        rate_limit_middleware,
+                    );
+                    (docs, route.layer(layer))
+                }));
+            }
+            None => authenticated_routes.extend(inference_routes),
+        }
+
+        if config.enable_kv_router_debug_endpoints {
+            authenticated_routes.push(super::kv_router_debug::kv_router_debug_router(
+                state.clone(),
+                None,
+            ));
+        }
+
+        match config.api_keys {
+            Some(api_keys) => {
+                routes.extend(authenticated_routes.into_iter().map(|(docs, route)| {
+                    let layer =
+                        axum::middleware::from_fn_with_state(api_keys.clone(), auth_middleware);
+                    (docs, route.layer(layer))
+                }));
+            }
+            None => routes.extend(authenticated_routes),
         }
 
         // for (route_docs, route) in routes.into_iter().chain(self.routes.into_iter()) {
@@ -181,6 +305,8 @@ impl HttpServiceConfigBuilder {
             router,
             port: config.port,
             host: config.host,
+            tls: config.tls,
+            rate_limiter,
             route_docs: all_docs,
         })
     }
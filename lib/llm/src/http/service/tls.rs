@@ -0,0 +1,368 @@
+// SPDX-FileCopyrightText: Copyright (c) 2024-2025 NVIDIA CORPORATION & AFFILIATES. All rights reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+//! TLS termination for the HTTP service.
+//!
+//! The certificate, key and optional client CA are PEM files. They are watched for changes and
+//! reloaded without a restart, so certificates can be rotated in place. New connections use the
+//! new certificate, open connections keep the one they were established with. If the files
+//! fail to load, for example half way through a rotation, the previous certificate stays in use.
+
+use std::{
+    net::SocketAddr,
+    path::PathBuf,
+    sync::{Arc, RwLock},
+    time::{Duration, SystemTime},
+};
+
+use anyhow::Context;
+use dynamo_runtime::pipeline::network::tcp::tls::{load_certs, load_key};
+use tokio::{
+    net::{TcpListener, TcpStream},
+    sync::mpsc,
+};
+use tokio_rustls::{
+    rustls::{
+        crypto::ring,
+        pki_types::{CertificateDer, PrivateKeyDer},
+        server::WebPkiClientVerifier,
+        RootCertStore, ServerConfig,
+    },
+    server::TlsStream,
+    TlsAcceptor,
+};
+use tokio_util::sync::{CancellationToken, DropGuard};
+
+/// How often the certificate files are checked for changes by default.
+pub const DEFAULT_TLS_RELOAD_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Connections that have not completed the TLS handshake by then are closed.
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Pause after a failed accept, which is usually running out of file descriptors.
+const ACCEPT_ERROR_BACKOFF: Duration = Duration::from_millis(100);
+
+/// Handshaken connections waiting to be served.
+const ACCEPT_QUEUE: usize = 128;
+
+/// TLS configuration for the HTTP service.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TlsConfig {
+    /// PEM certificate chain, the server certificate first
+    pub cert_path: PathBuf,
+
+    /// PEM private key of the server certificate
+    pub key_path: PathBuf,
+
+    /// PEM certificates of the CAs client certificates must be signed by. `None` does not ask
+    /// clients for a certificate.
+    pub client_ca_path: Option<PathBuf>,
+
+    /// How often the files are checked for changes
+    pub reload_interval: Duration,
+}
+
+impl TlsConfig {
+    pub fn new(cert_path: PathBuf, key_path: PathBuf) -> Self {
+        Self {
+            cert_path,
+            key_path,
+            client_ca_path: None,
+            reload_interval: DEFAULT_TLS_RELOAD_INTERVAL,
+        }
+    }
+
+    /// Require clients to present a certificate signed by one of the CAs in `client_ca_path`
+    pub fn with_client_ca_path(mut self, client_ca_path: Option<PathBuf>) -> Self {
+        self.client_ca_path = client_ca_path;
+        self
+    }
+
+    pub fn with_reload_interval(mut self, reload_interval: Duration) -> Self {
+        self.reload_interval = reload_interval;
+        self
+    }
+
+    /// Load the files into a rustls server configuration.
+    pub fn server_config(&self) -> anyhow::Result<ServerConfig> {
+        let provider = Arc::new(ring::default_provider());
+        let certs = load_certs(&self.cert_path)?;
+        let key = load_key(&self.key_path)?;
+
+        let builder = ServerConfig::builder_with_provider(provider.clone())
+            .with_safe_default_protocol_versions()?;
+        let builder = match &self.client_ca_path {
+            Some(path) => {
+                let mut roots = RootCertStore::empty();
+                for cert in load_certs(path)? {
+                    roots
+                        .add(cert)
+                        .with_context(|| format!("Invalid client CA certificate in {path:?}"))?;
+                }
+                let verifier =
+                    WebPkiClientVerifier::builder_with_provider(Arc::new(roots), provider)
+                        .build()
+                        .with_context(|| format!("Invalid client CA certificates in {path:?}"))?;
+                builder.with_client_cert_verifier(verifier)
+            }
+            None => builder.with_no_client_auth(),
+        };
+        let mut config = builder.with_single_cert(certs, key).with_context(|| {
+            format!(
+                "Invalid certificate {:?} or key {:?}",
+                self.cert_path, self.key_path
+            )
+        })?;
+        config.alpn_protocols = vec![b"h2".to_vec(), b"http/1.1".to_vec()];
+        Ok(config)
+    }
+
+    /// Last modification time of each file, to detect changes.
+    fn modified(&self) -> Vec<Option<SystemTime>> {
+        [
+            Some(&self.cert_path),
+            Some(&self.key_path),
+            self.client_ca_path.as_ref(),
+        ]
+        .into_iter()
+        .flatten()
+        .map(|path| std::fs::metadata(path).and_then(|m| m.modified()).ok())
+        .collect()
+    }
+}
+
+/// An [`axum::serve::Listener`] that completes the TLS handshake of every connection before
+/// handing it to axum. Handshakes run in their own tasks, so a slow client does not hold up the
+/// others.
+pub struct TlsListener {
+    local_addr: SocketAddr,
+    connections: mpsc::Receiver<(TlsStream<TcpStream>, SocketAddr)>,
+    // stops accepting and reloading when the listener is dropped
+    _tasks: DropGuard,
+}
+
+impl TlsListener {
+    pub fn new(listener: TcpListener, config: TlsConfig) -> anyhow::Result<Self> {
+        let local_addr = listener.local_addr()?;
+        let modified = config.modified();
+        let acceptor = Arc::new(RwLock::new(TlsAcceptor::from(Arc::new(
+            config.server_config()?,
+        ))));
+
+        let cancel = CancellationToken::new();
+        let (tx, connections) = mpsc::channel(ACCEPT_QUEUE);
+        tokio::spawn(accept_loop(
+            listener,
+            acceptor.clone(),
+            tx,
+            cancel.child_token(),
+        ));
+        tokio::spawn(reload_loop(
+            config,
+            acceptor,
+            modified,
+            cancel.child_token(),
+        ));
+
+        Ok(Self {
+            local_addr,
+            connections,
+            _tasks: cancel.drop_guard(),
+        })
+    }
+}
+
+impl axum::serve::Listener for TlsListener {
+    type Io = TlsStream<TcpStream>;
+    type Addr = SocketAddr;
+
+    async fn accept(&mut self) -> (Self::Io, Self::Addr) {
+        match self.connections.recv().await {
+            Some(connection) => connection,
+            // the accept loop only stops once we are dropped
+            None => std::future::pending().await,
+        }
+    }
+
+    fn local_addr(&self) -> std::io::Result<Self::Addr> {
+        Ok(self.local_addr)
+    }
+}
+
+async fn accept_loop(
+    listener: TcpListener,
+    acceptor: Arc<RwLock<TlsAcceptor>>,
+    tx: mpsc::Sender<(TlsStream<TcpStream>, SocketAddr)>,
+    cancel: CancellationToken,
+) {
+    loop {
+        let (stream, addr) = tokio::select! {
+            _ = cancel.cancelled() => return,
+            accepted = listener.accept() => match accepted {
+                Ok(accepted) => accepted,
+                Err(err) => {
+                    tracing::warn!(%err, "Failed to accept connection");
+                    tokio::time::sleep(ACCEPT_ERROR_BACKOFF).await;
+                    continue;
+                }
+            },
+        };
+        let acceptor = acceptor.read().unwrap().clone();
+        let tx = tx.clone();
+        tokio::spawn(async move {
+            match tokio::time::timeout(HANDSHAKE_TIMEOUT, acceptor.accept(stream)).await {
+                Ok(Ok(stream)) => {
+                    let _ = tx.send((stream, addr)).await;
+                }
+                Ok(Err(err)) => tracing::debug!(%addr, %err, "TLS handshake failed"),
+                Err(_) => tracing::debug!(%addr, "TLS handshake timed out"),
+            }
+        });
+    }
+}
+
+async fn reload_loop(
+    config: TlsConfig,
+    acceptor: Arc<RwLock<TlsAcceptor>>,
+    mut modified: Vec<Option<SystemTime>>,
+    cancel: CancellationToken,
+) {
+    let mut ticker = tokio::time::interval(config.reload_interval);
+    loop {
+        tokio::select! {
+            _ = cancel.cancelled() => return,
+            _ = ticker.tick() => {}
+        }
+        let current = config.modified();
+        if current == modified {
+            continue;
+        }
+        match config.server_config() {
+            Ok(server_config) => {
+                *acceptor.write().unwrap() = TlsAcceptor::from(Arc::new(server_config));
+                modified = current;
+                tracing::info!(cert = ?config.cert_path, "Reloaded TLS certificate");
+            }
+            // retried on the next tick, the files may still be being written
+            Err(err) => tracing::warn!(%err, "Failed to reload TLS certificate"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio_rustls::{
+        rustls::{pki_types::ServerName, ClientConfig},
+        TlsConnector,
+    };
+
+    // the fixtures are shared with the runtime's TLS tests
+    fn data(file: &str) -> PathBuf {
+        PathBuf::from(env!("CARGO_MANIFEST_DIR")).join(format!("../runtime/tests/data/tls/{file}"))
+    }
+
+    fn connector(client_cert: bool) -> TlsConnector {
+        let mut roots = RootCertStore::empty();
+        roots
+            .add(load_certs(&data("ca.pem")).unwrap().remove(0))
+            .unwrap();
+        let builder = ClientConfig::builder_with_provider(Arc::new(ring::default_provider()))
+            .with_safe_default_protocol_versions()
+            .unwrap()
+            .with_root_certificates(roots);
+        let config = if client_cert {
+            builder
+                .with_client_auth_cert(
+                    load_certs(&data("client.pem")).unwrap(),
+                    load_key(&data("client.key")).unwrap(),
+                )
+                .unwrap()
+        } else {
+            builder.with_no_client_auth()
+        };
+        TlsConnector::from(Arc::new(config))
+    }
+
+    /// Serve one connection with `config`, echoing a byte back.
+    async fn connect(config: TlsConfig, client_cert: bool) -> std::io::Result<u8> {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let mut listener = TlsListener::new(listener, config).unwrap();
+        let addr = axum::serve::Listener::local_addr(&listener).unwrap();
+        tokio::spawn(async move {
+            let (mut stream, _) = axum::serve::Listener::accept(&mut listener).await;
+            let byte = stream.read_u8().await.unwrap();
+            stream.write_u8(byte + 1).await.unwrap();
+            stream.flush().await.unwrap();
+        });
+
+        let stream = TcpStream::connect(addr).await?;
+        let server_name = ServerName::try_from("localhost").unwrap();
+        let mut stream = connector(client_cert).connect(server_name, stream).await?;
+        stream.write_u8(41).await?;
+        stream.flush().await?;
+        stream.read_u8().await
+    }
+
+    #[test]
+    fn test_server_config_errors() {
+        let config = TlsConfig::new(data("server.pem"), data("server.key"));
+        config.server_config().unwrap();
+
+        let missing = TlsConfig::new(data("server.pem"), data("missing.key"));
+        assert!(missing.server_config().is_err());
+
+        // a certificate is not a key
+        let swapped = TlsConfig::new(data("server.pem"), data("server.pem"));
+        assert!(swapped.server_config().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_tls_handshake() {
+        let config = TlsConfig::new(data("server.pem"), data("server.key"));
+        assert_eq!(connect(config, false).await.unwrap(), 42);
+    }
+
+    #[tokio::test]
+    async fn test_client_certificate_required() {
+        let config = TlsConfig::new(data("server.pem"), data("server.key"))
+            .with_client_ca_path(Some(data("ca.pem")));
+        assert_eq!(connect(config.clone(), true).await.unwrap(), 42);
+        assert!(connect(config, false).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_reload_on_change() {
+        let dir = tempfile::tempdir().unwrap();
+        let cert_path = dir.path().join("cert.pem");
+        let key_path = dir.path().join("key.pem");
+        std::fs::copy(data("client.pem"), &cert_path).unwrap();
+        std::fs::copy(data("client.key"), &key_path).unwrap();
+
+        let config = TlsConfig::new(cert_path.clone(), key_path.clone())
+            .with_reload_interval(Duration::from_millis(10));
+        let modified = config.modified();
+        let acceptor = Arc::new(RwLock::new(TlsAcceptor::from(Arc::new(
+            config.server_config().unwrap(),
+        ))));
+        let before = Arc::as_ptr(acceptor.read().unwrap().config());
+        let cancel = CancellationToken::new();
+        tokio::spawn(reload_loop(
+            config,
+            acceptor.clone(),
+            modified,
+            cancel.clone(),
+        ));
+
+        // modification times are not always more precise than a second
+        tokio::time::sleep(Duration::from_millis(1100)).await;
+        std::fs::copy(data("server.pem"), &cert_path).unwrap();
+        std::fs::copy(data("server.key"), &key_path).unwrap();
+        tokio::time::sleep(Duration::from_millis(200)).await;
+
+        let after = Arc::as_ptr(acceptor.read().unwrap().config());
+        assert_ne!(before, after);
+        cancel.cancel();
+    }
+}
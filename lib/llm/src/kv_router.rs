@@ -1,7 +1,7 @@
 // SPDX-FileCopyrightText: Copyright (c) 2024-2025 NVIDIA CORPORATION & AFFILIATES. All rights reserved.
 // SPDX-License-Identifier: Apache-2.0
 
-use std::sync::Arc;
+use std::{collections::HashMap, sync::Arc};
 
 use anyhow::Result;
 use dynamo_runtime::{
@@ -20,20 +20,29 @@ pub mod metrics_aggregator;
 pub mod protocols;
 pub mod publisher;
 pub mod recorder;
+pub mod resync;
 pub mod scheduler;
 pub mod scoring;
+pub mod selector;
+pub mod snapshot;
 
 use crate::{
     kv_router::{
-        indexer::{KvIndexer, KvIndexerInterface, RouterEvent},
+        indexer::{
+            compute_block_hash_for_seq, compute_block_hash_salt, KvIndexer, KvIndexerInterface,
+            OverlapScores, RadixTreeSnapshot, RouterEvent,
+        },
         metrics_aggregator::KvMetricsAggregator,
-        protocols::{LocalBlockHash, RouterRequest, RouterResponse, WorkerSelectionResult},
+        protocols::{
+            LocalBlockHash, RouterRequest, RouterResponse, RoutingDecision, WorkerSelectionResult,
+        },
         scheduler::{KvScheduler, KvSchedulerError, SchedulingRequest},
         scoring::ProcessedEndpoints,
+        selector::WorkerSelectorKind,
+        snapshot::{snapshot_loop, KvSnapshotConfig, SnapshotStore},
     },
     preprocessor::PreprocessedRequest,
-    protocols::common::llm_backend::LLMEngineOutput,
-    tokens::TokenBlockSequence,
+    protocols::common::{llm_backend::LLMEngineOutput, preprocessor::PeerBlock},
 };
 
 use dynamo_runtime::traits::events::EventSubscriber;
@@ -43,6 +52,23 @@ use dynamo_runtime::traits::events::EventSubscriber;
 pub const KV_EVENT_SUBJECT: &str = "kv_events";
 pub const KV_HIT_RATE_SUBJECT: &str = "kv-hit-rate";
 pub const KV_METRICS_ENDPOINT: &str = "load_metrics";
+pub const KV_STATE_ENDPOINT: &str = "kv_state";
+
+/// Context key of the [`WorkerPin`] of a request
+pub const WORKER_PIN: &str = "kv_router_worker_pin";
+
+/// Shared by the contexts of several requests to route them all to the worker chosen for the
+/// first one, e.g. the choices of an `n > 1` request, which share the KV cache of their prompt.
+#[derive(Debug, Clone, Default)]
+pub struct WorkerPin(Arc<tokio::sync::OnceCell<(i64, u32, Vec<PeerBlock>)>>);
+
+impl WorkerPin {
+    /// The blocks of the prompt the worker was expected to have cached when it was chosen, the
+    /// `estimated_prefix_hit_num_blocks` of the request. `None` until a KV router chose it.
+    pub fn overlap_blocks(&self) -> Option<u32> {
+        self.0.get().map(|(_, overlap_blocks, _)| *overlap_blocks)
+    }
+}
 
 /// A trait that users can implement to define custom selection logic
 pub trait WorkerSelector {
@@ -52,6 +78,17 @@ pub trait WorkerSelector {
         request: &SchedulingRequest,
         block_size: usize,
     ) -> Result<WorkerSelectionResult, KvSchedulerError>;
+
+    /// The per-worker scores behind the selection, lower is better. Only used to explain
+    /// routing decisions; selectors without a per-worker score return an empty map.
+    fn worker_logits(
+        &self,
+        _workers: &ProcessedEndpoints,
+        _request: &SchedulingRequest,
+        _block_size: usize,
+    ) -> HashMap<i64, f64> {
+        HashMap::new()
+    }
 }
 
 /// KV Router configuration parameters
@@ -68,6 +105,21 @@ pub struct KvRouterConfig {
     /// Weight for waiting requests in worker selection.
     /// Higher values avoid workers with queued requests. Default: 1.0
     pub waiting_requests_weight: f64,
+
+    /// The worker selection policy. Default: softmax
+    pub selector: WorkerSelectorKind,
+
+    /// Temperature of the softmax over worker logits.
+    /// Lower values are greedier, 0 always picks the best worker. Default: 1.0
+    pub router_temperature: f64,
+
+    /// Fraction of KV cache in use above which the cache-affinity policy
+    /// stops preferring a worker for its prefix overlap. Default: 0.9
+    pub max_gpu_cache_usage: f64,
+
+    /// Where and how often to snapshot the radix tree so it survives router restarts.
+    /// Default: None, the tree is rebuilt from KV events only
+    pub snapshot: Option<KvSnapshotConfig>,
 }
 
 impl Default for KvRouterConfig {
@@ -76,6 +128,10 @@ impl Default for KvRouterConfig {
             overlap_score_weight: 1.0,
             gpu_cache_usage_weight: 1.0,
             waiting_requests_weight: 1.0,
+            selector: WorkerSelectorKind::default(),
+            router_temperature: 1.0,
+            max_gpu_cache_usage: 0.9,
+            snapshot: None,
         }
     }
 }
@@ -95,8 +151,35 @@ impl KvRouterConfig {
                 .unwrap_or(default.gpu_cache_usage_weight),
             waiting_requests_weight: waiting_requests_weight
                 .unwrap_or(default.waiting_requests_weight),
+            ..default
+        }
+    }
+
+    /// Use the given worker selection policy, with an optional softmax temperature
+    /// and cache-affinity load cap. If a value is None, the default will be used.
+    pub fn with_selector(
+        self,
+        selector: WorkerSelectorKind,
+        router_temperature: Option<f64>,
+        max_gpu_cache_usage: Option<f64>,
+    ) -> Self {
+        Self {
+            selector,
+            router_temperature: router_temperature.unwrap_or(self.router_temperature),
+            max_gpu_cache_usage: max_gpu_cache_usage.unwrap_or(self.max_gpu_cache_usage),
+            ..self
         }
     }
+
+    /// Persist the radix tree with the given snapshot configuration.
+    pub fn with_snapshot(self, snapshot: Option<KvSnapshotConfig>) -> Self {
+        Self { snapshot, ..self }
+    }
+
+    /// Build the [`WorkerSelector`] for the configured policy.
+    pub fn worker_selector(&self) -> Box<dyn WorkerSelector + Send + Sync> {
+        self.selector.build(self)
+    }
 }
 
 /// A KvRouter only decides which worker you should use. It doesn't send you there.
@@ -112,6 +195,18 @@ impl KvRouter {
         component: Component,
         block_size: usize,
         selector: Option<Box<dyn WorkerSelector + Send + Sync>>,
+    ) -> Result<Self> {
+        Self::new_with_config(component, block_size, selector, &KvRouterConfig::default()).await
+    }
+
+    /// Create a router whose radix tree is restored from, and periodically saved to, the
+    /// `snapshot` store of `config`. Requests with a priority above 0 are kept off the workers
+    /// whose KV cache usage is above its `max_gpu_cache_usage`.
+    pub async fn new_with_config(
+        component: Component,
+        block_size: usize,
+        selector: Option<Box<dyn WorkerSelector + Send + Sync>>,
+        config: &KvRouterConfig,
     ) -> Result<Self> {
         let cancellation_token = component
             .drt()
@@ -121,12 +216,48 @@ impl KvRouter {
         tracing::info!("KV Routing initialized");
         let metrics_aggregator =
             KvMetricsAggregator::new(component.clone(), cancellation_token.clone()).await;
-        let indexer = KvIndexer::new(cancellation_token.clone(), block_size);
+
+        let (indexer, snapshot_store) = match config.snapshot.clone() {
+            Some(config) => {
+                let store = config.store(&component)?;
+                let snapshot = load_snapshot(&component, store.as_ref()).await;
+                let indexer = KvIndexer::new_with_snapshot(
+                    cancellation_token.clone(),
+                    None,
+                    block_size,
+                    snapshot,
+                );
+                (indexer, Some((store, config.interval)))
+            }
+            None => (KvIndexer::new(cancellation_token.clone(), block_size), None),
+        };
+        // rebuild a worker's subtree from its own state when its KV events go missing
+        let resync = resync::resync_loop(
+            component.clone(),
+            indexer.subscribe_event_gaps(),
+            indexer.resync_sender(),
+            cancellation_token.clone(),
+        );
+        tokio::spawn(async move {
+            if let Err(err) = resync.await {
+                tracing::error!(%err, "KV worker resync task failed");
+            }
+        });
+
+        if let Some((store, interval)) = snapshot_store {
+            tokio::spawn(snapshot_loop(
+                indexer.snapshot_handle(),
+                store,
+                interval,
+                cancellation_token.clone(),
+            ));
+        }
         let scheduler = KvScheduler::start(
             component.namespace().clone(),
             block_size,
             metrics_aggregator.endpoints_watcher(),
             selector,
+            config.max_gpu_cache_usage,
         )
         .await?;
 
@@ -159,40 +290,68 @@ impl KvRouter {
         })
     }
 
-    // [TODO] indexer needs to take 'lora_id' as parameter
-    pub async fn schedule(&self, token_ids: &Vec<u32>, _lora_id: u64) -> Result<i64> {
+    pub async fn schedule(&self, token_ids: &Vec<u32>, lora_id: u64) -> Result<i64> {
         // Extracting part of the code in KvRouter::generate() for only
         // the decision making part, routing is done by the caller
         let isl_tokens = token_ids.len();
         let overlap_scores = self
             .indexer
-            .find_matches_for_request(token_ids.as_slice())
+            .find_matches_for_request(token_ids.as_slice(), lora_id)
             .await?;
         tracing::debug!("KV router overlap_scores: {:?}", overlap_scores);
-        let worker_id = self.scheduler.schedule(overlap_scores, isl_tokens).await?;
+        let worker_id = self
+            .scheduler
+            .schedule(overlap_scores, isl_tokens, 0)
+            .await?;
         Ok(worker_id)
     }
 
+    /// Explain where these tokens would be routed, without scheduling them on a worker.
+    /// The chosen worker is a fresh sample, so for randomized selectors it may differ from the
+    /// worker a real request with the same tokens is sent to.
+    pub async fn explain(&self, tokens: &[u32], lora_id: u64) -> Result<RoutingDecision> {
+        if tokens.is_empty() {
+            anyhow::bail!("Cannot explain routing of an empty request");
+        }
+        let overlap_scores = self
+            .indexer
+            .find_matches_for_request(tokens, lora_id)
+            .await?;
+        let decision = self.scheduler.explain(overlap_scores, tokens.len()).await?;
+        Ok(decision)
+    }
+
     /// Give these tokens, find the worker with the best match in it's KV cache.
-    /// Returned overlap amount is in number of blocks.
-    async fn find_best_match(&self, tokens: &[u32]) -> anyhow::Result<(i64, u32)> {
+    /// Only blocks cached for the same LoRA adapter (`0` for the base model) count as overlap,
+    /// `salt` is the adapter or a [`compute_block_hash_salt`] of it.
+    /// Returned overlap amount is in number of blocks, followed by the [`PeerBlock`]s the other
+    /// workers could provide past it.
+    async fn find_best_match(
+        &self,
+        tokens: &[u32],
+        salt: u64,
+        priority: i32,
+    ) -> anyhow::Result<(i64, u32, Vec<PeerBlock>)> {
         let isl_tokens = tokens.len();
         let block_size = self.block_size;
 
-        let (complete_blocks, _partial_block) =
-            TokenBlockSequence::split_tokens(tokens, block_size, 1337_u64);
-
-        let local_block_hashes = complete_blocks
-            .into_iter()
-            .map(|block| LocalBlockHash(block.block_hash()))
-            .collect();
-        let overlap_scores = self.indexer.find_matches(local_block_hashes).await?;
+        let local_block_hashes = compute_block_hash_for_seq(tokens, block_size, salt);
+        let overlap_scores = self
+            .indexer
+            .find_matches(local_block_hashes.clone())
+            .await?;
         let worker_id = self
             .scheduler
-            .schedule(overlap_scores.clone(), isl_tokens)
+            .schedule(overlap_scores.clone(), isl_tokens, priority)
             .await?;
         let overlap_amount = overlap_scores.scores.get(&worker_id).copied().unwrap_or(0);
-        Ok((worker_id, overlap_amount))
+        let peer_blocks = peer_blocks(
+            &overlap_scores,
+            &local_block_hashes,
+            worker_id,
+            overlap_amount as usize,
+        );
+        Ok((worker_id, overlap_amount, peer_blocks))
     }
 
     /// Get the block size this router was configured with
@@ -201,6 +360,70 @@ impl KvRouter {
     }
 }
 
+/// The blocks past the first `overlap_blocks` of the prompt that workers other than `worker_id`
+/// have cached, up to the first block no worker has. Each block goes to the peer with the most
+/// overlap, so consecutive blocks tend to come from the same worker.
+fn peer_blocks(
+    overlap_scores: &OverlapScores,
+    block_hashes: &[LocalBlockHash],
+    worker_id: i64,
+    overlap_blocks: usize,
+) -> Vec<PeerBlock> {
+    overlap_scores
+        .block_workers
+        .iter()
+        .zip(block_hashes)
+        .skip(overlap_blocks)
+        .map_while(|(workers, block_hash)| {
+            workers
+                .iter()
+                .filter(|&&peer| peer != worker_id)
+                .max_by_key(|&&peer| {
+                    let score = overlap_scores.scores.get(&peer).copied().unwrap_or(0);
+                    (score, std::cmp::Reverse(peer))
+                })
+                .map(|&peer| PeerBlock {
+                    worker_id: peer,
+                    block_hash: block_hash.0,
+                })
+        })
+        .collect()
+}
+
+/// Load the last saved snapshot, dropping the workers which are no longer registered.
+/// Failing to load is not fatal: the router starts empty and fills up from KV events as before.
+async fn load_snapshot(
+    component: &Component,
+    store: &dyn SnapshotStore,
+) -> Option<RadixTreeSnapshot> {
+    let mut snapshot = match store.load().await {
+        Ok(Some(snapshot)) => snapshot,
+        Ok(None) => return None,
+        Err(err) => {
+            tracing::warn!(%err, "Failed to load KV router snapshot; starting empty");
+            return None;
+        }
+    };
+    match component.list_instances().await {
+        Ok(instances) => {
+            let live_workers = instances.iter().map(|instance| instance.id()).collect();
+            let dropped = snapshot.retain_workers(&live_workers);
+            if dropped > 0 {
+                tracing::info!(dropped, "Dropped departed workers from KV router snapshot");
+            }
+        }
+        Err(err) => {
+            tracing::warn!(%err, "Failed to list workers; restoring every worker in the snapshot");
+        }
+    }
+    tracing::info!(
+        workers = snapshot.workers.len(),
+        blocks = snapshot.num_blocks(),
+        "Restored KV router snapshot"
+    );
+    Some(snapshot)
+}
+
 #[async_trait]
 impl AsyncEngine<SingleIn<RouterRequest>, ManyOut<Annotated<RouterResponse>>, Error> for KvRouter {
     async fn generate(
@@ -208,7 +431,9 @@ impl AsyncEngine<SingleIn<RouterRequest>, ManyOut<Annotated<RouterResponse>>, Er
         request: SingleIn<RouterRequest>,
     ) -> Result<ManyOut<Annotated<RouterResponse>>> {
         let (request, ctx) = request.into_parts();
-        let (worker_id, _) = self.find_best_match(&request.tokens).await?;
+        let (worker_id, _, _) = self
+            .find_best_match(&request.tokens, request.lora_id.unwrap_or(0), 0)
+            .await?;
 
         let response = RouterResponse { worker_id };
         let response = Annotated::from_data(response);
@@ -242,14 +467,74 @@ impl AsyncEngine<SingleIn<PreprocessedRequest>, ManyOut<Annotated<LLMEngineOutpu
         match self.inner.client.instance_source.as_ref() {
             InstanceSource::Static => self.inner.r#static(request).await,
             InstanceSource::Dynamic(_) => {
-                let (instance_id, overlap_amount) =
-                    self.chooser.find_best_match(&request.token_ids).await?;
-                // Update the request with the estimated prefix hit blocks
+                let media_hashes: Vec<u64> = request.media.iter().map(|media| media.hash).collect();
+                let salt = compute_block_hash_salt(request.lora_id.unwrap_or(0), &media_hashes);
+                let priority = request.priority().unwrap_or(0);
+                let find_best_match = || {
+                    self.chooser
+                        .find_best_match(&request.token_ids, salt, priority)
+                };
+                let (instance_id, overlap_amount, peer_blocks) =
+                    match request.get::<WorkerPin>(WORKER_PIN) {
+                        Ok(pin) => pin.0.get_or_try_init(find_best_match).await?.clone(),
+                        Err(_) => find_best_match().await?,
+                    };
+                // Update the request with the estimated prefix hit blocks and where the rest of
+                // the cached prefix can be fetched from
                 let (mut backend_input, context) = request.into_parts();
                 backend_input.estimated_prefix_hit_num_blocks = Some(overlap_amount);
+                backend_input.peer_blocks = peer_blocks;
                 let updated_request = context.map(|_| backend_input);
                 self.inner.direct(updated_request, instance_id).await
             }
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_peer_blocks() {
+        let hashes: Vec<LocalBlockHash> = (1..=5).map(LocalBlockHash).collect();
+        let overlap_scores = OverlapScores {
+            scores: HashMap::from([(0, 1), (1, 3), (2, 4)]),
+            frequencies: vec![],
+            block_workers: vec![vec![0, 1, 2], vec![1, 2], vec![1, 2], vec![2]],
+        };
+
+        // Worker 0 has the first block, worker 2 the longest prefix after it
+        let peers = peer_blocks(&overlap_scores, &hashes, 0, 1);
+        assert_eq!(
+            peers,
+            vec![
+                PeerBlock {
+                    worker_id: 2,
+                    block_hash: 2
+                },
+                PeerBlock {
+                    worker_id: 2,
+                    block_hash: 3
+                },
+                PeerBlock {
+                    worker_id: 2,
+                    block_hash: 4
+                },
+            ]
+        );
+
+        // The worker with the longest prefix has nothing to fetch
+        assert!(peer_blocks(&overlap_scores, &hashes, 2, 4).is_empty());
+
+        // Blocks after the first one no peer has are not fetched
+        let peers = peer_blocks(&overlap_scores, &hashes, 1, 3);
+        assert_eq!(
+            peers,
+            vec![PeerBlock {
+                worker_id: 2,
+                block_hash: 4
+            }]
+        );
+    }
+}
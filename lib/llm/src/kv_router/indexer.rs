@@ -51,6 +51,7 @@ use std::{
     cell::RefCell,
     collections::{HashMap, HashSet, VecDeque},
     iter,
+    ops::Range,
     rc::Rc,
     sync::OnceLock,
     thread::JoinHandle,
@@ -100,39 +101,60 @@ pub fn compute_block_hash(data: &[u8]) -> LocalBlockHash {
     LocalBlockHash(compute_hash(data))
 }
 
-// /// Updated version of the `compute_block_hash` function that included the lora_id
-// pub fn compute_block_hash_v2(token_id: &[u32], lora_id: u64) {
-//     let mut bytes = Vec::new();
-//     for token in token_id {
-//         bytes.extend_from_slice(&token.to_le_bytes());
-//     }
-//     bytes.extend_from_slice(&lora_id.to_le_bytes());
-//     let hash = xxh3::xxh3_64_with_seed(&bytes, XXH3_SEED);
-// }
-
 /// Compute the hash for a sequence of tokens.
 ///
+/// The optional LoRA adapter is folded into every block hash so that identical prompts served
+/// by different adapters never alias each other in the radix tree. A `lora_id` of `0` denotes
+/// the base model and produces the same hashes as a token-only hash.
+///
 /// ### Arguments
 ///
 /// * `tokens` - A vector of `u32` tokens.
+/// * `kv_block_size` - The number of tokens per block.
+/// * `lora_id` - The LoRA adapter id the blocks are computed with, or `0` for the base model.
 ///
 /// ### Returns
 ///
 /// A vector of `LocalBlockHash` representing the computed hashes for each chunk of tokens.
-pub fn compute_block_hash_for_seq(tokens: &[u32], kv_block_size: usize) -> Vec<LocalBlockHash> {
+pub fn compute_block_hash_for_seq(
+    tokens: &[u32],
+    kv_block_size: usize,
+    lora_id: u64,
+) -> Vec<LocalBlockHash> {
     tokens
         .chunks_exact(kv_block_size) // Split into chunks of kv_block_size elements
         .map(|chunk| {
-            let bytes: Vec<u8> = chunk
+            let mut bytes: Vec<u8> = chunk
                 .iter()
                 .flat_map(|&num| num.to_le_bytes()) // Convert each i32 to its little-endian bytes
                 .collect();
 
+            if lora_id != 0 {
+                bytes.extend_from_slice(&lora_id.to_le_bytes());
+            }
+
             compute_block_hash(&Bytes::from(bytes)) // Convert the byte Vec to Bytes
         })
         .collect()
 }
 
+/// The value to fold into the block hashes of a prompt in place of the `lora_id` of
+/// [`compute_block_hash_for_seq`], for a prompt which attaches media.
+///
+/// The placeholder tokens of a media are the same whatever the media, so the hashes of the media
+/// are folded into every block: the prompt only matches blocks cached for the same adapter and
+/// the same media. Without media this is `lora_id`.
+pub fn compute_block_hash_salt(lora_id: u64, media_hashes: &[u64]) -> u64 {
+    if media_hashes.is_empty() {
+        return lora_id;
+    }
+    let bytes: Vec<u8> = std::iter::once(lora_id)
+        .chain(media_hashes.iter().copied())
+        .flat_map(u64::to_le_bytes)
+        .collect();
+    compute_hash(&bytes)
+}
+
 /// A [`KvCacheEvent`] on a specific LLM worker denoted by [`WorkerId`].
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct RouterEvent {
@@ -156,6 +178,19 @@ impl RouterEvent {
     pub fn new(worker_id: WorkerId, event: KvCacheEvent) -> Self {
         Self { worker_id, event }
     }
+
+    /// The ID of the worker emitting the event.
+    pub fn worker_id(&self) -> WorkerId {
+        self.worker_id
+    }
+
+    /// The LoRA adapter of the blocks carried by this event, if it is a store event for an adapter.
+    pub fn lora_id(&self) -> Option<u64> {
+        match &self.event.data {
+            KvCacheEventData::Stored(store) => store.lora_id,
+            _ => None,
+        }
+    }
 }
 
 /// A block in the Radix Tree.
@@ -200,6 +235,14 @@ pub struct RadixTree {
     lookup: HashMap<WorkerId, HashMap<ExternalSequenceBlockHash, SharedRadixBlock>>,
     /// The time buffer the radix tree should check when considering frequence of block accesses
     expiration_duration: Option<Duration>,
+    /// The id of the last event applied for each worker.
+    last_event_ids: HashMap<WorkerId, u64>,
+    /// Workers whose blocks were restored from a [`RadixTreeSnapshot`] and have not sent an event
+    /// since, with the last event id recorded in the snapshot.
+    restored_event_ids: HashMap<WorkerId, u64>,
+    /// Workers rebuilt from a state dump, with the last event id the dump reflects. Events up to
+    /// that id are already part of the dump and are skipped.
+    resynced_event_ids: HashMap<WorkerId, u64>,
 }
 
 impl Default for RadixTree {
@@ -219,6 +262,9 @@ impl RadixTree {
             root: Rc::new(RefCell::new(RadixBlock::new())),
             lookup: HashMap::new(),
             expiration_duration,
+            last_event_ids: HashMap::new(),
+            restored_event_ids: HashMap::new(),
+            resynced_event_ids: HashMap::new(),
         }
     }
 
@@ -247,6 +293,7 @@ impl RadixTree {
             };
             if let Some(block) = next_block {
                 scores.update_scores(&block.borrow().workers);
+                scores.add_block_workers(&block.borrow().workers);
 
                 if let Some(expiration_duration) = self.expiration_duration {
                     let mut block_mut = block.borrow_mut();
@@ -285,6 +332,34 @@ impl RadixTree {
         let (id, op) = (event.event_id, event.data);
         tracing::trace!(id, "Store operation: {:?}", op);
 
+        if let Some(&resynced_id) = self.resynced_event_ids.get(&worker_id) {
+            if id <= resynced_id {
+                tracing::trace!(
+                    worker_id = worker_id.to_string(),
+                    id,
+                    resynced_id,
+                    "Skipping event already reflected in the worker's state dump"
+                );
+                return;
+            }
+            self.resynced_event_ids.remove(&worker_id);
+        }
+
+        // A worker restarts its event ids from zero, so an id at or below the one recorded in the
+        // snapshot means the restored blocks no longer reflect the worker's cache.
+        if let Some(restored_id) = self.restored_event_ids.remove(&worker_id) {
+            if id <= restored_id {
+                tracing::debug!(
+                    worker_id = worker_id.to_string(),
+                    id,
+                    restored_id,
+                    "Worker restarted since the snapshot; dropping restored blocks"
+                );
+                self.clear_all_blocks(worker_id);
+            }
+        }
+        self.last_event_ids.insert(worker_id, id);
+
         let worker_lookup = self.lookup.entry(worker_id).or_default();
 
         match op {
@@ -380,6 +455,9 @@ impl RadixTree {
     }
 
     pub fn remove_worker(&mut self, worker: WorkerId) {
+        self.last_event_ids.remove(&worker);
+        self.restored_event_ids.remove(&worker);
+        self.resynced_event_ids.remove(&worker);
         if let Some((_, blocks)) = self.lookup.remove_entry(&worker) {
             blocks.iter().for_each(|(_, block)| {
                 block.borrow_mut().workers.remove(&worker);
@@ -403,6 +481,190 @@ impl RadixTree {
             }
         }
     }
+
+    /// Capture the blocks held by every worker, together with the id of the last event applied
+    /// for it, so the tree can be rebuilt with [`RadixTree::from_snapshot`].
+    pub fn snapshot(&self) -> RadixTreeSnapshot {
+        let mut workers: Vec<WorkerSnapshot> = self
+            .lookup
+            .iter()
+            .map(|(worker_id, worker_lookup)| {
+                // the tree is keyed by local hashes; recover the worker's external hashes
+                let external: HashMap<*const RefCell<RadixBlock>, ExternalSequenceBlockHash> =
+                    worker_lookup
+                        .iter()
+                        .map(|(hash, block)| (Rc::as_ptr(block), *hash))
+                        .collect();
+
+                // walk the tree from the root so parents are always listed before their children
+                let mut blocks = Vec::with_capacity(worker_lookup.len());
+                let mut stack = vec![(self.root.clone(), None)];
+                while let Some((current, parent_hash)) = stack.pop() {
+                    let current = current.borrow();
+                    let mut children: Vec<_> = current.children.iter().collect();
+                    children.sort_by_key(|(tokens_hash, _)| **tokens_hash);
+                    for (tokens_hash, child) in children {
+                        if !child.borrow().workers.contains(worker_id) {
+                            continue;
+                        }
+                        let Some(block_hash) = external.get(&Rc::as_ptr(child)).copied() else {
+                            continue;
+                        };
+                        blocks.push(SnapshotBlock {
+                            parent_hash,
+                            block_hash,
+                            tokens_hash: *tokens_hash,
+                        });
+                        stack.push((child.clone(), Some(block_hash)));
+                    }
+                }
+
+                WorkerSnapshot {
+                    worker_id: *worker_id,
+                    last_event_id: self.last_event_ids.get(worker_id).copied(),
+                    blocks,
+                }
+            })
+            .collect();
+        workers.sort_by_key(|worker| worker.worker_id);
+
+        RadixTreeSnapshot { workers }
+    }
+
+    /// Rebuild a `RadixTree` from a [`RadixTreeSnapshot`].
+    ///
+    /// Restored workers are tracked until their next event: if that event's id is not newer than
+    /// the snapshot's, the worker has restarted in the meantime and its restored blocks are dropped.
+    ///
+    /// ### Arguments
+    ///
+    /// * `snapshot` - The snapshot to restore.
+    /// * `expiration_duration` - The amount of time that block usage should be buffered.
+    ///
+    /// ### Returns
+    ///
+    /// A new `RadixTree`.
+    pub fn from_snapshot(
+        snapshot: RadixTreeSnapshot,
+        expiration_duration: Option<Duration>,
+    ) -> Self {
+        let mut tree = Self::new_with_frequency(expiration_duration);
+        for worker in snapshot.workers {
+            if let Some(last_event_id) = worker.last_event_id {
+                tree.restored_event_ids
+                    .insert(worker.worker_id, last_event_id);
+            }
+            tree.replay_worker(worker);
+        }
+        tree
+    }
+
+    /// The ids of the events lost between the last event applied for the worker and `event`,
+    /// if `event` skips ahead of the worker's event stream.
+    pub fn missing_events(&self, event: &RouterEvent) -> Option<Range<u64>> {
+        let last_event_id = *self.last_event_ids.get(&event.worker_id)?;
+        let id = event.event.event_id;
+        (id > last_event_id + 1).then(|| last_event_id + 1..id)
+    }
+
+    /// Replace everything known about a worker with a full dump of its state, such as after
+    /// [`RadixTree::missing_events`] reported lost events. Events the dump already reflects are
+    /// skipped when they arrive afterwards.
+    ///
+    /// ### Arguments
+    ///
+    /// * `state` - The worker's stored blocks and the id of the last event they reflect.
+    pub fn resync_worker(&mut self, state: WorkerSnapshot) {
+        let worker_id = state.worker_id;
+        self.remove_worker(worker_id);
+        if let Some(last_event_id) = state.last_event_id {
+            self.resynced_event_ids.insert(worker_id, last_event_id);
+        }
+        self.replay_worker(state);
+    }
+
+    /// Store a worker's blocks and record its last event id, bypassing the event id checks.
+    fn replay_worker(&mut self, worker: WorkerSnapshot) {
+        let (worker_id, last_event_id) = (worker.worker_id, worker.last_event_id);
+        let (restored, resynced) = (
+            self.restored_event_ids.remove(&worker_id),
+            self.resynced_event_ids.remove(&worker_id),
+        );
+
+        // keep track of the worker even when it holds no blocks
+        self.lookup.entry(worker_id).or_default();
+        for block in worker.blocks {
+            self.apply_event(RouterEvent::new(
+                worker_id,
+                KvCacheEvent {
+                    event_id: last_event_id.unwrap_or_default(),
+                    data: KvCacheEventData::Stored(KvCacheStoreData {
+                        parent_hash: block.parent_hash,
+                        blocks: vec![KvCacheStoredBlockData {
+                            block_hash: block.block_hash,
+                            tokens_hash: block.tokens_hash,
+                        }],
+                        lora_id: None,
+                        media_hashes: Vec::new(),
+                    }),
+                },
+            ));
+        }
+
+        match last_event_id {
+            Some(last_event_id) => self.last_event_ids.insert(worker_id, last_event_id),
+            None => self.last_event_ids.remove(&worker_id),
+        };
+        if let Some(restored) = restored {
+            self.restored_event_ids.insert(worker_id, restored);
+        }
+        if let Some(resynced) = resynced {
+            self.resynced_event_ids.insert(worker_id, resynced);
+        }
+    }
+}
+
+/// A block held by a worker, as recorded in a [`WorkerSnapshot`].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SnapshotBlock {
+    /// The external hash of the parent block, or `None` for a root block.
+    pub parent_hash: Option<ExternalSequenceBlockHash>,
+    /// The external hash of the block.
+    pub block_hash: ExternalSequenceBlockHash,
+    /// The local hash of the block's tokens.
+    pub tokens_hash: LocalBlockHash,
+}
+
+/// The blocks held by a single worker in a [`RadixTreeSnapshot`].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct WorkerSnapshot {
+    /// The ID of the worker.
+    pub worker_id: WorkerId,
+    /// The id of the last event applied for the worker, if any.
+    pub last_event_id: Option<u64>,
+    /// The worker's blocks, ordered so that every parent precedes its children.
+    pub blocks: Vec<SnapshotBlock>,
+}
+
+/// A serializable image of a [`RadixTree`], used to restore routing state across restarts.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct RadixTreeSnapshot {
+    pub workers: Vec<WorkerSnapshot>,
+}
+
+impl RadixTreeSnapshot {
+    /// Drop the workers which are not in `live_workers`, returning how many were dropped.
+    pub fn retain_workers(&mut self, live_workers: &HashSet<WorkerId>) -> usize {
+        let before = self.workers.len();
+        self.workers
+            .retain(|worker| live_workers.contains(&worker.worker_id));
+        before - self.workers.len()
+    }
+
+    /// The total number of blocks across all workers.
+    pub fn num_blocks(&self) -> usize {
+        self.workers.iter().map(|worker| worker.blocks.len()).sum()
+    }
 }
 
 /// Scores representing the overlap of workers.
@@ -412,6 +674,9 @@ pub struct OverlapScores {
     pub scores: HashMap<WorkerId, u32>,
     // List of frequencies that the blocks have been accessed. Entries with value 0 are omitted.
     pub frequencies: Vec<usize>,
+    // The workers holding each matched block of the sequence, in sequence order.
+    #[serde(default)]
+    pub block_workers: Vec<Vec<WorkerId>>,
 }
 
 impl Default for OverlapScores {
@@ -430,6 +695,7 @@ impl OverlapScores {
         Self {
             scores: HashMap::new(),
             frequencies: Vec::with_capacity(32),
+            block_workers: Vec::new(),
         }
     }
 
@@ -445,6 +711,13 @@ impl OverlapScores {
         }
     }
 
+    /// Record the workers holding the next matched block of the sequence.
+    pub fn add_block_workers(&mut self, workers: &HashSet<WorkerId>) {
+        let mut workers: Vec<WorkerId> = workers.iter().copied().collect();
+        workers.sort_unstable();
+        self.block_workers.push(workers);
+    }
+
     /// Add an entry in the frequency list.
     pub fn add_frequency(&mut self, frequency: usize) {
         if frequency != 0 {
@@ -487,6 +760,7 @@ pub trait KvIndexerInterface {
     /// ### Arguments
     ///
     /// * `tokens` - A vector of `u32` tokens.
+    /// * `lora_id` - The LoRA adapter the request targets, or `0` for the base model.
     ///
     /// ### Returns
     ///
@@ -494,6 +768,7 @@ pub trait KvIndexerInterface {
     async fn find_matches_for_request(
         &self,
         tokens: &[u32],
+        lora_id: u64,
     ) -> Result<OverlapScores, KvRouterError>;
 
     /// Apply a `RouterEvent` to the KV store.
@@ -524,6 +799,12 @@ pub struct KvIndexer {
     match_tx: mpsc::Sender<MatchRequest>,
     /// A sender for remove worker requests.
     remove_worker_tx: mpsc::Sender<WorkerId>,
+    /// A sender for snapshot requests.
+    snapshot_tx: mpsc::Sender<oneshot::Sender<RadixTreeSnapshot>>,
+    /// A sender for full worker state dumps, used to resync workers after lost events.
+    resync_tx: mpsc::Sender<WorkerSnapshot>,
+    /// Notifies subscribers of workers whose events were lost.
+    event_gap_tx: broadcast::Sender<WorkerId>,
     /// A handle to the background task managing the KV store.
     task: OnceLock<std::thread::JoinHandle<()>>,
     /// The size of the KV block this indexer can handle.
@@ -545,10 +826,35 @@ impl KvIndexer {
         token: CancellationToken,
         expiration_duration: Option<Duration>,
         kv_block_size: usize,
+    ) -> Self {
+        Self::new_with_snapshot(token, expiration_duration, kv_block_size, None)
+    }
+
+    /// Create a new `KvIndexer` whose tree starts from a previously taken snapshot.
+    ///
+    /// ### Arguments
+    ///
+    /// * `token` - A `CancellationToken` for managing shutdown.
+    /// * `expiration_duration` - The amount of time that block usage should be buffered.
+    /// * `kv_block_size` - The number of tokens per block.
+    /// * `snapshot` - The snapshot to restore, or `None` to start with an empty tree.
+    ///
+    /// ### Returns
+    ///
+    /// A new `KvIndexer`.
+    pub fn new_with_snapshot(
+        token: CancellationToken,
+        expiration_duration: Option<Duration>,
+        kv_block_size: usize,
+        snapshot: Option<RadixTreeSnapshot>,
     ) -> Self {
         let (event_tx, event_rx) = mpsc::channel::<RouterEvent>(2048);
         let (match_tx, match_rx) = mpsc::channel::<MatchRequest>(128);
         let (remove_worker_tx, remove_worker_rx) = mpsc::channel::<WorkerId>(16);
+        let (snapshot_tx, snapshot_rx) = mpsc::channel::<oneshot::Sender<RadixTreeSnapshot>>(4);
+        let (resync_tx, resync_rx) = mpsc::channel::<WorkerSnapshot>(16);
+        let (event_gap_tx, _) = broadcast::channel::<WorkerId>(128);
+        let event_gap_tx_clone = event_gap_tx.clone();
         let cancel_clone = token.clone();
         let task = std::thread::spawn(move || {
             // create a new tokio runtime which will only perform work on a single thread
@@ -566,7 +872,13 @@ impl KvIndexer {
                     let mut match_rx = match_rx;
                     let mut event_rx = event_rx;
                     let mut remove_worker_rx = remove_worker_rx;
-                    let mut trie = RadixTree::new_with_frequency(expiration_duration);
+                    let mut snapshot_rx = snapshot_rx;
+                    let mut resync_rx = resync_rx;
+                    let event_gap_tx = event_gap_tx_clone;
+                    let mut trie = match snapshot {
+                        Some(snapshot) => RadixTree::from_snapshot(snapshot, expiration_duration),
+                        None => RadixTree::new_with_frequency(expiration_duration),
+                    };
                     loop {
                         tokio::select! {
                             biased;
@@ -575,6 +887,26 @@ impl KvIndexer {
                                 trie.remove_worker(worker);
                             }
 
+                            Some(resp) = snapshot_rx.recv() => {
+                                // the snapshot reflects every state dump and event received before
+                                // it was asked for; events a dump already covers are skipped. Only
+                                // what is queued now is drained, so a steady inflow of events can't
+                                // keep the loop from serving matches
+                                for _ in 0..resync_rx.len() {
+                                    let Ok(state) = resync_rx.try_recv() else { break };
+                                    resync_worker(&mut trie, state);
+                                }
+                                for _ in 0..event_rx.len() {
+                                    let Ok(event) = event_rx.try_recv() else { break };
+                                    apply_event(&mut trie, event, &event_gap_tx);
+                                }
+                                let _ = resp.send(trie.snapshot());
+                            }
+
+                            Some(state) = resync_rx.recv() => {
+                                resync_worker(&mut trie, state);
+                            }
+
                             Some(req) = match_rx.recv() => {
                                 let matches = trie.find_matches(req.sequence, req.early_exit);
                                 let _ = req.resp.send(matches);
@@ -586,7 +918,7 @@ impl KvIndexer {
                             }
 
                             Some(event) = event_rx.recv() => {
-                                trie.apply_event(event);
+                                apply_event(&mut trie, event, &event_gap_tx);
                             }
                         }
                     }
@@ -606,6 +938,9 @@ impl KvIndexer {
             event_tx,
             match_tx,
             remove_worker_tx,
+            snapshot_tx,
+            resync_tx,
+            event_gap_tx,
             task: once,
             kv_block_size,
         }
@@ -627,6 +962,78 @@ impl KvIndexer {
     pub fn event_sender(&self) -> mpsc::Sender<RouterEvent> {
         self.event_tx.clone()
     }
+
+    /// Get a sender for full worker state dumps. Each dump replaces everything the indexer knows
+    /// about that worker.
+    pub fn resync_sender(&self) -> mpsc::Sender<WorkerSnapshot> {
+        self.resync_tx.clone()
+    }
+
+    /// Subscribe to the ids of workers whose KV events were lost, i.e. whose event ids skipped
+    /// ahead. Such workers should be resynced through [`KvIndexer::resync_sender`].
+    pub fn subscribe_event_gaps(&self) -> broadcast::Receiver<WorkerId> {
+        self.event_gap_tx.subscribe()
+    }
+
+    /// Get a handle which can take snapshots of the indexer's tree.
+    pub fn snapshot_handle(&self) -> SnapshotHandle {
+        SnapshotHandle {
+            snapshot_tx: self.snapshot_tx.clone(),
+        }
+    }
+
+    /// Take a snapshot of the indexer's tree.
+    pub async fn snapshot(&self) -> Result<RadixTreeSnapshot, KvRouterError> {
+        self.snapshot_handle().snapshot().await
+    }
+}
+
+/// Apply `event` to the tree, announcing the worker on `event_gap_tx` if events before it were
+/// lost.
+fn apply_event(
+    trie: &mut RadixTree,
+    event: RouterEvent,
+    event_gap_tx: &broadcast::Sender<WorkerId>,
+) {
+    if let Some(missing) = trie.missing_events(&event) {
+        tracing::warn!(
+            worker_id = event.worker_id.to_string(),
+            ?missing,
+            "Lost KV events from worker; requesting a resync"
+        );
+        // no subscribers just means nobody can resync the worker
+        let _ = event_gap_tx.send(event.worker_id);
+    }
+    trie.apply_event(event);
+}
+
+/// Replace everything the tree knows about a worker with its state dump.
+fn resync_worker(trie: &mut RadixTree, state: WorkerSnapshot) {
+    tracing::info!(
+        worker_id = state.worker_id.to_string(),
+        blocks = state.blocks.len(),
+        "Resyncing worker from its KV state dump"
+    );
+    trie.resync_worker(state);
+}
+
+/// A cloneable handle for taking snapshots of a [`KvIndexer`]'s tree from another task.
+#[derive(Clone)]
+pub struct SnapshotHandle {
+    snapshot_tx: mpsc::Sender<oneshot::Sender<RadixTreeSnapshot>>,
+}
+
+impl SnapshotHandle {
+    /// Take a snapshot of the indexer's tree.
+    pub async fn snapshot(&self) -> Result<RadixTreeSnapshot, KvRouterError> {
+        let (resp_tx, resp_rx) = oneshot::channel();
+        if self.snapshot_tx.send(resp_tx).await.is_err() {
+            return Err(KvRouterError::IndexerOffline);
+        }
+        resp_rx
+            .await
+            .map_err(|_| KvRouterError::IndexerDroppedRequest)
+    }
 }
 
 #[async_trait]
@@ -658,13 +1065,15 @@ impl KvIndexerInterface for KvIndexer {
     async fn find_matches_for_request(
         &self,
         tokens: &[u32],
+        lora_id: u64,
     ) -> Result<OverlapScores, KvRouterError> {
         tracing::debug!(
-            "Finding matches for request tokens: {:?} / len: {}",
+            "Finding matches for request tokens: {:?} / len: {} / lora_id: {}",
             tokens,
-            tokens.len()
+            tokens.len(),
+            lora_id
         );
-        let sequence = compute_block_hash_for_seq(tokens, self.kv_block_size);
+        let sequence = compute_block_hash_for_seq(tokens, self.kv_block_size, lora_id);
         tracing::debug!("Computed sequence: {:?}", sequence);
         self.find_matches(sequence).await
     }
@@ -834,6 +1243,20 @@ impl KvIndexerInterface for KvIndexerSharded {
                     Some(response) => {
                         scores.scores.extend(response.scores);
 
+                        // Each shard matched the sequence against its own workers, so the
+                        // holders of a block are the union across shards.
+                        if scores.block_workers.len() < response.block_workers.len() {
+                            scores
+                                .block_workers
+                                .resize(response.block_workers.len(), Vec::new());
+                        }
+                        for (workers, shard_workers) in
+                            scores.block_workers.iter_mut().zip(response.block_workers)
+                        {
+                            workers.extend(shard_workers);
+                            workers.sort_unstable();
+                        }
+
                         if response_num == 0 {
                             scores.frequencies = response.frequencies;
                         } else {
@@ -863,8 +1286,9 @@ impl KvIndexerInterface for KvIndexerSharded {
     async fn find_matches_for_request(
         &self,
         tokens: &[u32],
+        lora_id: u64,
     ) -> Result<OverlapScores, KvRouterError> {
-        let sequence = compute_block_hash_for_seq(tokens, self.kv_block_size);
+        let sequence = compute_block_hash_for_seq(tokens, self.kv_block_size, lora_id);
         self.find_matches(sequence).await
     }
 
@@ -911,8 +1335,10 @@ impl KvIndexerInterface for KvIndexerSharded {
 mod tests {
 
     use super::*;
+    use crate::kv_router::publisher::create_stored_blocks;
     use rstest::rstest;
     use rstest_reuse::{self, *};
+    use std::sync::{atomic::AtomicU32, Arc};
     use tokio::time;
     use tokio_util::sync::CancellationToken;
 
@@ -937,6 +1363,8 @@ mod tests {
         KvCacheEventData::Stored(KvCacheStoreData {
             parent_hash,
             blocks: make_blocks(hashes),
+            lora_id: None,
+            media_hashes: Vec::new(),
         })
     }
 
@@ -1175,6 +1603,26 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_block_workers() {
+        setup();
+        let mut trie = RadixTree::new();
+
+        trie.apply_event(create_store_event(0, 1, vec![1, 2], None));
+        trie.apply_event(create_store_event(1, 1, vec![1, 2, 3], None));
+
+        let scores = trie.find_matches(
+            vec![
+                LocalBlockHash(1),
+                LocalBlockHash(2),
+                LocalBlockHash(3),
+                LocalBlockHash(4),
+            ],
+            false,
+        );
+        assert_eq!(scores.block_workers, vec![vec![0, 1], vec![0, 1], vec![1]]);
+    }
+
     #[test]
     fn test_remove_worker() {
         setup();
@@ -1316,24 +1764,383 @@ mod tests {
         setup();
         // create a sequence of 64 elements
         let sequence = (0..kv_block_size).map(|i| i as u32).collect::<Vec<u32>>();
-        let hashes = compute_block_hash_for_seq(&sequence, kv_block_size);
+        let hashes = compute_block_hash_for_seq(&sequence, kv_block_size, 0);
         assert_eq!(hashes.len(), 1);
 
         // create a sequence of 65 elements
         let sequence = (0..(kv_block_size + 1))
             .map(|i| i as u32)
             .collect::<Vec<u32>>();
-        let hashes = compute_block_hash_for_seq(&sequence, kv_block_size);
+        let hashes = compute_block_hash_for_seq(&sequence, kv_block_size, 0);
         assert_eq!(hashes.len(), 1);
 
         // create a sequence of 129 elements
         let sequence = (0..(2 * kv_block_size + 1))
             .map(|i| i as u32)
             .collect::<Vec<u32>>();
-        let hashes = compute_block_hash_for_seq(&sequence, kv_block_size);
+        let hashes = compute_block_hash_for_seq(&sequence, kv_block_size, 0);
         assert_eq!(hashes.len(), 2);
     }
 
+    #[test]
+    fn test_compute_block_hash_for_seq_lora() {
+        setup();
+        let sequence = (0..32).collect::<Vec<u32>>();
+
+        let base = compute_block_hash_for_seq(&sequence, 16, 0);
+        let lora_1 = compute_block_hash_for_seq(&sequence, 16, 1);
+        let lora_2 = compute_block_hash_for_seq(&sequence, 16, 2);
+
+        // the base model hash is the plain token hash
+        let bytes: Vec<u8> = sequence[..16]
+            .iter()
+            .flat_map(|t| t.to_le_bytes())
+            .collect();
+        assert_eq!(base[0], compute_block_hash(&bytes));
+
+        assert_eq!(lora_1, compute_block_hash_for_seq(&sequence, 16, 1));
+        assert_ne!(base, lora_1);
+        assert_ne!(lora_1, lora_2);
+    }
+
+    #[test]
+    fn test_compute_block_hash_salt() {
+        setup();
+        assert_eq!(compute_block_hash_salt(3, &[]), 3);
+
+        let image_1 = compute_block_hash_salt(0, &[11]);
+        let image_2 = compute_block_hash_salt(0, &[12]);
+        assert_eq!(image_1, compute_block_hash_salt(0, &[11]));
+        assert_ne!(image_1, image_2);
+        assert_ne!(image_1, compute_block_hash_salt(3, &[11]));
+        assert_ne!(
+            compute_block_hash_salt(0, &[11, 12]),
+            compute_block_hash_salt(0, &[12, 11])
+        );
+    }
+
+    #[test]
+    fn test_radix_tree_media_isolation() {
+        setup();
+        let mut trie = RadixTree::new();
+        let kv_block_size = 4;
+        let tokens = (0..8).collect::<Vec<u32>>();
+
+        // the worker reports the blocks of a prompt with image 11
+        let blocks = create_stored_blocks(
+            kv_block_size,
+            &tokens,
+            &[4, 4],
+            &[1, 2],
+            0,
+            &[11],
+            &Arc::new(AtomicU32::new(0)),
+        );
+        trie.apply_event(RouterEvent::new(
+            0,
+            KvCacheEvent {
+                event_id: 0,
+                data: KvCacheEventData::Stored(KvCacheStoreData {
+                    parent_hash: None,
+                    blocks,
+                    lora_id: None,
+                    media_hashes: vec![11],
+                }),
+            },
+        ));
+
+        let request = |media_hashes: &[u64]| {
+            let salt = compute_block_hash_salt(0, media_hashes);
+            compute_block_hash_for_seq(&tokens, kv_block_size, salt)
+        };
+        assert_eq!(trie.find_matches(request(&[11]), false).scores[&0], 2);
+        // same tokens, different media
+        assert!(trie.find_matches(request(&[12]), false).scores.is_empty());
+        assert!(trie.find_matches(request(&[]), false).scores.is_empty());
+    }
+
+    #[test]
+    fn test_radix_tree_lora_isolation() {
+        setup();
+        let mut trie = RadixTree::new();
+        let kv_block_size = 4;
+
+        let worker_base = 0;
+        let worker_lora = 1;
+        let tokens = (0..8).collect::<Vec<u32>>();
+
+        let base_hashes = compute_block_hash_for_seq(&tokens, kv_block_size, 0);
+        let lora_hashes = compute_block_hash_for_seq(&tokens, kv_block_size, 7);
+
+        let store = |worker_id: WorkerId, hashes: &[LocalBlockHash], lora_id: Option<u64>| {
+            RouterEvent::new(
+                worker_id,
+                KvCacheEvent {
+                    event_id: 0,
+                    data: KvCacheEventData::Stored(KvCacheStoreData {
+                        parent_hash: None,
+                        blocks: hashes
+                            .iter()
+                            .enumerate()
+                            .map(|(i, hash)| KvCacheStoredBlockData {
+                                tokens_hash: *hash,
+                                block_hash: ExternalSequenceBlockHash(i as u64 + 1),
+                            })
+                            .collect(),
+                        lora_id,
+                        media_hashes: Vec::new(),
+                    }),
+                },
+            )
+        };
+
+        trie.apply_event(store(worker_base, &base_hashes, None));
+        trie.apply_event(store(worker_lora, &lora_hashes, Some(7)));
+
+        // identical prompts only match the worker holding cache for the same adapter
+        let scores = trie.find_matches(base_hashes, false).scores;
+        assert_eq!(scores.len(), 1);
+        assert_eq!(scores[&worker_base], 2);
+
+        let scores = trie.find_matches(lora_hashes, false).scores;
+        assert_eq!(scores.len(), 1);
+        assert_eq!(scores[&worker_lora], 2);
+
+        let other_lora = compute_block_hash_for_seq(&tokens, kv_block_size, 8);
+        assert!(trie.find_matches(other_lora, false).scores.is_empty());
+    }
+
+    #[test]
+    fn test_radix_tree_snapshot_roundtrip() {
+        setup();
+        let mut trie = RadixTree::new();
+
+        trie.apply_event(create_store_event(0, 1, vec![1, 2, 3], None));
+        trie.apply_event(create_store_event(
+            0,
+            2,
+            vec![4],
+            Some(ExternalSequenceBlockHash(300)),
+        ));
+        trie.apply_event(create_store_event(1, 5, vec![1, 2], None));
+        trie.apply_event(create_store_event(
+            1,
+            6,
+            vec![5],
+            Some(ExternalSequenceBlockHash(200)),
+        ));
+        trie.apply_event(create_remove_event(1, 7, vec![5]));
+
+        let snapshot = trie.snapshot();
+        assert_eq!(snapshot.workers.len(), 2);
+        assert_eq!(snapshot.workers[0].last_event_id, Some(2));
+        assert_eq!(snapshot.workers[1].last_event_id, Some(7));
+        assert_eq!(snapshot.num_blocks(), 6);
+
+        // survives serialization and rebuilds an equivalent tree
+        let json = serde_json::to_string(&snapshot).unwrap();
+        let restored: RadixTreeSnapshot = serde_json::from_str(&json).unwrap();
+        let restored = RadixTree::from_snapshot(restored, None);
+        assert_eq!(restored.snapshot(), snapshot);
+
+        for sequence in [vec![1, 2, 3, 4], vec![1, 2, 5]] {
+            let sequence: Vec<_> = sequence.into_iter().map(LocalBlockHash).collect();
+            assert_eq!(
+                restored.find_matches(sequence.clone(), false).scores,
+                trie.find_matches(sequence, false).scores
+            );
+        }
+    }
+
+    #[test]
+    fn test_radix_tree_snapshot_restarted_worker() {
+        setup();
+        let mut trie = RadixTree::new();
+        trie.apply_event(create_store_event(0, 10, vec![1, 2], None));
+        trie.apply_event(create_store_event(1, 10, vec![1, 2], None));
+
+        let mut restored = RadixTree::from_snapshot(trie.snapshot(), None);
+        let sequence = vec![LocalBlockHash(1), LocalBlockHash(2)];
+        assert_eq!(
+            restored.find_matches(sequence.clone(), false).scores.len(),
+            2
+        );
+
+        // worker 0 continues its event stream, so its restored blocks are kept
+        restored.apply_event(create_store_event(
+            0,
+            11,
+            vec![3],
+            Some(ExternalSequenceBlockHash(200)),
+        ));
+        // worker 1 restarted and numbers its events from zero again
+        restored.apply_event(create_store_event(1, 0, vec![7], None));
+
+        let scores = restored.find_matches(sequence, false).scores;
+        assert_eq!(scores.len(), 1);
+        assert_eq!(scores[&0], 2);
+        let scores = restored.find_matches(vec![LocalBlockHash(7)], false).scores;
+        assert_eq!(scores[&1], 1);
+    }
+
+    #[test]
+    fn test_radix_tree_snapshot_retain_workers() {
+        let mut trie = RadixTree::new();
+        trie.apply_event(create_store_event(0, 1, vec![1], None));
+        trie.apply_event(create_store_event(1, 1, vec![1], None));
+
+        let mut snapshot = trie.snapshot();
+        assert_eq!(snapshot.retain_workers(&HashSet::from([1])), 1);
+        let restored = RadixTree::from_snapshot(snapshot, None);
+        let scores = restored.find_matches(vec![LocalBlockHash(1)], false).scores;
+        assert_eq!(scores.keys().copied().collect::<Vec<_>>(), vec![1]);
+    }
+
+    #[test]
+    fn test_radix_tree_missing_events() {
+        let mut trie = RadixTree::new();
+        let first = create_store_event(0, 4, vec![1], None);
+        // nothing is known about a worker before its first event
+        assert_eq!(trie.missing_events(&first), None);
+        trie.apply_event(first);
+
+        let next = create_store_event(0, 5, vec![2], Some(ExternalSequenceBlockHash(100)));
+        assert_eq!(trie.missing_events(&next), None);
+        trie.apply_event(next);
+
+        // several raw events from one engine batch share an id
+        assert_eq!(
+            trie.missing_events(&create_remove_event(0, 5, vec![2])),
+            None
+        );
+        assert_eq!(
+            trie.missing_events(&create_remove_event(0, 9, vec![2])),
+            Some(6..9)
+        );
+        assert_eq!(
+            trie.missing_events(&create_remove_event(1, 9, vec![2])),
+            None
+        );
+    }
+
+    #[test]
+    fn test_radix_tree_resync_worker() {
+        setup();
+        let mut trie = RadixTree::new();
+        trie.apply_event(create_store_event(0, 1, vec![1, 2, 3], None));
+        trie.apply_event(create_store_event(1, 1, vec![1, 2], None));
+
+        // worker 0 lost the removal of block 3 and the store of block 4
+        let state = WorkerSnapshot {
+            worker_id: 0,
+            last_event_id: Some(3),
+            blocks: vec![
+                SnapshotBlock {
+                    parent_hash: None,
+                    block_hash: ExternalSequenceBlockHash(100),
+                    tokens_hash: LocalBlockHash(1),
+                },
+                SnapshotBlock {
+                    parent_hash: Some(ExternalSequenceBlockHash(100)),
+                    block_hash: ExternalSequenceBlockHash(400),
+                    tokens_hash: LocalBlockHash(4),
+                },
+            ],
+        };
+        trie.resync_worker(state);
+
+        let scores = trie
+            .find_matches(vec![LocalBlockHash(1), LocalBlockHash(2)], false)
+            .scores;
+        assert_eq!(scores[&0], 1);
+        assert_eq!(scores[&1], 2);
+        let scores = trie
+            .find_matches(vec![LocalBlockHash(1), LocalBlockHash(4)], false)
+            .scores;
+        assert_eq!(scores[&0], 2);
+
+        // an event the dump already reflects arrives late and is skipped
+        trie.apply_event(create_remove_event(0, 3, vec![4]));
+        let scores = trie
+            .find_matches(vec![LocalBlockHash(1), LocalBlockHash(4)], false)
+            .scores;
+        assert_eq!(scores[&0], 2);
+
+        // newer events apply as usual
+        trie.apply_event(create_remove_event(0, 4, vec![4]));
+        let scores = trie
+            .find_matches(vec![LocalBlockHash(1), LocalBlockHash(4)], false)
+            .scores;
+        assert_eq!(scores[&0], 1);
+    }
+
+    #[tokio::test]
+    async fn test_kv_indexer_event_gaps() {
+        setup();
+        let token = CancellationToken::new();
+        let kv_indexer = KvIndexer::new(token.clone(), 4);
+        let mut gaps = kv_indexer.subscribe_event_gaps();
+        let event_tx = kv_indexer.event_sender();
+
+        event_tx
+            .send(create_store_event(0, 1, vec![1], None))
+            .await
+            .unwrap();
+        event_tx
+            .send(create_store_event(
+                0,
+                4,
+                vec![2],
+                Some(ExternalSequenceBlockHash(100)),
+            ))
+            .await
+            .unwrap();
+        let worker = time::timeout(Duration::from_secs(1), gaps.recv())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(worker, 0);
+
+        kv_indexer
+            .resync_sender()
+            .send(WorkerSnapshot {
+                worker_id: 0,
+                last_event_id: Some(4),
+                blocks: vec![],
+            })
+            .await
+            .unwrap();
+        // the dump is queued, so the snapshot includes it even if it is served first
+        let snapshot = kv_indexer.snapshot().await.unwrap();
+        assert_eq!(snapshot.workers[0].last_event_id, Some(4));
+        assert_eq!(snapshot.num_blocks(), 0);
+        token.cancel();
+    }
+
+    #[tokio::test]
+    async fn test_kv_indexer_snapshot() {
+        setup();
+        let token = CancellationToken::new();
+        let kv_indexer = KvIndexer::new(token.clone(), 4);
+        kv_indexer
+            .event_sender()
+            .send(create_store_event(0, 1, vec![1, 2], None))
+            .await
+            .unwrap();
+
+        // the event is queued, so the snapshot includes it even if it is served first
+        let snapshot = kv_indexer.snapshot().await.unwrap();
+        assert_eq!(snapshot.num_blocks(), 2);
+
+        let restored = KvIndexer::new_with_snapshot(token.clone(), None, 4, Some(snapshot));
+        let scores = restored
+            .find_matches(vec![LocalBlockHash(1), LocalBlockHash(2)])
+            .await
+            .unwrap();
+        assert_eq!(scores.scores[&0], 2);
+        token.cancel();
+    }
+
     fn make_indexer(
         token: &CancellationToken,
         num_shards: usize,
@@ -1387,7 +2194,7 @@ mod tests {
         let kv_indexer = make_indexer(&token, num_shards, kv_block_size);
 
         let tokens = vec![1, 2, 3, 4];
-        let scores = kv_indexer.find_matches_for_request(&tokens).await;
+        let scores = kv_indexer.find_matches_for_request(&tokens, 0).await;
 
         assert!(scores.unwrap().scores.is_empty());
     }
@@ -1530,6 +2337,8 @@ mod tests {
                     block_hash: ExternalSequenceBlockHash(0),
                     tokens_hash: LocalBlockHash(13226331709069118873),
                 }],
+                lora_id: None,
+                media_hashes: Vec::new(),
             }),
         };
         let router_event = RouterEvent::new(worker_id, kv_cache_event);
@@ -13,12 +13,17 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use crate::kv_router::scoring::ProcessedEndpoints;
 use crate::tokens::Token;
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
 pub struct RouterRequest {
     pub tokens: Vec<Token>,
+    /// The LoRA adapter the request targets; `None` for the base model.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub lora_id: Option<u64>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
@@ -39,6 +44,29 @@ pub struct WorkerSelectionResult {
     pub overlap_blocks: usize,
 }
 
+/// Everything the router considered when choosing a worker for a request, for debugging.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RoutingDecision {
+    /// The worker id of the selected worker
+    pub worker_id: i64,
+
+    /// The total number of blocks required to prefill the request
+    pub required_blocks: u64,
+
+    /// The number of blocks the selected worker may already have cached
+    pub overlap_blocks: usize,
+
+    /// Cached prefix length in blocks, per worker. Workers without any overlap are omitted.
+    pub overlap_scores: HashMap<i64, u32>,
+
+    /// The selector's score per worker, lower is better. Empty for selectors that do not score
+    /// workers individually.
+    pub logits: HashMap<i64, f64>,
+
+    /// The load metrics of the workers at the time of the decision
+    pub endpoints: ProcessedEndpoints,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
 pub struct ForwardPassMetrics {
     // https://lmsys.org/blog/2024-12-04-sglang-v0-4/#data-parallelism-attention-for-deepseek-models
@@ -119,6 +147,15 @@ pub struct KvCacheStoreData {
     pub parent_hash: Option<ExternalSequenceBlockHash>,
     /// A list of stored blocked data.
     pub blocks: Vec<KvCacheStoredBlockData>,
+    /// The LoRA adapter the blocks were computed with; `None` for the base model.
+    /// The adapter is already folded into each block's `tokens_hash`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub lora_id: Option<u64>,
+    /// The hashes of the media of the request the blocks were computed for, see
+    /// [`crate::kv_router::indexer::compute_block_hash_salt`]. Like the adapter, they are already
+    /// folded into each block's `tokens_hash`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub media_hashes: Vec<u64>,
 }
 
 /// Represents data for a stored block.
@@ -208,6 +245,8 @@ mod tests {
                 block_hash: ExternalSequenceBlockHash(2),
                 tokens_hash: LocalBlockHash(3),
             }],
+            lora_id: Some(4),
+            media_hashes: vec![5],
         });
 
         let event = KvCacheEvent {
@@ -230,12 +269,27 @@ mod tests {
             assert_eq!(store_data.blocks.len(), 1);
             assert_eq!(store_data.blocks[0].block_hash.0, 2);
             assert_eq!(store_data.blocks[0].tokens_hash.0, 3);
+            assert_eq!(store_data.lora_id, Some(4));
+            assert_eq!(store_data.media_hashes, vec![5]);
         } else {
             panic!("Expected KvCacheEventData::Stored variant");
         }
         assert!(!deserialized.shutdown);
     }
 
+    #[test]
+    fn test_kv_cache_store_data_without_lora_id() {
+        // events from publishers that predate LoRA support deserialize as the base model
+        let serialized = r#"{"parent_hash":null,"blocks":[{"block_hash":2,"tokens_hash":3}]}"#;
+        let deserialized: KvCacheStoreData = serde_json::from_str(serialized).unwrap();
+        assert_eq!(deserialized.lora_id, None);
+        assert!(deserialized.media_hashes.is_empty());
+
+        let reserialized = serde_json::to_string(&deserialized).unwrap();
+        assert!(!reserialized.contains("lora_id"));
+        assert!(!reserialized.contains("media_hashes"));
+    }
+
     #[test]
     fn test_kv_cache_remove_data_serialization() {
         let remove_data = KvCacheRemoveData {
@@ -14,9 +14,12 @@
 // limitations under the License.
 
 use crate::kv_router::{
-    indexer::{compute_block_hash_for_seq, RouterEvent},
+    indexer::{
+        compute_block_hash_for_seq, compute_block_hash_salt, RouterEvent, SnapshotBlock,
+        WorkerSnapshot,
+    },
     protocols::*,
-    KV_EVENT_SUBJECT, KV_METRICS_ENDPOINT,
+    KV_EVENT_SUBJECT, KV_METRICS_ENDPOINT, KV_STATE_ENDPOINT,
 };
 use async_trait::async_trait;
 use dynamo_runtime::traits::{events::EventPublisher, DistributedRuntimeProvider};
@@ -30,7 +33,8 @@ use dynamo_runtime::{
     Error, Result,
 };
 use futures::stream;
-use std::sync::Arc;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
 use tokio::sync::mpsc;
 use tokio_util::sync::CancellationToken;
 
@@ -106,6 +110,8 @@ pub struct KvEventPublisher {
     cancellation_token: CancellationToken,
     /// The channel to send events to.
     tx: mpsc::UnboundedSender<KvCacheEvent>,
+    /// The blocks published as stored so far, served to routers that lost events.
+    state: KvStateEndpoint,
 }
 
 impl KvEventPublisher {
@@ -118,6 +124,7 @@ impl KvEventPublisher {
         let cancellation_token = CancellationToken::new();
 
         let (tx, rx) = mpsc::unbounded_channel::<KvCacheEvent>();
+        let state = KvStateEndpoint::new(worker_id);
 
         // Create our event source (if any)
         let mut source = None;
@@ -140,6 +147,7 @@ impl KvEventPublisher {
                 worker_id,
                 cancellation_token.clone(),
                 rx,
+                state.clone(),
             ));
 
         Ok(Self {
@@ -147,6 +155,7 @@ impl KvEventPublisher {
             source,
             cancellation_token,
             tx,
+            state,
         })
     }
 
@@ -159,6 +168,13 @@ impl KvEventPublisher {
         self.kv_block_size
     }
 
+    /// The endpoint serving the blocks this publisher has announced, so a router that lost
+    /// some of its events can rebuild its view of this worker.
+    /// Start it with [`KvStateEndpoint::create_endpoint`].
+    pub fn state_endpoint(&self) -> KvStateEndpoint {
+        self.state.clone()
+    }
+
     pub fn shutdown(&mut self) {
         if !self.cancellation_token.is_cancelled() {
             self.cancellation_token.cancel();
@@ -181,6 +197,7 @@ async fn start_event_processor<P: EventPublisher + Send + Sync + 'static>(
     worker_id: i64,
     cancellation_token: CancellationToken,
     mut rx: mpsc::UnboundedReceiver<KvCacheEvent>,
+    state: KvStateEndpoint,
 ) {
     loop {
         tokio::select! {
@@ -194,6 +211,10 @@ async fn start_event_processor<P: EventPublisher + Send + Sync + 'static>(
                     break;
                 };
 
+                // Record the event before publishing it, so any state dump a router requests after
+                // seeing the event already reflects it.
+                state.apply(&event);
+
                 // Encapsulate in a router event and publish.
                 let router_event = RouterEvent::new(worker_id, event);
                 if let Err(e) = publisher.publish(KV_EVENT_SUBJECT, &router_event).await {
@@ -204,6 +225,118 @@ async fn start_event_processor<P: EventPublisher + Send + Sync + 'static>(
     }
 }
 
+/// The blocks a worker has announced as stored, mirrored from its outgoing KV events.
+#[derive(Debug, Default)]
+struct PublishedBlocks {
+    /// The parent and tokens hash of each stored block, keyed by the block's hash.
+    blocks: HashMap<ExternalSequenceBlockHash, (Option<ExternalSequenceBlockHash>, LocalBlockHash)>,
+    /// The id of the last event published.
+    last_event_id: Option<u64>,
+}
+
+impl PublishedBlocks {
+    fn apply(&mut self, event: &KvCacheEvent) {
+        match &event.data {
+            KvCacheEventData::Stored(store) => {
+                let mut parent_hash = store.parent_hash;
+                for block in &store.blocks {
+                    self.blocks
+                        .insert(block.block_hash, (parent_hash, block.tokens_hash));
+                    parent_hash = Some(block.block_hash);
+                }
+            }
+            KvCacheEventData::Removed(remove) => {
+                for block_hash in &remove.block_hashes {
+                    self.blocks.remove(block_hash);
+                }
+            }
+            KvCacheEventData::Cleared => self.blocks.clear(),
+        }
+        self.last_event_id = Some(event.event_id);
+    }
+
+    /// The stored blocks reachable from a root, ordered so that every parent precedes its
+    /// children. Blocks whose parent was removed can't be part of a matched prefix, so they and
+    /// their descendants are left out.
+    fn ordered_blocks(&self) -> Vec<SnapshotBlock> {
+        let mut children: HashMap<
+            Option<ExternalSequenceBlockHash>,
+            Vec<ExternalSequenceBlockHash>,
+        > = HashMap::new();
+        for (block_hash, (parent_hash, _)) in &self.blocks {
+            children.entry(*parent_hash).or_default().push(*block_hash);
+        }
+
+        let mut ordered = Vec::with_capacity(self.blocks.len());
+        let mut stack = children.remove(&None).unwrap_or_default();
+        while let Some(block_hash) = stack.pop() {
+            let (parent_hash, tokens_hash) = self.blocks[&block_hash];
+            ordered.push(SnapshotBlock {
+                parent_hash,
+                block_hash,
+                tokens_hash,
+            });
+            if let Some(block_children) = children.remove(&Some(block_hash)) {
+                stack.extend(block_children);
+            }
+        }
+        ordered
+    }
+}
+
+/// Serves a full dump of the blocks a worker has announced through its [`KvEventPublisher`].
+///
+/// Routers request it when they detect a gap in the worker's event ids, and replace their view
+/// of the worker with the response.
+#[derive(Clone)]
+pub struct KvStateEndpoint {
+    worker_id: i64,
+    state: Arc<Mutex<PublishedBlocks>>,
+}
+
+impl KvStateEndpoint {
+    fn new(worker_id: i64) -> Self {
+        Self {
+            worker_id,
+            state: Arc::new(Mutex::new(PublishedBlocks::default())),
+        }
+    }
+
+    fn apply(&self, event: &KvCacheEvent) {
+        self.state.lock().unwrap().apply(event);
+    }
+
+    /// The worker's stored blocks and the id of the last event they reflect.
+    pub fn dump(&self) -> WorkerSnapshot {
+        let state = self.state.lock().unwrap();
+        WorkerSnapshot {
+            worker_id: self.worker_id,
+            last_event_id: state.last_event_id,
+            blocks: state.ordered_blocks(),
+        }
+    }
+
+    /// Serve the dump on the component's [`KV_STATE_ENDPOINT`].
+    pub async fn create_endpoint(self, component: Component) -> Result<()> {
+        let handler = Ingress::for_engine(Arc::new(self))?;
+        component
+            .endpoint(KV_STATE_ENDPOINT)
+            .endpoint_builder()
+            .handler(handler)
+            .start()
+            .await
+    }
+}
+
+#[async_trait]
+impl AsyncEngine<SingleIn<()>, ManyOut<Annotated<WorkerSnapshot>>, Error> for KvStateEndpoint {
+    async fn generate(&self, request: SingleIn<()>) -> Result<ManyOut<Annotated<WorkerSnapshot>>> {
+        let context = request.context();
+        let stream = stream::iter(vec![Annotated::from_data(self.dump())]);
+        Ok(ResponseStream::new(Box::pin(stream), context))
+    }
+}
+
 // Error handling configuration for ZMQ operations
 const INITIAL_BACKOFF_MS: u64 = 10;
 const MAX_BACKOFF_MS: u64 = 5000;
@@ -345,6 +478,7 @@ fn convert_event(
             token_ids,
             block_size,
             lora_id,
+            media_hashes,
         } => {
             let num_block_tokens = vec![block_size as u64; block_hashes.len()];
             KvCacheEvent {
@@ -357,8 +491,11 @@ fn convert_event(
                         &num_block_tokens,
                         &block_hashes,
                         lora_id.unwrap_or(0),
+                        &media_hashes,
                         warning_count,
                     ),
+                    lora_id: lora_id.filter(|id| *id != 0),
+                    media_hashes,
                 }),
             }
         }
@@ -381,28 +518,33 @@ fn convert_event(
     }
 }
 
+/// `salt` is the LoRA adapter of the block, or a [`compute_block_hash_salt`] of it.
 pub fn create_stored_block_from_parts(
     kv_block_size: usize,
     block_hash: i64,
     token_ids: &[u32],
-    _lora_id: u64,
+    salt: u64,
 ) -> KvCacheStoredBlockData {
-    let tokens_hash = compute_block_hash_for_seq(token_ids, kv_block_size)[0];
+    let tokens_hash = compute_block_hash_for_seq(token_ids, kv_block_size, salt)[0];
     KvCacheStoredBlockData {
         block_hash: ExternalSequenceBlockHash::from(block_hash),
         tokens_hash,
     }
 }
 
+/// The blocks of a request, hashed like the router hashes its prompt: with the LoRA adapter
+/// (`0` for the base model) and the hashes of the request's media folded in.
 pub fn create_stored_blocks(
     kv_block_size: usize,
     token_ids: &[u32],
     num_block_tokens: &[u64],
     block_hashes: &[i64],
     lora_id: u64,
+    media_hashes: &[u64],
     warning_count: &Arc<AtomicU32>,
 ) -> Vec<KvCacheStoredBlockData> {
     let mut blocks: Vec<KvCacheStoredBlockData> = Vec::new();
+    let salt = compute_block_hash_salt(lora_id, media_hashes);
 
     let mut token_offset: usize = 0;
     for (num_tokens_it, block_hash_it) in num_block_tokens.iter().zip(block_hashes.iter()) {
@@ -422,7 +564,7 @@ pub fn create_stored_blocks(
             kv_block_size,
             *block_hash_it,
             tokens,
-            lora_id,
+            salt,
         ));
         token_offset += *num_tokens_it as usize;
     }
@@ -449,6 +591,9 @@ enum RawKvEvent {
         token_ids: Vec<u32>,
         block_size: usize,
         lora_id: Option<u64>,
+        /// The hashes of the media of the request, from its `MediaInput`s
+        #[serde(default)]
+        media_hashes: Vec<u64>,
     },
     BlockRemoved {
         block_hashes: Vec<i64>,
@@ -544,10 +689,25 @@ mod test_event_processing {
         let stored = create_stored_block_from_parts(kv_block_size, blk_hash, &token_ids, 0);
 
         assert_eq!(stored.block_hash.0, blk_hash as u64);
-        let expected_hash = compute_block_hash_for_seq(&token_ids, 4)[0];
+        let expected_hash = compute_block_hash_for_seq(&token_ids, 4, 0)[0];
         assert_eq!(stored.tokens_hash, expected_hash);
     }
 
+    #[test]
+    fn test_create_stored_block_from_parts_with_lora() {
+        let kv_block_size = 4;
+        let token_ids = vec![10, 20, 30, 40];
+
+        let base = create_stored_block_from_parts(kv_block_size, 1, &token_ids, 0);
+        let lora = create_stored_block_from_parts(kv_block_size, 1, &token_ids, 3);
+
+        assert_ne!(base.tokens_hash, lora.tokens_hash);
+        assert_eq!(
+            lora.tokens_hash,
+            compute_block_hash_for_seq(&token_ids, 4, 3)[0]
+        );
+    }
+
     // ---------------------------------------------------------------------
     // create_stored_blocks -------------------------------------------------
     // ---------------------------------------------------------------------
@@ -565,6 +725,7 @@ mod test_event_processing {
             &num_block_tokens,
             &block_hashes,
             /*lora_id=*/ 0,
+            /*media_hashes=*/ &[],
             &Arc::new(AtomicU32::new(0)),
         );
 
@@ -588,6 +749,7 @@ mod test_event_processing {
             &num_block_tokens,
             &block_hashes,
             /*lora_id=*/ 0,
+            /*media_hashes=*/ &[],
             &warning_count,
         );
 
@@ -608,10 +770,60 @@ mod test_event_processing {
             token_ids: vec![1, 2, 3, 4, 5, 6, 7, 8],
             block_size: 4,
             lora_id: Some(0),
+            media_hashes: Vec::new(),
         };
 
         let out = convert_event(raw_evt, 42, kv_block_size, &Arc::new(AtomicU32::new(0)));
-        assert!(matches!(out.data, KvCacheEventData::Stored(_)));
+        let KvCacheEventData::Stored(store) = out.data else {
+            panic!("expected KvCacheEventData::Stored");
+        };
+        assert_eq!(store.lora_id, None);
+    }
+
+    #[test]
+    fn test_convert_event_block_stored_with_lora() {
+        let kv_block_size = 4;
+        let raw_evt = RawKvEvent::BlockStored {
+            block_hashes: vec![10],
+            parent_block_hash: None,
+            token_ids: vec![1, 2, 3, 4],
+            block_size: 4,
+            lora_id: Some(5),
+            media_hashes: Vec::new(),
+        };
+
+        let out = convert_event(raw_evt, 42, kv_block_size, &Arc::new(AtomicU32::new(0)));
+        let KvCacheEventData::Stored(store) = out.data else {
+            panic!("expected KvCacheEventData::Stored");
+        };
+        assert_eq!(store.lora_id, Some(5));
+        assert_eq!(
+            store.blocks[0].tokens_hash,
+            compute_block_hash_for_seq(&[1, 2, 3, 4], 4, 5)[0]
+        );
+    }
+
+    #[test]
+    fn test_convert_event_block_stored_with_media() {
+        let kv_block_size = 4;
+        let raw_evt = RawKvEvent::BlockStored {
+            block_hashes: vec![10],
+            parent_block_hash: None,
+            token_ids: vec![1, 2, 3, 4],
+            block_size: 4,
+            lora_id: None,
+            media_hashes: vec![11],
+        };
+
+        let out = convert_event(raw_evt, 42, kv_block_size, &Arc::new(AtomicU32::new(0)));
+        let KvCacheEventData::Stored(store) = out.data else {
+            panic!("expected KvCacheEventData::Stored");
+        };
+        assert_eq!(store.media_hashes, vec![11]);
+        assert_eq!(
+            store.blocks[0].tokens_hash,
+            compute_block_hash_for_seq(&[1, 2, 3, 4], 4, compute_block_hash_salt(0, &[11]))[0]
+        );
     }
 
     #[test]
@@ -717,7 +929,14 @@ mod tests_startup_helpers {
         tx.send(event).unwrap();
         drop(tx);
 
-        let handle = tokio::spawn(start_event_processor(component, 1, token, rx));
+        let state = KvStateEndpoint::new(1);
+        let handle = tokio::spawn(start_event_processor(
+            component,
+            1,
+            token,
+            rx,
+            state.clone(),
+        ));
 
         tokio::time::timeout(tokio::time::Duration::from_secs(1), handle)
             .await
@@ -728,6 +947,104 @@ mod tests_startup_helpers {
         assert_eq!(published.len(), 1);
         let (subject, _) = &published[0];
         assert_eq!(subject, &KV_EVENT_SUBJECT.to_string());
+        assert_eq!(state.dump().last_event_id, Some(1));
+    }
+
+    //--------------------------------------------------------------------
+    // Test KvStateEndpoint
+    //--------------------------------------------------------------------
+    fn stored_event(
+        event_id: u64,
+        parent_hash: Option<u64>,
+        blocks: &[(u64, u64)],
+    ) -> KvCacheEvent {
+        KvCacheEvent {
+            event_id,
+            data: KvCacheEventData::Stored(KvCacheStoreData {
+                parent_hash: parent_hash.map(ExternalSequenceBlockHash),
+                blocks: blocks
+                    .iter()
+                    .map(|(block_hash, tokens_hash)| KvCacheStoredBlockData {
+                        block_hash: ExternalSequenceBlockHash(*block_hash),
+                        tokens_hash: LocalBlockHash(*tokens_hash),
+                    })
+                    .collect(),
+                lora_id: None,
+                media_hashes: Vec::new(),
+            }),
+        }
+    }
+
+    #[test]
+    fn test_kv_state_endpoint_tracks_published_blocks() {
+        let state = KvStateEndpoint::new(7);
+        assert_eq!(state.dump().last_event_id, None);
+
+        state.apply(&stored_event(1, None, &[(10, 1), (20, 2)]));
+        state.apply(&stored_event(2, Some(20), &[(30, 3)]));
+        state.apply(&stored_event(3, Some(10), &[(40, 4)]));
+        state.apply(&KvCacheEvent {
+            event_id: 4,
+            data: KvCacheEventData::Removed(KvCacheRemoveData {
+                block_hashes: vec![ExternalSequenceBlockHash(40)],
+            }),
+        });
+
+        let dump = state.dump();
+        assert_eq!(dump.worker_id, 7);
+        assert_eq!(dump.last_event_id, Some(4));
+        let hashes: Vec<u64> = dump.blocks.iter().map(|b| b.block_hash.0).collect();
+        assert_eq!(hashes, vec![10, 20, 30]);
+        assert_eq!(
+            dump.blocks[2].parent_hash,
+            Some(ExternalSequenceBlockHash(20))
+        );
+        assert_eq!(dump.blocks[2].tokens_hash, LocalBlockHash(3));
+
+        state.apply(&KvCacheEvent {
+            event_id: 5,
+            data: KvCacheEventData::Cleared,
+        });
+        let dump = state.dump();
+        assert!(dump.blocks.is_empty());
+        assert_eq!(dump.last_event_id, Some(5));
+    }
+
+    #[test]
+    fn test_kv_state_endpoint_orders_parents_first() {
+        let state = KvStateEndpoint::new(0);
+        // the child is re-announced after its parent was removed and stored again
+        state.apply(&stored_event(1, None, &[(10, 1), (20, 2)]));
+        state.apply(&KvCacheEvent {
+            event_id: 2,
+            data: KvCacheEventData::Removed(KvCacheRemoveData {
+                block_hashes: vec![ExternalSequenceBlockHash(10)],
+            }),
+        });
+        state.apply(&stored_event(3, None, &[(10, 1)]));
+
+        let dump = state.dump();
+        let hashes: Vec<u64> = dump.blocks.iter().map(|b| b.block_hash.0).collect();
+        assert_eq!(hashes, vec![10, 20]);
+    }
+
+    #[test]
+    fn test_kv_state_endpoint_drops_orphans() {
+        let state = KvStateEndpoint::new(0);
+        state.apply(&stored_event(1, None, &[(10, 1), (20, 2), (30, 3)]));
+        state.apply(&stored_event(2, Some(30), &[(40, 4)]));
+        state.apply(&KvCacheEvent {
+            event_id: 3,
+            data: KvCacheEventData::Removed(KvCacheRemoveData {
+                block_hashes: vec![ExternalSequenceBlockHash(20)],
+            }),
+        });
+
+        // the blocks past the removed one are no longer a prefix of anything
+        let dump = state.dump();
+        let hashes: Vec<u64> = dump.blocks.iter().map(|b| b.block_hash.0).collect();
+        assert_eq!(hashes, vec![10]);
+        assert_eq!(dump.blocks[0].parent_hash, None);
     }
 
     //--------------------------------------------------------------------
@@ -768,6 +1085,7 @@ mod tests_startup_helpers {
             token_ids: vec![0, 1, 2, 3],
             block_size: 4,
             lora_id: None,
+            media_hashes: Vec::new(),
         }];
 
         let batch = KvEventBatch { ts: 0.0, events };
@@ -795,6 +1113,7 @@ mod tests_startup_helpers {
         let KvCacheEventData::Stored(KvCacheStoreData {
             parent_hash,
             blocks,
+            ..
         }) = event.data
         else {
             panic!("expected KvCacheStoreData");
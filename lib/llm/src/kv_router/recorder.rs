@@ -47,6 +47,8 @@ mod tests {
         KvCacheEventData::Stored(KvCacheStoreData {
             parent_hash,
             blocks: make_blocks(hashes),
+            lora_id: None,
+            media_hashes: Vec::new(),
         })
     }
 
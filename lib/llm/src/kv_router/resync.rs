@@ -0,0 +1,98 @@
+// SPDX-FileCopyrightText: Copyright (c) 2024-2025 NVIDIA CORPORATION & AFFILIATES. All rights reserved.
+// SPDX-License-Identifier: Apache-2.0
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Rebuilds the router's view of a worker after some of its KV events were lost, from the full
+//! state dump the worker serves on [`KV_STATE_ENDPOINT`].
+
+use std::collections::HashSet;
+
+use dynamo_runtime::{component::Component, pipeline::PushRouter, protocols::annotated::Annotated};
+use futures::StreamExt;
+use tokio::sync::{broadcast, mpsc};
+use tokio_util::sync::CancellationToken;
+
+use crate::kv_router::{
+    indexer::{WorkerId, WorkerSnapshot},
+    KV_STATE_ENDPOINT,
+};
+
+/// Fetch the state of every worker reported on `event_gaps` and send it to the indexer through
+/// `resync_tx`, until `cancel` is triggered.
+pub async fn resync_loop(
+    component: Component,
+    mut event_gaps: broadcast::Receiver<WorkerId>,
+    resync_tx: mpsc::Sender<WorkerSnapshot>,
+    cancel: CancellationToken,
+) -> anyhow::Result<()> {
+    let client = component.endpoint(KV_STATE_ENDPOINT).client().await?;
+    let router =
+        PushRouter::<(), Annotated<WorkerSnapshot>>::from_client(client, Default::default())
+            .await?;
+
+    loop {
+        let worker_id = tokio::select! {
+            _ = cancel.cancelled() => return Ok(()),
+            worker_id = event_gaps.recv() => worker_id,
+        };
+        let mut workers = HashSet::new();
+        match worker_id {
+            Ok(worker_id) => {
+                workers.insert(worker_id);
+            }
+            Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                tracing::warn!(skipped, "Missed KV event gap notifications");
+            }
+            Err(broadcast::error::RecvError::Closed) => return Ok(()),
+        }
+        // a worker that keeps losing events only needs one resync per batch of gaps
+        while let Ok(worker_id) = event_gaps.try_recv() {
+            workers.insert(worker_id);
+        }
+
+        for worker_id in workers {
+            match fetch_state(&router, worker_id).await {
+                Ok(state) => {
+                    if resync_tx.send(state).await.is_err() {
+                        tracing::debug!("KV indexer is gone; stopping resyncs");
+                        return Ok(());
+                    }
+                }
+                Err(err) => {
+                    tracing::warn!(worker_id, %err, "Failed to fetch KV state from worker")
+                }
+            }
+        }
+    }
+}
+
+async fn fetch_state(
+    router: &PushRouter<(), Annotated<WorkerSnapshot>>,
+    worker_id: WorkerId,
+) -> anyhow::Result<WorkerSnapshot> {
+    let mut stream = router.direct(().into(), worker_id).await?;
+    let Some(response) = stream.next().await else {
+        anyhow::bail!("No response from worker");
+    };
+    let Some(state) = response.into_result()? else {
+        anyhow::bail!("Empty response from worker");
+    };
+    if state.worker_id != worker_id {
+        anyhow::bail!(
+            "Worker responded with the state of worker {}",
+            state.worker_id
+        );
+    }
+    Ok(state)
+}
@@ -17,14 +17,15 @@ use dynamo_runtime::component::Namespace;
 use dynamo_runtime::traits::events::EventPublisher;
 use rand::Rng;
 use serde::{Deserialize, Serialize};
-use std::borrow::BorrowMut;
+use std::borrow::{BorrowMut, Cow};
 use std::collections::HashMap;
 
-use super::protocols::WorkerSelectionResult;
+use super::protocols::{RoutingDecision, WorkerSelectionResult};
 use super::WorkerSelector;
 use crate::kv_router::indexer::OverlapScores;
 pub use crate::kv_router::protocols::ForwardPassMetrics;
 use crate::kv_router::scoring::ProcessedEndpoints;
+use crate::kv_router::selector::cache_usage;
 use crate::kv_router::KvRouterConfig;
 use crate::kv_router::KV_HIT_RATE_SUBJECT;
 
@@ -74,10 +75,31 @@ impl Endpoint {
 pub struct SchedulingRequest {
     pub isl_tokens: usize,
     pub overlap: OverlapScores,
+    /// Higher is more urgent, 0 by default
+    pub priority: i32,
     resp_tx: tokio::sync::oneshot::Sender<i64>,
 }
 
 impl SchedulingRequest {
+    /// Create a request to be scheduled along with the receiver for the selected worker id.
+    pub fn new(
+        isl_tokens: usize,
+        overlap: OverlapScores,
+    ) -> (Self, tokio::sync::oneshot::Receiver<i64>) {
+        let (resp_tx, resp_rx) = tokio::sync::oneshot::channel();
+        let request = Self {
+            isl_tokens,
+            overlap,
+            priority: 0,
+            resp_tx,
+        };
+        (request, resp_rx)
+    }
+
+    pub fn with_priority(self, priority: i32) -> Self {
+        Self { priority, ..self }
+    }
+
     pub fn respond(self, worker_id: i64) {
         if self.resp_tx.send(worker_id).is_err() {
             tracing::trace!("failed to send response to requestor");
@@ -85,8 +107,15 @@ impl SchedulingRequest {
     }
 }
 
+/// A request to explain where a [`SchedulingRequest`] would be routed, without routing it.
+type ExplainRequest = (
+    SchedulingRequest,
+    tokio::sync::oneshot::Sender<Result<RoutingDecision, KvSchedulerError>>,
+);
+
 pub struct KvScheduler {
     request_tx: tokio::sync::mpsc::Sender<SchedulingRequest>,
+    explain_tx: tokio::sync::mpsc::Sender<ExplainRequest>,
 }
 
 impl KvScheduler {
@@ -95,6 +124,7 @@ impl KvScheduler {
         block_size: usize,
         endpoints_rx: tokio::sync::watch::Receiver<ProcessedEndpoints>,
         selector: Option<Box<dyn WorkerSelector + Send + Sync>>,
+        max_gpu_cache_usage: f64,
     ) -> Result<Self, KvSchedulerError> {
         let selector = selector.unwrap_or(Box::new(DefaultWorkerSelector::default()));
         let mut endpoints_rx = endpoints_rx;
@@ -112,10 +142,12 @@ impl KvScheduler {
 
         // Channel to accept new scheduling requests
         let (request_tx, request_rx) = tokio::sync::mpsc::channel::<SchedulingRequest>(1024);
+        let (explain_tx, explain_rx) = tokio::sync::mpsc::channel::<ExplainRequest>(64);
         // Background task to handle scheduling requests
         tokio::spawn(async move {
             let mut request: SchedulingRequest;
             let mut request_rx = request_rx;
+            let mut explain_rx = explain_rx;
             tracing::trace!("scheduler background task started");
 
             'outer: loop {
@@ -139,9 +171,29 @@ impl KvScheduler {
                         endpoints = endpoints_rx.borrow_and_update().clone();
                         continue 'outer;
                     }
+
+                    Some((explained, resp_tx)) = explain_rx.recv() => {
+                        // a dry run: nothing is charged to the worker and errors go back to the
+                        // caller instead of stopping the scheduler
+                        let decision = explain_selection(
+                            selector.as_ref(),
+                            &candidate_workers(&endpoints, &explained, max_gpu_cache_usage),
+                            &explained,
+                            block_size,
+                        );
+                        if resp_tx.send(decision).is_err() {
+                            tracing::trace!("failed to send explanation to requestor");
+                        }
+                        continue 'outer;
+                    }
                 };
                 loop {
-                    match selector.select_worker(&endpoints, &request, block_size) {
+                    let selection = selector.select_worker(
+                        &candidate_workers(&endpoints, &request, max_gpu_cache_usage),
+                        &request,
+                        block_size,
+                    );
+                    match selection {
                         Ok(selection) => {
                             let worker_id = process_worker_selection(
                                 endpoints.borrow_mut(),
@@ -173,20 +225,20 @@ impl KvScheduler {
             tracing::trace!("background endpoint subscriber shutting down");
         });
 
-        Ok(KvScheduler { request_tx })
+        Ok(KvScheduler {
+            request_tx,
+            explain_tx,
+        })
     }
 
     pub async fn schedule(
         &self,
         overlap: OverlapScores,
         isl_tokens: usize,
+        priority: i32,
     ) -> Result<i64, KvSchedulerError> {
-        let (resp_tx, resp_rx) = tokio::sync::oneshot::channel();
-        let request = SchedulingRequest {
-            isl_tokens,
-            overlap,
-            resp_tx,
-        };
+        let (request, resp_rx) = SchedulingRequest::new(isl_tokens, overlap);
+        let request = request.with_priority(priority);
         self.request_tx
             .send(request)
             .await
@@ -196,6 +248,73 @@ impl KvScheduler {
             .map_err(|_| KvSchedulerError::SubscriberShutdown)?;
         Ok(res)
     }
+
+    /// Explain which worker a request with these overlap scores would be scheduled on, without
+    /// updating the predicted load of that worker.
+    pub async fn explain(
+        &self,
+        overlap: OverlapScores,
+        isl_tokens: usize,
+    ) -> Result<RoutingDecision, KvSchedulerError> {
+        let (request, _) = SchedulingRequest::new(isl_tokens, overlap);
+        let (resp_tx, resp_rx) = tokio::sync::oneshot::channel();
+        self.explain_tx
+            .send((request, resp_tx))
+            .await
+            .map_err(|_| KvSchedulerError::SubscriberShutdown)?;
+        resp_rx
+            .await
+            .map_err(|_| KvSchedulerError::SubscriberShutdown)?
+    }
+}
+
+/// The workers a request may be scheduled on. Requests with a priority above 0 are kept off the
+/// workers whose KV cache usage is at or above `max_gpu_cache_usage`, where they would wait behind
+/// the requests already running, unless every worker is that loaded.
+fn candidate_workers<'a>(
+    workers: &'a ProcessedEndpoints,
+    request: &SchedulingRequest,
+    max_gpu_cache_usage: f64,
+) -> Cow<'a, ProcessedEndpoints> {
+    if request.priority <= 0 {
+        return Cow::Borrowed(workers);
+    }
+    let unloaded: HashMap<i64, Endpoint> = workers
+        .endpoints
+        .iter()
+        .filter(|(_, ep)| cache_usage(ep) < max_gpu_cache_usage)
+        .map(|(worker_id, ep)| (*worker_id, ep.clone()))
+        .collect();
+    if unloaded.is_empty() || unloaded.len() == workers.endpoints.len() {
+        return Cow::Borrowed(workers);
+    }
+    tracing::debug!(
+        priority = request.priority,
+        "keeping request off {} loaded workers",
+        workers.endpoints.len() - unloaded.len()
+    );
+    Cow::Owned(ProcessedEndpoints {
+        endpoints: unloaded,
+        load_avg: workers.load_avg,
+        load_std: workers.load_std,
+    })
+}
+
+fn explain_selection(
+    selector: &(dyn WorkerSelector + Send + Sync),
+    workers: &ProcessedEndpoints,
+    request: &SchedulingRequest,
+    block_size: usize,
+) -> Result<RoutingDecision, KvSchedulerError> {
+    let selection = selector.select_worker(workers, request, block_size)?;
+    Ok(RoutingDecision {
+        worker_id: selection.worker_id,
+        required_blocks: selection.required_blocks,
+        overlap_blocks: selection.overlap_blocks,
+        overlap_scores: request.overlap.scores.clone(),
+        logits: selector.worker_logits(workers, request, block_size),
+        endpoints: workers.clone(),
+    })
 }
 
 // This becomes the driver function that handles the selection result
@@ -228,11 +347,20 @@ pub fn process_worker_selection(
 }
 
 // Helper function for softmax sampling
+// A temperature of zero (or below) degenerates to always picking the lowest logit.
 fn softmax_sample(logits: &HashMap<i64, f64>, temperature: f64) -> i64 {
     if logits.is_empty() {
         panic!("Empty logits for softmax sampling");
     }
 
+    if temperature <= 0.0 {
+        return logits
+            .iter()
+            .min_by(|a, b| a.1.total_cmp(b.1).then(a.0.cmp(b.0)))
+            .map(|(worker_id, _)| *worker_id)
+            .unwrap();
+    }
+
     let keys: Vec<_> = logits.keys().copied().collect();
     let values: Vec<_> = logits.values().copied().collect();
 
@@ -292,21 +420,16 @@ impl DefaultWorkerSelector {
             kv_router_config: kv_router_config.unwrap_or_default(),
         }
     }
-}
 
-impl WorkerSelector for DefaultWorkerSelector {
-    fn select_worker(
+    /// The logit of every worker. `trace_formula` logs how each was computed; only selection
+    /// does, so explaining a decision does not log the formula a second time.
+    fn logits(
         &self,
         workers: &ProcessedEndpoints,
         request: &SchedulingRequest,
         block_size: usize,
-    ) -> Result<WorkerSelectionResult, KvSchedulerError> {
-        assert!(request.isl_tokens > 0);
-
-        if workers.endpoints.is_empty() {
-            return Err(KvSchedulerError::NoEndpoints);
-        }
-
+        trace_formula: bool,
+    ) -> HashMap<i64, f64> {
         let request_blocks = request.isl_tokens.div_ceil(block_size);
         let mut worker_logits = HashMap::new();
 
@@ -333,14 +456,36 @@ impl WorkerSelector for DefaultWorkerSelector {
 
             worker_logits.insert(worker_id, logit);
 
-            tracing::info!(
-                "Formula for {worker_id}: {logit:.3} = {:.1} * {normalized_new_blocks:.3} + {:.1} * {gpu_cache_usage:.3} + {:.1} * {num_requests_waiting:.3}",
-                self.kv_router_config.overlap_score_weight,
-                self.kv_router_config.gpu_cache_usage_weight,
-                self.kv_router_config.waiting_requests_weight,
-            );
+            if trace_formula {
+                tracing::info!(
+                    "Formula for {worker_id}: {logit:.3} = {:.1} * {normalized_new_blocks:.3} + {:.1} * {gpu_cache_usage:.3} + {:.1} * {num_requests_waiting:.3}",
+                    self.kv_router_config.overlap_score_weight,
+                    self.kv_router_config.gpu_cache_usage_weight,
+                    self.kv_router_config.waiting_requests_weight,
+                );
+            }
         }
 
+        worker_logits
+    }
+}
+
+impl WorkerSelector for DefaultWorkerSelector {
+    fn select_worker(
+        &self,
+        workers: &ProcessedEndpoints,
+        request: &SchedulingRequest,
+        block_size: usize,
+    ) -> Result<WorkerSelectionResult, KvSchedulerError> {
+        assert!(request.isl_tokens > 0);
+
+        if workers.endpoints.is_empty() {
+            return Err(KvSchedulerError::NoEndpoints);
+        }
+
+        let request_blocks = request.isl_tokens.div_ceil(block_size);
+        let worker_logits = self.logits(workers, request, block_size, true);
+
         // Return early if no valid workers found
         if worker_logits.is_empty() || worker_logits.values().all(|&v| v == 0.0) {
             tracing::warn!("All worker logits are zero. Fallback to random routing.");
@@ -358,7 +503,7 @@ impl WorkerSelector for DefaultWorkerSelector {
         }
 
         // Use softmax sampling to select worker
-        let temperature = 1.0; // You can make this configurable if needed
+        let temperature = self.kv_router_config.router_temperature;
         let best_worker_id = softmax_sample(&worker_logits, temperature);
 
         let overlap_blocks = request
@@ -381,6 +526,15 @@ impl WorkerSelector for DefaultWorkerSelector {
             overlap_blocks,
         })
     }
+
+    fn worker_logits(
+        &self,
+        workers: &ProcessedEndpoints,
+        request: &SchedulingRequest,
+        block_size: usize,
+    ) -> HashMap<i64, f64> {
+        self.logits(workers, request, block_size, false)
+    }
 }
 
 #[cfg(test)]
@@ -414,6 +568,19 @@ mod tests {
         assert_eq!(softmax_sample(&logits, 1.0), worker_id);
     }
 
+    #[test]
+    fn test_softmax_sample_zero_temperature() {
+        let logits = HashMap::from([(1, 0.7), (2, 0.2), (3, 0.9)]);
+
+        for _ in 0..10 {
+            assert_eq!(
+                softmax_sample(&logits, 0.0),
+                2,
+                "Should pick the lowest logit"
+            );
+        }
+    }
+
     // Helper to create a worker endpoint
     fn create_endpoint(
         worker_id: i64,
@@ -459,17 +626,43 @@ mod tests {
         overlap_blocks: u32,
     }
     fn create_request(overlaps: Vec<WorkerOverlap>, isl_tokens: usize) -> SchedulingRequest {
-        SchedulingRequest {
-            isl_tokens,
-            overlap: OverlapScores {
-                scores: overlaps
-                    .into_iter()
-                    .map(|wo| (wo.worker_id, wo.overlap_blocks))
-                    .collect(),
-                frequencies: vec![],
-            },
-            resp_tx: tokio::sync::oneshot::channel().0,
-        }
+        let overlap = OverlapScores {
+            scores: overlaps
+                .into_iter()
+                .map(|wo| (wo.worker_id, wo.overlap_blocks))
+                .collect(),
+            frequencies: vec![],
+            block_workers: vec![],
+        };
+        SchedulingRequest::new(isl_tokens, overlap).0
+    }
+
+    #[test]
+    fn test_urgent_requests_avoid_loaded_workers() {
+        // (worker_id, kv_active_blocks) out of 100 blocks
+        let worker = |worker_id: i64, kv_active_blocks: u64| {
+            let mut ep = create_endpoint(worker_id, 0.0, 0);
+            ep.data.kv_active_blocks = kv_active_blocks;
+            ep.data.kv_total_blocks = 100;
+            (worker_id, ep)
+        };
+        let workers = ProcessedEndpoints {
+            endpoints: HashMap::from([worker(1, 95), worker(2, 50)]),
+            load_avg: 0.0,
+            load_std: 0.0,
+        };
+        let request = create_request(vec![], 100);
+
+        let candidates = candidate_workers(&workers, &request, 0.9);
+        assert_eq!(candidates.endpoints.len(), 2);
+
+        let request = request.with_priority(1);
+        let candidates = candidate_workers(&workers, &request, 0.9);
+        assert_eq!(candidates.endpoints.keys().collect::<Vec<_>>(), vec![&2]);
+
+        // when every worker is loaded, all of them stay candidates
+        let candidates = candidate_workers(&workers, &request, 0.4);
+        assert_eq!(candidates.endpoints.len(), 2);
     }
 
     #[test]
@@ -0,0 +1,487 @@
+// SPDX-FileCopyrightText: Copyright (c) 2024-2025 NVIDIA CORPORATION & AFFILIATES. All rights reserved.
+// SPDX-License-Identifier: Apache-2.0
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Built-in worker selection policies for the KV router.
+//!
+//! Each policy implements [`WorkerSelector`] and is registered under a name in
+//! [`WorkerSelectorKind`], so frontends can pick one at startup (e.g. `--kv-selector power-of-two`)
+//! without recompiling.
+
+use std::collections::HashMap;
+
+use rand::Rng;
+use strum::EnumString;
+
+use super::protocols::WorkerSelectionResult;
+use super::scheduler::{DefaultWorkerSelector, Endpoint, KvSchedulerError, SchedulingRequest};
+use super::scoring::ProcessedEndpoints;
+use super::{KvRouterConfig, WorkerSelector};
+
+/// The names of the built-in [`WorkerSelector`] policies.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, EnumString, strum::Display)]
+#[strum(serialize_all = "kebab-case")]
+pub enum WorkerSelectorKind {
+    /// Weighted cost of prefix overlap, KV cache usage and waiting requests,
+    /// sampled with a softmax over the worker logits. See [`DefaultWorkerSelector`].
+    #[default]
+    Softmax,
+
+    /// Sample two workers at random and keep the less loaded one.
+    PowerOfTwo,
+
+    /// Pick the worker with the fewest outstanding tokens once this request's
+    /// uncached blocks are added to it.
+    LeastOutstandingTokens,
+
+    /// Pick the worker with the largest prefix overlap among the workers whose KV cache
+    /// usage is below a cap, falling back to the least loaded worker.
+    CacheAffinity,
+}
+
+impl WorkerSelectorKind {
+    pub fn all() -> Vec<Self> {
+        vec![
+            Self::Softmax,
+            Self::PowerOfTwo,
+            Self::LeastOutstandingTokens,
+            Self::CacheAffinity,
+        ]
+    }
+
+    /// Create the selector for this policy, configured from `config`.
+    pub fn build(&self, config: &KvRouterConfig) -> Box<dyn WorkerSelector + Send + Sync> {
+        match self {
+            Self::Softmax => Box::new(DefaultWorkerSelector::new(Some(config.clone()))),
+            Self::PowerOfTwo => Box::new(PowerOfTwoSelector),
+            Self::LeastOutstandingTokens => Box::new(LeastOutstandingTokensSelector),
+            Self::CacheAffinity => Box::new(CacheAffinitySelector::new(config.max_gpu_cache_usage)),
+        }
+    }
+}
+
+/// Power of two random choices on load.
+///
+/// Load is the fraction of KV cache in use plus the number of waiting requests.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct PowerOfTwoSelector;
+
+impl WorkerSelector for PowerOfTwoSelector {
+    fn select_worker(
+        &self,
+        workers: &ProcessedEndpoints,
+        request: &SchedulingRequest,
+        block_size: usize,
+    ) -> Result<WorkerSelectionResult, KvSchedulerError> {
+        let worker_ids = sorted_worker_ids(workers)?;
+
+        let worker_id = if worker_ids.len() == 1 {
+            worker_ids[0]
+        } else {
+            let mut rng = rand::rng();
+            let first = rng.random_range(0..worker_ids.len());
+            let mut second = rng.random_range(0..worker_ids.len() - 1);
+            if second >= first {
+                second += 1;
+            }
+            let (a, b) = (worker_ids[first], worker_ids[second]);
+            let load_a = load(&workers.endpoints[&a]);
+            let load_b = load(&workers.endpoints[&b]);
+            tracing::debug!(
+                "power-of-two: worker {a} load {load_a:.3}, worker {b} load {load_b:.3}"
+            );
+
+            match load_a.total_cmp(&load_b) {
+                std::cmp::Ordering::Less => a,
+                std::cmp::Ordering::Greater => b,
+                // equally loaded, prefer the one with more of the prefix cached
+                std::cmp::Ordering::Equal if overlap(request, b) > overlap(request, a) => b,
+                std::cmp::Ordering::Equal => a,
+            }
+        };
+
+        Ok(selection(request, block_size, worker_id))
+    }
+
+    fn worker_logits(
+        &self,
+        workers: &ProcessedEndpoints,
+        _request: &SchedulingRequest,
+        _block_size: usize,
+    ) -> HashMap<i64, f64> {
+        workers
+            .endpoints
+            .iter()
+            .map(|(worker_id, ep)| (*worker_id, load(ep)))
+            .collect()
+    }
+}
+
+/// Least outstanding tokens.
+///
+/// A worker's outstanding tokens are its active KV blocks plus the blocks of this
+/// request it does not already have cached, in tokens.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct LeastOutstandingTokensSelector;
+
+impl WorkerSelector for LeastOutstandingTokensSelector {
+    fn select_worker(
+        &self,
+        workers: &ProcessedEndpoints,
+        request: &SchedulingRequest,
+        block_size: usize,
+    ) -> Result<WorkerSelectionResult, KvSchedulerError> {
+        let worker_id = sorted_worker_ids(workers)?
+            .into_iter()
+            .min_by_key(|worker_id| {
+                let ep = &workers.endpoints[worker_id];
+                let outstanding_tokens = outstanding_tokens(ep, request, block_size, *worker_id);
+                tracing::debug!(
+                    "least-outstanding-tokens: worker {worker_id} outstanding {outstanding_tokens}"
+                );
+                (outstanding_tokens, ep.data.num_requests_waiting)
+            })
+            .expect("worker ids are not empty");
+
+        Ok(selection(request, block_size, worker_id))
+    }
+
+    fn worker_logits(
+        &self,
+        workers: &ProcessedEndpoints,
+        request: &SchedulingRequest,
+        block_size: usize,
+    ) -> HashMap<i64, f64> {
+        workers
+            .endpoints
+            .iter()
+            .map(|(worker_id, ep)| {
+                let outstanding_tokens = outstanding_tokens(ep, request, block_size, *worker_id);
+                (*worker_id, outstanding_tokens as f64)
+            })
+            .collect()
+    }
+}
+
+/// Cache affinity with a load cap.
+///
+/// Routes to the worker with the largest prefix overlap, as long as its KV cache usage is
+/// below `max_gpu_cache_usage`. When every worker is above the cap the least loaded worker
+/// is used instead, so a hot prefix cannot pin all traffic to one saturated worker.
+#[derive(Debug, Clone, Copy)]
+pub struct CacheAffinitySelector {
+    max_gpu_cache_usage: f64,
+}
+
+impl CacheAffinitySelector {
+    pub fn new(max_gpu_cache_usage: f64) -> Self {
+        Self {
+            max_gpu_cache_usage,
+        }
+    }
+}
+
+impl WorkerSelector for CacheAffinitySelector {
+    fn select_worker(
+        &self,
+        workers: &ProcessedEndpoints,
+        request: &SchedulingRequest,
+        block_size: usize,
+    ) -> Result<WorkerSelectionResult, KvSchedulerError> {
+        let worker_ids = sorted_worker_ids(workers)?;
+
+        let under_cap = worker_ids
+            .iter()
+            .copied()
+            .filter(|worker_id| {
+                cache_usage(&workers.endpoints[worker_id]) < self.max_gpu_cache_usage
+            })
+            .max_by(|a, b| {
+                let usage_a = cache_usage(&workers.endpoints[a]);
+                let usage_b = cache_usage(&workers.endpoints[b]);
+                overlap(request, *a)
+                    .cmp(&overlap(request, *b))
+                    // on equal overlap the less used worker wins
+                    .then(usage_b.total_cmp(&usage_a))
+                    .then(b.cmp(a))
+            });
+
+        let worker_id = match under_cap {
+            Some(worker_id) => worker_id,
+            None => {
+                tracing::debug!(
+                    "cache-affinity: all workers above {:.2} cache usage; using least loaded",
+                    self.max_gpu_cache_usage
+                );
+                worker_ids
+                    .into_iter()
+                    .min_by(|a, b| {
+                        load(&workers.endpoints[a]).total_cmp(&load(&workers.endpoints[b]))
+                    })
+                    .expect("worker ids are not empty")
+            }
+        };
+
+        Ok(selection(request, block_size, worker_id))
+    }
+
+    /// Workers under the cap are scored by their negated overlap. Workers above it are left out,
+    /// unless every worker is above it, in which case all are scored by load.
+    fn worker_logits(
+        &self,
+        workers: &ProcessedEndpoints,
+        request: &SchedulingRequest,
+        _block_size: usize,
+    ) -> HashMap<i64, f64> {
+        let under_cap: HashMap<i64, f64> = workers
+            .endpoints
+            .iter()
+            .filter(|(_, ep)| cache_usage(ep) < self.max_gpu_cache_usage)
+            .map(|(worker_id, _)| (*worker_id, -(overlap(request, *worker_id) as f64)))
+            .collect();
+        if !under_cap.is_empty() {
+            return under_cap;
+        }
+        workers
+            .endpoints
+            .iter()
+            .map(|(worker_id, ep)| (*worker_id, load(ep)))
+            .collect()
+    }
+}
+
+/// Worker ids in a stable order, so ties are broken deterministically.
+fn sorted_worker_ids(workers: &ProcessedEndpoints) -> Result<Vec<i64>, KvSchedulerError> {
+    if workers.endpoints.is_empty() {
+        return Err(KvSchedulerError::NoEndpoints);
+    }
+    let mut worker_ids: Vec<i64> = workers.endpoints.keys().copied().collect();
+    worker_ids.sort_unstable();
+    Ok(worker_ids)
+}
+
+fn overlap(request: &SchedulingRequest, worker_id: i64) -> usize {
+    request.overlap.scores.get(&worker_id).copied().unwrap_or(0) as usize
+}
+
+/// The fraction of the KV cache of a worker in use
+pub(crate) fn cache_usage(ep: &Endpoint) -> f64 {
+    if ep.data.kv_total_blocks == 0 {
+        return 1.0;
+    }
+    ep.data.kv_active_blocks as f64 / ep.data.kv_total_blocks as f64
+}
+
+fn load(ep: &Endpoint) -> f64 {
+    cache_usage(ep) + ep.data.num_requests_waiting as f64
+}
+
+/// The worker's active blocks plus the blocks of this request it does not have cached, in tokens.
+fn outstanding_tokens(
+    ep: &Endpoint,
+    request: &SchedulingRequest,
+    block_size: usize,
+    worker_id: i64,
+) -> u64 {
+    let request_blocks = request.isl_tokens.div_ceil(block_size) as u64;
+    let new_blocks = request_blocks.saturating_sub(overlap(request, worker_id) as u64);
+    (ep.data.kv_active_blocks + new_blocks) * block_size as u64
+}
+
+fn selection(
+    request: &SchedulingRequest,
+    block_size: usize,
+    worker_id: i64,
+) -> WorkerSelectionResult {
+    WorkerSelectionResult {
+        worker_id,
+        required_blocks: request.isl_tokens.div_ceil(block_size) as u64,
+        overlap_blocks: overlap(request, worker_id),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::kv_router::indexer::OverlapScores;
+    use crate::kv_router::protocols::ForwardPassMetrics;
+    use std::str::FromStr;
+
+    const BLOCK_SIZE: usize = 16;
+
+    // (worker_id, kv_active_blocks, num_requests_waiting), out of 100 total blocks
+    fn create_workers(workers: &[(i64, u64, u64)]) -> ProcessedEndpoints {
+        let endpoints = workers
+            .iter()
+            .map(|&(worker_id, kv_active_blocks, num_requests_waiting)| {
+                let ep = Endpoint {
+                    name: format!("worker-{worker_id}"),
+                    subject: format!("worker-subject-{worker_id:x}"),
+                    data: ForwardPassMetrics {
+                        kv_active_blocks,
+                        kv_total_blocks: 100,
+                        num_requests_waiting,
+                        ..Default::default()
+                    },
+                };
+                (worker_id, ep)
+            })
+            .collect();
+        ProcessedEndpoints {
+            endpoints,
+            load_avg: 0.0,
+            load_std: 0.0,
+        }
+    }
+
+    fn create_request(overlaps: &[(i64, u32)], isl_tokens: usize) -> SchedulingRequest {
+        let overlap = OverlapScores {
+            scores: overlaps.iter().copied().collect::<HashMap<_, _>>(),
+            frequencies: vec![],
+            block_workers: vec![],
+        };
+        SchedulingRequest::new(isl_tokens, overlap).0
+    }
+
+    #[test]
+    fn test_selector_kind_names() {
+        for kind in WorkerSelectorKind::all() {
+            assert_eq!(
+                WorkerSelectorKind::from_str(&kind.to_string()).unwrap(),
+                kind
+            );
+        }
+        assert_eq!(
+            WorkerSelectorKind::from_str("power-of-two").unwrap(),
+            WorkerSelectorKind::PowerOfTwo
+        );
+        assert_eq!(
+            WorkerSelectorKind::from_str("least-outstanding-tokens").unwrap(),
+            WorkerSelectorKind::LeastOutstandingTokens
+        );
+        assert!(WorkerSelectorKind::from_str("round-robin").is_err());
+    }
+
+    #[test]
+    fn test_all_selectors_no_endpoints() {
+        let workers = create_workers(&[]);
+        let request = create_request(&[], 100);
+        let config = KvRouterConfig::default();
+
+        for kind in WorkerSelectorKind::all() {
+            let selector = kind.build(&config);
+            assert!(
+                matches!(
+                    selector.select_worker(&workers, &request, BLOCK_SIZE),
+                    Err(KvSchedulerError::NoEndpoints)
+                ),
+                "{kind} should fail without endpoints"
+            );
+        }
+    }
+
+    #[test]
+    fn test_power_of_two_two_workers() {
+        // with two workers both are always sampled, so the less loaded one wins
+        let workers = create_workers(&[(1, 90, 2), (2, 10, 0)]);
+        let request = create_request(&[(1, 4)], 64);
+
+        for _ in 0..10 {
+            let result = PowerOfTwoSelector
+                .select_worker(&workers, &request, BLOCK_SIZE)
+                .unwrap();
+            assert_eq!(result.worker_id, 2);
+            assert_eq!(result.required_blocks, 4);
+            assert_eq!(result.overlap_blocks, 0);
+        }
+    }
+
+    #[test]
+    fn test_least_outstanding_tokens() {
+        // worker 1: 20 active + (4 - 4) new = 20 blocks
+        // worker 2: 18 active + (4 - 0) new = 22 blocks
+        let workers = create_workers(&[(1, 20, 0), (2, 18, 0)]);
+        let request = create_request(&[(1, 4)], 64);
+
+        let result = LeastOutstandingTokensSelector
+            .select_worker(&workers, &request, BLOCK_SIZE)
+            .unwrap();
+        assert_eq!(result.worker_id, 1);
+        assert_eq!(result.overlap_blocks, 4);
+    }
+
+    #[test]
+    fn test_cache_affinity_prefers_overlap_under_cap() {
+        let workers = create_workers(&[(1, 50, 0), (2, 10, 0), (3, 95, 0)]);
+        let request = create_request(&[(1, 3), (2, 1), (3, 4)], 64);
+        let selector = CacheAffinitySelector::new(0.9);
+
+        // worker 3 has the most overlap but is above the cap
+        let result = selector
+            .select_worker(&workers, &request, BLOCK_SIZE)
+            .unwrap();
+        assert_eq!(result.worker_id, 1);
+        assert_eq!(result.overlap_blocks, 3);
+    }
+
+    #[test]
+    fn test_cache_affinity_falls_back_to_least_loaded() {
+        let workers = create_workers(&[(1, 95, 0), (2, 92, 0)]);
+        let request = create_request(&[(1, 4)], 64);
+        let selector = CacheAffinitySelector::new(0.9);
+
+        let result = selector
+            .select_worker(&workers, &request, BLOCK_SIZE)
+            .unwrap();
+        assert_eq!(result.worker_id, 2);
+    }
+
+    #[test]
+    fn test_worker_logits_rank_the_selection() {
+        let workers = create_workers(&[(1, 20, 0), (2, 18, 0), (3, 95, 0)]);
+        let request = create_request(&[(1, 4), (3, 4)], 64);
+        let config = KvRouterConfig::default();
+
+        for kind in WorkerSelectorKind::all() {
+            let selector = kind.build(&config);
+            let logits = selector.worker_logits(&workers, &request, BLOCK_SIZE);
+            assert!(!logits.is_empty(), "{kind} should score workers");
+        }
+
+        // 20 active + 0 new blocks beats 18 active + 4 new blocks
+        let logits = LeastOutstandingTokensSelector.worker_logits(&workers, &request, BLOCK_SIZE);
+        assert_eq!(logits[&1], 20.0 * BLOCK_SIZE as f64);
+        assert_eq!(logits[&2], 22.0 * BLOCK_SIZE as f64);
+
+        // worker 3 is above the cap, so it is not a candidate
+        let logits = CacheAffinitySelector::new(0.9).worker_logits(&workers, &request, BLOCK_SIZE);
+        assert_eq!(logits, HashMap::from([(1, -4.0), (2, -0.0)]));
+    }
+
+    #[test]
+    fn test_softmax_zero_temperature_is_greedy() {
+        let workers = create_workers(&[(1, 10, 0), (2, 80, 3)]);
+        let request = create_request(&[(1, 4)], 64);
+        let config =
+            KvRouterConfig::default().with_selector(WorkerSelectorKind::Softmax, Some(0.0), None);
+        let selector = config.worker_selector();
+
+        for _ in 0..10 {
+            let result = selector
+                .select_worker(&workers, &request, BLOCK_SIZE)
+                .unwrap();
+            assert_eq!(result.worker_id, 1);
+        }
+    }
+}
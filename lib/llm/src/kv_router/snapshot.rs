@@ -0,0 +1,497 @@
+// SPDX-FileCopyrightText: Copyright (c) 2024-2025 NVIDIA CORPORATION & AFFILIATES. All rights reserved.
+// SPDX-License-Identifier: Apache-2.0
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Persistence of the KV router's radix tree, so a restarted router does not have to wait for
+//! fresh KV events before it can route by prefix overlap again.
+
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    str::FromStr,
+    sync::Mutex,
+    time::Duration,
+};
+
+use anyhow::Context;
+use async_trait::async_trait;
+use dynamo_runtime::{
+    component::Component,
+    slug::Slug,
+    storage::key_value_store::{EtcdStorage, KeyValueStore, StorageOutcome},
+};
+use serde::{Deserialize, Serialize};
+use tokio_util::sync::CancellationToken;
+
+use crate::kv_router::indexer::{RadixTreeSnapshot, SnapshotHandle, WorkerId};
+
+/// The key-value store bucket router snapshots are written to.
+pub const KV_ROUTER_SNAPSHOT_BUCKET: &str = "kv_router_snapshots";
+
+/// The largest value a [`KeyValueSnapshotStore`] writes, below etcd's default request size limit
+/// of 1.5 MiB.
+pub const MAX_SNAPSHOT_VALUE_BYTES: usize = 1024 * 1024;
+
+/// How often the radix tree is snapshotted by default.
+pub const DEFAULT_SNAPSHOT_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Where radix tree snapshots are stored.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SnapshotTarget {
+    /// JSON files on the router's local disk, one per routed component and model, named after
+    /// this path.
+    File(PathBuf),
+    /// The runtime's etcd key-value store, keyed by the routed component and model.
+    Etcd,
+}
+
+impl FromStr for SnapshotTarget {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "" => anyhow::bail!("Snapshot target cannot be empty"),
+            "etcd" => Ok(SnapshotTarget::Etcd),
+            path => Ok(SnapshotTarget::File(PathBuf::from(path))),
+        }
+    }
+}
+
+/// Snapshot persistence configuration for the KV router.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KvSnapshotConfig {
+    /// Where snapshots are stored.
+    pub target: SnapshotTarget,
+
+    /// How often the radix tree is snapshotted. Default: 30s
+    pub interval: Duration,
+
+    /// The model the router serves, if it routes one of several models of a process
+    pub model_name: Option<String>,
+}
+
+impl KvSnapshotConfig {
+    pub fn new(target: SnapshotTarget, interval: Option<Duration>) -> Self {
+        Self {
+            target,
+            interval: interval.unwrap_or(DEFAULT_SNAPSHOT_INTERVAL),
+            model_name: None,
+        }
+    }
+
+    pub fn with_model_name(mut self, model_name: impl Into<String>) -> Self {
+        self.model_name = Some(model_name.into());
+        self
+    }
+
+    /// Build the store for the configured target. Snapshots are keyed by the component being
+    /// routed to and the model, so the routers of different models do not overwrite each other.
+    pub fn store(&self, component: &Component) -> anyhow::Result<Box<dyn SnapshotStore>> {
+        let key = self.key(component);
+        match &self.target {
+            SnapshotTarget::File(path) => {
+                Ok(Box::new(FileSnapshotStore::new(snapshot_path(path, &key))))
+            }
+            SnapshotTarget::Etcd => {
+                let Some(etcd_client) = component.drt().etcd_client() else {
+                    anyhow::bail!("Cannot store KV router snapshots in etcd on a static runtime");
+                };
+                Ok(Box::new(KeyValueSnapshotStore::new(
+                    Box::new(EtcdStorage::new(etcd_client)),
+                    key,
+                )))
+            }
+        }
+    }
+
+    fn key(&self, component: &Component) -> String {
+        match &self.model_name {
+            Some(model_name) => format!("{}/{model_name}", component.path()),
+            None => component.path(),
+        }
+    }
+}
+
+/// The file the snapshot for `key` is stored in: `path` with the slugified key added before the
+/// extension, e.g. `router.json` becomes `router.ns_backend.json`.
+fn snapshot_path(path: &Path, key: &str) -> PathBuf {
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let file_name = match path.extension() {
+        Some(extension) => format!(
+            "{stem}.{}.{}",
+            Slug::slugify(key),
+            extension.to_string_lossy()
+        ),
+        None => format!("{stem}.{}", Slug::slugify(key)),
+    };
+    path.with_file_name(file_name)
+}
+
+/// Somewhere a [`RadixTreeSnapshot`] can be saved to and loaded back from.
+#[async_trait]
+pub trait SnapshotStore: Send + Sync {
+    /// Save the snapshot, replacing any previously saved one.
+    async fn save(&self, snapshot: &RadixTreeSnapshot) -> anyhow::Result<()>;
+
+    /// Load the last saved snapshot, or `None` if there is none.
+    async fn load(&self) -> anyhow::Result<Option<RadixTreeSnapshot>>;
+}
+
+/// Stores the snapshot as a JSON file on local disk.
+pub struct FileSnapshotStore {
+    path: PathBuf,
+}
+
+impl FileSnapshotStore {
+    pub fn new(path: PathBuf) -> Self {
+        Self { path }
+    }
+}
+
+#[async_trait]
+impl SnapshotStore for FileSnapshotStore {
+    async fn save(&self, snapshot: &RadixTreeSnapshot) -> anyhow::Result<()> {
+        let json = serde_json::to_vec(snapshot)?;
+        // write next to the target and rename, so a crash mid-write never leaves a torn snapshot
+        let mut tmp_path = self.path.clone().into_os_string();
+        tmp_path.push(".tmp");
+        tokio::fs::write(&tmp_path, json)
+            .await
+            .with_context(|| format!("Failed writing KV router snapshot to {tmp_path:?}"))?;
+        tokio::fs::rename(&tmp_path, &self.path)
+            .await
+            .with_context(|| format!("Failed moving KV router snapshot to {:?}", self.path))?;
+        Ok(())
+    }
+
+    async fn load(&self) -> anyhow::Result<Option<RadixTreeSnapshot>> {
+        let json = match tokio::fs::read(&self.path).await {
+            Ok(json) => json,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(err) => {
+                return Err(err)
+                    .with_context(|| format!("Failed reading KV router snapshot {:?}", self.path))
+            }
+        };
+        Ok(Some(serde_json::from_slice(&json)?))
+    }
+}
+
+/// Stores the snapshot in a [`KeyValueStore`], each worker under its own key so that every write
+/// stays below the store's request size limit. The key of the store lists the workers of the last
+/// snapshot.
+pub struct KeyValueSnapshotStore {
+    store: Box<dyn KeyValueStore>,
+    key: String,
+    /// The revision to write each key with next. Keys without one are created.
+    revisions: Mutex<HashMap<String, u64>>,
+}
+
+/// What the key of a [`KeyValueSnapshotStore`] holds
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct SnapshotIndex {
+    workers: Vec<WorkerId>,
+}
+
+impl KeyValueSnapshotStore {
+    pub fn new(store: Box<dyn KeyValueStore>, key: String) -> Self {
+        Self {
+            store,
+            key,
+            revisions: Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn worker_key(&self, worker_id: WorkerId) -> String {
+        format!("{}/workers/{worker_id}", self.key)
+    }
+
+    /// Write `value` under `key`, replacing whatever is there.
+    async fn put(&self, key: String, value: String) -> anyhow::Result<()> {
+        let bucket = self
+            .store
+            .get_or_create_bucket(KV_ROUTER_SNAPSHOT_BUCKET, None)
+            .await?;
+
+        let revision = self.revisions.lock().unwrap().get(&key).copied();
+        let outcome = match bucket
+            .insert(key.clone(), value.clone(), revision.unwrap_or(0))
+            .await?
+        {
+            // left behind by a previous router; overwrite it
+            StorageOutcome::Exists(existing) => {
+                bucket.insert(key.clone(), value, existing + 1).await?
+            }
+            outcome => outcome,
+        };
+        let (StorageOutcome::Created(revision) | StorageOutcome::Exists(revision)) = outcome;
+        self.revisions.lock().unwrap().insert(key, revision + 1);
+        Ok(())
+    }
+
+    async fn get(&self, key: &str) -> anyhow::Result<Option<bytes::Bytes>> {
+        let Some(bucket) = self.store.get_bucket(KV_ROUTER_SNAPSHOT_BUCKET).await? else {
+            return Ok(None);
+        };
+        Ok(bucket.get(key).await?)
+    }
+
+    async fn delete(&self, key: &str) -> anyhow::Result<()> {
+        let Some(bucket) = self.store.get_bucket(KV_ROUTER_SNAPSHOT_BUCKET).await? else {
+            return Ok(());
+        };
+        bucket.delete(key).await?;
+        self.revisions.lock().unwrap().remove(key);
+        Ok(())
+    }
+
+    /// The index of the last saved snapshot. An index which can't be read counts as none.
+    async fn index(&self) -> anyhow::Result<Option<SnapshotIndex>> {
+        let Some(json) = self.get(&self.key).await? else {
+            return Ok(None);
+        };
+        Ok(serde_json::from_slice(&json).ok())
+    }
+}
+
+#[async_trait]
+impl SnapshotStore for KeyValueSnapshotStore {
+    /// Write every worker that fits in [`MAX_SNAPSHOT_VALUE_BYTES`], then the index. Workers that
+    /// don't fit are left out of the snapshot and reported as an error.
+    async fn save(&self, snapshot: &RadixTreeSnapshot) -> anyhow::Result<()> {
+        let previous = self.index().await?.unwrap_or_default();
+
+        let mut index = SnapshotIndex::default();
+        let mut oversized = Vec::new();
+        for worker in &snapshot.workers {
+            let json = serde_json::to_string(worker)?;
+            if json.len() > MAX_SNAPSHOT_VALUE_BYTES {
+                oversized.push(format!("{} ({} bytes)", worker.worker_id, json.len()));
+                continue;
+            }
+            self.put(self.worker_key(worker.worker_id), json).await?;
+            index.workers.push(worker.worker_id);
+        }
+        self.put(self.key.clone(), serde_json::to_string(&index)?)
+            .await?;
+
+        for worker_id in previous.workers {
+            if !index.workers.contains(&worker_id) {
+                self.delete(&self.worker_key(worker_id)).await?;
+            }
+        }
+
+        if !oversized.is_empty() {
+            anyhow::bail!(
+                "The KV router snapshot of workers {} is larger than the {MAX_SNAPSHOT_VALUE_BYTES} \
+                 bytes a key-value store entry can hold; they were not saved, use a file snapshot \
+                 target for trees this large",
+                oversized.join(", ")
+            );
+        }
+        Ok(())
+    }
+
+    async fn load(&self) -> anyhow::Result<Option<RadixTreeSnapshot>> {
+        let Some(json) = self.get(&self.key).await? else {
+            return Ok(None);
+        };
+        let index: SnapshotIndex = serde_json::from_slice(&json)
+            .context("Invalid KV router snapshot index, it may predate per-worker snapshots")?;
+
+        let mut snapshot = RadixTreeSnapshot::default();
+        for worker_id in index.workers {
+            match self.get(&self.worker_key(worker_id)).await? {
+                Some(json) => snapshot.workers.push(serde_json::from_slice(&json)?),
+                None => tracing::warn!(worker_id, "KV router snapshot of the worker is missing"),
+            }
+        }
+        Ok(Some(snapshot))
+    }
+}
+
+/// Periodically save snapshots of an indexer's tree until `cancel` is triggered.
+pub async fn snapshot_loop(
+    handle: SnapshotHandle,
+    store: Box<dyn SnapshotStore>,
+    interval: Duration,
+    cancel: CancellationToken,
+) {
+    let mut ticker = tokio::time::interval(interval);
+    // the first tick completes immediately and there is nothing new to save yet
+    ticker.tick().await;
+    loop {
+        tokio::select! {
+            _ = cancel.cancelled() => {
+                tracing::debug!("KV router snapshot loop shutting down");
+                return;
+            }
+            _ = ticker.tick() => {}
+        }
+
+        let snapshot = match handle.snapshot().await {
+            Ok(snapshot) => snapshot,
+            Err(err) => {
+                tracing::debug!(%err, "KV indexer is gone; stopping snapshots");
+                return;
+            }
+        };
+        match store.save(&snapshot).await {
+            Ok(()) => tracing::trace!(
+                workers = snapshot.workers.len(),
+                blocks = snapshot.num_blocks(),
+                "Saved KV router snapshot"
+            ),
+            Err(err) => tracing::warn!(%err, "Failed to save KV router snapshot"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::kv_router::indexer::{SnapshotBlock, WorkerSnapshot};
+    use crate::kv_router::protocols::{ExternalSequenceBlockHash, LocalBlockHash};
+    use dynamo_runtime::storage::key_value_store::MemoryStorage;
+
+    fn snapshot(last_event_id: u64) -> RadixTreeSnapshot {
+        RadixTreeSnapshot {
+            workers: vec![WorkerSnapshot {
+                worker_id: 7,
+                last_event_id: Some(last_event_id),
+                blocks: vec![
+                    SnapshotBlock {
+                        parent_hash: None,
+                        block_hash: ExternalSequenceBlockHash(100),
+                        tokens_hash: LocalBlockHash(1),
+                    },
+                    SnapshotBlock {
+                        parent_hash: Some(ExternalSequenceBlockHash(100)),
+                        block_hash: ExternalSequenceBlockHash(200),
+                        tokens_hash: LocalBlockHash(2),
+                    },
+                ],
+            }],
+        }
+    }
+
+    #[test]
+    fn test_snapshot_target_from_str() {
+        assert_eq!(
+            "etcd".parse::<SnapshotTarget>().unwrap(),
+            SnapshotTarget::Etcd
+        );
+        assert_eq!(
+            "/tmp/router.json".parse::<SnapshotTarget>().unwrap(),
+            SnapshotTarget::File(PathBuf::from("/tmp/router.json"))
+        );
+        assert!("".parse::<SnapshotTarget>().is_err());
+    }
+
+    #[test]
+    fn test_snapshot_path() {
+        assert_eq!(
+            snapshot_path(Path::new("/tmp/router.json"), "ns/backend/llama-3"),
+            PathBuf::from("/tmp/router.ns_backend_llama-3.json")
+        );
+        assert_eq!(
+            snapshot_path(Path::new("/tmp/router"), "ns/backend"),
+            PathBuf::from("/tmp/router.ns_backend")
+        );
+        // every model gets its own file
+        assert_ne!(
+            snapshot_path(Path::new("router.json"), "ns/backend/a"),
+            snapshot_path(Path::new("router.json"), "ns/backend/b")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_file_snapshot_store() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = FileSnapshotStore::new(dir.path().join("router.json"));
+        assert!(store.load().await.unwrap().is_none());
+
+        store.save(&snapshot(1)).await.unwrap();
+        store.save(&snapshot(2)).await.unwrap();
+        assert_eq!(store.load().await.unwrap(), Some(snapshot(2)));
+    }
+
+    #[tokio::test]
+    async fn test_key_value_snapshot_store() {
+        let storage = MemoryStorage::new();
+        let store = KeyValueSnapshotStore::new(Box::new(storage.clone()), "ns/worker".to_string());
+        assert!(store.load().await.unwrap().is_none());
+
+        store.save(&snapshot(1)).await.unwrap();
+        store.save(&snapshot(2)).await.unwrap();
+        assert_eq!(store.load().await.unwrap(), Some(snapshot(2)));
+
+        // a restarted router overwrites the snapshot left by its predecessor
+        let restarted = KeyValueSnapshotStore::new(Box::new(storage), "ns/worker".to_string());
+        assert_eq!(restarted.load().await.unwrap(), Some(snapshot(2)));
+        restarted.save(&snapshot(3)).await.unwrap();
+        assert_eq!(restarted.load().await.unwrap(), Some(snapshot(3)));
+    }
+
+    fn worker(worker_id: WorkerId, num_blocks: u64) -> WorkerSnapshot {
+        let blocks = (0..num_blocks)
+            .map(|i| SnapshotBlock {
+                parent_hash: i.checked_sub(1).map(ExternalSequenceBlockHash),
+                block_hash: ExternalSequenceBlockHash(i),
+                tokens_hash: LocalBlockHash(i),
+            })
+            .collect();
+        WorkerSnapshot {
+            worker_id,
+            last_event_id: Some(num_blocks),
+            blocks,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_key_value_snapshot_store_per_worker() {
+        let storage = MemoryStorage::new();
+        let store = KeyValueSnapshotStore::new(Box::new(storage.clone()), "ns/worker".to_string());
+
+        // together the workers are larger than a single entry can hold
+        let workers: Vec<_> = (1..=4).map(|worker_id| worker(worker_id, 6000)).collect();
+        let tree = RadixTreeSnapshot { workers };
+        assert!(serde_json::to_string(&tree).unwrap().len() > MAX_SNAPSHOT_VALUE_BYTES);
+        store.save(&tree).await.unwrap();
+        assert_eq!(store.load().await.unwrap(), Some(tree.clone()));
+
+        // each worker has its own entry, and the entries of removed workers go away
+        let bucket = storage
+            .get_bucket(KV_ROUTER_SNAPSHOT_BUCKET)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(bucket.entries().await.unwrap().len(), 5);
+        let smaller = RadixTreeSnapshot {
+            workers: tree.workers[..2].to_vec(),
+        };
+        store.save(&smaller).await.unwrap();
+        assert_eq!(bucket.entries().await.unwrap().len(), 3);
+
+        // a restarted router loads what its predecessor saved
+        let restarted = KeyValueSnapshotStore::new(Box::new(storage), "ns/worker".to_string());
+        assert_eq!(restarted.load().await.unwrap(), Some(smaller.clone()));
+
+        // a worker too large for an entry is reported, the others are still saved
+        let mut oversized = smaller.clone();
+        oversized.workers.push(worker(9, 40_000));
+        assert!(restarted.save(&oversized).await.is_err());
+        assert_eq!(restarted.load().await.unwrap(), Some(smaller));
+    }
+}
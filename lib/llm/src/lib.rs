@@ -16,16 +16,19 @@ pub mod disagg_router;
 pub mod discovery;
 pub mod engines;
 pub mod gguf;
+pub mod guided_decoding;
 pub mod http;
 pub mod hub;
 // pub mod key_value_store;
 pub mod kv_router;
 pub mod local_model;
+pub mod migration;
 pub mod mocker;
 pub mod model_card;
 pub mod model_type;
 pub mod preprocessor;
 pub mod protocols;
+pub mod reasoning;
 pub mod recorder;
 pub mod request_template;
 pub mod tokenizers;
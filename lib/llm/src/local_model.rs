@@ -79,6 +79,24 @@ impl LocalModel {
         self.card.kv_cache_block_size = block_size;
     }
 
+    /// Override the parser of the tool calls in the output, which is usually detected from the
+    /// model name.
+    pub fn set_tool_call_parser(&mut self, parser: String) {
+        self.card.tool_call_parser = Some(parser);
+    }
+
+    /// Override the format of the reasoning in the output, which is usually detected from the
+    /// model name.
+    pub fn set_reasoning_parser(&mut self, parser: String) {
+        self.card.reasoning_parser = Some(parser);
+    }
+
+    /// Serve the model as this LoRA adapter, so KV aware routing matches its requests with the
+    /// blocks workers computed with the adapter.
+    pub fn set_lora_id(&mut self, lora_id: u64) {
+        self.card.lora_id = (lora_id != 0).then_some(lora_id);
+    }
+
     /// Make an LLM ready for use:
     /// - Download it from Hugging Face (and NGC in future) if necessary
     /// - Resolve the path
@@ -0,0 +1,361 @@
+// SPDX-FileCopyrightText: Copyright (c) 2024-2025 NVIDIA CORPORATION & AFFILIATES. All rights reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+//! Migration of requests whose worker goes away mid-stream.
+//!
+//! A worker that dies after it started streaming a response leaves the stream ended without a
+//! finish reason. [`Migration`] notices, and sends the request again to another worker with the
+//! tokens generated so far appended to the prompt, so the response carries on where it stopped.
+
+use std::{sync::Arc, time::SystemTime};
+
+use anyhow::{Error, Result};
+use futures::stream::{self, StreamExt};
+
+use dynamo_runtime::{
+    logging::TraceContext,
+    pipeline::{
+        async_trait, AsyncEngineContext, AsyncEngineContextProvider, Context, ManyOut, Operator,
+        ResponseStream, ServerStreamingEngine, SingleIn,
+    },
+    protocols::annotated::Annotated,
+};
+
+use crate::protocols::{
+    common::llm_backend::{LLMEngineOutput, PreprocessedRequest},
+    TokenIdType,
+};
+
+/// Sends requests whose response stream ended early again, up to `migration_limit` times.
+/// A limit of 0 disables migration.
+pub struct Migration {
+    migration_limit: u32,
+}
+
+impl Migration {
+    pub fn new(migration_limit: u32) -> Arc<Self> {
+        Arc::new(Self { migration_limit })
+    }
+}
+
+#[async_trait]
+impl
+    Operator<
+        SingleIn<PreprocessedRequest>,
+        ManyOut<Annotated<LLMEngineOutput>>,
+        SingleIn<PreprocessedRequest>,
+        ManyOut<Annotated<LLMEngineOutput>>,
+    > for Migration
+{
+    async fn generate(
+        &self,
+        request: SingleIn<PreprocessedRequest>,
+        next: ServerStreamingEngine<PreprocessedRequest, Annotated<LLMEngineOutput>>,
+    ) -> Result<ManyOut<Annotated<LLMEngineOutput>>> {
+        if self.migration_limit == 0 {
+            return next.generate(request).await;
+        }
+
+        let inner: &PreprocessedRequest = &request;
+        let preprocessed = inner.clone();
+        let priority = request.priority();
+        let deadline = request.deadline();
+        let trace_context = request.trace_context().cloned();
+
+        let stream = next.generate(request).await?;
+        let context = stream.context();
+
+        let state = MigrationState {
+            stream,
+            next,
+            context: context.clone(),
+            request: preprocessed,
+            priority,
+            deadline,
+            trace_context,
+            generated: Vec::new(),
+            migrations_left: self.migration_limit,
+            migrated: false,
+            stop_forwarded: false,
+            finished: false,
+            done: false,
+        };
+        let stream = stream::unfold(state, |mut state| async move {
+            let output = state.next_output().await?;
+            Some((output, state))
+        });
+
+        Ok(ResponseStream::new(Box::pin(stream), context))
+    }
+}
+
+struct MigrationState {
+    /// The stream of the worker currently serving the request
+    stream: ManyOut<Annotated<LLMEngineOutput>>,
+    next: ServerStreamingEngine<PreprocessedRequest, Annotated<LLMEngineOutput>>,
+    /// The context of the original request, which the client stops
+    context: Arc<dyn AsyncEngineContext>,
+    request: PreprocessedRequest,
+    priority: Option<i32>,
+    deadline: Option<SystemTime>,
+    trace_context: Option<TraceContext>,
+    /// The tokens streamed so far, across all workers
+    generated: Vec<TokenIdType>,
+    migrations_left: u32,
+    /// Whether `stream` is a migrated one, with its own context
+    migrated: bool,
+    stop_forwarded: bool,
+    /// Whether the response completed, or failed in a way another worker would not fix
+    finished: bool,
+    done: bool,
+}
+
+impl MigrationState {
+    async fn next_output(&mut self) -> Option<Annotated<LLMEngineOutput>> {
+        if self.done {
+            return None;
+        }
+        loop {
+            let output = if self.migrated && !self.stop_forwarded {
+                // a migrated request has a context of its own, stopping the original one
+                // has to stop it as well
+                tokio::select! {
+                    output = self.stream.next() => output,
+                    _ = self.context.stopped() => {
+                        if self.context.is_killed() {
+                            self.stream.context().kill();
+                        } else {
+                            self.stream.context().stop_generating();
+                        }
+                        self.stop_forwarded = true;
+                        continue;
+                    }
+                }
+            } else {
+                self.stream.next().await
+            };
+
+            match output {
+                Some(output) => {
+                    if output.is_error() {
+                        self.finished = true;
+                    }
+                    if let Some(data) = &output.data {
+                        self.generated.extend(&data.token_ids);
+                        if data.finish_reason.is_some() {
+                            self.finished = true;
+                        }
+                    }
+                    return Some(output);
+                }
+                None if self.finished || self.context.is_stopped() => {
+                    self.done = true;
+                    return None;
+                }
+                None => {
+                    if self.migrate().await {
+                        continue;
+                    }
+                    self.done = true;
+                    return Some(Annotated::from_error(
+                        "Stream ended before generation completed".to_string(),
+                    ));
+                }
+            }
+        }
+    }
+
+    /// Send the request, continued from the tokens generated so far, to another worker.
+    /// Returns false if the migration limit is reached.
+    async fn migrate(&mut self) -> bool {
+        let request_id = self.context.id().to_string();
+        while self.migrations_left > 0 {
+            self.migrations_left -= 1;
+            tracing::info!(
+                request_id,
+                generated_tokens = self.generated.len(),
+                "Stream ended before generation completed; migrating the request"
+            );
+
+            // a new context: the original one may be pinned to the worker which went away
+            let mut context = Context::with_id(self.continuation(), request_id.clone());
+            context.set_priority(self.priority);
+            context.set_deadline(self.deadline);
+            context.set_trace_context(self.trace_context.clone());
+            match self.next.generate(context).await {
+                Ok(stream) => {
+                    self.stream = stream;
+                    self.migrated = true;
+                    self.stop_forwarded = false;
+                    return true;
+                }
+                Err(err) => {
+                    tracing::warn!(request_id, %err, "Failed to migrate the request");
+                }
+            }
+        }
+        tracing::warn!(request_id, "Stream ended before generation completed");
+        false
+    }
+
+    /// The request with the tokens generated so far as part of the prompt
+    fn continuation(&self) -> PreprocessedRequest {
+        let mut request = self.request.clone();
+        let generated = self.generated.len() as u32;
+        request.token_ids.extend(&self.generated);
+        if let Some(max_tokens) = &mut request.stop_conditions.max_tokens {
+            *max_tokens = max_tokens.saturating_sub(generated);
+        }
+        if let Some(min_tokens) = &mut request.stop_conditions.min_tokens {
+            *min_tokens = min_tokens.saturating_sub(generated);
+        }
+        // the router fills these in for the worker it picks
+        request.estimated_prefix_hit_num_blocks = None;
+        request.peer_blocks.clear();
+        request
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocols::common::{SamplingOptions, StopConditions};
+    use dynamo_runtime::pipeline::AsyncEngine;
+    use std::sync::Mutex;
+
+    /// Streams the outputs of the next scripted attempt, recording the requests it gets
+    struct ScriptedEngine {
+        attempts: Mutex<Vec<Vec<Annotated<LLMEngineOutput>>>>,
+        requests: Mutex<Vec<PreprocessedRequest>>,
+    }
+
+    impl ScriptedEngine {
+        fn new(mut attempts: Vec<Vec<Annotated<LLMEngineOutput>>>) -> Arc<Self> {
+            attempts.reverse();
+            Arc::new(Self {
+                attempts: Mutex::new(attempts),
+                requests: Mutex::new(Vec::new()),
+            })
+        }
+    }
+
+    #[async_trait]
+    impl AsyncEngine<SingleIn<PreprocessedRequest>, ManyOut<Annotated<LLMEngineOutput>>, Error>
+        for ScriptedEngine
+    {
+        async fn generate(
+            &self,
+            request: SingleIn<PreprocessedRequest>,
+        ) -> Result<ManyOut<Annotated<LLMEngineOutput>>> {
+            let (request, context) = request.into_parts();
+            self.requests.lock().unwrap().push(request);
+            let Some(outputs) = self.attempts.lock().unwrap().pop() else {
+                anyhow::bail!("No instances found");
+            };
+            Ok(ResponseStream::new(
+                Box::pin(stream::iter(outputs)),
+                context.context(),
+            ))
+        }
+    }
+
+    fn token(token_id: TokenIdType) -> Annotated<LLMEngineOutput> {
+        let mut output = LLMEngineOutput::stop();
+        output.token_ids = vec![token_id];
+        output.finish_reason = None;
+        Annotated::from_data(output)
+    }
+
+    fn last_token(token_id: TokenIdType) -> Annotated<LLMEngineOutput> {
+        let mut output = LLMEngineOutput::length();
+        output.token_ids = vec![token_id];
+        Annotated::from_data(output)
+    }
+
+    fn request() -> SingleIn<PreprocessedRequest> {
+        let request = PreprocessedRequest::builder()
+            .token_ids(vec![1, 2, 3])
+            .stop_conditions(StopConditions {
+                max_tokens: Some(10),
+                ..Default::default()
+            })
+            .sampling_options(SamplingOptions::default())
+            .estimated_prefix_hit_num_blocks(Some(1))
+            .build()
+            .unwrap();
+        Context::new(request)
+    }
+
+    async fn run(
+        migration_limit: u32,
+        engine: &Arc<ScriptedEngine>,
+    ) -> Vec<Annotated<LLMEngineOutput>> {
+        let stream = Migration::new(migration_limit)
+            .generate(request(), engine.clone())
+            .await
+            .unwrap();
+        stream.collect().await
+    }
+
+    fn token_ids(outputs: &[Annotated<LLMEngineOutput>]) -> Vec<TokenIdType> {
+        outputs
+            .iter()
+            .filter_map(|output| output.data.as_ref())
+            .flat_map(|data| data.token_ids.clone())
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_migrate_truncated_stream() {
+        let engine = ScriptedEngine::new(vec![
+            vec![token(10), token(11)],
+            vec![token(12), last_token(13)],
+        ]);
+        let outputs = run(3, &engine).await;
+
+        assert_eq!(token_ids(&outputs), vec![10, 11, 12, 13]);
+        assert!(outputs.iter().all(|output| !output.is_error()));
+
+        let requests = engine.requests.lock().unwrap();
+        assert_eq!(requests.len(), 2);
+        assert_eq!(requests[1].token_ids, vec![1, 2, 3, 10, 11]);
+        assert_eq!(requests[1].stop_conditions.max_tokens, Some(8));
+        assert_eq!(requests[1].estimated_prefix_hit_num_blocks, None);
+    }
+
+    #[tokio::test]
+    async fn test_finished_stream_is_not_migrated() {
+        let engine = ScriptedEngine::new(vec![vec![token(10), last_token(11)]]);
+        let outputs = run(3, &engine).await;
+
+        assert_eq!(token_ids(&outputs), vec![10, 11]);
+        assert_eq!(engine.requests.lock().unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_migration_limit() {
+        let engine = ScriptedEngine::new(vec![vec![token(10)], vec![token(11)]]);
+        let outputs = run(1, &engine).await;
+
+        assert_eq!(token_ids(&outputs), vec![10, 11]);
+        assert!(outputs.last().unwrap().is_error());
+        assert_eq!(engine.requests.lock().unwrap().len(), 2);
+
+        // failing to send the request again counts against the limit
+        let engine = ScriptedEngine::new(vec![vec![token(10)]]);
+        let outputs = run(2, &engine).await;
+        assert_eq!(token_ids(&outputs), vec![10]);
+        assert!(outputs.last().unwrap().is_error());
+        assert_eq!(engine.requests.lock().unwrap().len(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_disabled() {
+        let engine = ScriptedEngine::new(vec![vec![token(10)], vec![token(11)]]);
+        let outputs = run(0, &engine).await;
+
+        assert_eq!(token_ids(&outputs), vec![10]);
+        assert!(outputs.iter().all(|output| !output.is_error()));
+        assert_eq!(engine.requests.lock().unwrap().len(), 1);
+    }
+}
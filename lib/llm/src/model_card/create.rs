@@ -91,6 +91,9 @@ impl ModelDeploymentCard {
             last_published: None,
             context_length,
             kv_cache_block_size: 0,
+            tool_call_parser: None,
+            reasoning_parser: None,
+            lora_id: None,
         })
     }
 
@@ -129,6 +132,9 @@ impl ModelDeploymentCard {
             last_published: None,
             context_length,
             kv_cache_block_size: 0, // set later
+            tool_call_parser: None,
+            reasoning_parser: None,
+            lora_id: None,
         })
     }
 }
@@ -122,6 +122,23 @@ pub struct ModelDeploymentCard {
     /// Size of a KV cache block - vllm only currently
     /// Passed to the engine and the KV router.
     pub kv_cache_block_size: usize,
+
+    /// Name of the parser of the tool calls in the output, see
+    /// [`crate::preprocessor::tools::ToolCallParserRegistry`]. Detected from the model name if
+    /// not set.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tool_call_parser: Option<String>,
+
+    /// Name of the format of the reasoning in the output of a thinking model, see
+    /// [`crate::reasoning::ReasoningFormat::from_name`]. Detected from the model name if not set.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reasoning_parser: Option<String>,
+
+    /// The LoRA adapter this model is served with, for a worker registering an adapter as its own
+    /// model. `None` for the base model. Requests for the model are KV routed on blocks
+    /// computed with this adapter.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub lora_id: Option<u64>,
 }
 
 impl ModelDeploymentCard {
@@ -23,6 +23,7 @@
 //!
 //! The Preprocessor will accept any IngressRequest and transform it to a BackendRequest.
 
+pub mod media;
 pub mod prompt;
 pub mod tools;
 
@@ -30,25 +31,37 @@ use anyhow::Result;
 use futures::stream::{self, StreamExt};
 use prompt::OAIPromptFormatter;
 use rayon::iter::{IntoParallelRefIterator, ParallelIterator};
-use std::{collections::HashMap, sync::Arc};
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::Arc,
+};
 use tracing;
 
+use crate::kv_router::{WorkerPin, WORKER_PIN};
 use crate::model_card::model::{ModelDeploymentCard, ModelInfo, TokenizerKind};
+use crate::preprocessor::media::MediaLoader;
 use crate::preprocessor::prompt::OAIChatLikeRequest;
+use crate::preprocessor::tools::{
+    detect_tool_call_parser, ToolCallParserFactory, ToolCallParserRegistry,
+};
+use crate::protocols::common::StopConditions;
 use crate::tokenizers::Encoding;
 
 use dynamo_runtime::engine::{AsyncEngine, AsyncEngineContextProvider, ResponseStream};
 use dynamo_runtime::pipeline::{
-    async_trait, AsyncEngineContext, Error, ManyOut, Operator, SingleIn,
+    async_trait, AsyncEngineContext, Context, Error, ManyOut, Operator, SingleIn,
 };
 use dynamo_runtime::protocols::annotated::{Annotated, AnnotationsProvider};
 
 use crate::protocols::{
-    common::{SamplingOptionsProvider, StopConditionsProvider},
+    common::{
+        GuidedDecodingOptionsProvider, OutputOptionsProvider, SamplingOptionsProvider,
+        StopConditionsProvider,
+    },
     openai::{
         chat_completions::{NvCreateChatCompletionRequest, NvCreateChatCompletionStreamResponse},
         completions::{NvCreateCompletionRequest, NvCreateCompletionResponse},
-        nvext::NvExtProvider,
+        nvext::{NvExtProvider, TruncationMode},
         DeltaGeneratorExt,
     },
 };
@@ -66,6 +79,9 @@ pub struct LLMMetricAnnotation {
     pub input_tokens: usize,
     pub output_tokens: usize,
     pub chunk_tokens: usize,
+    /// Input tokens the KV router expected the worker to have cached
+    #[serde(default)]
+    pub cached_tokens: usize,
 }
 
 impl LLMMetricAnnotation {
@@ -96,16 +112,77 @@ impl LLMMetricAnnotation {
     }
 }
 
+/// The tool call parser named in the model card, or else the one matching the model name
+fn tool_call_parser(
+    mdc: &ModelDeploymentCard,
+    registry: &ToolCallParserRegistry,
+) -> Option<ToolCallParserFactory> {
+    let name = match &mdc.tool_call_parser {
+        Some(name) => name.as_str(),
+        None => detect_tool_call_parser(&mdc.display_name)?,
+    };
+    let parser = registry.get(name);
+    if parser.is_none() {
+        tracing::warn!(
+            model = %mdc.display_name,
+            parser = name,
+            "Unknown tool call parser, tool calls will be returned as content"
+        );
+    }
+    parser
+}
+
+/// Give the context of a request its priority and deadline, which the routers and the workers
+/// read, and stop the request when its deadline passes
+fn set_urgency(request: &mut SingleIn<PreprocessedRequest>) {
+    let inner: &PreprocessedRequest = request;
+    let (priority, deadline) = (inner.priority, inner.deadline());
+    request.set_priority(priority);
+    request.set_deadline(deadline);
+}
+
+/// The prompt of a request leaves no room for the output in the context of the model
+#[derive(Debug, thiserror::Error)]
+#[error(
+    "This model's maximum context length is {context_length} tokens, however the prompt has {prompt_tokens} tokens"
+)]
+pub struct ContextLengthExceeded {
+    pub context_length: usize,
+    pub prompt_tokens: usize,
+}
+
 pub struct OpenAIPreprocessor {
     mdcsum: String,
     formatter: Arc<dyn OAIPromptFormatter>,
     tokenizer: Arc<dyn Tokenizer>,
     model_info: Arc<dyn ModelInfo>,
+    /// 0 if the model card does not know it
+    context_length: usize,
+    /// To count the cached prompt tokens of KV routed requests
+    kv_cache_block_size: usize,
+    /// None if the tool calls of the model are not parsed
+    tool_call_parser: Option<ToolCallParserFactory>,
+    /// The LoRA adapter of the model, None for the base model
+    lora_id: Option<u64>,
+    media_loader: MediaLoader,
 }
 
 impl OpenAIPreprocessor {
     pub async fn new(mdc: ModelDeploymentCard) -> Result<Arc<Self>> {
+        Self::new_with_tool_call_parsers(mdc, &ToolCallParserRegistry::default()).await
+    }
+
+    /// Like [`OpenAIPreprocessor::new`], with the tool call parser of the model looked up in
+    /// `registry` instead of the built-in parsers.
+    pub async fn new_with_tool_call_parsers(
+        mdc: ModelDeploymentCard,
+        registry: &ToolCallParserRegistry,
+    ) -> Result<Arc<Self>> {
         let mdcsum = mdc.mdcsum();
+        let context_length = mdc.context_length;
+        let kv_cache_block_size = mdc.kv_cache_block_size;
+        let lora_id = mdc.lora_id;
+        let tool_call_parser = tool_call_parser(&mdc, registry);
         let formatter = PromptFormatter::from_mdc(mdc.clone()).await?;
         let PromptFormatter::OAI(formatter) = formatter;
 
@@ -128,15 +205,26 @@ impl OpenAIPreprocessor {
             );
         };
         let model_info = model_info.get_model_info().await?;
+        let media_loader = MediaLoader::new()?;
 
         Ok(Arc::new(Self {
             formatter,
             tokenizer,
             model_info,
             mdcsum,
+            context_length,
+            kv_cache_block_size,
+            tool_call_parser,
+            lora_id,
+            media_loader,
         }))
     }
 
+    /// The LoRA adapter of the model's card, set on every request of the model
+    pub fn lora_id(&self) -> Option<u64> {
+        self.lora_id
+    }
+
     /// Encode a string to it's tokens
     pub fn tokenize(&self, s: &str) -> anyhow::Result<Encoding> {
         self.tokenizer.encode(s)
@@ -152,6 +240,8 @@ impl OpenAIPreprocessor {
         R: OAIChatLikeRequest
             + AnnotationsProvider
             + SamplingOptionsProvider
+            + OutputOptionsProvider
+            + GuidedDecodingOptionsProvider
             + StopConditionsProvider
             + NvExtProvider,
     >(
@@ -160,6 +250,13 @@ impl OpenAIPreprocessor {
     ) -> Result<(PreprocessedRequest, HashMap<String, String>)> {
         let mut annotations = HashMap::new();
         let mut builder = PreprocessedRequest::builder();
+        let mut stop_conditions = request.extract_stop_conditions()?;
+        let truncation = request
+            .nvext()
+            .and_then(|ext| ext.truncation)
+            .unwrap_or_default();
+        // the longest prompt of the request
+        let mut prompt_tokens = 0;
 
         // match request type before any conversion/processing
         match request.prompt_input_type() {
@@ -167,9 +264,11 @@ impl OpenAIPreprocessor {
                 if let Some(token_input) = request.extract_tokens() {
                     match token_input {
                         TokenInput::Single(tokens) => {
+                            prompt_tokens = tokens.len();
                             builder.token_ids(tokens);
                         }
                         TokenInput::Batch(token_batches) => {
+                            prompt_tokens = token_batches.iter().map(Vec::len).max().unwrap_or(0);
                             if token_batches.len() == 1 {
                                 builder.token_ids(token_batches[0].clone());
                             } else {
@@ -204,6 +303,20 @@ impl OpenAIPreprocessor {
                                 self.tokenizer.encode(&formatted_prompt)
                             })?;
 
+                            // dropping turns would drop the placeholders of their media
+                            let (formatted_prompt, encoding) = if truncation == TruncationMode::Left
+                                && !use_raw_prompt
+                                && !request.has_media()
+                                && !self.fits_context(
+                                    encoding.token_ids.len(),
+                                    stop_conditions.max_tokens,
+                                ) {
+                                self.render_truncated(request, stop_conditions.max_tokens)?
+                            } else {
+                                (formatted_prompt, encoding)
+                            };
+                            prompt_tokens = encoding.token_ids.len();
+
                             if request.has_annotation(ANNOTATION_FORMATTED_PROMPT) {
                                 annotations.insert(
                                     ANNOTATION_FORMATTED_PROMPT.to_string(),
@@ -230,6 +343,7 @@ impl OpenAIPreprocessor {
                                 .collect();
 
                             let token_batches = token_batches?;
+                            prompt_tokens = token_batches.iter().map(Vec::len).max().unwrap_or(0);
                             builder.batch_token_ids(Some(token_batches));
                             builder.token_ids(vec![]);
                         }
@@ -238,7 +352,8 @@ impl OpenAIPreprocessor {
             }
         }
 
-        let mut stop_conditions = request.extract_stop_conditions()?;
+        self.apply_context_length(prompt_tokens, &mut stop_conditions)?;
+
         if let Some(stop_tokens) = &mut stop_conditions.stop_token_ids_hidden {
             for eos_token in self.model_info.eos_token_ids() {
                 if !stop_tokens.contains(&eos_token) {
@@ -257,17 +372,194 @@ impl OpenAIPreprocessor {
         }
 
         builder.stop_conditions(stop_conditions);
-        builder.sampling_options(request.extract_sampling_options()?);
+        let sampling_options = request.extract_sampling_options()?;
+        let mut output_options = request.extract_output_options()?;
+        // best_of ranks its candidates by likelihood, so it needs the logprob of every sampled
+        // token; they only reach the client if it asked for them
+        if sampling_options.best_of > Some(sampling_options.n.unwrap_or(1))
+            && output_options.logprobs.is_none()
+        {
+            output_options.logprobs = Some(0);
+        }
+        builder.sampling_options(sampling_options);
+        builder.output_options(output_options);
+        builder.guided_decoding(request.extract_guided_decoding_options()?);
         builder.annotations(request.annotations().unwrap_or_default());
         builder.mdc_sum(Some(self.mdcsum.clone()));
         builder.estimated_prefix_hit_num_blocks(None);
+        builder.lora_id(self.lora_id);
+        builder.priority(request.nvext().and_then(|ext| ext.priority));
+        builder.deadline_ms(request.nvext().and_then(|ext| ext.deadline_ms));
 
         Ok((builder.build()?, annotations))
     }
 
+    /// Whether a prompt leaves room for `max_tokens` in the context of the model, or for at least
+    /// one token if the request does not limit them
+    fn fits_context(&self, prompt_tokens: usize, max_tokens: Option<u32>) -> bool {
+        self.context_length == 0
+            || prompt_tokens + max_tokens.unwrap_or(1).max(1) as usize <= self.context_length
+    }
+
+    /// Reject a prompt which leaves no room for the output in the context of the model, and clamp
+    /// `max_tokens` to the room it leaves.
+    fn apply_context_length(
+        &self,
+        prompt_tokens: usize,
+        stop_conditions: &mut StopConditions,
+    ) -> Result<()> {
+        if self.context_length == 0 {
+            return Ok(());
+        }
+        if prompt_tokens >= self.context_length {
+            return Err(ContextLengthExceeded {
+                context_length: self.context_length,
+                prompt_tokens,
+            }
+            .into());
+        }
+        let remaining = u32::try_from(self.context_length - prompt_tokens).unwrap_or(u32::MAX);
+        stop_conditions.max_tokens = Some(
+            stop_conditions
+                .max_tokens
+                .map_or(remaining, |max_tokens| max_tokens.min(remaining)),
+        );
+        Ok(())
+    }
+
+    /// Render a chat without its oldest turns, dropping as few as needed for the prompt to fit in
+    /// the context of the model. A turn is a user message and the messages which answer it.
+    /// System messages and the last turn are always kept, so the prompt can still be too long.
+    fn render_truncated<R: OAIChatLikeRequest>(
+        &self,
+        request: &R,
+        max_tokens: Option<u32>,
+    ) -> Result<(String, Encoding)> {
+        let has_role = |message: &minijinja::Value, roles: &[&str]| {
+            message
+                .get_attr("role")
+                .ok()
+                .is_some_and(|role| role.as_str().is_some_and(|role| roles.contains(&role)))
+        };
+
+        let messages = request.messages().try_iter()?.collect::<Vec<_>>();
+        let num_system = messages
+            .iter()
+            .take_while(|message| has_role(message, &["system", "developer"]))
+            .count();
+        let (system, conversation) = messages.split_at(num_system);
+
+        let mut start = 0;
+        loop {
+            let truncated = TruncatedChat {
+                request,
+                messages: system
+                    .iter()
+                    .chain(&conversation[start..])
+                    .cloned()
+                    .collect::<Vec<_>>()
+                    .into(),
+            };
+            let formatted_prompt = self.formatter.render(&truncated)?;
+            let encoding =
+                tokio::task::block_in_place(|| self.tokenizer.encode(&formatted_prompt))?;
+            if self.fits_context(encoding.token_ids.len(), max_tokens) {
+                return Ok((formatted_prompt, encoding));
+            }
+
+            let next_turn = conversation
+                .iter()
+                .skip(start + 1)
+                .position(|message| has_role(message, &["user"]));
+            match next_turn {
+                Some(next_turn) => start += next_turn + 1,
+                None => return Ok((formatted_prompt, encoding)),
+            }
+        }
+    }
+
+    /// Send a request to the next engine, fanned out into one request per choice if it asks
+    /// for more than one. The outputs of each choice carry its `index`.
+    ///
+    /// The choices share the prompt, and their requests share the [`WorkerPin`] `pin` so the KV
+    /// router sends them all to the worker which has the prompt cached.
+    async fn generate_choices(
+        mut request: SingleIn<PreprocessedRequest>,
+        pin: &WorkerPin,
+        next: &Arc<
+            dyn AsyncEngine<
+                SingleIn<PreprocessedRequest>,
+                ManyOut<Annotated<BackendOutput>>,
+                Error,
+            >,
+        >,
+    ) -> Result<ManyOut<Annotated<BackendOutput>>, Error> {
+        let sampling_options = &request.sampling_options;
+        let num_choices = sampling_options.best_of.or(sampling_options.n).unwrap_or(1);
+        if num_choices <= 1 {
+            request.insert(WORKER_PIN, pin.clone());
+            return next.generate(request).await;
+        }
+
+        let (request, context) = request.into_parts();
+        let mut contexts: Vec<Arc<dyn AsyncEngineContext>> = Vec::new();
+        let mut streams = Vec::new();
+        for index in 0..num_choices as u32 {
+            let mut choice = request.clone();
+            choice.sampling_options.n = None;
+            choice.sampling_options.best_of = None;
+            // with the same seed every choice would be the same
+            if let Some(seed) = &mut choice.sampling_options.seed {
+                *seed = seed.wrapping_add(index as i64);
+            }
+
+            let mut choice = Context::with_id(choice, format!("{}-{}", context.id(), index));
+            choice.insert(WORKER_PIN, pin.clone());
+            choice.set_priority(context.priority());
+            choice.set_deadline(context.deadline());
+            choice.set_trace_context(context.trace_context().cloned());
+            let stream = match next.generate(choice).await {
+                Ok(stream) => stream,
+                Err(err) => {
+                    for context in &contexts {
+                        context.stop_generating();
+                    }
+                    return Err(err);
+                }
+            };
+            contexts.push(stream.context());
+            streams.push(stream.map(move |mut output| {
+                if let Some(data) = &mut output.data {
+                    data.index = Some(index);
+                }
+                output
+            }));
+        }
+
+        let context = Arc::new(ChoicesContext {
+            id: context.id().to_string(),
+            choices: contexts,
+        });
+        Ok(ResponseStream::new(
+            Box::pin(stream::select_all(streams)),
+            context,
+        ))
+    }
+
+    /// The prompt tokens the worker of a request was expected to have cached, from the overlap
+    /// the KV router found when it chose the worker. 0 without KV routing.
+    fn cached_tokens(&self, pin: &WorkerPin, prompt_tokens: usize) -> usize {
+        let overlap_blocks = pin.overlap_blocks().unwrap_or(0) as usize;
+        (overlap_blocks * self.kv_cache_block_size).min(prompt_tokens)
+    }
+
+    /// Convert the outputs of the backend to responses with `generator`, annotated with the
+    /// [`LLMMetricAnnotation`] of the request. If the request asked for it, the stream ends with
+    /// a response of the usage of the whole request, with `cached_tokens` of its prompt cached.
     pub fn transform_postprocessor_stream<Resp: Send + Sync + 'static + std::fmt::Debug>(
         stream: ManyOut<Annotated<BackendOutput>>,
         generator: Box<dyn DeltaGeneratorExt<Resp>>,
+        cached_tokens: usize,
     ) -> ManyOut<Annotated<Resp>> {
         let context = stream.context();
 
@@ -277,6 +569,11 @@ impl OpenAIPreprocessor {
             context: Arc<dyn AsyncEngineContext>,
             cancelled: bool,
             cumulative_output_tokens: usize,
+            cached_tokens: usize,
+            // the backend stream ended, what the generator held back is being sent
+            finished: bool,
+            held_back: VecDeque<Resp>,
+            usage_sent: bool,
         }
 
         let state = State {
@@ -285,12 +582,27 @@ impl OpenAIPreprocessor {
             context: context.clone(),
             cancelled: false,
             cumulative_output_tokens: 0,
+            cached_tokens,
+            finished: false,
+            held_back: VecDeque::new(),
+            usage_sent: false,
         };
 
         // transform the common response stream into a chat response stream
         let stream = stream::unfold(state, |mut inner| {
             async move {
-                if let Some(response) = inner.response_stream.next().await {
+                let response = if inner.finished {
+                    None
+                } else {
+                    inner.response_stream.next().await
+                };
+                if response.is_none() && !inner.finished {
+                    inner.finished = true;
+                    if !inner.cancelled {
+                        inner.held_back = inner.response_generator.finish_stream().into();
+                    }
+                }
+                if let Some(response) = response {
                     if inner.cancelled {
                         tracing::debug!(
                             request_id = inner.context.id(),
@@ -339,6 +651,7 @@ impl OpenAIPreprocessor {
                         input_tokens: isl,
                         output_tokens: current_osl,
                         chunk_tokens,
+                        cached_tokens: inner.cached_tokens,
                     };
 
                     if let Ok(metrics_annotated) = llm_metrics.to_annotation::<()>() {
@@ -356,6 +669,28 @@ impl OpenAIPreprocessor {
                     );
 
                     Some((response, inner))
+                } else if let Some(response) = inner.held_back.pop_front() {
+                    Some((Annotated::from_data(response), inner))
+                } else if inner.response_generator.is_usage_enabled() && !inner.usage_sent {
+                    // the last response of the stream has the usage of the whole request
+                    inner.usage_sent = true;
+                    let prompt_tokens = inner.response_generator.get_isl().unwrap_or(0);
+                    // SAFETY: Casting from `usize` to `u32` could lead to precision loss after
+                    // `u32::MAX`, but this will not be an issue until context lengths exceed
+                    // 4_294_967_295.
+                    let completion_tokens = inner.cumulative_output_tokens as u32;
+                    let usage = async_openai::types::CompletionUsage {
+                        prompt_tokens,
+                        completion_tokens,
+                        total_tokens: prompt_tokens + completion_tokens,
+                        prompt_tokens_details: Some(async_openai::types::PromptTokensDetails {
+                            audio_tokens: None,
+                            cached_tokens: Some(inner.cached_tokens as u32),
+                        }),
+                        completion_tokens_details: None,
+                    };
+                    let response = inner.response_generator.create_usage_chunk(usage);
+                    Some((Annotated::from_data(response), inner))
                 } else {
                     // stream closed with out graceful closure
                     // we did not detect an is_finished/completed message
@@ -369,6 +704,78 @@ impl OpenAIPreprocessor {
     }
 }
 
+/// The context of a request fanned out into one request per choice
+#[derive(Debug)]
+struct ChoicesContext {
+    id: String,
+    choices: Vec<Arc<dyn AsyncEngineContext>>,
+}
+
+#[async_trait]
+impl AsyncEngineContext for ChoicesContext {
+    fn id(&self) -> &str {
+        &self.id
+    }
+
+    fn is_stopped(&self) -> bool {
+        self.choices.iter().all(|choice| choice.is_stopped())
+    }
+
+    fn is_killed(&self) -> bool {
+        self.choices.iter().all(|choice| choice.is_killed())
+    }
+
+    async fn stopped(&self) {
+        futures::future::join_all(self.choices.iter().map(|choice| choice.stopped())).await;
+    }
+
+    async fn killed(&self) {
+        futures::future::join_all(self.choices.iter().map(|choice| choice.killed())).await;
+    }
+
+    fn stop_generating(&self) {
+        for choice in &self.choices {
+            choice.stop_generating();
+        }
+    }
+
+    fn stop(&self) {
+        for choice in &self.choices {
+            choice.stop();
+        }
+    }
+
+    fn kill(&self) {
+        for choice in &self.choices {
+            choice.kill();
+        }
+    }
+}
+
+/// A chat request rendered with only some of its messages
+struct TruncatedChat<'a, R> {
+    request: &'a R,
+    messages: minijinja::Value,
+}
+
+impl<R: OAIChatLikeRequest> OAIChatLikeRequest for TruncatedChat<'_, R> {
+    fn messages(&self) -> minijinja::Value {
+        self.messages.clone()
+    }
+
+    fn tools(&self) -> Option<minijinja::Value> {
+        self.request.tools()
+    }
+
+    fn tool_choice(&self) -> Option<minijinja::Value> {
+        self.request.tool_choice()
+    }
+
+    fn should_add_generation_prompt(&self) -> bool {
+        self.request.should_add_generation_prompt()
+    }
+}
+
 // for pals, we do not want to add the generation prompt to the formatted prompt
 // we also need to know if the template support this add_generation_prompt bool
 // any prompt template that does not support this should return an error
@@ -401,14 +808,33 @@ impl
         let response_generator = request.response_generator();
         let mut response_generator = Box::new(response_generator);
 
+        // parse the tool calls out of the output if the model can call the request's tools
+        if let Some(tool_call_parser) = &self.tool_call_parser {
+            let tools_enabled = request.inner.tools.as_ref().is_some_and(|t| !t.is_empty())
+                && !matches!(
+                    request.inner.tool_choice,
+                    Some(async_openai::types::ChatCompletionToolChoiceOption::None)
+                );
+            if tools_enabled {
+                response_generator.set_tool_call_parser(tool_call_parser());
+            }
+        }
+
         // convert the chat completion request to a common completion request
-        let (common_request, annotations) = self.preprocess_request(&request)?;
+        let (mut common_request, annotations) = self.preprocess_request(&request)?;
+
+        // attach the images and audio the prompt has the placeholders of
+        if request.has_media() {
+            common_request.media = self.media_loader.load(&request.inner.messages).await?;
+        }
 
         // update isl
-        response_generator.update_isl(common_request.token_ids.len() as u32);
+        let isl = common_request.token_ids.len();
+        response_generator.update_isl(isl as u32);
 
         // repack the common completion request
-        let common_request = context.map(|_| common_request);
+        let mut common_request = context.map(|_| common_request);
+        set_urgency(&mut common_request);
 
         // create a stream of annotations this will be prepend to the response stream
         let annotations: Vec<Annotated<NvCreateChatCompletionStreamResponse>> = annotations
@@ -418,10 +844,16 @@ impl
         let annotations_stream = stream::iter(annotations);
 
         // forward the common completion request to the next operator
-        let response_stream = next.generate(common_request).await?;
+        let pin = WorkerPin::default();
+        let response_stream = Self::generate_choices(common_request, &pin, &next).await?;
+        let cached_tokens = self.cached_tokens(&pin, isl);
 
         // transform the postprocessor stream
-        let stream = Self::transform_postprocessor_stream(response_stream, response_generator);
+        let stream = Self::transform_postprocessor_stream(
+            response_stream,
+            response_generator,
+            cached_tokens,
+        );
         let context = stream.context();
 
         // prepend the annotations to the response stream
@@ -462,10 +894,12 @@ impl
         let (common_request, annotations) = self.preprocess_request(&request)?;
 
         // update isl
-        response_generator.update_isl(common_request.token_ids.len() as u32);
+        let isl = common_request.token_ids.len();
+        response_generator.update_isl(isl as u32);
 
         // repack the common completion request
-        let common_request = context.map(|_| common_request);
+        let mut common_request = context.map(|_| common_request);
+        set_urgency(&mut common_request);
 
         // create a stream of annotations this will be prepend to the response stream
         let annotations: Vec<Annotated<NvCreateCompletionResponse>> = annotations
@@ -475,10 +909,16 @@ impl
         let annotations_stream = stream::iter(annotations);
 
         // forward the common completion request to the next operator
-        let response_stream = next.generate(common_request).await?;
+        let pin = WorkerPin::default();
+        let response_stream = Self::generate_choices(common_request, &pin, &next).await?;
+        let cached_tokens = self.cached_tokens(&pin, isl);
 
         // transform the postprocessor stream
-        let stream = Self::transform_postprocessor_stream(response_stream, response_generator);
+        let stream = Self::transform_postprocessor_stream(
+            response_stream,
+            response_generator,
+            cached_tokens,
+        );
         let context = stream.context();
 
         // prepend the annotations to the response stream
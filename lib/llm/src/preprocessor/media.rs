@@ -0,0 +1,406 @@
+// SPDX-FileCopyrightText: Copyright (c) 2024-2025 NVIDIA CORPORATION & AFFILIATES. All rights reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+//! The images and audio of chat messages, loaded into the [`MediaInput`] of the preprocessed
+//! request. The chat template renders the placeholder tokens of each media in the prompt.
+
+use std::{
+    env,
+    net::{IpAddr, SocketAddr},
+    sync::Arc,
+    time::Duration,
+};
+
+use anyhow::Result;
+use async_openai::types::{
+    ChatCompletionRequestMessage, ChatCompletionRequestUserMessageContent,
+    ChatCompletionRequestUserMessageContentPart, InputAudio, InputAudioFormat,
+};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use futures::{StreamExt, TryStreamExt};
+use reqwest::{
+    dns::{Addrs, Name, Resolve, Resolving},
+    redirect,
+};
+use url::{Host, Url};
+
+use crate::http::service::error::HttpError;
+use crate::protocols::common::llm_backend::{MediaInput, MediaKind};
+
+/// Comma separated hosts the frontend may download images from, `*` for any public host.
+/// Unset, only `data:` URLs are accepted.
+pub const MEDIA_ALLOWED_HOSTS_ENV_VAR: &str = "DYN_MEDIA_ALLOWED_HOSTS";
+
+/// The largest media a request can attach
+pub const MAX_MEDIA_BYTES: usize = 32 * 1024 * 1024;
+
+/// The most media a request can attach
+pub const MAX_MEDIA_PARTS: usize = 16;
+
+/// How many media of a request are loaded at the same time
+const MAX_CONCURRENT_LOADS: usize = 4;
+
+const MAX_REDIRECTS: usize = 5;
+
+const FETCH_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Decodes the media of data URLs and base64 audio, and downloads the media of http(s) URLs
+/// on the allowed hosts.
+#[derive(Debug, Clone)]
+pub struct MediaLoader {
+    client: reqwest::Client,
+    allowed_hosts: Arc<Vec<String>>,
+}
+
+impl MediaLoader {
+    /// A loader downloading from the hosts of [`MEDIA_ALLOWED_HOSTS_ENV_VAR`].
+    pub fn new() -> Result<Self> {
+        let allowed_hosts = env::var(MEDIA_ALLOWED_HOSTS_ENV_VAR)
+            .map(|hosts| {
+                hosts
+                    .split(',')
+                    .map(|host| host.trim().to_lowercase())
+                    .filter(|host| !host.is_empty())
+                    .collect()
+            })
+            .unwrap_or_default();
+        Self::with_allowed_hosts(allowed_hosts)
+    }
+
+    /// A loader downloading from `allowed_hosts`, `*` allowing any public host. Whatever the
+    /// hosts, only public addresses are connected to.
+    pub fn with_allowed_hosts(allowed_hosts: Vec<String>) -> Result<Self> {
+        let allowed_hosts = Arc::new(allowed_hosts);
+        let redirect_hosts = allowed_hosts.clone();
+        let redirect = redirect::Policy::custom(move |attempt| {
+            if attempt.previous().len() >= MAX_REDIRECTS {
+                attempt.error("too many redirects")
+            } else if let Err(err) = check_url(&redirect_hosts, attempt.url()) {
+                attempt.error(err)
+            } else {
+                attempt.follow()
+            }
+        });
+        let client = reqwest::Client::builder()
+            .timeout(FETCH_TIMEOUT)
+            .no_proxy()
+            .redirect(redirect)
+            .dns_resolver(Arc::new(PublicResolver))
+            .build()?;
+        Ok(Self {
+            client,
+            allowed_hosts,
+        })
+    }
+
+    /// The media of the messages, in the order they appear in. Media which can not be loaded
+    /// fail the request with a 400 [`HttpError`].
+    pub async fn load(&self, messages: &[ChatCompletionRequestMessage]) -> Result<Vec<MediaInput>> {
+        let parts: Vec<_> = messages
+            .iter()
+            .filter_map(|message| match message {
+                ChatCompletionRequestMessage::User(message) => match &message.content {
+                    ChatCompletionRequestUserMessageContent::Array(parts) => Some(parts),
+                    ChatCompletionRequestUserMessageContent::Text(_) => None,
+                },
+                _ => None,
+            })
+            .flatten()
+            .filter(|part| !matches!(part, ChatCompletionRequestUserMessageContentPart::Text(_)))
+            .collect();
+        if parts.len() > MAX_MEDIA_PARTS {
+            return Err(bad_request(format!(
+                "A request can attach at most {MAX_MEDIA_PARTS} images and audio, got {}",
+                parts.len()
+            )));
+        }
+
+        let media: Vec<_> = futures::stream::iter(parts)
+            .map(|part| self.load_part(part))
+            .buffered(MAX_CONCURRENT_LOADS)
+            .try_collect()
+            .await?;
+        Ok(media.into_iter().flatten().collect())
+    }
+
+    async fn load_part(
+        &self,
+        part: &ChatCompletionRequestUserMessageContentPart,
+    ) -> Result<Option<MediaInput>> {
+        match part {
+            ChatCompletionRequestUserMessageContentPart::Text(_) => Ok(None),
+            ChatCompletionRequestUserMessageContentPart::ImageUrl(part) => {
+                self.load_image(&part.image_url.url).await.map(Some)
+            }
+            ChatCompletionRequestUserMessageContentPart::InputAudio(part) => {
+                load_audio(&part.input_audio).map(Some)
+            }
+        }
+    }
+
+    async fn load_image(&self, url: &str) -> Result<MediaInput> {
+        if url.starts_with("data:") {
+            let (mime_type, data) = decode_data_url(url)?;
+            return Ok(MediaInput::new(MediaKind::Image, mime_type, data));
+        }
+
+        let parsed = Url::parse(url)
+            .map_err(|err| bad_request(format!("Invalid image URL '{url}': {err}")))?;
+        if !matches!(parsed.scheme(), "http" | "https") {
+            return Err(bad_request(format!(
+                "Unsupported image URL scheme '{}', expected http, https or data",
+                parsed.scheme()
+            )));
+        }
+        check_url(&self.allowed_hosts, &parsed)
+            .map_err(|err| bad_request(format!("Image URL '{url}' is not allowed: {err}")))?;
+
+        let mut response = self
+            .client
+            .get(parsed)
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .map_err(|err| bad_request(format!("Failed to fetch image '{url}': {err}")))?;
+        if response
+            .content_length()
+            .is_some_and(|length| length > MAX_MEDIA_BYTES as u64)
+        {
+            return Err(too_large(url));
+        }
+        let mime_type = response
+            .headers()
+            .get(reqwest::header::CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .map(str::to_string);
+
+        // the length header is optional, count the bytes as they arrive
+        let mut data = Vec::new();
+        while let Some(chunk) = response
+            .chunk()
+            .await
+            .map_err(|err| bad_request(format!("Failed to fetch image '{url}': {err}")))?
+        {
+            if data.len() + chunk.len() > MAX_MEDIA_BYTES {
+                return Err(too_large(url));
+            }
+            data.extend_from_slice(&chunk);
+        }
+        Ok(MediaInput::new(MediaKind::Image, mime_type, data))
+    }
+}
+
+/// Resolves host names to their public addresses only, so that an allowed host can not point the
+/// frontend at its own network.
+struct PublicResolver;
+
+impl Resolve for PublicResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        Box::pin(resolve_public(name))
+    }
+}
+
+async fn resolve_public(name: Name) -> Result<Addrs, Box<dyn std::error::Error + Send + Sync>> {
+    let addrs: Vec<SocketAddr> = tokio::net::lookup_host((name.as_str(), 0))
+        .await?
+        .filter(|addr| is_public(addr.ip()))
+        .collect();
+    if addrs.is_empty() {
+        return Err(format!("{} has no public address", name.as_str()).into());
+    }
+    Ok(Box::new(addrs.into_iter()))
+}
+
+/// Whether `url` is on one of the `allowed_hosts`. IP addresses must be public, host names are
+/// checked by the [`PublicResolver`].
+fn check_url(allowed_hosts: &[String], url: &Url) -> Result<(), String> {
+    let host = url.host().ok_or("the URL has no host")?;
+    let ip = match host {
+        Host::Domain(_) => None,
+        Host::Ipv4(ip) => Some(IpAddr::V4(ip)),
+        Host::Ipv6(ip) => Some(IpAddr::V6(ip)),
+    };
+    if ip.is_some_and(|ip| !is_public(ip)) {
+        return Err(format!("{host} is not a public address"));
+    }
+
+    let host = host.to_string();
+    if !allowed_hosts
+        .iter()
+        .any(|allowed| allowed == "*" || *allowed == host)
+    {
+        return Err(format!("{host} is not in {MEDIA_ALLOWED_HOSTS_ENV_VAR}"));
+    }
+    Ok(())
+}
+
+/// Whether `ip` is reachable on the internet, rather than a private, loopback, link-local or
+/// otherwise special address.
+fn is_public(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => {
+            let [a, b, ..] = ip.octets();
+            !(ip.is_private()
+                || ip.is_loopback()
+                || ip.is_link_local()
+                || ip.is_unspecified()
+                || ip.is_broadcast()
+                || ip.is_documentation()
+                || ip.is_multicast()
+                // shared address space of carrier-grade NAT, 100.64.0.0/10
+                || (a == 100 && (b & 0xc0) == 64)
+                // "this network", 0.0.0.0/8
+                || a == 0)
+        }
+        IpAddr::V6(ip) => {
+            if let Some(ip) = ip.to_ipv4_mapped() {
+                return is_public(IpAddr::V4(ip));
+            }
+            let first = ip.segments()[0];
+            !(ip.is_loopback()
+                || ip.is_unspecified()
+                || ip.is_multicast()
+                // unique local, fc00::/7
+                || (first & 0xfe00) == 0xfc00
+                // link-local, fe80::/10
+                || (first & 0xffc0) == 0xfe80)
+        }
+    }
+}
+
+fn load_audio(audio: &InputAudio) -> Result<MediaInput> {
+    let data = STANDARD
+        .decode(&audio.data)
+        .map_err(|err| bad_request(format!("Invalid base64 audio data: {err}")))?;
+    if data.len() > MAX_MEDIA_BYTES {
+        return Err(too_large("input_audio"));
+    }
+    let mime_type = match audio.format {
+        InputAudioFormat::Wav => "audio/wav",
+        InputAudioFormat::Mp3 => "audio/mpeg",
+    };
+    Ok(MediaInput::new(
+        MediaKind::Audio,
+        Some(mime_type.to_string()),
+        data,
+    ))
+}
+
+/// The MIME type and the data of a base64 data URL, `data:image/png;base64,...`
+fn decode_data_url(url: &str) -> Result<(Option<String>, Vec<u8>)> {
+    let (header, data) = url
+        .strip_prefix("data:")
+        .and_then(|url| url.split_once(','))
+        .ok_or_else(|| bad_request("Invalid data URL, expected a ',' after the header".into()))?;
+    let Some(mime_type) = header.strip_suffix(";base64") else {
+        return Err(bad_request("Only base64 data URLs are supported".into()));
+    };
+    let data = STANDARD
+        .decode(data)
+        .map_err(|err| bad_request(format!("Invalid base64 data URL: {err}")))?;
+    if data.len() > MAX_MEDIA_BYTES {
+        return Err(too_large("data URL"));
+    }
+    let mime_type = (!mime_type.is_empty()).then(|| mime_type.to_string());
+    Ok((mime_type, data))
+}
+
+fn too_large(source: &str) -> anyhow::Error {
+    bad_request(format!(
+        "Media of '{source}' is larger than {MAX_MEDIA_BYTES} bytes"
+    ))
+}
+
+fn bad_request(message: String) -> anyhow::Error {
+    HttpError { code: 400, message }.into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn code(err: anyhow::Error) -> u16 {
+        err.downcast_ref::<HttpError>().unwrap().code
+    }
+
+    #[test]
+    fn test_is_public() {
+        for ip in ["8.8.8.8", "1.1.1.1", "2606:4700:4700::1111"] {
+            assert!(is_public(ip.parse().unwrap()), "{ip}");
+        }
+        for ip in [
+            "127.0.0.1",
+            "10.1.2.3",
+            "172.16.0.1",
+            "192.168.1.1",
+            "169.254.169.254",
+            "100.64.0.1",
+            "0.0.0.0",
+            "::1",
+            "::",
+            "fd00::1",
+            "fe80::1",
+            "::ffff:127.0.0.1",
+            "::ffff:169.254.169.254",
+        ] {
+            assert!(!is_public(ip.parse().unwrap()), "{ip}");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_remote_images_are_opt_in() {
+        let loader = MediaLoader::with_allowed_hosts(vec![]).unwrap();
+        let err = loader
+            .load_image("https://example.com/cat.png")
+            .await
+            .unwrap_err();
+        assert_eq!(code(err), 400);
+
+        let loader = MediaLoader::with_allowed_hosts(vec!["images.example.com".into()]).unwrap();
+        let err = loader
+            .load_image("https://example.com/cat.png")
+            .await
+            .unwrap_err();
+        assert_eq!(code(err), 400);
+    }
+
+    #[tokio::test]
+    async fn test_private_addresses_are_refused() {
+        let loader = MediaLoader::with_allowed_hosts(vec!["*".into()]).unwrap();
+        for url in [
+            "http://127.0.0.1:8000/cat.png",
+            "http://169.254.169.254/latest/meta-data",
+            "http://[::1]/cat.png",
+            // resolves to the loopback address
+            "http://localhost/cat.png",
+        ] {
+            let err = loader.load_image(url).await.unwrap_err();
+            assert_eq!(code(err), 400, "{url}");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_too_many_media() {
+        let image = serde_json::json!({
+            "type": "image_url",
+            "image_url": { "url": "data:image/png;base64,iVBORw0KGgo=" }
+        });
+        let message = |images: usize| -> ChatCompletionRequestMessage {
+            serde_json::from_value(serde_json::json!({
+                "role": "user",
+                "content": vec![image.clone(); images],
+            }))
+            .unwrap()
+        };
+
+        let loader = MediaLoader::with_allowed_hosts(vec![]).unwrap();
+        let media = loader.load(&[message(MAX_MEDIA_PARTS)]).await.unwrap();
+        assert_eq!(media.len(), MAX_MEDIA_PARTS);
+
+        let err = loader
+            .load(&[message(MAX_MEDIA_PARTS + 1)])
+            .await
+            .unwrap_err();
+        assert_eq!(code(err), 400);
+    }
+}
@@ -68,6 +68,12 @@ pub trait OAIChatLikeRequest {
 
     fn should_add_generation_prompt(&self) -> bool;
 
+    /// Whether the messages attach images or audio, which [`OAIChatLikeRequest::messages`]
+    /// leaves placeholders of
+    fn has_media(&self) -> bool {
+        false
+    }
+
     /// Returns the type of input for the prompt. Default is Text.
     fn prompt_input_type(&self) -> PromptInput {
         PromptInput::Text(TextInput::Single(String::new()))
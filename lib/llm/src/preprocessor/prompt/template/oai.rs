@@ -23,10 +23,31 @@ use crate::protocols::openai::{
 use tracing;
 
 use crate::preprocessor::prompt::{PromptInput, TextInput, TokenInput};
+use async_openai::types::{
+    ChatCompletionRequestMessage, ChatCompletionRequestUserMessageContent,
+    ChatCompletionRequestUserMessageContentPart,
+};
 
 impl OAIChatLikeRequest for NvCreateChatCompletionRequest {
     fn messages(&self) -> Value {
-        Value::from_serialize(&self.inner.messages)
+        if !self.has_media() {
+            return Value::from_serialize(&self.inner.messages);
+        }
+        let mut messages = serde_json::to_value(&self.inner.messages).unwrap_or_default();
+        media_placeholders(&mut messages);
+        Value::from_serialize(&messages)
+    }
+
+    fn has_media(&self) -> bool {
+        self.inner.messages.iter().any(|message| match message {
+            ChatCompletionRequestMessage::User(message) => match &message.content {
+                ChatCompletionRequestUserMessageContent::Array(parts) => parts.iter().any(|part| {
+                    !matches!(part, ChatCompletionRequestUserMessageContentPart::Text(_))
+                }),
+                ChatCompletionRequestUserMessageContent::Text(_) => false,
+            },
+            _ => false,
+        })
     }
 
     fn tools(&self) -> Option<Value> {
@@ -61,6 +82,26 @@ impl OAIChatLikeRequest for NvCreateChatCompletionRequest {
     }
 }
 
+/// Replace the media parts of the messages by the parts Hugging Face chat templates render the
+/// placeholder tokens of, `{"type": "image"}` and `{"type": "audio"}`. The media themselves are
+/// sent apart from the prompt.
+fn media_placeholders(messages: &mut serde_json::Value) {
+    let parts = messages
+        .as_array_mut()
+        .into_iter()
+        .flatten()
+        .filter_map(|message| message.get_mut("content")?.as_array_mut())
+        .flatten();
+    for part in parts {
+        let kind = match part.get("type").and_then(|kind| kind.as_str()) {
+            Some("image_url") => "image",
+            Some("input_audio") => "audio",
+            _ => continue,
+        };
+        *part = serde_json::json!({ "type": kind });
+    }
+}
+
 impl OAIChatLikeRequest for NvCreateCompletionRequest {
     fn messages(&self) -> minijinja::value::Value {
         let message = async_openai::types::ChatCompletionRequestMessage::User(
@@ -13,9 +13,11 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+mod parsers;
 mod request;
 mod response;
 
+pub use parsers::*;
 pub use request::*;
 pub use response::*;
 use serde_json::Value;
@@ -0,0 +1,250 @@
+// SPDX-FileCopyrightText: Copyright (c) 2024-2025 NVIDIA CORPORATION & AFFILIATES. All rights reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+//! Streaming parsers of the tool calls models write in their output.
+//!
+//! Each model family has its own markup for tool calls. A [`ToolCallParser`] takes the output of
+//! a request as it is generated, splits it into the content for the user and OpenAI style
+//! `tool_calls` deltas, and hides the markup. Parsers are looked up by name in a
+//! [`ToolCallParserRegistry`], the name comes from
+//! [`crate::model_card::model::ModelDeploymentCard::tool_call_parser`] or is detected from the
+//! model name with [`detect_tool_call_parser`].
+
+mod deepseek_v3;
+mod hermes;
+mod json_call;
+mod llama3_json;
+mod mistral;
+
+pub use deepseek_v3::DeepSeekV3Parser;
+pub use hermes::HermesParser;
+pub use llama3_json::Llama3JsonParser;
+pub use mistral::MistralParser;
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+/// One delta of a tool call, as in the `tool_calls` of an OpenAI chat completion chunk. The first
+/// delta of a call has its `id` and `name`, the next ones carry fragments of its JSON arguments.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ToolCallDelta {
+    /// Index of the call in the output of the choice
+    pub index: u32,
+    pub id: Option<String>,
+    pub name: Option<String>,
+    pub arguments: String,
+}
+
+/// What a [`ToolCallParser`] made of a piece of output
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ParsedOutput {
+    /// The text which is not part of a tool call
+    pub content: String,
+    pub tool_calls: Vec<ToolCallDelta>,
+}
+
+impl ParsedOutput {
+    pub fn is_empty(&self) -> bool {
+        self.content.is_empty() && self.tool_calls.is_empty()
+    }
+
+    pub fn extend(&mut self, other: ParsedOutput) {
+        self.content.push_str(&other.content);
+        self.tool_calls.extend(other.tool_calls);
+    }
+}
+
+/// Incrementally separates the tool calls of one output from its content
+pub trait ToolCallParser: Send + Sync + std::fmt::Debug {
+    /// Parse the next piece of the output. Text which could be the start of markup is held back
+    /// until the next pieces tell.
+    fn parse(&mut self, text: &str) -> ParsedOutput;
+
+    /// The output is complete: return what was held back. Markup which did not become a tool call
+    /// is returned as content, a call cut short in its arguments is still a call.
+    fn finish(&mut self) -> ParsedOutput;
+
+    /// Whether the output had any tool call
+    fn has_tool_calls(&self) -> bool;
+
+    fn clone_box(&self) -> Box<dyn ToolCallParser>;
+}
+
+impl Clone for Box<dyn ToolCallParser> {
+    fn clone(&self) -> Self {
+        self.clone_box()
+    }
+}
+
+/// Creates the parser of one output
+pub type ToolCallParserFactory = Arc<dyn Fn() -> Box<dyn ToolCallParser> + Send + Sync>;
+
+/// Tool call parsers by name. The default registry has the built-in parsers: `hermes` (also
+/// Qwen), `llama3_json`, `mistral` and `deepseek_v3`.
+#[derive(Clone)]
+pub struct ToolCallParserRegistry {
+    factories: HashMap<String, ToolCallParserFactory>,
+}
+
+impl Default for ToolCallParserRegistry {
+    fn default() -> Self {
+        let mut registry = Self::empty();
+        registry.register("hermes", || Box::new(HermesParser::default()));
+        registry.register("llama3_json", || Box::new(Llama3JsonParser::default()));
+        registry.register("mistral", || Box::new(MistralParser::default()));
+        registry.register("deepseek_v3", || Box::new(DeepSeekV3Parser::default()));
+        registry
+    }
+}
+
+impl ToolCallParserRegistry {
+    /// A registry without the built-in parsers
+    pub fn empty() -> Self {
+        Self {
+            factories: HashMap::new(),
+        }
+    }
+
+    /// Add a parser, replacing any parser of the same name
+    pub fn register(
+        &mut self,
+        name: impl Into<String>,
+        factory: impl Fn() -> Box<dyn ToolCallParser> + Send + Sync + 'static,
+    ) {
+        self.factories.insert(name.into(), Arc::new(factory));
+    }
+
+    pub fn get(&self, name: &str) -> Option<ToolCallParserFactory> {
+        self.factories.get(name).cloned()
+    }
+
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.factories.keys().map(String::as_str)
+    }
+}
+
+/// The built-in parser for the tool calls of a model, guessed from its name
+pub fn detect_tool_call_parser(model_name: &str) -> Option<&'static str> {
+    let model_name = model_name.to_lowercase();
+    if model_name.contains("deepseek") {
+        Some("deepseek_v3")
+    } else if model_name.contains("mistral") || model_name.contains("mixtral") {
+        Some("mistral")
+    } else if model_name.contains("qwen") || model_name.contains("hermes") {
+        Some("hermes")
+    } else if model_name.contains("llama-3") || model_name.contains("llama3") {
+        Some("llama3_json")
+    } else {
+        None
+    }
+}
+
+fn new_call_id() -> String {
+    format!("call-{}", uuid::Uuid::new_v4())
+}
+
+/// Where the first of some markers is in a piece of text
+enum Marker<'a> {
+    /// `marker` is the index of the marker found
+    Found {
+        before: &'a str,
+        marker: usize,
+        after: &'a str,
+    },
+    /// `held` is the end of the text which could be the start of a marker
+    NotFound { safe: &'a str, held: &'a str },
+}
+
+fn find_marker<'a>(text: &'a str, markers: &[&str]) -> Marker<'a> {
+    let found = markers
+        .iter()
+        .enumerate()
+        .filter_map(|(marker, m)| text.find(m).map(|start| (start, marker)))
+        .min();
+    if let Some((start, marker)) = found {
+        return Marker::Found {
+            before: &text[..start],
+            marker,
+            after: &text[start + markers[marker].len()..],
+        };
+    }
+
+    let held = text
+        .char_indices()
+        .map(|(start, _)| start)
+        .find(|&start| {
+            markers
+                .iter()
+                .any(|marker| marker.starts_with(&text[start..]))
+        })
+        .unwrap_or(text.len());
+    Marker::NotFound {
+        safe: &text[..held],
+        held: &text[held..],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Feed `output` to `parser` a few characters at a time
+    pub(super) fn parse_in_pieces(
+        parser: &mut dyn ToolCallParser,
+        output: &str,
+    ) -> (String, Vec<(String, String)>) {
+        let chars = output.chars().collect::<Vec<_>>();
+        let mut parsed = ParsedOutput::default();
+        for piece in chars.chunks(3) {
+            parsed.extend(parser.parse(&piece.iter().collect::<String>()));
+        }
+        parsed.extend(parser.finish());
+
+        let mut calls: Vec<(String, String)> = Vec::new();
+        for delta in parsed.tool_calls {
+            let index = delta.index as usize;
+            if let Some(name) = delta.name {
+                assert_eq!(index, calls.len());
+                assert!(delta.id.is_some());
+                calls.push((name, String::new()));
+            }
+            calls[index].1.push_str(&delta.arguments);
+        }
+        (parsed.content, calls)
+    }
+
+    #[test]
+    fn test_find_marker() {
+        assert!(matches!(
+            find_marker("hello <tool", &["<tool_call>"]),
+            Marker::NotFound {
+                safe: "hello ",
+                held: "<tool"
+            }
+        ));
+        assert!(matches!(
+            find_marker("a</b>c<x>", &["<x>", "</b>"]),
+            Marker::Found {
+                before: "a",
+                marker: 1,
+                after: "c<x>"
+            }
+        ));
+    }
+
+    #[test]
+    fn test_detect_tool_call_parser() {
+        assert_eq!(
+            detect_tool_call_parser("Qwen/Qwen2.5-7B-Instruct"),
+            Some("hermes")
+        );
+        assert_eq!(
+            detect_tool_call_parser("meta-llama/Llama-3.1-8B-Instruct"),
+            Some("llama3_json")
+        );
+        assert_eq!(detect_tool_call_parser("gpt2"), None);
+        for name in ["hermes", "llama3_json", "mistral", "deepseek_v3"] {
+            assert!(ToolCallParserRegistry::default().get(name).is_some());
+        }
+    }
+}
@@ -0,0 +1,289 @@
+// SPDX-FileCopyrightText: Copyright (c) 2024-2025 NVIDIA CORPORATION & AFFILIATES. All rights reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+use super::{find_marker, new_call_id, Marker, ParsedOutput, ToolCallDelta, ToolCallParser};
+
+const CALLS_BEGIN: &str = "<｜tool▁calls▁begin｜>";
+const CALLS_END: &str = "<｜tool▁calls▁end｜>";
+const CALL_BEGIN: &str = "<｜tool▁call▁begin｜>";
+const CALL_END: &str = "<｜tool▁call▁end｜>";
+const TOOL_SEP: &str = "<｜tool▁sep｜>";
+const JSON_FENCE: &str = "```json";
+const FENCE_END: [&str; 2] = ["\n```", "```"];
+
+/// DeepSeek V3 tool calls, with the name after the type and the arguments in a JSON code block:
+///
+/// ```text
+/// <｜tool▁calls▁begin｜><｜tool▁call▁begin｜>function<｜tool▁sep｜>get_weather
+/// ```json
+/// {"city": "Paris"}
+/// ```<｜tool▁call▁end｜><｜tool▁calls▁end｜>
+/// ```
+///
+/// V3.1 writes the name before the separator and the arguments without the code block.
+#[derive(Clone, Debug, Default)]
+pub struct DeepSeekV3Parser {
+    state: State,
+    /// Text which can not be parsed yet
+    pending: String,
+    num_calls: u32,
+    /// Whether any arguments of the current call were sent
+    arguments_sent: bool,
+}
+
+#[derive(Clone, Debug, Default)]
+enum State {
+    #[default]
+    Content,
+    /// Between the calls
+    Calls,
+    /// Before the separator of a call
+    Head,
+    /// The line of the name, after the separator
+    Name,
+    /// `fence_checked` is whether the start of the code block was looked for
+    Arguments { fence_checked: bool },
+    /// After the code block of the arguments, before the end of the call
+    ArgumentsEnd,
+}
+
+impl DeepSeekV3Parser {
+    fn start_call(&mut self, name: &str, parsed: &mut ParsedOutput) {
+        parsed.tool_calls.push(ToolCallDelta {
+            index: self.num_calls,
+            id: Some(new_call_id()),
+            name: Some(name.trim().to_string()),
+            arguments: String::new(),
+        });
+        self.arguments_sent = false;
+        self.state = State::Arguments {
+            fence_checked: false,
+        };
+    }
+
+    fn push_arguments(&mut self, arguments: &str, parsed: &mut ParsedOutput) {
+        if !arguments.is_empty() {
+            parsed
+                .tool_calls
+                .push(self.arguments(arguments.to_string()));
+            self.arguments_sent = true;
+        }
+    }
+
+    fn end_call(&mut self, parsed: &mut ParsedOutput) {
+        if !self.arguments_sent {
+            parsed.tool_calls.push(self.arguments("{}".to_string()));
+        }
+        self.num_calls += 1;
+    }
+
+    fn arguments(&self, arguments: String) -> ToolCallDelta {
+        ToolCallDelta {
+            index: self.num_calls,
+            id: None,
+            name: None,
+            arguments,
+        }
+    }
+}
+
+impl ToolCallParser for DeepSeekV3Parser {
+    fn parse(&mut self, text: &str) -> ParsedOutput {
+        self.pending.push_str(text);
+        let mut parsed = ParsedOutput::default();
+        loop {
+            let pending = std::mem::take(&mut self.pending);
+            match self.state {
+                State::Content => match find_marker(&pending, &[CALLS_BEGIN]) {
+                    Marker::Found { before, after, .. } => {
+                        parsed.content.push_str(before);
+                        self.pending = after.to_string();
+                        self.state = State::Calls;
+                    }
+                    Marker::NotFound { safe, held } => {
+                        parsed.content.push_str(safe);
+                        self.pending = held.to_string();
+                        return parsed;
+                    }
+                },
+                State::Calls => match find_marker(&pending, &[CALL_BEGIN, CALLS_END]) {
+                    Marker::Found { marker, after, .. } => {
+                        self.pending = after.to_string();
+                        self.state = if marker == 0 {
+                            State::Head
+                        } else {
+                            State::Content
+                        };
+                    }
+                    Marker::NotFound { held, .. } => {
+                        self.pending = held.to_string();
+                        return parsed;
+                    }
+                },
+                State::Head => match find_marker(&pending, &[TOOL_SEP]) {
+                    Marker::Found { before, after, .. } => {
+                        self.pending = after.to_string();
+                        if before.trim() == "function" {
+                            self.state = State::Name;
+                        } else {
+                            self.start_call(before, &mut parsed);
+                        }
+                    }
+                    Marker::NotFound { .. } => {
+                        self.pending = pending;
+                        return parsed;
+                    }
+                },
+                State::Name => match pending.split_once('\n') {
+                    Some((name, after)) => {
+                        self.pending = after.to_string();
+                        self.start_call(name, &mut parsed);
+                    }
+                    None => {
+                        self.pending = pending;
+                        return parsed;
+                    }
+                },
+                State::Arguments {
+                    fence_checked: false,
+                } => {
+                    let trimmed = pending.trim_start();
+                    if let Some(after) = trimmed.strip_prefix(JSON_FENCE) {
+                        self.pending = after.to_string();
+                    } else if trimmed.is_empty() || JSON_FENCE.starts_with(trimmed) {
+                        self.pending = pending;
+                        return parsed;
+                    } else {
+                        self.pending = trimmed.to_string();
+                    }
+                    self.state = State::Arguments {
+                        fence_checked: true,
+                    };
+                }
+                State::Arguments {
+                    fence_checked: true,
+                } => {
+                    let pending = if self.arguments_sent {
+                        pending
+                    } else {
+                        pending.trim_start().to_string()
+                    };
+                    if pending.is_empty() {
+                        return parsed;
+                    }
+                    let markers = [FENCE_END[0], FENCE_END[1], CALL_END];
+                    match find_marker(&pending, &markers) {
+                        Marker::Found {
+                            before,
+                            marker,
+                            after,
+                        } => {
+                            self.push_arguments(before.trim_end(), &mut parsed);
+                            self.end_call(&mut parsed);
+                            self.pending = after.to_string();
+                            self.state = if markers[marker] == CALL_END {
+                                State::Calls
+                            } else {
+                                State::ArgumentsEnd
+                            };
+                        }
+                        Marker::NotFound { safe, .. } => {
+                            // trailing whitespace could be before the end of the code block
+                            let arguments = safe.trim_end();
+                            self.push_arguments(arguments, &mut parsed);
+                            self.pending = pending[arguments.len()..].to_string();
+                            return parsed;
+                        }
+                    }
+                }
+                State::ArgumentsEnd => match find_marker(&pending, &[CALL_END]) {
+                    Marker::Found { after, .. } => {
+                        self.pending = after.to_string();
+                        self.state = State::Calls;
+                    }
+                    Marker::NotFound { held, .. } => {
+                        self.pending = held.to_string();
+                        return parsed;
+                    }
+                },
+            }
+        }
+    }
+
+    fn finish(&mut self) -> ParsedOutput {
+        let mut parsed = ParsedOutput::default();
+        match std::mem::take(&mut self.state) {
+            State::Content => parsed.content.push_str(&self.pending),
+            // the output was cut short in the arguments of a call
+            State::Arguments { .. } => {
+                let arguments = std::mem::take(&mut self.pending);
+                self.push_arguments(arguments.trim(), &mut parsed);
+                self.end_call(&mut parsed);
+            }
+            // the markup did not become a call
+            State::Calls | State::Head | State::Name if self.num_calls == 0 => {
+                parsed.content.push_str(CALLS_BEGIN);
+            }
+            State::Calls | State::Head | State::Name | State::ArgumentsEnd => {}
+        }
+        self.pending.clear();
+        parsed
+    }
+
+    fn has_tool_calls(&self) -> bool {
+        self.num_calls > 0
+    }
+
+    fn clone_box(&self) -> Box<dyn ToolCallParser> {
+        Box::new(self.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::tests::parse_in_pieces;
+    use super::*;
+
+    #[test]
+    fn test_v3_calls() {
+        let output = "Checking.<｜tool▁calls▁begin｜><｜tool▁call▁begin｜>function<｜tool▁sep｜>get_weather\n```json\n{\"city\": \"Paris\"}\n```<｜tool▁call▁end｜>\n<｜tool▁call▁begin｜>function<｜tool▁sep｜>get_time\n```json\n{}\n```<｜tool▁call▁end｜><｜tool▁calls▁end｜>";
+        let mut parser = DeepSeekV3Parser::default();
+        let (content, calls) = parse_in_pieces(&mut parser, output);
+        assert_eq!(content, "Checking.");
+        assert_eq!(
+            calls,
+            vec![
+                (
+                    "get_weather".to_string(),
+                    r#"{"city": "Paris"}"#.to_string()
+                ),
+                ("get_time".to_string(), "{}".to_string()),
+            ]
+        );
+        assert!(parser.has_tool_calls());
+    }
+
+    #[test]
+    fn test_v3_1_calls() {
+        let output = "<｜tool▁calls▁begin｜><｜tool▁call▁begin｜>get_weather<｜tool▁sep｜>{\"city\": \"Paris\"}<｜tool▁call▁end｜><｜tool▁calls▁end｜>";
+        let mut parser = DeepSeekV3Parser::default();
+        let (content, calls) = parse_in_pieces(&mut parser, output);
+        assert_eq!(content, "");
+        assert_eq!(
+            calls,
+            vec![(
+                "get_weather".to_string(),
+                r#"{"city": "Paris"}"#.to_string()
+            )]
+        );
+    }
+
+    #[test]
+    fn test_no_calls() {
+        let mut parser = DeepSeekV3Parser::default();
+        let (content, calls) = parse_in_pieces(&mut parser, "no <｜tool calls");
+        assert_eq!(content, "no <｜tool calls");
+        assert!(calls.is_empty());
+        assert!(!parser.has_tool_calls());
+    }
+}
@@ -0,0 +1,172 @@
+// SPDX-FileCopyrightText: Copyright (c) 2024-2025 NVIDIA CORPORATION & AFFILIATES. All rights reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+use super::json_call::{JsonCall, JsonCallEnd};
+use super::{find_marker, Marker, ParsedOutput, ToolCallParser};
+
+const CALL_START: &str = "<tool_call>";
+const CALL_END: &str = "</tool_call>";
+
+/// Hermes and Qwen tool calls: JSON objects between tags, anywhere in the output.
+///
+/// ```text
+/// <tool_call>
+/// {"name": "get_weather", "arguments": {"city": "Paris"}}
+/// </tool_call>
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct HermesParser {
+    state: State,
+    /// Text which can not be parsed yet
+    pending: String,
+    num_calls: u32,
+}
+
+#[derive(Clone, Debug, Default)]
+enum State {
+    #[default]
+    Content,
+    Call(JsonCall),
+    /// After the JSON object of a call, before its end tag
+    CallEnd,
+}
+
+impl ToolCallParser for HermesParser {
+    fn parse(&mut self, text: &str) -> ParsedOutput {
+        self.pending.push_str(text);
+        let mut parsed = ParsedOutput::default();
+        loop {
+            let pending = std::mem::take(&mut self.pending);
+            match &mut self.state {
+                State::Content => {
+                    // the whitespace between calls is not content
+                    let pending = if self.num_calls > 0 {
+                        let trimmed = pending.trim_start();
+                        if trimmed.is_empty() {
+                            self.pending = pending;
+                            return parsed;
+                        }
+                        trimmed
+                    } else {
+                        &pending
+                    };
+                    match find_marker(pending, &[CALL_START]) {
+                        Marker::Found { before, after, .. } => {
+                            parsed.content.push_str(before);
+                            self.state = State::Call(JsonCall::new(self.num_calls));
+                            self.pending = after.to_string();
+                        }
+                        Marker::NotFound { safe, held } => {
+                            parsed.content.push_str(safe);
+                            self.pending = held.to_string();
+                            return parsed;
+                        }
+                    }
+                }
+                State::Call(call) => match call.push(&pending, &mut parsed.tool_calls) {
+                    None => return parsed,
+                    Some(JsonCallEnd::Complete(after)) => {
+                        self.num_calls += 1;
+                        self.state = State::CallEnd;
+                        self.pending = after;
+                    }
+                    Some(JsonCallEnd::Invalid(text)) => {
+                        parsed.content.push_str(CALL_START);
+                        parsed.content.push_str(&text);
+                        self.state = State::Content;
+                    }
+                },
+                State::CallEnd => {
+                    let trimmed = pending.trim_start();
+                    if let Some(after) = trimmed.strip_prefix(CALL_END) {
+                        self.pending = after.to_string();
+                        self.state = State::Content;
+                    } else if CALL_END.starts_with(trimmed) {
+                        self.pending = pending;
+                        return parsed;
+                    } else {
+                        // no end tag, carry on with the content
+                        self.pending = trimmed.to_string();
+                        self.state = State::Content;
+                    }
+                }
+            }
+        }
+    }
+
+    fn finish(&mut self) -> ParsedOutput {
+        let mut parsed = ParsedOutput::default();
+        match std::mem::take(&mut self.state) {
+            State::Content if self.num_calls > 0 && self.pending.trim().is_empty() => {}
+            State::Content => parsed.content.push_str(&self.pending),
+            // the output was cut short in the arguments of a call
+            State::Call(call) if call.is_started() => self.num_calls += 1,
+            State::Call(call) => {
+                parsed.content.push_str(CALL_START);
+                parsed.content.push_str(call.text());
+            }
+            State::CallEnd => {}
+        }
+        self.pending.clear();
+        parsed
+    }
+
+    fn has_tool_calls(&self) -> bool {
+        self.num_calls > 0
+    }
+
+    fn clone_box(&self) -> Box<dyn ToolCallParser> {
+        Box::new(self.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::tests::parse_in_pieces;
+    use super::*;
+
+    #[test]
+    fn test_content_and_calls() {
+        let output = "Let me check.<tool_call>\n{\"name\": \"get_weather\", \"arguments\": {\"city\": \"Paris\"}}\n</tool_call>\n<tool_call>\n{\"name\": \"get_time\", \"arguments\": {}}\n</tool_call>";
+        let mut parser = HermesParser::default();
+        let (content, calls) = parse_in_pieces(&mut parser, output);
+        assert_eq!(content, "Let me check.");
+        assert_eq!(
+            calls,
+            vec![
+                (
+                    "get_weather".to_string(),
+                    r#"{"city": "Paris"}"#.to_string()
+                ),
+                ("get_time".to_string(), "{}".to_string()),
+            ]
+        );
+        assert!(parser.has_tool_calls());
+    }
+
+    #[test]
+    fn test_no_calls() {
+        let mut parser = HermesParser::default();
+        let (content, calls) = parse_in_pieces(&mut parser, "a < b and <tool> is not a call");
+        assert_eq!(content, "a < b and <tool> is not a call");
+        assert!(calls.is_empty());
+        assert!(!parser.has_tool_calls());
+    }
+
+    #[test]
+    fn test_unfinished_call() {
+        let mut parser = HermesParser::default();
+        let (content, calls) = parse_in_pieces(&mut parser, "<tool_call>{\"nam");
+        assert_eq!(content, "<tool_call>{\"nam");
+        assert!(calls.is_empty());
+
+        let mut parser = HermesParser::default();
+        let (content, calls) = parse_in_pieces(
+            &mut parser,
+            "<tool_call>{\"name\": \"f\", \"arguments\": {\"a",
+        );
+        assert_eq!(content, "");
+        assert_eq!(calls, vec![("f".to_string(), "{\"a".to_string())]);
+        assert!(parser.has_tool_calls());
+    }
+}
@@ -0,0 +1,262 @@
+// SPDX-FileCopyrightText: Copyright (c) 2024-2025 NVIDIA CORPORATION & AFFILIATES. All rights reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+use std::ops::Range;
+
+use super::{new_call_id, ToolCallDelta};
+
+/// Streams one tool call written as a JSON object, `{"name": ..., "arguments": {...}}`, as it
+/// is generated. Llama names the arguments `parameters`.
+///
+/// The name is sent as soon as it is complete and the arguments as they come, if the name comes
+/// first. Otherwise the arguments are sent with the name.
+#[derive(Clone, Debug)]
+pub(super) struct JsonCall {
+    index: u32,
+    buffer: String,
+    name_sent: bool,
+    /// Bytes of the arguments sent
+    arguments_sent: usize,
+}
+
+/// The end of a [`JsonCall`]
+pub(super) enum JsonCallEnd {
+    /// The object is complete, this is the text after it
+    Complete(String),
+    /// The text is not a tool call, this is all of it
+    Invalid(String),
+}
+
+impl JsonCall {
+    pub fn new(index: u32) -> Self {
+        Self {
+            index,
+            buffer: String::new(),
+            name_sent: false,
+            arguments_sent: 0,
+        }
+    }
+
+    /// The text of the call so far
+    pub fn text(&self) -> &str {
+        &self.buffer
+    }
+
+    /// Whether the call was sent, an output cut short after this is still a call
+    pub fn is_started(&self) -> bool {
+        self.name_sent
+    }
+
+    pub fn push(&mut self, text: &str, deltas: &mut Vec<ToolCallDelta>) -> Option<JsonCallEnd> {
+        self.buffer.push_str(text);
+        let Some(scan) = scan_object(&self.buffer) else {
+            return Some(JsonCallEnd::Invalid(std::mem::take(&mut self.buffer)));
+        };
+        if scan.end.is_some() && scan.name.is_none() {
+            return Some(JsonCallEnd::Invalid(std::mem::take(&mut self.buffer)));
+        }
+
+        if !self.name_sent {
+            if let Some(name) = scan.name {
+                deltas.push(ToolCallDelta {
+                    index: self.index,
+                    id: Some(new_call_id()),
+                    name: Some(name),
+                    arguments: String::new(),
+                });
+                self.name_sent = true;
+            }
+        }
+        if self.name_sent {
+            if let Some(arguments) = &scan.arguments {
+                let from = arguments.start + self.arguments_sent;
+                if arguments.end > from {
+                    deltas.push(self.arguments(self.buffer[from..arguments.end].to_string()));
+                    self.arguments_sent = arguments.end - arguments.start;
+                }
+            }
+        }
+
+        let end = scan.end?;
+        if scan.arguments.is_none() {
+            deltas.push(self.arguments("{}".to_string()));
+        }
+        Some(JsonCallEnd::Complete(self.buffer[end..].to_string()))
+    }
+
+    fn arguments(&self, arguments: String) -> ToolCallDelta {
+        ToolCallDelta {
+            index: self.index,
+            id: None,
+            name: None,
+            arguments,
+        }
+    }
+}
+
+#[derive(Default)]
+struct ObjectScan {
+    name: Option<String>,
+    /// The arguments so far, up to the end of the text if they are not complete
+    arguments: Option<Range<usize>>,
+    /// The end of the object, if it is complete
+    end: Option<usize>,
+}
+
+/// Scan the start of a JSON object, `None` if the text can not be one
+fn scan_object(text: &str) -> Option<ObjectScan> {
+    let bytes = text.as_bytes();
+    let mut scan = ObjectScan::default();
+    let mut pos = skip_whitespace(bytes, 0);
+    match bytes.get(pos) {
+        None => return Some(scan),
+        Some(b'{') => pos += 1,
+        Some(_) => return None,
+    }
+
+    loop {
+        pos = skip_whitespace(bytes, pos);
+        match bytes.get(pos) {
+            None => return Some(scan),
+            Some(b'}') => {
+                scan.end = Some(pos + 1);
+                return Some(scan);
+            }
+            Some(b',') => {
+                pos += 1;
+                continue;
+            }
+            Some(b'"') => {}
+            Some(_) => return None,
+        }
+
+        let Some(key_end) = value_end(bytes, pos) else {
+            return Some(scan);
+        };
+        let key: String = serde_json::from_str(&text[pos..key_end]).ok()?;
+        pos = skip_whitespace(bytes, key_end);
+        match bytes.get(pos) {
+            None => return Some(scan),
+            Some(b':') => pos = skip_whitespace(bytes, pos + 1),
+            Some(_) => return None,
+        }
+        if pos == bytes.len() {
+            return Some(scan);
+        }
+
+        let end = value_end(bytes, pos);
+        match key.as_str() {
+            "name" => {
+                if let Some(end) = end {
+                    scan.name = Some(serde_json::from_str(&text[pos..end]).ok()?);
+                }
+            }
+            "arguments" | "parameters" => {
+                scan.arguments = Some(pos..end.unwrap_or(bytes.len()));
+            }
+            _ => {}
+        }
+        match end {
+            Some(end) => pos = end,
+            None => return Some(scan),
+        }
+    }
+}
+
+fn skip_whitespace(bytes: &[u8], mut pos: usize) -> usize {
+    while bytes.get(pos).is_some_and(u8::is_ascii_whitespace) {
+        pos += 1;
+    }
+    pos
+}
+
+/// The end of the JSON value which starts at `start`, `None` if the text ends first
+fn value_end(bytes: &[u8], start: usize) -> Option<usize> {
+    let mut depth = 0usize;
+    let mut in_string = false;
+    let mut escaped = false;
+    for (pos, &byte) in bytes.iter().enumerate().skip(start) {
+        if in_string {
+            if escaped {
+                escaped = false;
+            } else if byte == b'\\' {
+                escaped = true;
+            } else if byte == b'"' {
+                in_string = false;
+                if depth == 0 {
+                    return Some(pos + 1);
+                }
+            }
+            continue;
+        }
+        match byte {
+            b'"' => in_string = true,
+            b'{' | b'[' => depth += 1,
+            b'}' | b']' if depth == 0 => return Some(pos),
+            b'}' | b']' => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(pos + 1);
+                }
+            }
+            b',' if depth == 0 => return Some(pos),
+            byte if depth == 0 && byte.is_ascii_whitespace() => return Some(pos),
+            _ => {}
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_json_call_in_pieces() {
+        let text =
+            r#"{"name": "get_weather", "arguments": {"city": "Paris", "days": [1, 2]}} rest"#;
+        let mut call = JsonCall::new(0);
+        let mut deltas = Vec::new();
+        let mut rest = None;
+        for piece in text.as_bytes().chunks(4) {
+            let piece = std::str::from_utf8(piece).unwrap();
+            match &mut rest {
+                Some(rest) => rest.push_str(piece),
+                None => {
+                    if let Some(JsonCallEnd::Complete(after)) = call.push(piece, &mut deltas) {
+                        rest = Some(after);
+                    }
+                }
+            }
+        }
+        assert_eq!(rest.as_deref(), Some(" rest"));
+        assert_eq!(deltas[0].name.as_deref(), Some("get_weather"));
+        let arguments = deltas
+            .iter()
+            .map(|d| d.arguments.as_str())
+            .collect::<String>();
+        assert_eq!(arguments, r#"{"city": "Paris", "days": [1, 2]}"#);
+        assert!(deltas.len() > 2);
+    }
+
+    #[test]
+    fn test_arguments_before_name() {
+        let mut deltas = Vec::new();
+        let end = JsonCall::new(1).push(r#"{"parameters": {"a": 1}, "name": "f"}"#, &mut deltas);
+        assert!(matches!(end, Some(JsonCallEnd::Complete(_))));
+        assert_eq!(deltas[0].name.as_deref(), Some("f"));
+        assert_eq!(deltas[1].arguments, r#"{"a": 1}"#);
+        assert!(deltas.iter().all(|d| d.index == 1));
+    }
+
+    #[test]
+    fn test_not_a_call() {
+        let mut deltas = Vec::new();
+        let end = JsonCall::new(0).push(r#"{"answer": 42}"#, &mut deltas);
+        assert!(matches!(end, Some(JsonCallEnd::Invalid(text)) if text == r#"{"answer": 42}"#));
+        assert!(deltas.is_empty());
+
+        let end = JsonCall::new(0).push("[1, 2]", &mut deltas);
+        assert!(matches!(end, Some(JsonCallEnd::Invalid(_))));
+    }
+}
@@ -0,0 +1,153 @@
+// SPDX-FileCopyrightText: Copyright (c) 2024-2025 NVIDIA CORPORATION & AFFILIATES. All rights reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+use super::json_call::{JsonCall, JsonCallEnd};
+use super::{ParsedOutput, ToolCallParser};
+
+const PYTHON_TAG: &str = "<|python_tag|>";
+
+/// Llama 3.x JSON tool calls: the whole output is the calls, separated by `;`, optionally after
+/// the python tag. An output which does not start with a JSON object is content.
+///
+/// ```text
+/// <|python_tag|>{"name": "get_weather", "parameters": {"city": "Paris"}}
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct Llama3JsonParser {
+    state: State,
+    /// Text which can not be parsed yet
+    pending: String,
+    num_calls: u32,
+}
+
+#[derive(Clone, Debug, Default)]
+enum State {
+    /// Before the first non whitespace text of the output
+    #[default]
+    Start,
+    Content,
+    Call(JsonCall),
+    /// After a call, before the next one
+    CallNext,
+}
+
+impl ToolCallParser for Llama3JsonParser {
+    fn parse(&mut self, text: &str) -> ParsedOutput {
+        self.pending.push_str(text);
+        let mut parsed = ParsedOutput::default();
+        loop {
+            let pending = std::mem::take(&mut self.pending);
+            match &mut self.state {
+                State::Start => {
+                    let trimmed = pending.trim_start();
+                    if let Some(after) = trimmed.strip_prefix(PYTHON_TAG) {
+                        self.pending = after.to_string();
+                        continue;
+                    }
+                    if trimmed.is_empty() || PYTHON_TAG.starts_with(trimmed) {
+                        self.pending = pending;
+                        return parsed;
+                    }
+                    if trimmed.starts_with('{') {
+                        self.pending = trimmed.to_string();
+                        self.state = State::Call(JsonCall::new(self.num_calls));
+                    } else {
+                        self.pending = pending;
+                        self.state = State::Content;
+                    }
+                }
+                State::Content => {
+                    parsed.content.push_str(&pending);
+                    return parsed;
+                }
+                State::Call(call) => match call.push(&pending, &mut parsed.tool_calls) {
+                    None => return parsed,
+                    Some(JsonCallEnd::Complete(after)) => {
+                        self.num_calls += 1;
+                        self.state = State::CallNext;
+                        self.pending = after;
+                    }
+                    Some(JsonCallEnd::Invalid(text)) => {
+                        parsed.content.push_str(&text);
+                        self.state = State::Content;
+                    }
+                },
+                State::CallNext => {
+                    let trimmed = pending.trim_start();
+                    let next = trimmed.strip_prefix(';').unwrap_or(trimmed).trim_start();
+                    if next.is_empty() {
+                        self.pending = pending;
+                        return parsed;
+                    }
+                    if next.starts_with('{') {
+                        self.pending = next.to_string();
+                        self.state = State::Call(JsonCall::new(self.num_calls));
+                    } else {
+                        self.pending = trimmed.to_string();
+                        self.state = State::Content;
+                    }
+                }
+            }
+        }
+    }
+
+    fn finish(&mut self) -> ParsedOutput {
+        let mut parsed = ParsedOutput::default();
+        match std::mem::take(&mut self.state) {
+            State::Start | State::Content => parsed.content.push_str(&self.pending),
+            // the output was cut short in the arguments of a call
+            State::Call(call) if call.is_started() => self.num_calls += 1,
+            State::Call(call) => parsed.content.push_str(call.text()),
+            State::CallNext => {}
+        }
+        self.pending.clear();
+        parsed
+    }
+
+    fn has_tool_calls(&self) -> bool {
+        self.num_calls > 0
+    }
+
+    fn clone_box(&self) -> Box<dyn ToolCallParser> {
+        Box::new(self.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::tests::parse_in_pieces;
+    use super::*;
+
+    #[test]
+    fn test_calls() {
+        let output = r#"<|python_tag|>{"name": "get_weather", "parameters": {"city": "Paris"}}; {"name": "get_time", "parameters": {}}"#;
+        let mut parser = Llama3JsonParser::default();
+        let (content, calls) = parse_in_pieces(&mut parser, output);
+        assert_eq!(content, "");
+        assert_eq!(
+            calls,
+            vec![
+                (
+                    "get_weather".to_string(),
+                    r#"{"city": "Paris"}"#.to_string()
+                ),
+                ("get_time".to_string(), "{}".to_string()),
+            ]
+        );
+        assert!(parser.has_tool_calls());
+    }
+
+    #[test]
+    fn test_content() {
+        let mut parser = Llama3JsonParser::default();
+        let (content, calls) = parse_in_pieces(&mut parser, "The answer is {\"name\": 1}");
+        assert_eq!(content, "The answer is {\"name\": 1}");
+        assert!(calls.is_empty());
+
+        let mut parser = Llama3JsonParser::default();
+        let (content, calls) = parse_in_pieces(&mut parser, "{\"answer\": 42}");
+        assert_eq!(content, "{\"answer\": 42}");
+        assert!(calls.is_empty());
+        assert!(!parser.has_tool_calls());
+    }
+}
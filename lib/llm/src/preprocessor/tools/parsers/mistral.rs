@@ -0,0 +1,213 @@
+// SPDX-FileCopyrightText: Copyright (c) 2024-2025 NVIDIA CORPORATION & AFFILIATES. All rights reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+use super::json_call::{JsonCall, JsonCallEnd};
+use super::{find_marker, Marker, ParsedOutput, ToolCallParser};
+
+const CALLS_START: &str = "[TOOL_CALLS]";
+
+/// Mistral tool calls: a JSON list of calls after a marker, usually at the end of the output.
+///
+/// ```text
+/// [TOOL_CALLS] [{"name": "get_weather", "arguments": {"city": "Paris"}}]
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct MistralParser {
+    state: State,
+    /// Text which can not be parsed yet
+    pending: String,
+    num_calls: u32,
+}
+
+#[derive(Clone, Debug, Default)]
+enum State {
+    #[default]
+    Content,
+    /// After the marker, before the list
+    ListStart,
+    /// `in_list` is false for a single call without a list
+    Call { call: JsonCall, in_list: bool },
+    /// After a call in the list, before the next one or the end of the list
+    ListNext,
+}
+
+impl ToolCallParser for MistralParser {
+    fn parse(&mut self, text: &str) -> ParsedOutput {
+        self.pending.push_str(text);
+        let mut parsed = ParsedOutput::default();
+        loop {
+            let pending = std::mem::take(&mut self.pending);
+            match &mut self.state {
+                State::Content => {
+                    // the whitespace after calls is not content
+                    let pending = if self.num_calls > 0 {
+                        let trimmed = pending.trim_start();
+                        if trimmed.is_empty() {
+                            self.pending = pending;
+                            return parsed;
+                        }
+                        trimmed
+                    } else {
+                        &pending
+                    };
+                    match find_marker(pending, &[CALLS_START]) {
+                        Marker::Found { before, after, .. } => {
+                            parsed.content.push_str(before);
+                            self.state = State::ListStart;
+                            self.pending = after.to_string();
+                        }
+                        Marker::NotFound { safe, held } => {
+                            parsed.content.push_str(safe);
+                            self.pending = held.to_string();
+                            return parsed;
+                        }
+                    }
+                }
+                State::ListStart => {
+                    let trimmed = pending.trim_start();
+                    let in_list = match trimmed.chars().next() {
+                        None => {
+                            self.pending = pending;
+                            return parsed;
+                        }
+                        Some('[') => true,
+                        Some('{') => false,
+                        Some(_) => {
+                            // not a list of calls after all
+                            parsed.content.push_str(CALLS_START);
+                            self.pending = pending;
+                            self.state = State::Content;
+                            continue;
+                        }
+                    };
+                    let start = if in_list { 1 } else { 0 };
+                    self.pending = trimmed[start..].to_string();
+                    self.state = State::Call {
+                        call: JsonCall::new(self.num_calls),
+                        in_list,
+                    };
+                }
+                State::Call { call, in_list } => {
+                    let in_list = *in_list;
+                    match call.push(&pending, &mut parsed.tool_calls) {
+                        None => return parsed,
+                        Some(JsonCallEnd::Complete(after)) => {
+                            self.num_calls += 1;
+                            self.state = if in_list {
+                                State::ListNext
+                            } else {
+                                State::Content
+                            };
+                            self.pending = after;
+                        }
+                        Some(JsonCallEnd::Invalid(text)) => {
+                            if self.num_calls == 0 {
+                                parsed.content.push_str(CALLS_START);
+                            }
+                            parsed.content.push_str(&text);
+                            self.state = State::Content;
+                        }
+                    }
+                }
+                State::ListNext => {
+                    let trimmed = pending.trim_start();
+                    match trimmed.chars().next() {
+                        None => {
+                            self.pending = pending;
+                            return parsed;
+                        }
+                        Some(',') => {
+                            self.pending = trimmed[1..].to_string();
+                            self.state = State::Call {
+                                call: JsonCall::new(self.num_calls),
+                                in_list: true,
+                            };
+                        }
+                        Some(']') => {
+                            self.pending = trimmed[1..].to_string();
+                            self.state = State::Content;
+                        }
+                        Some(_) => {
+                            self.pending = trimmed.to_string();
+                            self.state = State::Content;
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    fn finish(&mut self) -> ParsedOutput {
+        let mut parsed = ParsedOutput::default();
+        match std::mem::take(&mut self.state) {
+            State::Content if self.num_calls > 0 && self.pending.trim().is_empty() => {}
+            State::Content => parsed.content.push_str(&self.pending),
+            State::ListStart => {
+                parsed.content.push_str(CALLS_START);
+                parsed.content.push_str(&self.pending);
+            }
+            // the output was cut short in the arguments of a call
+            State::Call { call, .. } if call.is_started() => self.num_calls += 1,
+            State::Call { call, .. } => {
+                if self.num_calls == 0 {
+                    parsed.content.push_str(CALLS_START);
+                }
+                parsed.content.push_str(call.text());
+            }
+            State::ListNext => {}
+        }
+        self.pending.clear();
+        parsed
+    }
+
+    fn has_tool_calls(&self) -> bool {
+        self.num_calls > 0
+    }
+
+    fn clone_box(&self) -> Box<dyn ToolCallParser> {
+        Box::new(self.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::tests::parse_in_pieces;
+    use super::*;
+
+    #[test]
+    fn test_list_of_calls() {
+        let output = r#"[TOOL_CALLS] [{"name": "get_weather", "arguments": {"city": "Paris"}}, {"name": "get_time", "arguments": {"tz": "CET"}}]"#;
+        let mut parser = MistralParser::default();
+        let (content, calls) = parse_in_pieces(&mut parser, output);
+        assert_eq!(content, "");
+        assert_eq!(
+            calls,
+            vec![
+                (
+                    "get_weather".to_string(),
+                    r#"{"city": "Paris"}"#.to_string()
+                ),
+                ("get_time".to_string(), r#"{"tz": "CET"}"#.to_string()),
+            ]
+        );
+        assert!(parser.has_tool_calls());
+    }
+
+    #[test]
+    fn test_single_call_after_content() {
+        let output = r#"Sure.[TOOL_CALLS]{"name": "get_time", "arguments": {}}"#;
+        let mut parser = MistralParser::default();
+        let (content, calls) = parse_in_pieces(&mut parser, output);
+        assert_eq!(content, "Sure.");
+        assert_eq!(calls, vec![("get_time".to_string(), "{}".to_string())]);
+    }
+
+    #[test]
+    fn test_no_calls() {
+        let mut parser = MistralParser::default();
+        let (content, calls) = parse_in_pieces(&mut parser, "[TOOL] [TOOL_CALLS] no");
+        assert_eq!(content, "[TOOL] [TOOL_CALLS] no");
+        assert!(calls.is_empty());
+        assert!(!parser.has_tool_calls());
+    }
+}
@@ -45,6 +45,14 @@ pub trait StopConditionsProvider {
     fn extract_stop_conditions(&self) -> Result<StopConditions>;
 }
 
+pub trait OutputOptionsProvider {
+    fn extract_output_options(&self) -> Result<OutputOptions>;
+}
+
+pub trait GuidedDecodingOptionsProvider {
+    fn extract_guided_decoding_options(&self) -> Result<Option<GuidedDecodingOptions>>;
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
 pub enum FinishReason {
     #[serde(rename = "eos")]
@@ -366,6 +374,43 @@ pub struct OutputOptions {
     /// the tokenizer. This is useful for inspecting the behavior of prompt
     /// templates that are applied during the backend preprocessing.
     pub formatted_prompt: Option<bool>,
+
+    /// What the Backend does with the reasoning of a thinking model. If not set, the reasoning
+    /// is left in the text of the output.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reasoning: Option<ReasoningMode>,
+}
+
+/// What the Backend does with the reasoning of a thinking model, see [`crate::reasoning`]
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ReasoningMode {
+    /// Return it apart from the text of the output
+    Separate,
+
+    /// Drop it, only its tokens are counted
+    Drop,
+}
+
+/// Constrains the output of a request to a format, see [`crate::guided_decoding`].
+///
+/// Engines which support it constrain their sampling to the format; the Backend rejects the
+/// outputs of the others which do not match it.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum GuidedDecodingOptions {
+    /// A JSON object
+    JsonObject,
+
+    /// JSON which is valid against this JSON schema
+    JsonSchema(serde_json::Value),
+
+    /// Text which fully matches this regular expression
+    Regex(String),
+
+    /// Text which matches this context-free grammar, in the EBNF syntax of the engine.
+    /// Only enforced by the engine, the Backend does not validate it.
+    Grammar(String),
 }
 
 // Struct for log probability information
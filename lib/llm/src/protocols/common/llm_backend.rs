@@ -15,13 +15,28 @@
 
 use serde::{Deserialize, Serialize};
 
-pub use super::preprocessor::PreprocessedRequest;
+pub use super::preprocessor::{MediaInput, MediaKind, PreprocessedRequest};
 pub use super::FinishReason;
 use crate::protocols::TokenIdType;
 
 pub type TokenType = Option<String>;
 pub type LogProbs = Vec<f64>;
 
+/// The most likely tokens at one position of the output, most likely first
+pub type TopLogProbs = Vec<TopLogProb>;
+
+/// One of the most likely tokens at a position of the output
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct TopLogProb {
+    pub token_id: TokenIdType,
+
+    /// The decoded token. Filled in by the Backend if the engine does not detokenize.
+    #[serde(default)]
+    pub token: TokenType,
+
+    pub logprob: f64,
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct BackendOutput {
     /// New token_ids generated from the LLM Engine
@@ -40,6 +55,9 @@ pub struct BackendOutput {
     /// Optional log probabilities
     pub log_probs: Option<LogProbs>,
 
+    /// Optional most likely alternatives, one entry per token in `token_ids`
+    pub top_logprobs: Option<Vec<TopLogProbs>>,
+
     // TODO: Enrich this with more information as can apply our first-level postprocessing
     // logic and return more detailed information
     pub finish_reason: Option<FinishReason>,
@@ -48,6 +66,15 @@ pub struct BackendOutput {
 
     // Index field for batch requests to match OpenAI format
     pub index: Option<u32>,
+
+    /// The reasoning of a thinking model, when the request asks for it apart from `text`.
+    /// See [`super::OutputOptions::reasoning`].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reasoning_text: Option<String>,
+
+    /// How many of `token_ids` are reasoning
+    #[serde(default)]
+    pub reasoning_tokens: u32,
 }
 
 /// The LLM engine and backnd with manage it's own state, specifically translating how a
@@ -73,9 +100,13 @@ pub struct LLMEngineOutput {
     /// cumulative log probabilities
     pub cum_log_probs: Option<f64>,
 
-    /// Optional log probabilities
+    /// Optional log probabilities, one per token in `token_ids`
     pub log_probs: Option<LogProbs>,
 
+    /// Optional most likely alternatives, one entry per token in `token_ids`.
+    /// Requested with [`super::OutputOptions::logprobs`].
+    pub top_logprobs: Option<Vec<TopLogProbs>>,
+
     // TODO: Enrich this with more information as can apply our first-level postprocessing
     // logic and return more detailed information
     pub finish_reason: Option<FinishReason>,
@@ -92,6 +123,7 @@ impl LLMEngineOutput {
             text: None,
             cum_log_probs: None,
             log_probs: None,
+            top_logprobs: None,
             finish_reason: Some(FinishReason::Cancelled),
             index: None,
         }
@@ -104,6 +136,7 @@ impl LLMEngineOutput {
             text: None,
             cum_log_probs: None,
             log_probs: None,
+            top_logprobs: None,
             finish_reason: Some(FinishReason::Stop),
             index: None,
         }
@@ -116,6 +149,7 @@ impl LLMEngineOutput {
             text: None,
             cum_log_probs: None,
             log_probs: None,
+            top_logprobs: None,
             finish_reason: Some(FinishReason::Length),
             index: None,
         }
@@ -128,6 +162,7 @@ impl LLMEngineOutput {
             text: None,
             cum_log_probs: None,
             log_probs: None,
+            top_logprobs: None,
             finish_reason: Some(FinishReason::Error(err_msg)),
             index: None,
         }
@@ -13,10 +13,12 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
 use derive_builder::Builder;
 use serde::{Deserialize, Serialize};
 
-use super::{SamplingOptions, StopConditions};
+use super::{GuidedDecodingOptions, OutputOptions, SamplingOptions, StopConditions};
 use crate::protocols::TokenIdType;
 
 /// [`PreprocessedRequest`] is the internal representation of an LLM request. The [`dynamo.llm-preprocessor`]
@@ -38,6 +40,16 @@ pub struct PreprocessedRequest {
     /// are needed.
     pub sampling_options: SamplingOptions,
 
+    /// OutputOptions control what the inference engine returns besides tokens, such as logprobs.
+    #[builder(default)]
+    #[serde(default)]
+    pub output_options: OutputOptions,
+
+    /// Constrains the output to a format, such as JSON of a schema.
+    #[builder(default)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub guided_decoding: Option<GuidedDecodingOptions>,
+
     /// The EOS token ID(s) for the Model
     /// Not every backend needs this, but those that do can find it here.
     /// TODO - refactor this to a better location
@@ -55,12 +67,109 @@ pub struct PreprocessedRequest {
     /// Estimated number of prefix hit tokens (only used in kv aware routing)
     #[builder(default)]
     pub estimated_prefix_hit_num_blocks: Option<u32>,
+
+    /// The LoRA adapter this request should be served with; `None` for the base model.
+    /// KV aware routing only counts cached blocks computed with the same adapter.
+    #[builder(default)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub lora_id: Option<u64>,
+
+    /// The images and audio of the prompt, in the order of their placeholder tokens in
+    /// `token_ids`. Only engines of multimodal models use them.
+    #[builder(default)]
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub media: Vec<MediaInput>,
+
+    /// How urgent the request is, higher is more urgent. `None` is the default priority, 0.
+    #[builder(default)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub priority: Option<i32>,
+
+    /// The Unix time, in milliseconds, by which the request must be complete
+    #[builder(default)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub deadline_ms: Option<u64>,
+
+    /// The prompt blocks past `estimated_prefix_hit_num_blocks` that other workers have cached,
+    /// in prompt order, so the worker can fetch them instead of computing them. Set by the KV
+    /// router; the first entry is the block right after the routed worker's cached prefix.
+    #[builder(default)]
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub peer_blocks: Vec<PeerBlock>,
+}
+
+/// A prompt block cached by a worker other than the one a request was routed to
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PeerBlock {
+    /// The worker holding the block
+    pub worker_id: i64,
+
+    /// The KV router's hash of the block's tokens
+    pub block_hash: u64,
+}
+
+/// What kind of media a [`MediaInput`] is
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum MediaKind {
+    Image,
+    Audio,
+}
+
+/// A media of the prompt, fetched or decoded from the request
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct MediaInput {
+    pub kind: MediaKind,
+
+    /// The MIME type of `data`, if the request or the server gave it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mime_type: Option<String>,
+
+    /// The encoded media, such as a PNG image or a WAV file. Base64 on the wire.
+    #[serde(with = "base64_data")]
+    pub data: Vec<u8>,
+
+    /// The hash of `data`. Prompts with the same tokens but different media must not share
+    /// their KV cache, so KV aware routing folds it into the block hashes.
+    pub hash: u64,
+}
+
+impl MediaInput {
+    pub fn new(kind: MediaKind, mime_type: Option<String>, data: Vec<u8>) -> Self {
+        let hash = xxhash_rust::xxh3::xxh3_64(&data);
+        Self {
+            kind,
+            mime_type,
+            data,
+            hash,
+        }
+    }
+}
+
+mod base64_data {
+    use base64::{engine::general_purpose::STANDARD, Engine};
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(data: &[u8], serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&STANDARD.encode(data))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<u8>, D::Error> {
+        let data = String::deserialize(deserializer)?;
+        STANDARD.decode(data).map_err(serde::de::Error::custom)
+    }
 }
 
 impl PreprocessedRequest {
     pub fn has_annotation(&self, annotation: &str) -> bool {
         self.annotations.contains(&annotation.to_string())
     }
+
+    /// The `deadline_ms` of the request as a [`SystemTime`]
+    pub fn deadline(&self) -> Option<SystemTime> {
+        self.deadline_ms
+            .map(|deadline_ms| UNIX_EPOCH + Duration::from_millis(deadline_ms))
+    }
 }
 
 impl PreprocessedRequest {
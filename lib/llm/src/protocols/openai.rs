@@ -19,7 +19,10 @@ use anyhow::Result;
 use serde::{Deserialize, Serialize};
 
 use super::{
-    common::{self, SamplingOptionsProvider, StopConditionsProvider},
+    common::{
+        self, GuidedDecodingOptionsProvider, OutputOptionsProvider, SamplingOptionsProvider,
+        StopConditionsProvider,
+    },
     ContentProvider,
 };
 
@@ -28,6 +31,7 @@ pub mod completions;
 pub mod embeddings;
 pub mod models;
 pub mod nvext;
+pub mod responses;
 
 /// Minimum allowed value for OpenAI's `temperature` sampling option
 pub const MIN_TEMPERATURE: f32 = 0.0;
@@ -65,6 +69,15 @@ pub const MAX_PRESENCE_PENALTY: f32 = 2.0;
 /// Allowed range of values for OpenAI's `presence_penalty` sampling option
 pub const PRESENCE_PENALTY_RANGE: (f32, f32) = (MIN_PRESENCE_PENALTY, MAX_PRESENCE_PENALTY);
 
+/// Maximum allowed value for OpenAI's chat completions `top_logprobs` option
+pub const MAX_TOP_LOGPROBS: u8 = 20;
+
+/// Maximum allowed value for OpenAI's completions `logprobs` option
+pub const MAX_COMPLETION_LOGPROBS: u8 = 5;
+
+/// Maximum allowed value for OpenAI's `n` and `best_of` options
+pub const MAX_N: u8 = 128;
+
 #[derive(Serialize, Deserialize, Debug)]
 pub struct AnnotatedDelta<R> {
     pub delta: R,
@@ -82,6 +95,12 @@ trait OpenAISamplingOptionsProvider {
 
     fn get_presence_penalty(&self) -> Option<f32>;
 
+    /// The number of choices to return
+    fn get_n(&self) -> Option<u8>;
+
+    /// The number of choices to generate, of which the `n` most likely are returned
+    fn get_best_of(&self) -> Option<u8>;
+
     fn nvext(&self) -> Option<&nvext::NvExt>;
 }
 
@@ -95,6 +114,22 @@ trait OpenAIStopConditionsProvider {
     fn nvext(&self) -> Option<&nvext::NvExt>;
 }
 
+trait OpenAIOutputOptionsProvider {
+    /// The number of most likely alternatives to return with the logprob of each output token,
+    /// or `None` if logprobs were not requested.
+    fn get_logprobs(&self) -> Result<Option<u32>>;
+
+    /// What to do with the reasoning of thinking models, `None` to leave it in the output
+    fn get_reasoning_mode(&self) -> Option<common::ReasoningMode>;
+}
+
+trait OpenAIGuidedDecodingOptionsProvider {
+    /// The `response_format` of the request, if the API has one
+    fn get_response_format(&self) -> Option<&async_openai::types::ResponseFormat>;
+
+    fn nvext(&self) -> Option<&nvext::NvExt>;
+}
+
 impl<T: OpenAISamplingOptionsProvider> SamplingOptionsProvider for T {
     fn extract_sampling_options(&self) -> Result<common::SamplingOptions> {
         // let result = self.validate();
@@ -111,6 +146,10 @@ impl<T: OpenAISamplingOptionsProvider> SamplingOptionsProvider for T {
                 .map_err(|e| anyhow::anyhow!("Error validating frequency_penalty: {}", e))?;
         let presence_penalty = validate_range(self.get_presence_penalty(), &PRESENCE_PENALTY_RANGE)
             .map_err(|e| anyhow::anyhow!("Error validating presence_penalty: {}", e))?;
+        let n = validate_range(self.get_n(), &(1, MAX_N))
+            .map_err(|e| anyhow::anyhow!("Error validating n: {}", e))?;
+        let best_of = validate_range(self.get_best_of(), &(n.unwrap_or(1), MAX_N))
+            .map_err(|e| anyhow::anyhow!("Error validating best_of: {}", e))?;
 
         if let Some(nvext) = self.nvext() {
             let greedy = nvext.greed_sampling.unwrap_or(false);
@@ -121,8 +160,8 @@ impl<T: OpenAISamplingOptionsProvider> SamplingOptionsProvider for T {
         }
 
         Ok(common::SamplingOptions {
-            n: None,
-            best_of: None,
+            n: n.map(Into::into),
+            best_of: best_of.map(Into::into),
             frequency_penalty,
             presence_penalty,
             repetition_penalty: None,
@@ -165,6 +204,51 @@ impl<T: OpenAIStopConditionsProvider> StopConditionsProvider for T {
     }
 }
 
+impl<T: OpenAIOutputOptionsProvider> OutputOptionsProvider for T {
+    fn extract_output_options(&self) -> Result<common::OutputOptions> {
+        Ok(common::OutputOptions {
+            logprobs: self.get_logprobs()?,
+            reasoning: self.get_reasoning_mode(),
+            ..Default::default()
+        })
+    }
+}
+
+impl<T: OpenAIGuidedDecodingOptionsProvider> GuidedDecodingOptionsProvider for T {
+    fn extract_guided_decoding_options(&self) -> Result<Option<common::GuidedDecodingOptions>> {
+        use async_openai::types::ResponseFormat;
+
+        let mut options = Vec::new();
+        match self.get_response_format() {
+            None | Some(ResponseFormat::Text) => {}
+            Some(ResponseFormat::JsonObject) => {
+                options.push(common::GuidedDecodingOptions::JsonObject)
+            }
+            Some(ResponseFormat::JsonSchema { json_schema }) => {
+                options.push(match &json_schema.schema {
+                    Some(schema) => common::GuidedDecodingOptions::JsonSchema(schema.clone()),
+                    None => common::GuidedDecodingOptions::JsonObject,
+                })
+            }
+        }
+        if let Some(nvext) = self.nvext() {
+            if let Some(regex) = &nvext.guided_regex {
+                options.push(common::GuidedDecodingOptions::Regex(regex.clone()));
+            }
+            if let Some(grammar) = &nvext.guided_grammar {
+                options.push(common::GuidedDecodingOptions::Grammar(grammar.clone()));
+            }
+        }
+
+        if options.len() > 1 {
+            anyhow::bail!(
+                "Only one of response_format, guided_regex and guided_grammar can be set"
+            );
+        }
+        Ok(options.pop())
+    }
+}
+
 // todo - move to common location
 fn validate_range<T>(value: Option<T>, range: &(T, T)) -> Result<Option<T>>
 where
@@ -190,4 +274,16 @@ pub trait DeltaGeneratorExt<ResponseType: Send + Sync + 'static + std::fmt::Debu
 
     /// Gets the current prompt token count (Input Sequence Length).
     fn get_isl(&self) -> Option<u32>;
+
+    /// Whether the request asked for the usage of the whole request at the end of the stream,
+    /// with `stream_options.include_usage`.
+    fn is_usage_enabled(&self) -> bool;
+
+    /// The last response of the stream when [`Self::is_usage_enabled`]: no choices, and the
+    /// usage of the whole request, completed with what the generator counted itself.
+    fn create_usage_chunk(&self, usage: async_openai::types::CompletionUsage) -> ResponseType;
+
+    /// The responses still held back when the stream ends without a finish reason, for example
+    /// text a tool call parser kept until it knew whether it starts a tool call.
+    fn finish_stream(&mut self) -> Vec<ResponseType>;
 }
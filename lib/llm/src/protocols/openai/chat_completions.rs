@@ -13,14 +13,21 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use std::collections::HashMap;
+
 use dynamo_runtime::protocols::annotated::AnnotationsProvider;
-use serde::{Deserialize, Serialize};
+use serde::{de::DeserializeOwned, Deserialize, Deserializer, Serialize, Serializer};
+use serde_json::Value;
 use validator::Validate;
 
+use super::common;
 use super::nvext::NvExt;
 use super::nvext::NvExtProvider;
+use super::OpenAIGuidedDecodingOptionsProvider;
+use super::OpenAIOutputOptionsProvider;
 use super::OpenAISamplingOptionsProvider;
 use super::OpenAIStopConditionsProvider;
+use super::{validate_range, MAX_TOP_LOGPROBS};
 
 mod aggregator;
 mod delta;
@@ -48,24 +55,120 @@ pub struct NvCreateChatCompletionRequest {
 /// `CreateChatCompletionResponse`.
 ///
 /// # Fields
-/// - `inner`: The base OpenAI unary chat completion response, embedded
-///   using `serde(flatten)`.
-#[derive(Serialize, Deserialize, Validate, Debug, Clone)]
+/// - `inner`: The base OpenAI unary chat completion response.
+/// - `reasoning_content`: The reasoning of thinking models by choice index, serialized as the
+///   `reasoning_content` of the choice messages.
+#[derive(Validate, Debug, Clone)]
 pub struct NvCreateChatCompletionResponse {
-    #[serde(flatten)]
     pub inner: async_openai::types::CreateChatCompletionResponse,
+
+    pub reasoning_content: HashMap<u32, String>,
 }
 
 /// A response structure for streamed chat completions, embedding OpenAI's
 /// `CreateChatCompletionStreamResponse`.
 ///
 /// # Fields
-/// - `inner`: The base OpenAI streaming chat completion response, embedded
-///   using `serde(flatten)`.
-#[derive(Serialize, Deserialize, Validate, Debug, Clone)]
+/// - `inner`: The base OpenAI streaming chat completion response.
+/// - `reasoning_content`: The reasoning of thinking models by choice index, serialized as the
+///   `reasoning_content` of the choice deltas.
+#[derive(Validate, Debug, Clone)]
 pub struct NvCreateChatCompletionStreamResponse {
-    #[serde(flatten)]
     pub inner: async_openai::types::CreateChatCompletionStreamResponse,
+
+    pub reasoning_content: HashMap<u32, String>,
+}
+
+impl Serialize for NvCreateChatCompletionResponse {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serialize_with_reasoning(&self.inner, "message", &self.reasoning_content, serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for NvCreateChatCompletionResponse {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let (inner, reasoning_content) = deserialize_with_reasoning(deserializer, "message")?;
+        Ok(Self {
+            inner,
+            reasoning_content,
+        })
+    }
+}
+
+impl Serialize for NvCreateChatCompletionStreamResponse {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serialize_with_reasoning(&self.inner, "delta", &self.reasoning_content, serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for NvCreateChatCompletionStreamResponse {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let (inner, reasoning_content) = deserialize_with_reasoning(deserializer, "delta")?;
+        Ok(Self {
+            inner,
+            reasoning_content,
+        })
+    }
+}
+
+/// Serializes a chat completion response with the reasoning of each choice in the
+/// `reasoning_content` of its `field`, which the OpenAI types do not have.
+fn serialize_with_reasoning<T: Serialize, S: Serializer>(
+    inner: &T,
+    field: &str,
+    reasoning_content: &HashMap<u32, String>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    if reasoning_content.is_empty() {
+        return inner.serialize(serializer);
+    }
+    let mut value = serde_json::to_value(inner).map_err(serde::ser::Error::custom)?;
+    for choice in choices_mut(&mut value) {
+        let reasoning = choice_index(choice).and_then(|index| reasoning_content.get(&index));
+        if let (Some(reasoning), Some(Value::Object(field))) = (reasoning, choice.get_mut(field)) {
+            field.insert(
+                "reasoning_content".to_string(),
+                Value::String(reasoning.clone()),
+            );
+        }
+    }
+    value.serialize(serializer)
+}
+
+/// The inverse of [`serialize_with_reasoning`]
+fn deserialize_with_reasoning<'de, T: DeserializeOwned, D: Deserializer<'de>>(
+    deserializer: D,
+    field: &str,
+) -> Result<(T, HashMap<u32, String>), D::Error> {
+    let mut value = Value::deserialize(deserializer)?;
+    let mut reasoning_content = HashMap::new();
+    for choice in choices_mut(&mut value) {
+        let index = choice_index(choice);
+        let reasoning = match choice.get_mut(field) {
+            Some(Value::Object(field)) => field.remove("reasoning_content"),
+            _ => None,
+        };
+        if let (Some(index), Some(Value::String(reasoning))) = (index, reasoning) {
+            reasoning_content.insert(index, reasoning);
+        }
+    }
+    let inner = serde_json::from_value(value).map_err(serde::de::Error::custom)?;
+    Ok((inner, reasoning_content))
+}
+
+fn choices_mut(value: &mut Value) -> impl Iterator<Item = &mut Value> {
+    value
+        .get_mut("choices")
+        .and_then(Value::as_array_mut)
+        .into_iter()
+        .flatten()
+}
+
+fn choice_index(choice: &Value) -> Option<u32> {
+    choice
+        .get("index")
+        .and_then(Value::as_u64)
+        .and_then(|index| index.try_into().ok())
 }
 
 /// Implements `NvExtProvider` for `NvCreateChatCompletionRequest`,
@@ -131,12 +234,64 @@ impl OpenAISamplingOptionsProvider for NvCreateChatCompletionRequest {
         self.inner.presence_penalty
     }
 
+    /// Retrieves the number of choices to generate, if set.
+    fn get_n(&self) -> Option<u8> {
+        self.inner.n
+    }
+
+    /// Returns `None`, as chat completions do not support `best_of`.
+    fn get_best_of(&self) -> Option<u8> {
+        None
+    }
+
     /// Returns a reference to the optional `NvExt` extension, if available.
     fn nvext(&self) -> Option<&NvExt> {
         self.nvext.as_ref()
     }
 }
 
+/// Implements `OpenAIGuidedDecodingOptionsProvider` for `NvCreateChatCompletionRequest`,
+/// constraining the output with `response_format` or the `NvExt` guided decoding options.
+impl OpenAIGuidedDecodingOptionsProvider for NvCreateChatCompletionRequest {
+    /// Retrieves the response format, if set.
+    fn get_response_format(&self) -> Option<&async_openai::types::ResponseFormat> {
+        self.inner.response_format.as_ref()
+    }
+
+    /// Returns a reference to the optional `NvExt` extension, if available.
+    fn nvext(&self) -> Option<&NvExt> {
+        self.nvext.as_ref()
+    }
+}
+
+/// Implements `OpenAIOutputOptionsProvider` for `NvCreateChatCompletionRequest`,
+/// mapping `logprobs` and `top_logprobs` to the number of alternatives per token.
+impl OpenAIOutputOptionsProvider for NvCreateChatCompletionRequest {
+    fn get_logprobs(&self) -> anyhow::Result<Option<u32>> {
+        let top_logprobs = validate_range(self.inner.top_logprobs, &(0, MAX_TOP_LOGPROBS))
+            .map_err(|e| anyhow::anyhow!("Error validating top_logprobs: {}", e))?;
+        if !self.inner.logprobs.unwrap_or(false) {
+            if top_logprobs.is_some() {
+                anyhow::bail!("top_logprobs requires logprobs to be true");
+            }
+            return Ok(None);
+        }
+        Ok(Some(top_logprobs.unwrap_or(0).into()))
+    }
+
+    /// The reasoning is returned in `reasoning_content`, unless `nvext.include_reasoning` is false.
+    fn get_reasoning_mode(&self) -> Option<common::ReasoningMode> {
+        match self
+            .nvext
+            .as_ref()
+            .and_then(|nvext| nvext.include_reasoning)
+        {
+            Some(false) => Some(common::ReasoningMode::Drop),
+            _ => Some(common::ReasoningMode::Separate),
+        }
+    }
+}
+
 /// Implements `OpenAIStopConditionsProvider` for `NvCreateChatCompletionRequest`,
 /// providing access to stop conditions that control chat completion behavior.
 impl OpenAIStopConditionsProvider for NvCreateChatCompletionRequest {
@@ -60,6 +60,10 @@ struct DeltaChoice {
     finish_reason: Option<async_openai::types::FinishReason>,
     /// Optional log probabilities for the chat choice.
     logprobs: Option<async_openai::types::ChatChoiceLogprobs>,
+    /// The tool calls of the choice, by the index of their chunks.
+    tool_calls: Vec<async_openai::types::ChatCompletionMessageToolCall>,
+    /// The accumulated reasoning of a thinking model.
+    reasoning: String,
 }
 
 impl Default for DeltaAggregator {
@@ -124,6 +128,7 @@ impl DeltaAggregator {
                     }
 
                     // Aggregate choices incrementally.
+                    let mut reasoning_content = delta.reasoning_content;
                     for choice in delta.inner.choices {
                         let state_choice =
                             aggregator
@@ -134,7 +139,9 @@ impl DeltaAggregator {
                                     text: "".to_string(),
                                     role: choice.delta.role,
                                     finish_reason: None,
-                                    logprobs: choice.logprobs,
+                                    logprobs: None,
+                                    tool_calls: Vec::new(),
+                                    reasoning: String::new(),
                                 });
 
                         // Append content if available.
@@ -142,6 +149,63 @@ impl DeltaAggregator {
                             state_choice.text.push_str(content);
                         }
 
+                        // Append reasoning if available.
+                        if let Some(reasoning) = reasoning_content.remove(&choice.index) {
+                            state_choice.reasoning.push_str(&reasoning);
+                        }
+
+                        // Append the tool call chunks to their calls.
+                        for chunk in choice.delta.tool_calls.into_iter().flatten() {
+                            let index = chunk.index as usize;
+                            if state_choice.tool_calls.len() <= index {
+                                state_choice.tool_calls.resize_with(index + 1, || {
+                                    async_openai::types::ChatCompletionMessageToolCall {
+                                        id: String::new(),
+                                        r#type:
+                                            async_openai::types::ChatCompletionToolType::Function,
+                                        function: async_openai::types::FunctionCall {
+                                            name: String::new(),
+                                            arguments: String::new(),
+                                        },
+                                    }
+                                });
+                            }
+                            let tool_call = &mut state_choice.tool_calls[index];
+                            if let Some(id) = chunk.id {
+                                tool_call.id = id;
+                            }
+                            if let Some(function) = chunk.function {
+                                if let Some(name) = function.name {
+                                    tool_call.function.name.push_str(&name);
+                                }
+                                if let Some(arguments) = function.arguments {
+                                    tool_call.function.arguments.push_str(&arguments);
+                                }
+                            }
+                        }
+
+                        // Append log probabilities if available.
+                        if let Some(logprobs) = choice.logprobs {
+                            let state_logprobs = state_choice.logprobs.get_or_insert(
+                                async_openai::types::ChatChoiceLogprobs {
+                                    content: None,
+                                    refusal: None,
+                                },
+                            );
+                            if let Some(content) = logprobs.content {
+                                state_logprobs
+                                    .content
+                                    .get_or_insert_with(Vec::new)
+                                    .extend(content);
+                            }
+                            if let Some(refusal) = logprobs.refusal {
+                                state_logprobs
+                                    .refusal
+                                    .get_or_insert_with(Vec::new)
+                                    .extend(refusal);
+                            }
+                        }
+
                         // Update finish reason if provided.
                         if let Some(finish_reason) = choice.finish_reason {
                             state_choice.finish_reason = Some(finish_reason);
@@ -153,12 +217,20 @@ impl DeltaAggregator {
             .await;
 
         // Return early if an error was encountered.
-        let aggregator = if let Some(error) = aggregator.error {
+        let mut aggregator = if let Some(error) = aggregator.error {
             return Err(error);
         } else {
             aggregator
         };
 
+        // Extract the reasoning of the choices, which the OpenAI types do not have.
+        let reasoning_content = aggregator
+            .choices
+            .iter_mut()
+            .filter(|(_, choice)| !choice.reasoning.is_empty())
+            .map(|(index, choice)| (*index, std::mem::take(&mut choice.reasoning)))
+            .collect();
+
         // Extract aggregated choices and sort them by index.
         let mut choices: Vec<_> = aggregator
             .choices
@@ -180,7 +252,10 @@ impl DeltaAggregator {
             service_tier: aggregator.service_tier,
         };
 
-        let response = NvCreateChatCompletionResponse { inner };
+        let response = NvCreateChatCompletionResponse {
+            inner,
+            reasoning_content,
+        };
 
         Ok(response)
     }
@@ -193,11 +268,17 @@ impl From<DeltaChoice> for async_openai::types::ChatChoice {
     /// # Note
     /// The `function_call` field is deprecated.
     fn from(delta: DeltaChoice) -> Self {
+        // A message with only tool calls has no content.
+        let content = if delta.text.is_empty() && !delta.tool_calls.is_empty() {
+            None
+        } else {
+            Some(delta.text)
+        };
         async_openai::types::ChatChoice {
             message: async_openai::types::ChatCompletionResponseMessage {
                 role: delta.role.expect("delta should have a Role"),
-                content: Some(delta.text),
-                tool_calls: None,
+                content,
+                tool_calls: (!delta.tool_calls.is_empty()).then_some(delta.tool_calls),
                 refusal: None,
                 function_call: None,
                 audio: None,
@@ -279,7 +360,10 @@ mod tests {
             object: "chat.completion".to_string(),
         };
 
-        let data = NvCreateChatCompletionStreamResponse { inner };
+        let data = NvCreateChatCompletionStreamResponse {
+            inner,
+            reasoning_content: Default::default(),
+        };
 
         Annotated {
             data: Some(data),
@@ -380,6 +464,291 @@ mod tests {
         assert_eq!(choice.message.role, async_openai::types::Role::User);
     }
 
+    #[tokio::test]
+    async fn test_logprobs_are_concatenated() {
+        let token_logprob =
+            |token: &str, logprob: f32| async_openai::types::ChatCompletionTokenLogprob {
+                token: token.to_string(),
+                logprob,
+                bytes: Some(token.as_bytes().to_vec()),
+                top_logprobs: vec![],
+            };
+        let mut annotated_delta1 = create_test_delta(
+            0,
+            "Hello,",
+            Some(async_openai::types::Role::Assistant),
+            None,
+        );
+        annotated_delta1.data.as_mut().unwrap().inner.choices[0].logprobs =
+            Some(async_openai::types::ChatChoiceLogprobs {
+                content: Some(vec![token_logprob("Hello", -0.1), token_logprob(",", -0.2)]),
+                refusal: None,
+            });
+        let mut annotated_delta2 = create_test_delta(
+            0,
+            " world!",
+            None,
+            Some(async_openai::types::FinishReason::Stop),
+        );
+        annotated_delta2.data.as_mut().unwrap().inner.choices[0].logprobs =
+            Some(async_openai::types::ChatChoiceLogprobs {
+                content: Some(vec![token_logprob(" world!", -0.3)]),
+                refusal: None,
+            });
+
+        let stream = Box::pin(stream::iter(vec![annotated_delta1, annotated_delta2]));
+        let response = DeltaAggregator::apply(stream).await.unwrap();
+
+        let logprobs = response.inner.choices[0].logprobs.as_ref().unwrap();
+        let tokens: Vec<&str> = logprobs
+            .content
+            .as_ref()
+            .unwrap()
+            .iter()
+            .map(|logprob| logprob.token.as_str())
+            .collect();
+        assert_eq!(tokens, vec!["Hello", ",", " world!"]);
+        assert!(logprobs.refusal.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_interleaved_choices_from_backend() {
+        use crate::protocols::common::{llm_backend::BackendOutput, FinishReason};
+        use crate::protocols::openai::chat_completions::delta::{
+            DeltaGenerator, DeltaGeneratorOptions,
+        };
+        use crate::protocols::openai::DeltaGeneratorExt;
+
+        let mut generator =
+            DeltaGenerator::new("test_model".to_string(), DeltaGeneratorOptions::default());
+        let output = |index: u32, text: &str, finish_reason: Option<FinishReason>| BackendOutput {
+            token_ids: vec![1],
+            tokens: vec![Some(text.to_string())],
+            text: Some(text.to_string()),
+            cum_log_probs: None,
+            log_probs: None,
+            top_logprobs: None,
+            finish_reason,
+            index: Some(index),
+            reasoning_text: None,
+            reasoning_tokens: 0,
+        };
+        let deltas: Vec<_> = vec![
+            output(1, "Good", None),
+            output(0, "Hello", None),
+            output(0, " there", Some(FinishReason::Stop)),
+            output(1, " day", Some(FinishReason::Length)),
+        ]
+        .into_iter()
+        .map(|output| Annotated::from_data(generator.choice_from_postprocessor(output).unwrap()))
+        .collect();
+
+        let response = DeltaAggregator::apply(Box::pin(stream::iter(deltas)))
+            .await
+            .unwrap();
+
+        let choices: Vec<_> = response
+            .inner
+            .choices
+            .iter()
+            .map(|choice| {
+                (
+                    choice.index,
+                    choice.message.content.as_deref().unwrap(),
+                    choice.finish_reason,
+                )
+            })
+            .collect();
+        assert_eq!(
+            choices,
+            vec![
+                (
+                    0,
+                    "Hello there",
+                    Some(async_openai::types::FinishReason::Stop)
+                ),
+                (
+                    1,
+                    "Good day",
+                    Some(async_openai::types::FinishReason::Length)
+                ),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_tool_calls_from_backend() {
+        use crate::preprocessor::tools::HermesParser;
+        use crate::protocols::common::{llm_backend::BackendOutput, FinishReason};
+        use crate::protocols::openai::chat_completions::delta::{
+            DeltaGenerator, DeltaGeneratorOptions,
+        };
+        use crate::protocols::openai::DeltaGeneratorExt;
+
+        let mut generator =
+            DeltaGenerator::new("test_model".to_string(), DeltaGeneratorOptions::default());
+        generator.set_tool_call_parser(Box::new(HermesParser::default()));
+        let output = |text: &str, finish_reason: Option<FinishReason>| BackendOutput {
+            token_ids: vec![1],
+            tokens: vec![Some(text.to_string())],
+            text: Some(text.to_string()),
+            cum_log_probs: None,
+            log_probs: None,
+            top_logprobs: None,
+            finish_reason,
+            index: None,
+            reasoning_text: None,
+            reasoning_tokens: 0,
+        };
+        let deltas: Vec<_> = vec![
+            output("<tool_", None),
+            output("call>{\"name\": \"get_weather\", ", None),
+            output("\"arguments\": {\"city\": ", None),
+            output("\"Paris\"}}</tool_call>", Some(FinishReason::EoS)),
+        ]
+        .into_iter()
+        .map(|output| Annotated::from_data(generator.choice_from_postprocessor(output).unwrap()))
+        .collect();
+
+        let response = DeltaAggregator::apply(Box::pin(stream::iter(deltas)))
+            .await
+            .unwrap();
+
+        let choice = &response.inner.choices[0];
+        assert!(choice.message.content.is_none());
+        assert_eq!(
+            choice.finish_reason,
+            Some(async_openai::types::FinishReason::ToolCalls)
+        );
+        let tool_calls = choice.message.tool_calls.as_ref().unwrap();
+        assert_eq!(tool_calls.len(), 1);
+        assert!(tool_calls[0].id.starts_with("call-"));
+        assert_eq!(tool_calls[0].function.name, "get_weather");
+        assert_eq!(tool_calls[0].function.arguments, r#"{"city": "Paris"}"#);
+    }
+
+    #[tokio::test]
+    async fn test_held_back_text_without_finish_reason() {
+        use crate::preprocessor::tools::HermesParser;
+        use crate::protocols::common::llm_backend::BackendOutput;
+        use crate::protocols::openai::chat_completions::delta::{
+            DeltaGenerator, DeltaGeneratorOptions,
+        };
+        use crate::protocols::openai::DeltaGeneratorExt;
+
+        let mut generator =
+            DeltaGenerator::new("test_model".to_string(), DeltaGeneratorOptions::default());
+        generator.set_tool_call_parser(Box::new(HermesParser::default()));
+        let output = BackendOutput {
+            token_ids: vec![1, 2],
+            tokens: vec![None, None],
+            text: Some("The answer <tool_".to_string()),
+            cum_log_probs: None,
+            log_probs: None,
+            top_logprobs: None,
+            finish_reason: None,
+            index: None,
+            reasoning_text: None,
+            reasoning_tokens: 0,
+        };
+        // the stream ends without a finish reason, while the parser holds back "<tool_"
+        let mut deltas = vec![generator.choice_from_postprocessor(output).unwrap()];
+        deltas.extend(generator.finish_stream());
+        assert!(generator.finish_stream().is_empty());
+
+        let response = DeltaAggregator::apply(Box::pin(stream::iter(
+            deltas.into_iter().map(Annotated::from_data),
+        )))
+        .await
+        .unwrap();
+
+        let choice = &response.inner.choices[0];
+        assert_eq!(choice.message.content.as_deref(), Some("The answer <tool_"));
+        assert!(choice.message.tool_calls.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_reasoning_from_backend() {
+        use crate::protocols::common::{llm_backend::BackendOutput, FinishReason};
+        use crate::protocols::openai::chat_completions::delta::{
+            DeltaGenerator, DeltaGeneratorOptions,
+        };
+        use crate::protocols::openai::DeltaGeneratorExt;
+
+        let options = DeltaGeneratorOptions {
+            enable_usage: true,
+            enable_logprobs: false,
+        };
+        let mut generator = DeltaGenerator::new("test_model".to_string(), options);
+        let output = |text: Option<&str>, reasoning: Option<&str>| BackendOutput {
+            token_ids: vec![1, 2],
+            tokens: vec![None, None],
+            text: text.map(str::to_string),
+            cum_log_probs: None,
+            log_probs: None,
+            top_logprobs: None,
+            finish_reason: None,
+            index: None,
+            reasoning_text: reasoning.map(str::to_string),
+            reasoning_tokens: if reasoning.is_some() { 2 } else { 0 },
+        };
+        let mut deltas: Vec<_> = vec![
+            output(None, Some("Two and")),
+            output(None, Some(" two.")),
+            output(Some("4"), None),
+        ]
+        .into_iter()
+        .map(|output| generator.choice_from_postprocessor(output).unwrap())
+        .collect();
+
+        // the reasoning is streamed in the deltas of the choices
+        let chunk = serde_json::to_value(&deltas[0]).unwrap();
+        assert_eq!(chunk["choices"][0]["delta"]["reasoning_content"], "Two and");
+        assert!(chunk["usage"].is_null());
+        let chunk: NvCreateChatCompletionStreamResponse = serde_json::from_value(chunk).unwrap();
+        assert_eq!(chunk.reasoning_content[&0], "Two and");
+
+        // the usage is reported by a last chunk without choices
+        let usage_chunk = generator.create_usage_chunk(async_openai::types::CompletionUsage {
+            prompt_tokens: 3,
+            completion_tokens: 6,
+            total_tokens: 9,
+            prompt_tokens_details: None,
+            completion_tokens_details: None,
+        });
+        assert!(usage_chunk.inner.choices.is_empty());
+        deltas.push(usage_chunk);
+
+        let deltas = deltas
+            .into_iter()
+            .map(Annotated::from_data)
+            .collect::<Vec<_>>();
+        let response = DeltaAggregator::apply(Box::pin(stream::iter(deltas)))
+            .await
+            .unwrap();
+        assert_eq!(response.reasoning_content[&0], "Two and two.");
+        assert_eq!(
+            response.inner.choices[0].message.content.as_deref(),
+            Some("4")
+        );
+        let usage = response.inner.usage.as_ref().unwrap();
+        assert_eq!(usage.completion_tokens, 6);
+        assert_eq!(
+            usage
+                .completion_tokens_details
+                .as_ref()
+                .unwrap()
+                .reasoning_tokens,
+            Some(4)
+        );
+
+        let response = serde_json::to_value(&response).unwrap();
+        assert_eq!(
+            response["choices"][0]["message"]["reasoning_content"],
+            "Two and two."
+        );
+    }
+
     #[allow(deprecated)]
     #[tokio::test]
     async fn test_multiple_choices() {
@@ -421,7 +790,10 @@ mod tests {
             object: "chat.completion".to_string(),
         };
 
-        let data = NvCreateChatCompletionStreamResponse { inner: delta };
+        let data = NvCreateChatCompletionStreamResponse {
+            inner: delta,
+            reasoning_content: Default::default(),
+        };
 
         // Wrap it in Annotated and create a stream
         let annotated_delta = Annotated {
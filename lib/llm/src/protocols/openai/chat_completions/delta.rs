@@ -13,7 +13,10 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use std::collections::HashMap;
+
 use super::{NvCreateChatCompletionRequest, NvCreateChatCompletionStreamResponse};
+use crate::preprocessor::tools::{ToolCallDelta, ToolCallParser};
 use crate::protocols::common;
 
 /// Provides a method for generating a [`DeltaGenerator`] from a chat completion request.
@@ -24,7 +27,11 @@ impl NvCreateChatCompletionRequest {
     /// * [`DeltaGenerator`] configured with model name and response options.
     pub fn response_generator(&self) -> DeltaGenerator {
         let options = DeltaGeneratorOptions {
-            enable_usage: true,
+            enable_usage: self
+                .inner
+                .stream_options
+                .as_ref()
+                .is_some_and(|options| options.include_usage),
             enable_logprobs: self.inner.logprobs.unwrap_or(false),
         };
 
@@ -35,7 +42,7 @@ impl NvCreateChatCompletionRequest {
 /// Configuration options for the [`DeltaGenerator`], controlling response behavior.
 #[derive(Debug, Clone, Default)]
 pub struct DeltaGeneratorOptions {
-    /// Determines whether token usage statistics should be included in the response.
+    /// Determines whether the stream ends with a chunk of the token usage of the request.
     pub enable_usage: bool,
     /// Determines whether log probabilities should be included in the response.
     pub enable_logprobs: bool,
@@ -62,6 +69,10 @@ pub struct DeltaGenerator {
     msg_counter: u64,
     /// Configuration options for response generation.
     options: DeltaGeneratorOptions,
+    /// Parser of the tool calls in the output, cloned for each choice.
+    tool_call_parser: Option<Box<dyn ToolCallParser>>,
+    /// The tool call parsers of the choices, by index.
+    choice_parsers: HashMap<u32, Box<dyn ToolCallParser>>,
 }
 
 impl DeltaGenerator {
@@ -101,9 +112,17 @@ impl DeltaGenerator {
             usage,
             msg_counter: 0,
             options,
+            tool_call_parser: None,
+            choice_parsers: HashMap::new(),
         }
     }
 
+    /// Parses the tool calls out of the generated text with `parser`, and returns them as
+    /// `tool_calls` deltas instead of content.
+    pub fn set_tool_call_parser(&mut self, parser: Box<dyn ToolCallParser>) {
+        self.tool_call_parser = Some(parser);
+    }
+
     /// Updates the prompt token usage count.
     ///
     /// # Arguments
@@ -152,11 +171,6 @@ impl DeltaGenerator {
 
         let choices = vec![choice];
 
-        let mut usage = self.usage.clone();
-        if self.options.enable_usage {
-            usage.total_tokens = usage.prompt_tokens + usage.completion_tokens;
-        }
-
         async_openai::types::CreateChatCompletionStreamResponse {
             id: self.id.clone(),
             object: self.object.clone(),
@@ -164,11 +178,7 @@ impl DeltaGenerator {
             model: self.model.clone(),
             system_fingerprint: self.system_fingerprint.clone(),
             choices,
-            usage: if self.options.enable_usage {
-                Some(usage)
-            } else {
-                None
-            },
+            usage: None,
             service_tier: self.service_tier.clone(),
         }
     }
@@ -191,24 +201,38 @@ impl crate::protocols::openai::DeltaGeneratorExt<NvCreateChatCompletionStreamRes
         &mut self,
         delta: crate::protocols::common::llm_backend::BackendOutput,
     ) -> anyhow::Result<NvCreateChatCompletionStreamResponse> {
-        // Aggregate token usage if enabled.
-        if self.options.enable_usage {
-            // SAFETY: Casting from `usize` to `u32` could lead to precision loss after `u32::MAX`,
-            // but this will not be an issue until context lengths exceed 4_294_967_295.
-            let token_length: u32 = delta
-                .token_ids
-                .len()
-                .try_into()
-                .expect("token_ids length exceeds u32::MAX");
-
-            self.usage.completion_tokens += token_length;
+        // Aggregate token usage, for the usage chunk at the end of the stream.
+        // SAFETY: Casting from `usize` to `u32` could lead to precision loss after `u32::MAX`,
+        // but this will not be an issue until context lengths exceed 4_294_967_295.
+        let token_length: u32 = delta
+            .token_ids
+            .len()
+            .try_into()
+            .expect("token_ids length exceeds u32::MAX");
+
+        self.usage.completion_tokens += token_length;
+
+        // Reasoning tokens are also completion tokens.
+        if delta.reasoning_tokens > 0 {
+            let details = self.usage.completion_tokens_details.get_or_insert(
+                async_openai::types::CompletionTokensDetails {
+                    accepted_prediction_tokens: None,
+                    audio_tokens: None,
+                    reasoning_tokens: None,
+                    rejected_prediction_tokens: None,
+                },
+            );
+            *details.reasoning_tokens.get_or_insert(0) += delta.reasoning_tokens;
         }
 
-        // TODO: Implement log probabilities aggregation.
-        let logprobs = None;
+        let logprobs = if self.options.enable_logprobs {
+            create_logprobs(&delta)
+        } else {
+            None
+        };
 
         // Map backend finish reasons to OpenAI's finish reasons.
-        let finish_reason = match delta.finish_reason {
+        let mut finish_reason = match delta.finish_reason {
             Some(common::FinishReason::EoS) => Some(async_openai::types::FinishReason::Stop),
             Some(common::FinishReason::Stop) => Some(async_openai::types::FinishReason::Stop),
             Some(common::FinishReason::Length) => Some(async_openai::types::FinishReason::Length),
@@ -222,16 +246,164 @@ impl crate::protocols::openai::DeltaGeneratorExt<NvCreateChatCompletionStreamRes
             None => None,
         };
 
-        // Create the streaming response.
-        let index = 0;
-        let stream_response = self.create_choice(index, delta.text, finish_reason, logprobs);
+        // Create the streaming response, for the choice the output belongs to.
+        let index = delta.index.unwrap_or(0);
+        let mut text = delta.text;
+        let mut tool_calls = Vec::new();
+        if let Some(tool_call_parser) = &self.tool_call_parser {
+            // Separate the tool calls from the content, the parser holds back text which could
+            // be the start of a tool call until the next delta.
+            let parser = self
+                .choice_parsers
+                .entry(index)
+                .or_insert_with(|| tool_call_parser.clone());
+            let mut parsed = parser.parse(text.as_deref().unwrap_or_default());
+            if finish_reason.is_some() {
+                parsed.extend(parser.finish());
+                if parser.has_tool_calls()
+                    && matches!(finish_reason, Some(async_openai::types::FinishReason::Stop))
+                {
+                    finish_reason = Some(async_openai::types::FinishReason::ToolCalls);
+                }
+            }
+            text = (!parsed.content.is_empty()).then_some(parsed.content);
+            tool_calls = parsed.tool_calls;
+            if finish_reason.is_some() {
+                self.choice_parsers.remove(&index);
+            }
+        }
+
+        let mut stream_response = self.create_choice(index, text, finish_reason, logprobs);
+        if !tool_calls.is_empty() {
+            stream_response.choices[0].delta.tool_calls =
+                Some(tool_calls.into_iter().map(tool_call_chunk).collect());
+        }
+
+        let mut reasoning_content = HashMap::new();
+        if let Some(reasoning) = delta.reasoning_text {
+            reasoning_content.insert(index, reasoning);
+        }
 
         Ok(NvCreateChatCompletionStreamResponse {
             inner: stream_response,
+            reasoning_content,
         })
     }
 
     fn get_isl(&self) -> Option<u32> {
         Some(self.usage.prompt_tokens)
     }
+
+    fn is_usage_enabled(&self) -> bool {
+        self.options.enable_usage
+    }
+
+    fn create_usage_chunk(
+        &self,
+        mut usage: async_openai::types::CompletionUsage,
+    ) -> NvCreateChatCompletionStreamResponse {
+        if usage.completion_tokens_details.is_none() {
+            usage.completion_tokens_details = self.usage.completion_tokens_details.clone();
+        }
+        let inner = async_openai::types::CreateChatCompletionStreamResponse {
+            id: self.id.clone(),
+            object: self.object.clone(),
+            created: self.created,
+            model: self.model.clone(),
+            system_fingerprint: self.system_fingerprint.clone(),
+            choices: vec![],
+            usage: Some(usage),
+            service_tier: self.service_tier.clone(),
+        };
+        NvCreateChatCompletionStreamResponse {
+            inner,
+            reasoning_content: HashMap::new(),
+        }
+    }
+
+    fn finish_stream(&mut self) -> Vec<NvCreateChatCompletionStreamResponse> {
+        // choices which finished have no parser left
+        let mut parsers: Vec<_> = self.choice_parsers.drain().collect();
+        parsers.sort_by_key(|(index, _)| *index);
+        parsers
+            .into_iter()
+            .filter_map(|(index, mut parser)| {
+                let parsed = parser.finish();
+                if parsed.content.is_empty() && parsed.tool_calls.is_empty() {
+                    return None;
+                }
+                let text = (!parsed.content.is_empty()).then_some(parsed.content);
+                let mut inner = self.create_choice(index, text, None, None);
+                if !parsed.tool_calls.is_empty() {
+                    inner.choices[0].delta.tool_calls =
+                        Some(parsed.tool_calls.into_iter().map(tool_call_chunk).collect());
+                }
+                Some(NvCreateChatCompletionStreamResponse {
+                    inner,
+                    reasoning_content: HashMap::new(),
+                })
+            })
+            .collect()
+    }
+}
+
+/// Converts a parsed tool call delta to OpenAI's chat format.
+fn tool_call_chunk(
+    delta: ToolCallDelta,
+) -> async_openai::types::ChatCompletionMessageToolCallChunk {
+    async_openai::types::ChatCompletionMessageToolCallChunk {
+        index: delta.index,
+        id: delta.id,
+        r#type: Some(async_openai::types::ChatCompletionToolType::Function),
+        function: Some(async_openai::types::FunctionCallStream {
+            name: delta.name,
+            arguments: Some(delta.arguments),
+        }),
+    }
+}
+
+/// Converts the log probabilities of a backend response to OpenAI's chat format.
+///
+/// # Returns
+/// * `None` if the engine did not return log probabilities.
+fn create_logprobs(
+    delta: &common::llm_backend::BackendOutput,
+) -> Option<async_openai::types::ChatChoiceLogprobs> {
+    let log_probs = delta.log_probs.as_ref()?;
+    let content = log_probs
+        .iter()
+        .enumerate()
+        .map(|(i, logprob)| {
+            let token = delta.tokens.get(i).cloned().flatten().unwrap_or_default();
+            let top_logprobs = delta
+                .top_logprobs
+                .as_ref()
+                .and_then(|top_logprobs| top_logprobs.get(i))
+                .map(|top_logprobs| {
+                    top_logprobs
+                        .iter()
+                        .map(|top| {
+                            let token = top.token.clone().unwrap_or_default();
+                            async_openai::types::TopLogprobs {
+                                bytes: Some(token.as_bytes().to_vec()),
+                                token,
+                                logprob: top.logprob as f32,
+                            }
+                        })
+                        .collect()
+                })
+                .unwrap_or_default();
+            async_openai::types::ChatCompletionTokenLogprob {
+                bytes: Some(token.as_bytes().to_vec()),
+                token,
+                logprob: *logprob as f32,
+                top_logprobs,
+            }
+        })
+        .collect();
+
+    Some(async_openai::types::ChatChoiceLogprobs {
+        content: Some(content),
+        refusal: None,
+    })
 }
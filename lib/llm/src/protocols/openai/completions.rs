@@ -21,7 +21,9 @@ use validator::Validate;
 use super::{
     common::{self, SamplingOptionsProvider, StopConditionsProvider},
     nvext::{NvExt, NvExtProvider},
-    ContentProvider, OpenAISamplingOptionsProvider, OpenAIStopConditionsProvider,
+    validate_range, ContentProvider, OpenAIGuidedDecodingOptionsProvider,
+    OpenAIOutputOptionsProvider, OpenAISamplingOptionsProvider, OpenAIStopConditionsProvider,
+    MAX_COMPLETION_LOGPROBS,
 };
 
 mod aggregator;
@@ -43,6 +45,11 @@ pub struct NvCreateCompletionRequest {
 pub struct NvCreateCompletionResponse {
     #[serde(flatten)]
     pub inner: async_openai::types::CreateCompletionResponse,
+
+    /// Cumulative log probability of the choice of this chunk so far, used to pick the
+    /// `best_of` choices. Only known in the process which generated the response.
+    #[serde(skip)]
+    pub cum_log_probs: Option<f64>,
 }
 
 impl ContentProvider for async_openai::types::Choice {
@@ -124,11 +131,42 @@ impl OpenAISamplingOptionsProvider for NvCreateCompletionRequest {
         self.inner.presence_penalty
     }
 
+    fn get_n(&self) -> Option<u8> {
+        self.inner.n
+    }
+
+    fn get_best_of(&self) -> Option<u8> {
+        self.inner.best_of
+    }
+
+    fn nvext(&self) -> Option<&NvExt> {
+        self.nvext.as_ref()
+    }
+}
+
+impl OpenAIGuidedDecodingOptionsProvider for NvCreateCompletionRequest {
+    fn get_response_format(&self) -> Option<&async_openai::types::ResponseFormat> {
+        None
+    }
+
     fn nvext(&self) -> Option<&NvExt> {
         self.nvext.as_ref()
     }
 }
 
+impl OpenAIOutputOptionsProvider for NvCreateCompletionRequest {
+    fn get_logprobs(&self) -> anyhow::Result<Option<u32>> {
+        let logprobs = validate_range(self.inner.logprobs, &(0, MAX_COMPLETION_LOGPROBS))
+            .map_err(|e| anyhow::anyhow!("Error validating logprobs: {}", e))?;
+        Ok(logprobs.map(Into::into))
+    }
+
+    /// Completions return the raw text, reasoning included.
+    fn get_reasoning_mode(&self) -> Option<common::ReasoningMode> {
+        None
+    }
+}
+
 impl OpenAIStopConditionsProvider for NvCreateCompletionRequest {
     fn get_max_tokens(&self) -> Option<u32> {
         self.inner.max_tokens
@@ -184,7 +222,10 @@ impl ResponseFactory {
             system_fingerprint: self.system_fingerprint.clone(),
             usage,
         };
-        NvCreateCompletionResponse { inner }
+        NvCreateCompletionResponse {
+            inner,
+            cum_log_probs: None,
+        }
     }
 }
 
@@ -41,6 +41,7 @@ struct DeltaChoice {
     text: String,
     finish_reason: Option<FinishReason>,
     logprobs: Option<async_openai::types::Logprobs>,
+    cum_log_probs: Option<f64>,
 }
 
 impl Default for DeltaAggregator {
@@ -65,6 +66,23 @@ impl DeltaAggregator {
     /// Aggregates a stream of [`Annotated<CompletionResponse>`]s into a single [`CompletionResponse`].
     pub async fn apply(
         stream: DataStream<Annotated<NvCreateCompletionResponse>>,
+    ) -> Result<NvCreateCompletionResponse> {
+        Self::aggregate(stream, None).await
+    }
+
+    /// Aggregates the `best_of` choices of a stream into a single [`CompletionResponse`] with
+    /// the `n` choices of the highest cumulative log probability, most likely first. Fails if
+    /// the engine did not return the log probabilities of every choice.
+    pub async fn apply_best_of(
+        stream: DataStream<Annotated<NvCreateCompletionResponse>>,
+        n: usize,
+    ) -> Result<NvCreateCompletionResponse> {
+        Self::aggregate(stream, Some(n)).await
+    }
+
+    async fn aggregate(
+        stream: DataStream<Annotated<NvCreateCompletionResponse>>,
+        best_of_n: Option<usize>,
     ) -> Result<NvCreateCompletionResponse> {
         let aggregator = stream
             .fold(DeltaAggregator::new(), |mut aggregator, delta| async move {
@@ -94,6 +112,7 @@ impl DeltaAggregator {
                     }
 
                     // handle the choices
+                    let cum_log_probs = delta.cum_log_probs;
                     for choice in delta.inner.choices {
                         let state_choice =
                             aggregator
@@ -103,12 +122,29 @@ impl DeltaAggregator {
                                     index: choice.index,
                                     text: "".to_string(),
                                     finish_reason: None,
-                                    logprobs: choice.logprobs,
+                                    logprobs: None,
+                                    cum_log_probs: None,
                                 });
 
                         state_choice.text.push_str(&choice.text);
 
-                        // TODO - handle logprobs
+                        if cum_log_probs.is_some() {
+                            state_choice.cum_log_probs = cum_log_probs;
+                        }
+
+                        if let Some(logprobs) = choice.logprobs {
+                            match &mut state_choice.logprobs {
+                                Some(state_logprobs) => {
+                                    state_logprobs.tokens.extend(logprobs.tokens);
+                                    state_logprobs
+                                        .token_logprobs
+                                        .extend(logprobs.token_logprobs);
+                                    state_logprobs.top_logprobs.extend(logprobs.top_logprobs);
+                                    state_logprobs.text_offset.extend(logprobs.text_offset);
+                                }
+                                None => state_choice.logprobs = Some(logprobs),
+                            }
+                        }
 
                         // Handle CompletionFinishReason -> FinishReason conversation
                         state_choice.finish_reason = match choice.finish_reason {
@@ -137,14 +173,31 @@ impl DeltaAggregator {
         };
 
         // extra the aggregated deltas and sort by index
-        let mut choices: Vec<_> = aggregator
-            .choices
-            .into_values()
+        let mut choices: Vec<_> = aggregator.choices.into_values().collect();
+        choices.sort_by(|a, b| a.index.cmp(&b.index));
+
+        // keep the most likely choices
+        if let Some(n) = best_of_n {
+            if choices.iter().any(|choice| choice.cum_log_probs.is_none()) {
+                anyhow::bail!(
+                    "best_of requires log probabilities, which the engine did not return"
+                );
+            }
+            choices.sort_by(|a, b| {
+                let likelihood = |choice: &DeltaChoice| choice.cum_log_probs.unwrap_or(f64::MIN);
+                likelihood(b).total_cmp(&likelihood(a))
+            });
+            choices.truncate(n);
+            for (index, choice) in choices.iter_mut().enumerate() {
+                choice.index = index as u32;
+            }
+        }
+
+        let choices = choices
+            .into_iter()
             .map(async_openai::types::Choice::from)
             .collect();
 
-        choices.sort_by(|a, b| a.index.cmp(&b.index));
-
         let inner = async_openai::types::CreateCompletionResponse {
             id: aggregator.id,
             created: aggregator.created,
@@ -155,7 +208,10 @@ impl DeltaAggregator {
             choices,
         };
 
-        let response = NvCreateCompletionResponse { inner };
+        let response = NvCreateCompletionResponse {
+            inner,
+            cum_log_probs: None,
+        };
 
         Ok(response)
     }
@@ -187,6 +243,15 @@ impl NvCreateCompletionResponse {
     ) -> Result<NvCreateCompletionResponse> {
         DeltaAggregator::apply(stream).await
     }
+
+    /// Aggregates an annotated stream of the `best_of` choices of a request, keeping the `n`
+    /// most likely ones. See [`DeltaAggregator::apply_best_of`].
+    pub async fn from_annotated_stream_best_of(
+        stream: DataStream<Annotated<NvCreateCompletionResponse>>,
+        n: usize,
+    ) -> Result<NvCreateCompletionResponse> {
+        DeltaAggregator::apply_best_of(stream, n).await
+    }
 }
 
 #[cfg(test)]
@@ -225,7 +290,10 @@ mod tests {
             object: "text_completion".to_string(),
         };
 
-        let response = NvCreateCompletionResponse { inner };
+        let response = NvCreateCompletionResponse {
+            inner,
+            cum_log_probs: None,
+        };
 
         Annotated {
             data: Some(response),
@@ -326,6 +394,155 @@ mod tests {
         );
     }
 
+    #[tokio::test]
+    async fn test_logprobs_from_backend() {
+        use crate::protocols::common::llm_backend::{BackendOutput, TopLogProb};
+        use crate::protocols::openai::completions::delta::{DeltaGenerator, DeltaGeneratorOptions};
+        use crate::protocols::openai::DeltaGeneratorExt;
+
+        let mut generator = DeltaGenerator::new(
+            "meta/llama-3.1-8b".to_string(),
+            DeltaGeneratorOptions {
+                enable_usage: false,
+                enable_logprobs: true,
+            },
+        );
+        let output = |token_id: u32, token: &str, logprob: f64| BackendOutput {
+            token_ids: vec![token_id],
+            tokens: vec![Some(token.to_string())],
+            text: Some(token.to_string()),
+            cum_log_probs: None,
+            log_probs: Some(vec![logprob]),
+            top_logprobs: Some(vec![vec![TopLogProb {
+                token_id,
+                token: Some(token.to_string()),
+                logprob,
+            }]]),
+            finish_reason: None,
+            index: None,
+            reasoning_text: None,
+            reasoning_tokens: 0,
+        };
+        let deltas = vec![
+            Annotated::from_data(
+                generator
+                    .choice_from_postprocessor(output(1, "Hello", -0.5))
+                    .unwrap(),
+            ),
+            Annotated::from_data(
+                generator
+                    .choice_from_postprocessor(output(2, " world", -1.5))
+                    .unwrap(),
+            ),
+        ];
+
+        let response = DeltaAggregator::apply(Box::pin(stream::iter(deltas)))
+            .await
+            .unwrap();
+
+        let logprobs = response.inner.choices[0].logprobs.as_ref().unwrap();
+        assert_eq!(logprobs.tokens, vec!["Hello", " world"]);
+        assert_eq!(logprobs.token_logprobs, vec![Some(-0.5), Some(-1.5)]);
+        assert_eq!(logprobs.text_offset, vec![0, 5]);
+        assert_eq!(
+            logprobs.top_logprobs[1],
+            serde_json::json!({" world": -1.5})
+        );
+    }
+
+    #[tokio::test]
+    async fn test_usage_chunk() {
+        use crate::protocols::common::llm_backend::BackendOutput;
+        use crate::protocols::openai::completions::delta::{DeltaGenerator, DeltaGeneratorOptions};
+        use crate::protocols::openai::DeltaGeneratorExt;
+
+        let mut generator = DeltaGenerator::new(
+            "meta/llama-3.1-8b".to_string(),
+            DeltaGeneratorOptions {
+                enable_usage: true,
+                enable_logprobs: false,
+            },
+        );
+        let output = BackendOutput {
+            token_ids: vec![1, 2],
+            tokens: vec![None, None],
+            text: Some("Hello world".to_string()),
+            cum_log_probs: None,
+            log_probs: None,
+            top_logprobs: None,
+            finish_reason: None,
+            index: None,
+            reasoning_text: None,
+            reasoning_tokens: 0,
+        };
+        let delta = generator.choice_from_postprocessor(output).unwrap();
+        assert!(delta.inner.usage.is_none());
+
+        let usage = generator.create_usage_chunk(async_openai::types::CompletionUsage {
+            prompt_tokens: 5,
+            completion_tokens: 2,
+            total_tokens: 7,
+            prompt_tokens_details: Some(async_openai::types::PromptTokensDetails {
+                audio_tokens: None,
+                cached_tokens: Some(4),
+            }),
+            completion_tokens_details: None,
+        });
+        assert!(usage.inner.choices.is_empty());
+
+        let deltas = vec![Annotated::from_data(delta), Annotated::from_data(usage)];
+        let response = DeltaAggregator::apply(Box::pin(stream::iter(deltas)))
+            .await
+            .unwrap();
+
+        assert_eq!(response.inner.choices.len(), 1);
+        assert_eq!(response.inner.choices[0].text, "Hello world");
+        let usage = response.inner.usage.unwrap();
+        assert_eq!(usage.total_tokens, 7);
+        assert_eq!(usage.prompt_tokens_details.unwrap().cached_tokens, Some(4));
+    }
+
+    #[tokio::test]
+    async fn test_best_of() {
+        let delta = |index: u32, text: &str, cum_log_probs: f64| {
+            let mut delta = create_test_delta(index, text, Some("stop".to_string()));
+            delta.data.as_mut().unwrap().cum_log_probs = Some(cum_log_probs);
+            delta
+        };
+        let deltas = vec![
+            delta(0, "unlikely", -9.0),
+            delta(1, "likely", -1.0),
+            delta(2, "less likely", -2.0),
+        ];
+
+        let response = DeltaAggregator::apply_best_of(Box::pin(stream::iter(deltas)), 2)
+            .await
+            .unwrap();
+
+        let choices: Vec<_> = response
+            .inner
+            .choices
+            .iter()
+            .map(|choice| (choice.index, choice.text.as_str()))
+            .collect();
+        assert_eq!(choices, vec![(0, "likely"), (1, "less likely")]);
+    }
+
+    #[tokio::test]
+    async fn test_best_of_without_logprobs() {
+        let deltas = vec![
+            create_test_delta(0, "one", Some("stop".to_string())),
+            create_test_delta(1, "two", Some("stop".to_string())),
+        ];
+
+        // without log probabilities the choices cannot be ranked
+        assert!(
+            DeltaAggregator::apply_best_of(Box::pin(stream::iter(deltas)), 1)
+                .await
+                .is_err()
+        );
+    }
+
     #[tokio::test]
     async fn test_multiple_choices() {
         // Create a delta with multiple choices
@@ -352,7 +569,10 @@ mod tests {
             object: "text_completion".to_string(),
         };
 
-        let response = NvCreateCompletionResponse { inner };
+        let response = NvCreateCompletionResponse {
+            inner,
+            cum_log_probs: None,
+        };
 
         let annotated_delta = Annotated {
             data: Some(response),
@@ -13,6 +13,8 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use std::collections::HashMap;
+
 use super::{NvCreateCompletionRequest, NvCreateCompletionResponse};
 use crate::protocols::common;
 
@@ -21,8 +23,12 @@ impl NvCreateCompletionRequest {
     // inspect the request to extract options
     pub fn response_generator(&self) -> DeltaGenerator {
         let options = DeltaGeneratorOptions {
-            enable_usage: true,
-            enable_logprobs: false,
+            enable_usage: self
+                .inner
+                .stream_options
+                .as_ref()
+                .is_some_and(|options| options.include_usage),
+            enable_logprobs: self.inner.logprobs.is_some(),
         };
 
         DeltaGenerator::new(self.inner.model.clone(), options)
@@ -43,6 +49,10 @@ pub struct DeltaGenerator {
     model: String,
     system_fingerprint: Option<String>,
     usage: async_openai::types::CompletionUsage,
+    // characters of text generated so far by each choice, for the `text_offset` of logprobs
+    text_offsets: HashMap<u32, u32>,
+    // cumulative log probability of each choice so far
+    cum_log_probs: HashMap<u32, f64>,
     options: DeltaGeneratorOptions,
 }
 
@@ -74,6 +84,8 @@ impl DeltaGenerator {
             model,
             system_fingerprint: None,
             usage,
+            text_offsets: HashMap::new(),
+            cum_log_probs: HashMap::new(),
             options,
         }
     }
@@ -90,11 +102,6 @@ impl DeltaGenerator {
     ) -> NvCreateCompletionResponse {
         // todo - update for tool calling
 
-        let mut usage = self.usage.clone();
-        if self.options.enable_usage {
-            usage.total_tokens = usage.prompt_tokens + usage.completion_tokens;
-        }
-
         let inner = async_openai::types::CreateCompletionResponse {
             id: self.id.clone(),
             object: self.object.clone(),
@@ -107,14 +114,58 @@ impl DeltaGenerator {
                 finish_reason,
                 logprobs: None,
             }],
-            usage: if self.options.enable_usage {
-                Some(usage)
-            } else {
-                None
-            },
+            usage: None,
         };
 
-        NvCreateCompletionResponse { inner }
+        NvCreateCompletionResponse {
+            inner,
+            cum_log_probs: None,
+        }
+    }
+
+    /// Converts the log probabilities of a backend response to OpenAI's completions format.
+    /// Returns `None` if the engine did not return log probabilities.
+    fn create_logprobs(
+        &mut self,
+        delta: &common::llm_backend::BackendOutput,
+    ) -> Option<async_openai::types::Logprobs> {
+        let log_probs = delta.log_probs.as_ref()?;
+        let text_offset = self
+            .text_offsets
+            .entry(delta.index.unwrap_or(0))
+            .or_default();
+        let mut logprobs = async_openai::types::Logprobs {
+            tokens: Vec::with_capacity(log_probs.len()),
+            token_logprobs: Vec::with_capacity(log_probs.len()),
+            top_logprobs: Vec::with_capacity(log_probs.len()),
+            text_offset: Vec::with_capacity(log_probs.len()),
+        };
+        for (i, logprob) in log_probs.iter().enumerate() {
+            let token = delta.tokens.get(i).cloned().flatten().unwrap_or_default();
+            let top_logprobs: serde_json::Map<String, serde_json::Value> = delta
+                .top_logprobs
+                .as_ref()
+                .and_then(|top_logprobs| top_logprobs.get(i))
+                .into_iter()
+                .flatten()
+                .map(|top| {
+                    (
+                        top.token.clone().unwrap_or_default(),
+                        serde_json::json!(top.logprob as f32),
+                    )
+                })
+                .collect();
+
+            logprobs.text_offset.push(*text_offset);
+            // SAFETY: a single completion will not exceed u32::MAX characters
+            *text_offset += token.chars().count() as u32;
+            logprobs.tokens.push(token);
+            logprobs.token_logprobs.push(Some(*logprob as f32));
+            logprobs
+                .top_logprobs
+                .push(serde_json::Value::Object(top_logprobs));
+        }
+        Some(logprobs)
     }
 }
 
@@ -124,29 +175,76 @@ impl crate::protocols::openai::DeltaGeneratorExt<NvCreateCompletionResponse> for
         delta: common::llm_backend::BackendOutput,
     ) -> anyhow::Result<NvCreateCompletionResponse> {
         // aggregate usage
-        if self.options.enable_usage {
-            // SAFETY: Casting from `usize` to `u32` could lead to precision loss after `u32::MAX`,
-            // but this will not be an issue until context lengths exceed 4_294_967_295.
-            let token_length: u32 = delta
-                .token_ids
-                .len()
-                .try_into()
-                .expect("token_ids length exceeds u32::MAX");
-
-            self.usage.completion_tokens += token_length;
-        }
-
-        // TODO logprobs
+        // SAFETY: Casting from `usize` to `u32` could lead to precision loss after `u32::MAX`,
+        // but this will not be an issue until context lengths exceed 4_294_967_295.
+        let token_length: u32 = delta
+            .token_ids
+            .len()
+            .try_into()
+            .expect("token_ids length exceeds u32::MAX");
+
+        self.usage.completion_tokens += token_length;
+
+        let logprobs = if self.options.enable_logprobs {
+            self.create_logprobs(&delta)
+        } else {
+            None
+        };
 
         let finish_reason = delta.finish_reason.map(Into::into);
 
-        // create choice
+        // track the cumulative log probability of the choice, from the engine if it reports it.
+        // It stays unknown until the engine returns a log probability.
         let index = delta.index.unwrap_or(0);
-        let response = self.create_choice(index, delta.text.clone(), finish_reason);
+        let cum_log_probs = match (delta.cum_log_probs, &delta.log_probs) {
+            (Some(engine_cum_log_probs), _) => {
+                self.cum_log_probs.insert(index, engine_cum_log_probs);
+                Some(engine_cum_log_probs)
+            }
+            (None, Some(log_probs)) => {
+                let cum_log_probs = self.cum_log_probs.entry(index).or_default();
+                *cum_log_probs += log_probs.iter().sum::<f64>();
+                Some(*cum_log_probs)
+            }
+            (None, None) => self.cum_log_probs.get(&index).copied(),
+        };
+
+        // create choice
+        let mut response = self.create_choice(index, delta.text.clone(), finish_reason);
+        response.inner.choices[0].logprobs = logprobs;
+        response.cum_log_probs = cum_log_probs;
         Ok(response)
     }
 
     fn get_isl(&self) -> Option<u32> {
         Some(self.usage.prompt_tokens)
     }
+
+    fn is_usage_enabled(&self) -> bool {
+        self.options.enable_usage
+    }
+
+    fn create_usage_chunk(
+        &self,
+        usage: async_openai::types::CompletionUsage,
+    ) -> NvCreateCompletionResponse {
+        let inner = async_openai::types::CreateCompletionResponse {
+            id: self.id.clone(),
+            object: self.object.clone(),
+            created: self.created,
+            model: self.model.clone(),
+            system_fingerprint: self.system_fingerprint.clone(),
+            choices: vec![],
+            usage: Some(usage),
+        };
+
+        NvCreateCompletionResponse {
+            inner,
+            cum_log_probs: None,
+        }
+    }
+
+    fn finish_stream(&mut self) -> Vec<NvCreateCompletionResponse> {
+        Vec::new()
+    }
 }
@@ -13,6 +13,8 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
 use derive_builder::Builder;
 use serde::{Deserialize, Serialize};
 use validator::{Validate, ValidationError};
@@ -61,6 +63,54 @@ pub struct NvExt {
     #[serde(default, skip_serializing_if = "Option::is_none")]
     #[builder(default, setter(strip_option))]
     pub annotations: Option<Vec<String>>,
+
+    /// If set, the output is constrained to fully match this regular expression.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[builder(default, setter(into, strip_option))]
+    pub guided_regex: Option<String>,
+
+    /// If set, the output is constrained to match this context-free grammar, in the EBNF syntax
+    /// of the engine.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[builder(default, setter(into, strip_option))]
+    pub guided_grammar: Option<String>,
+
+    /// What the preprocessor does with a prompt which does not fit in the context of the model.
+    /// By default the request is rejected.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[builder(default, setter(strip_option))]
+    pub truncation: Option<TruncationMode>,
+
+    /// If false, the reasoning of thinking models is dropped from chat completions. By default
+    /// it is returned in the `reasoning_content` of the choices.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[builder(default, setter(strip_option))]
+    pub include_reasoning: Option<bool>,
+
+    /// How urgent the request is, higher is more urgent. 0 by default. With KV aware routing,
+    /// requests with a priority above 0 are kept off workers whose KV cache is nearly full.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[builder(default, setter(strip_option))]
+    pub priority: Option<i32>,
+
+    /// The Unix time, in milliseconds, by which the request must be complete. Generation is
+    /// stopped when it passes. The `X-Request-Deadline` header of the HTTP service sets it too.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[builder(default, setter(strip_option))]
+    pub deadline_ms: Option<u64>,
+}
+
+/// How the preprocessor shortens a prompt which does not fit in the context of the model
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum TruncationMode {
+    /// Reject the request
+    #[default]
+    Disabled,
+
+    /// Drop the oldest turns of a chat, always keeping its system messages and its last turn.
+    /// Completion prompts and chats with images or audio are not truncated.
+    Left,
 }
 
 impl Default for NvExt {
@@ -73,9 +123,20 @@ impl NvExt {
     pub fn builder() -> NvExtBuilder {
         NvExtBuilder::default()
     }
+
+    /// The `deadline_ms` of the request as a [`SystemTime`]
+    pub fn deadline(&self) -> Option<SystemTime> {
+        self.deadline_ms
+            .map(|deadline_ms| UNIX_EPOCH + Duration::from_millis(deadline_ms))
+    }
 }
 
-fn validate_nv_ext(_nv_ext: &NvExt) -> Result<(), ValidationError> {
+fn validate_nv_ext(nv_ext: &NvExt) -> Result<(), ValidationError> {
+    if nv_ext.guided_regex.is_some() && nv_ext.guided_grammar.is_some() {
+        let mut error = ValidationError::new("guided_decoding");
+        error.message = Some("only one of guided_regex and guided_grammar can be set".into());
+        return Err(error);
+    }
     Ok(())
 }
 
@@ -114,6 +175,10 @@ mod tests {
         assert_eq!(nv_ext.top_k, None);
         assert_eq!(nv_ext.repetition_penalty, None);
         assert_eq!(nv_ext.greed_sampling, None);
+        assert_eq!(nv_ext.truncation, None);
+        assert_eq!(nv_ext.include_reasoning, None);
+        assert_eq!(nv_ext.priority, None);
+        assert_eq!(nv_ext.deadline(), None);
     }
 
     // Test valid builder configurations
@@ -136,6 +201,19 @@ mod tests {
         assert!(nv_ext.validate().is_ok());
     }
 
+    #[test]
+    fn test_guided_decoding_options_are_exclusive() {
+        let nv_ext = NvExt::builder()
+            .guided_regex("[a-z]+")
+            .guided_grammar("root ::= \"a\"")
+            .build()
+            .unwrap();
+        assert!(nv_ext.validate().is_err());
+
+        let nv_ext = NvExt::builder().guided_regex("[a-z]+").build().unwrap();
+        assert!(nv_ext.validate().is_ok());
+    }
+
     // Test invalid `top_k` validation using proptest
     proptest! {
         #[test]
@@ -0,0 +1,704 @@
+// SPDX-FileCopyrightText: Copyright (c) 2024-2025 NVIDIA CORPORATION & AFFILIATES. All rights reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+//! The OpenAI Responses API.
+//!
+//! Responses requests are served by the chat completions engine of the model: the request's
+//! instructions and input items become chat messages, and the stream of chat completion deltas
+//! is turned back into Responses output items and streaming events by [`ResponseGenerator`].
+
+use std::collections::HashMap;
+
+use async_openai::types::{
+    ChatCompletionMessageToolCall, ChatCompletionNamedToolChoice,
+    ChatCompletionRequestAssistantMessageArgs, ChatCompletionRequestMessage,
+    ChatCompletionRequestSystemMessageArgs, ChatCompletionRequestToolMessageArgs,
+    ChatCompletionRequestUserMessageArgs, ChatCompletionStreamOptions, ChatCompletionTool,
+    ChatCompletionToolChoiceOption, ChatCompletionToolType, CreateChatCompletionRequestArgs,
+    FunctionCall, FunctionName, FunctionObject,
+};
+use serde::{Deserialize, Serialize};
+
+use super::chat_completions::NvCreateChatCompletionRequest;
+use super::nvext::NvExt;
+
+mod generator;
+
+pub use generator::ResponseGenerator;
+
+/// A request to the `/v1/responses` endpoint.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct NvCreateResponseRequest {
+    pub model: String,
+
+    pub input: ResponseInput,
+
+    /// Prepended as a system message. Unlike the input, not carried over to chained responses.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub instructions: Option<String>,
+
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tools: Vec<ResponseTool>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tool_choice: Option<ResponseToolChoice>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub parallel_tool_calls: Option<bool>,
+
+    /// Continue the conversation of this stored response
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub previous_response_id: Option<String>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stream: Option<bool>,
+
+    /// Store the response so it can be retrieved and chained. Default: true
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub store: Option<bool>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub temperature: Option<f32>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub top_p: Option<f32>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_output_tokens: Option<u32>,
+
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub metadata: HashMap<String, String>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub user: Option<String>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub nvext: Option<NvExt>,
+}
+
+/// The input of a request: a user message, or a list of items.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(untagged)]
+pub enum ResponseInput {
+    Text(String),
+    Items(Vec<ResponseInputItem>),
+}
+
+/// An input item. Messages may leave out their `type`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(untagged)]
+pub enum ResponseInputItem {
+    Item(ResponseItem),
+    Message(ResponseMessage),
+}
+
+/// An item of a conversation, as found in the input and output of responses.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ResponseItem {
+    Message(ResponseMessage),
+    FunctionCall(FunctionCallItem),
+    FunctionCallOutput(FunctionCallOutputItem),
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ResponseMessage {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+
+    pub role: MessageRole,
+
+    pub content: MessageContent,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub status: Option<ItemStatus>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum MessageRole {
+    User,
+    Assistant,
+    System,
+    Developer,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(untagged)]
+pub enum MessageContent {
+    Text(String),
+    Parts(Vec<ContentPart>),
+}
+
+impl MessageContent {
+    /// The text parts of the content, concatenated
+    pub fn text(&self) -> String {
+        match self {
+            MessageContent::Text(text) => text.clone(),
+            MessageContent::Parts(parts) => parts
+                .iter()
+                .filter_map(|part| match part {
+                    ContentPart::InputText { text } | ContentPart::OutputText { text, .. } => {
+                        Some(text.as_str())
+                    }
+                    ContentPart::Refusal { .. } => None,
+                })
+                .collect(),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ContentPart {
+    InputText {
+        text: String,
+    },
+    OutputText {
+        text: String,
+        #[serde(default)]
+        annotations: Vec<serde_json::Value>,
+    },
+    Refusal {
+        refusal: String,
+    },
+}
+
+/// A call of one of the request's tools by the model
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct FunctionCallItem {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+
+    /// Identifies the call in the `function_call_output` item that answers it
+    pub call_id: String,
+
+    pub name: String,
+
+    /// JSON encoded arguments
+    pub arguments: String,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub status: Option<ItemStatus>,
+}
+
+/// The result of a function call, sent by the client
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct FunctionCallOutputItem {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+
+    pub call_id: String,
+
+    pub output: String,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub status: Option<ItemStatus>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ItemStatus {
+    InProgress,
+    Completed,
+    Incomplete,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ResponseTool {
+    Function(FunctionTool),
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct FunctionTool {
+    pub name: String,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+
+    /// JSON schema of the arguments
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub parameters: Option<serde_json::Value>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub strict: Option<bool>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(untagged)]
+pub enum ResponseToolChoice {
+    Mode(ToolChoiceMode),
+    Function(NamedToolChoice),
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ToolChoiceMode {
+    None,
+    Auto,
+    Required,
+}
+
+/// Force a call of this function
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct NamedToolChoice {
+    /// Always `function`
+    #[serde(rename = "type")]
+    pub kind: String,
+
+    pub name: String,
+}
+
+/// A response object, returned by the `/v1/responses` endpoints and by the streaming events
+/// that start and end a response.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct NvResponse {
+    pub id: String,
+
+    /// Always `response`
+    pub object: String,
+
+    /// Unix timestamp in seconds
+    pub created_at: u64,
+
+    pub status: ResponseStatus,
+
+    pub model: String,
+
+    pub output: Vec<ResponseItem>,
+
+    pub instructions: Option<String>,
+
+    pub previous_response_id: Option<String>,
+
+    pub incomplete_details: Option<IncompleteDetails>,
+
+    pub error: Option<ResponseError>,
+
+    pub usage: Option<ResponseUsage>,
+
+    pub tools: Vec<ResponseTool>,
+
+    pub tool_choice: Option<ResponseToolChoice>,
+
+    pub temperature: Option<f32>,
+
+    pub top_p: Option<f32>,
+
+    pub max_output_tokens: Option<u32>,
+
+    pub store: bool,
+
+    pub metadata: HashMap<String, String>,
+
+    pub user: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ResponseStatus {
+    InProgress,
+    Completed,
+    Incomplete,
+    Failed,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct IncompleteDetails {
+    pub reason: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ResponseError {
+    pub code: String,
+    pub message: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ResponseUsage {
+    pub input_tokens: u32,
+    pub output_tokens: u32,
+    pub total_tokens: u32,
+}
+
+/// An event of a streamed response. Sent as an SSE event named after its `type`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ResponseStreamEvent {
+    pub sequence_number: u64,
+
+    #[serde(flatten)]
+    pub kind: ResponseEventKind,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "type")]
+pub enum ResponseEventKind {
+    #[serde(rename = "response.created")]
+    Created { response: NvResponse },
+
+    #[serde(rename = "response.in_progress")]
+    InProgress { response: NvResponse },
+
+    #[serde(rename = "response.output_item.added")]
+    OutputItemAdded {
+        output_index: u32,
+        item: ResponseItem,
+    },
+
+    #[serde(rename = "response.content_part.added")]
+    ContentPartAdded {
+        item_id: String,
+        output_index: u32,
+        content_index: u32,
+        part: ContentPart,
+    },
+
+    #[serde(rename = "response.output_text.delta")]
+    OutputTextDelta {
+        item_id: String,
+        output_index: u32,
+        content_index: u32,
+        delta: String,
+    },
+
+    #[serde(rename = "response.output_text.done")]
+    OutputTextDone {
+        item_id: String,
+        output_index: u32,
+        content_index: u32,
+        text: String,
+    },
+
+    #[serde(rename = "response.content_part.done")]
+    ContentPartDone {
+        item_id: String,
+        output_index: u32,
+        content_index: u32,
+        part: ContentPart,
+    },
+
+    #[serde(rename = "response.function_call_arguments.delta")]
+    FunctionCallArgumentsDelta {
+        item_id: String,
+        output_index: u32,
+        delta: String,
+    },
+
+    #[serde(rename = "response.function_call_arguments.done")]
+    FunctionCallArgumentsDone {
+        item_id: String,
+        output_index: u32,
+        arguments: String,
+    },
+
+    #[serde(rename = "response.output_item.done")]
+    OutputItemDone {
+        output_index: u32,
+        item: ResponseItem,
+    },
+
+    #[serde(rename = "response.completed")]
+    Completed { response: NvResponse },
+
+    #[serde(rename = "response.incomplete")]
+    Incomplete { response: NvResponse },
+
+    #[serde(rename = "response.failed")]
+    Failed { response: NvResponse },
+}
+
+impl ResponseEventKind {
+    /// The event's `type`, used as the SSE event name
+    pub fn name(&self) -> &'static str {
+        match self {
+            ResponseEventKind::Created { .. } => "response.created",
+            ResponseEventKind::InProgress { .. } => "response.in_progress",
+            ResponseEventKind::OutputItemAdded { .. } => "response.output_item.added",
+            ResponseEventKind::ContentPartAdded { .. } => "response.content_part.added",
+            ResponseEventKind::OutputTextDelta { .. } => "response.output_text.delta",
+            ResponseEventKind::OutputTextDone { .. } => "response.output_text.done",
+            ResponseEventKind::ContentPartDone { .. } => "response.content_part.done",
+            ResponseEventKind::FunctionCallArgumentsDelta { .. } => {
+                "response.function_call_arguments.delta"
+            }
+            ResponseEventKind::FunctionCallArgumentsDone { .. } => {
+                "response.function_call_arguments.done"
+            }
+            ResponseEventKind::OutputItemDone { .. } => "response.output_item.done",
+            ResponseEventKind::Completed { .. } => "response.completed",
+            ResponseEventKind::Incomplete { .. } => "response.incomplete",
+            ResponseEventKind::Failed { .. } => "response.failed",
+        }
+    }
+}
+
+impl NvCreateResponseRequest {
+    /// The request's input as items. A text input is a single user message.
+    pub fn input_items(&self) -> Vec<ResponseItem> {
+        match &self.input {
+            ResponseInput::Text(text) => vec![ResponseItem::Message(ResponseMessage {
+                id: None,
+                role: MessageRole::User,
+                content: MessageContent::Text(text.clone()),
+                status: None,
+            })],
+            ResponseInput::Items(items) => items
+                .iter()
+                .map(|item| match item {
+                    ResponseInputItem::Item(item) => item.clone(),
+                    ResponseInputItem::Message(message) => ResponseItem::Message(message.clone()),
+                })
+                .collect(),
+        }
+    }
+
+    /// The streaming chat completions request for the conversation `items`, which are the
+    /// items of the chained responses followed by [`Self::input_items`].
+    pub fn to_chat_request(
+        &self,
+        items: &[ResponseItem],
+    ) -> anyhow::Result<NvCreateChatCompletionRequest> {
+        let mut inner = CreateChatCompletionRequestArgs::default()
+            .model(self.model.clone())
+            .messages(chat_messages(self.instructions.as_deref(), items)?)
+            .stream(true)
+            .build()?;
+        inner.temperature = self.temperature;
+        inner.top_p = self.top_p;
+        inner.max_completion_tokens = self.max_output_tokens;
+        inner.parallel_tool_calls = self.parallel_tool_calls;
+        inner.user = self.user.clone();
+        if !self.tools.is_empty() {
+            inner.tools = Some(self.tools.iter().map(chat_tool).collect());
+        }
+        inner.tool_choice = self.tool_choice.as_ref().map(chat_tool_choice);
+        // the usage of the response is taken from the last chunk
+        inner.stream_options = Some(ChatCompletionStreamOptions {
+            include_usage: true,
+        });
+
+        Ok(NvCreateChatCompletionRequest {
+            inner,
+            nvext: self.nvext.clone(),
+        })
+    }
+
+    /// The in progress response to this request, without output
+    pub fn response(&self, id: String, created_at: u64) -> NvResponse {
+        NvResponse {
+            id,
+            object: "response".to_string(),
+            created_at,
+            status: ResponseStatus::InProgress,
+            model: self.model.clone(),
+            output: Vec::new(),
+            instructions: self.instructions.clone(),
+            previous_response_id: self.previous_response_id.clone(),
+            incomplete_details: None,
+            error: None,
+            usage: None,
+            tools: self.tools.clone(),
+            tool_choice: self.tool_choice.clone(),
+            temperature: self.temperature,
+            top_p: self.top_p,
+            max_output_tokens: self.max_output_tokens,
+            store: self.store.unwrap_or(true),
+            metadata: self.metadata.clone(),
+            user: self.user.clone(),
+        }
+    }
+}
+
+/// Convert conversation items to chat messages. Consecutive function calls, and function calls
+/// following an assistant message, become the tool calls of a single assistant message.
+fn chat_messages(
+    instructions: Option<&str>,
+    items: &[ResponseItem],
+) -> anyhow::Result<Vec<ChatCompletionRequestMessage>> {
+    let mut messages = Vec::with_capacity(items.len() + 1);
+    if let Some(instructions) = instructions {
+        messages.push(
+            ChatCompletionRequestSystemMessageArgs::default()
+                .content(instructions)
+                .build()?
+                .into(),
+        );
+    }
+
+    for item in items {
+        match item {
+            ResponseItem::Message(message) => {
+                let text = message.content.text();
+                let message = match message.role {
+                    MessageRole::User => ChatCompletionRequestUserMessageArgs::default()
+                        .content(text)
+                        .build()?
+                        .into(),
+                    MessageRole::Assistant => ChatCompletionRequestAssistantMessageArgs::default()
+                        .content(text)
+                        .build()?
+                        .into(),
+                    MessageRole::System | MessageRole::Developer => {
+                        ChatCompletionRequestSystemMessageArgs::default()
+                            .content(text)
+                            .build()?
+                            .into()
+                    }
+                };
+                messages.push(message);
+            }
+            ResponseItem::FunctionCall(call) => {
+                let tool_call = ChatCompletionMessageToolCall {
+                    id: call.call_id.clone(),
+                    r#type: ChatCompletionToolType::Function,
+                    function: FunctionCall {
+                        name: call.name.clone(),
+                        arguments: call.arguments.clone(),
+                    },
+                };
+                match messages.last_mut() {
+                    Some(ChatCompletionRequestMessage::Assistant(assistant)) => assistant
+                        .tool_calls
+                        .get_or_insert_with(Vec::new)
+                        .push(tool_call),
+                    _ => messages.push(
+                        ChatCompletionRequestAssistantMessageArgs::default()
+                            .tool_calls(vec![tool_call])
+                            .build()?
+                            .into(),
+                    ),
+                }
+            }
+            ResponseItem::FunctionCallOutput(output) => {
+                messages.push(
+                    ChatCompletionRequestToolMessageArgs::default()
+                        .content(output.output.clone())
+                        .tool_call_id(output.call_id.clone())
+                        .build()?
+                        .into(),
+                );
+            }
+        }
+    }
+    Ok(messages)
+}
+
+fn chat_tool(tool: &ResponseTool) -> ChatCompletionTool {
+    match tool {
+        ResponseTool::Function(function) => ChatCompletionTool {
+            r#type: ChatCompletionToolType::Function,
+            function: FunctionObject {
+                name: function.name.clone(),
+                description: function.description.clone(),
+                parameters: function.parameters.clone(),
+                strict: function.strict,
+            },
+        },
+    }
+}
+
+fn chat_tool_choice(tool_choice: &ResponseToolChoice) -> ChatCompletionToolChoiceOption {
+    match tool_choice {
+        ResponseToolChoice::Mode(ToolChoiceMode::None) => ChatCompletionToolChoiceOption::None,
+        ResponseToolChoice::Mode(ToolChoiceMode::Auto) => ChatCompletionToolChoiceOption::Auto,
+        ResponseToolChoice::Mode(ToolChoiceMode::Required) => {
+            ChatCompletionToolChoiceOption::Required
+        }
+        ResponseToolChoice::Function(function) => {
+            ChatCompletionToolChoiceOption::Named(ChatCompletionNamedToolChoice {
+                r#type: ChatCompletionToolType::Function,
+                function: FunctionName {
+                    name: function.name.clone(),
+                },
+            })
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_input_items() {
+        let request: NvCreateResponseRequest = serde_json::from_value(serde_json::json!({
+            "model": "llama",
+            "input": [
+                {"role": "user", "content": "What is the weather in Paris?"},
+                {"type": "function_call", "call_id": "call_1", "name": "weather", "arguments": "{\"city\":\"Paris\"}"},
+                {"type": "function_call_output", "call_id": "call_1", "output": "sunny"},
+                {"type": "message", "role": "user", "content": [{"type": "input_text", "text": "And tomorrow?"}]}
+            ]
+        }))
+        .unwrap();
+
+        let items = request.input_items();
+        assert_eq!(items.len(), 4);
+        assert!(matches!(&items[0], ResponseItem::Message(m) if m.role == MessageRole::User));
+        assert!(matches!(&items[1], ResponseItem::FunctionCall(c) if c.name == "weather"));
+        assert!(matches!(&items[2], ResponseItem::FunctionCallOutput(o) if o.output == "sunny"));
+        let ResponseItem::Message(message) = &items[3] else {
+            panic!("expected a message");
+        };
+        assert_eq!(message.content.text(), "And tomorrow?");
+    }
+
+    #[test]
+    fn test_to_chat_request() {
+        let request: NvCreateResponseRequest = serde_json::from_value(serde_json::json!({
+            "model": "llama",
+            "instructions": "Be brief.",
+            "input": "Hi",
+            "max_output_tokens": 64,
+            "tools": [{"type": "function", "name": "weather", "parameters": {"type": "object"}}],
+            "tool_choice": {"type": "function", "name": "weather"}
+        }))
+        .unwrap();
+        let history = vec![
+            ResponseItem::Message(ResponseMessage {
+                id: Some("msg_1".to_string()),
+                role: MessageRole::Assistant,
+                content: MessageContent::Parts(vec![ContentPart::OutputText {
+                    text: "Hello".to_string(),
+                    annotations: vec![],
+                }]),
+                status: Some(ItemStatus::Completed),
+            }),
+            ResponseItem::FunctionCall(FunctionCallItem {
+                id: None,
+                call_id: "call_1".to_string(),
+                name: "weather".to_string(),
+                arguments: "{}".to_string(),
+                status: None,
+            }),
+        ];
+        let items: Vec<ResponseItem> = history.into_iter().chain(request.input_items()).collect();
+
+        let chat = request.to_chat_request(&items).unwrap();
+        assert_eq!(chat.inner.model, "llama");
+        assert_eq!(chat.inner.stream, Some(true));
+        assert_eq!(chat.inner.max_completion_tokens, Some(64));
+        assert_eq!(
+            chat.inner.tools.as_ref().unwrap()[0].function.name,
+            "weather"
+        );
+        assert!(matches!(
+            chat.inner.tool_choice,
+            Some(ChatCompletionToolChoiceOption::Named(_))
+        ));
+
+        // instructions, then the assistant turn with its tool call, then the new input
+        let messages = &chat.inner.messages;
+        assert_eq!(messages.len(), 3);
+        assert!(matches!(
+            messages[0],
+            ChatCompletionRequestMessage::System(_)
+        ));
+        let ChatCompletionRequestMessage::Assistant(assistant) = &messages[1] else {
+            panic!("expected an assistant message");
+        };
+        assert_eq!(assistant.tool_calls.as_ref().unwrap()[0].id, "call_1");
+        assert!(matches!(messages[2], ChatCompletionRequestMessage::User(_)));
+    }
+}
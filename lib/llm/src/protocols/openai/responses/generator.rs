@@ -0,0 +1,496 @@
+// SPDX-FileCopyrightText: Copyright (c) 2024-2025 NVIDIA CORPORATION & AFFILIATES. All rights reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+use async_openai::types::FinishReason;
+
+use super::{
+    ContentPart, FunctionCallItem, IncompleteDetails, ItemStatus, MessageContent, MessageRole,
+    NvResponse, ResponseError, ResponseEventKind, ResponseItem, ResponseMessage, ResponseStatus,
+    ResponseStreamEvent, ResponseUsage,
+};
+use crate::protocols::openai::chat_completions::NvCreateChatCompletionStreamResponse;
+
+/// An output item that is still being generated
+#[derive(Debug)]
+enum OpenItem {
+    Message {
+        item_id: String,
+        text: String,
+    },
+    FunctionCall {
+        /// Index of the tool call in the chat completion deltas
+        index: u32,
+        item_id: String,
+        call_id: String,
+        name: String,
+        arguments: String,
+    },
+}
+
+/// Turns the chat completion deltas of a response into Responses streaming events, and builds
+/// the final [`NvResponse`].
+///
+/// Output items are opened as their first delta arrives and closed by [`Self::finish`].
+#[derive(Debug)]
+pub struct ResponseGenerator {
+    response: NvResponse,
+    items: Vec<OpenItem>,
+    sequence_number: u64,
+    finish_reason: Option<FinishReason>,
+    error: Option<String>,
+}
+
+impl ResponseGenerator {
+    /// `response` is the in progress response, without output
+    pub fn new(response: NvResponse) -> Self {
+        Self {
+            response,
+            items: Vec::new(),
+            sequence_number: 0,
+            finish_reason: None,
+            error: None,
+        }
+    }
+
+    /// The `response.created` and `response.in_progress` events
+    pub fn start(&mut self) -> Vec<ResponseStreamEvent> {
+        vec![
+            self.event(ResponseEventKind::Created {
+                response: self.response.clone(),
+            }),
+            self.event(ResponseEventKind::InProgress {
+                response: self.response.clone(),
+            }),
+        ]
+    }
+
+    /// The events for a chat completion delta
+    pub fn process(
+        &mut self,
+        chunk: &NvCreateChatCompletionStreamResponse,
+    ) -> Vec<ResponseStreamEvent> {
+        let mut events = Vec::new();
+        if let Some(usage) = &chunk.inner.usage {
+            self.response.usage = Some(ResponseUsage {
+                input_tokens: usage.prompt_tokens,
+                output_tokens: usage.completion_tokens,
+                total_tokens: usage.prompt_tokens + usage.completion_tokens,
+            });
+        }
+
+        // Responses have a single output, only the first choice is used
+        let Some(choice) = chunk.inner.choices.first() else {
+            return events;
+        };
+        if let Some(finish_reason) = choice.finish_reason {
+            self.finish_reason = Some(finish_reason);
+        }
+
+        if let Some(delta) = choice.delta.content.as_ref().filter(|d| !d.is_empty()) {
+            let output_index = match self.message_index() {
+                Some(output_index) => output_index,
+                None => self.open_message(&mut events),
+            };
+            let OpenItem::Message { item_id, text } = &mut self.items[output_index] else {
+                unreachable!("message_index returns the index of a message");
+            };
+            text.push_str(delta);
+            let kind = ResponseEventKind::OutputTextDelta {
+                item_id: item_id.clone(),
+                output_index: output_index as u32,
+                content_index: 0,
+                delta: delta.clone(),
+            };
+            events.push(self.event(kind));
+        }
+
+        for tool_call in choice.delta.tool_calls.iter().flatten() {
+            let output_index = match self.function_call_index(tool_call.index) {
+                Some(output_index) => output_index,
+                None => {
+                    let name = tool_call
+                        .function
+                        .as_ref()
+                        .and_then(|f| f.name.clone())
+                        .unwrap_or_default();
+                    let call_id = tool_call
+                        .id
+                        .clone()
+                        .unwrap_or_else(|| format!("call_{}", uuid::Uuid::new_v4().simple()));
+                    self.open_function_call(tool_call.index, call_id, name, &mut events)
+                }
+            };
+            let Some(delta) = tool_call
+                .function
+                .as_ref()
+                .and_then(|f| f.arguments.as_ref())
+                .filter(|a| !a.is_empty())
+            else {
+                continue;
+            };
+            let OpenItem::FunctionCall {
+                item_id, arguments, ..
+            } = &mut self.items[output_index]
+            else {
+                unreachable!("function_call_index returns the index of a function call");
+            };
+            arguments.push_str(delta);
+            let kind = ResponseEventKind::FunctionCallArgumentsDelta {
+                item_id: item_id.clone(),
+                output_index: output_index as u32,
+                delta: delta.clone(),
+            };
+            events.push(self.event(kind));
+        }
+
+        events
+    }
+
+    /// Record an error of the stream. [`Self::finish`] will fail the response.
+    pub fn fail(&mut self, message: impl Into<String>) {
+        self.error = Some(message.into());
+    }
+
+    /// Close the output items and emit the final `response.completed`, `response.incomplete`
+    /// or `response.failed` event.
+    pub fn finish(&mut self) -> Vec<ResponseStreamEvent> {
+        let incomplete_reason = match self.finish_reason {
+            Some(FinishReason::Length) => Some("max_output_tokens"),
+            Some(FinishReason::ContentFilter) => Some("content_filter"),
+            _ => None,
+        };
+        let status = if self.error.is_some() {
+            ResponseStatus::Failed
+        } else if incomplete_reason.is_some() {
+            ResponseStatus::Incomplete
+        } else {
+            ResponseStatus::Completed
+        };
+        let item_status = match status {
+            ResponseStatus::Completed => ItemStatus::Completed,
+            _ => ItemStatus::Incomplete,
+        };
+
+        let mut events = Vec::new();
+        for (output_index, item) in std::mem::take(&mut self.items).into_iter().enumerate() {
+            let output_index = output_index as u32;
+            let item = match item {
+                OpenItem::Message { item_id, text } => {
+                    let part = ContentPart::OutputText {
+                        text: text.clone(),
+                        annotations: vec![],
+                    };
+                    events.push(self.event(ResponseEventKind::OutputTextDone {
+                        item_id: item_id.clone(),
+                        output_index,
+                        content_index: 0,
+                        text,
+                    }));
+                    events.push(self.event(ResponseEventKind::ContentPartDone {
+                        item_id: item_id.clone(),
+                        output_index,
+                        content_index: 0,
+                        part: part.clone(),
+                    }));
+                    ResponseItem::Message(ResponseMessage {
+                        id: Some(item_id),
+                        role: MessageRole::Assistant,
+                        content: MessageContent::Parts(vec![part]),
+                        status: Some(item_status),
+                    })
+                }
+                OpenItem::FunctionCall {
+                    item_id,
+                    call_id,
+                    name,
+                    arguments,
+                    ..
+                } => {
+                    events.push(self.event(ResponseEventKind::FunctionCallArgumentsDone {
+                        item_id: item_id.clone(),
+                        output_index,
+                        arguments: arguments.clone(),
+                    }));
+                    ResponseItem::FunctionCall(FunctionCallItem {
+                        id: Some(item_id),
+                        call_id,
+                        name,
+                        arguments,
+                        status: Some(item_status),
+                    })
+                }
+            };
+            self.response.output.push(item.clone());
+            events.push(self.event(ResponseEventKind::OutputItemDone { output_index, item }));
+        }
+
+        self.response.status = status;
+        if let Some(message) = self.error.take() {
+            self.response.error = Some(ResponseError {
+                code: "server_error".to_string(),
+                message,
+            });
+        }
+        self.response.incomplete_details = incomplete_reason.map(|reason| IncompleteDetails {
+            reason: reason.to_string(),
+        });
+
+        let response = self.response.clone();
+        let kind = match status {
+            ResponseStatus::Failed => ResponseEventKind::Failed { response },
+            ResponseStatus::Incomplete => ResponseEventKind::Incomplete { response },
+            _ => ResponseEventKind::Completed { response },
+        };
+        events.push(self.event(kind));
+        events
+    }
+
+    /// The response. Complete once [`Self::finish`] was called.
+    pub fn response(&self) -> &NvResponse {
+        &self.response
+    }
+
+    pub fn into_response(self) -> NvResponse {
+        self.response
+    }
+
+    fn event(&mut self, kind: ResponseEventKind) -> ResponseStreamEvent {
+        let sequence_number = self.sequence_number;
+        self.sequence_number += 1;
+        ResponseStreamEvent {
+            sequence_number,
+            kind,
+        }
+    }
+
+    fn message_index(&self) -> Option<usize> {
+        self.items
+            .iter()
+            .position(|item| matches!(item, OpenItem::Message { .. }))
+    }
+
+    fn function_call_index(&self, index: u32) -> Option<usize> {
+        self.items
+            .iter()
+            .position(|item| matches!(item, OpenItem::FunctionCall { index: i, .. } if *i == index))
+    }
+
+    fn open_message(&mut self, events: &mut Vec<ResponseStreamEvent>) -> usize {
+        let output_index = self.items.len();
+        let item_id = format!("msg_{}", uuid::Uuid::new_v4().simple());
+        let item = ResponseItem::Message(ResponseMessage {
+            id: Some(item_id.clone()),
+            role: MessageRole::Assistant,
+            content: MessageContent::Parts(vec![]),
+            status: Some(ItemStatus::InProgress),
+        });
+        events.push(self.event(ResponseEventKind::OutputItemAdded {
+            output_index: output_index as u32,
+            item,
+        }));
+        events.push(self.event(ResponseEventKind::ContentPartAdded {
+            item_id: item_id.clone(),
+            output_index: output_index as u32,
+            content_index: 0,
+            part: ContentPart::OutputText {
+                text: String::new(),
+                annotations: vec![],
+            },
+        }));
+        self.items.push(OpenItem::Message {
+            item_id,
+            text: String::new(),
+        });
+        output_index
+    }
+
+    fn open_function_call(
+        &mut self,
+        index: u32,
+        call_id: String,
+        name: String,
+        events: &mut Vec<ResponseStreamEvent>,
+    ) -> usize {
+        let output_index = self.items.len();
+        let item_id = format!("fc_{}", uuid::Uuid::new_v4().simple());
+        let item = ResponseItem::FunctionCall(FunctionCallItem {
+            id: Some(item_id.clone()),
+            call_id: call_id.clone(),
+            name: name.clone(),
+            arguments: String::new(),
+            status: Some(ItemStatus::InProgress),
+        });
+        events.push(self.event(ResponseEventKind::OutputItemAdded {
+            output_index: output_index as u32,
+            item,
+        }));
+        self.items.push(OpenItem::FunctionCall {
+            index,
+            item_id,
+            call_id,
+            name,
+            arguments: String::new(),
+        });
+        output_index
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::NvCreateResponseRequest;
+    use super::*;
+    use async_openai::types::{
+        ChatChoiceStream, ChatCompletionMessageToolCallChunk, ChatCompletionStreamResponseDelta,
+        ChatCompletionToolType, CompletionUsage, CreateChatCompletionStreamResponse,
+        FunctionCallStream,
+    };
+
+    fn request() -> NvCreateResponseRequest {
+        serde_json::from_value(serde_json::json!({"model": "llama", "input": "Hi"})).unwrap()
+    }
+
+    #[allow(deprecated)]
+    fn chunk(
+        content: Option<&str>,
+        tool_calls: Option<Vec<ChatCompletionMessageToolCallChunk>>,
+        finish_reason: Option<FinishReason>,
+    ) -> NvCreateChatCompletionStreamResponse {
+        NvCreateChatCompletionStreamResponse {
+            inner: CreateChatCompletionStreamResponse {
+                id: "chatcmpl-1".to_string(),
+                object: "chat.completion.chunk".to_string(),
+                created: 0,
+                model: "llama".to_string(),
+                system_fingerprint: None,
+                service_tier: None,
+                choices: vec![ChatChoiceStream {
+                    index: 0,
+                    delta: ChatCompletionStreamResponseDelta {
+                        role: None,
+                        content: content.map(str::to_string),
+                        tool_calls,
+                        function_call: None,
+                        refusal: None,
+                    },
+                    finish_reason,
+                    logprobs: None,
+                }],
+                usage: Some(CompletionUsage {
+                    prompt_tokens: 3,
+                    completion_tokens: 2,
+                    total_tokens: 5,
+                    prompt_tokens_details: None,
+                    completion_tokens_details: None,
+                }),
+            },
+            reasoning_content: Default::default(),
+        }
+    }
+
+    fn names(events: &[ResponseStreamEvent]) -> Vec<&'static str> {
+        events.iter().map(|e| e.kind.name()).collect()
+    }
+
+    #[test]
+    fn test_text_response() {
+        let mut generator = ResponseGenerator::new(request().response("resp_1".to_string(), 0));
+        let mut events = generator.start();
+        events.extend(generator.process(&chunk(Some("Hello"), None, None)));
+        events.extend(generator.process(&chunk(Some(" world"), None, Some(FinishReason::Stop))));
+        events.extend(generator.finish());
+
+        assert_eq!(
+            names(&events),
+            vec![
+                "response.created",
+                "response.in_progress",
+                "response.output_item.added",
+                "response.content_part.added",
+                "response.output_text.delta",
+                "response.output_text.delta",
+                "response.output_text.done",
+                "response.content_part.done",
+                "response.output_item.done",
+                "response.completed",
+            ]
+        );
+        let sequence: Vec<u64> = events.iter().map(|e| e.sequence_number).collect();
+        assert_eq!(sequence, (0..events.len() as u64).collect::<Vec<_>>());
+
+        let response = generator.into_response();
+        assert_eq!(response.status, ResponseStatus::Completed);
+        assert_eq!(response.usage.unwrap().total_tokens, 5);
+        let ResponseItem::Message(message) = &response.output[0] else {
+            panic!("expected a message");
+        };
+        assert_eq!(message.content.text(), "Hello world");
+    }
+
+    #[test]
+    fn test_function_call_response() {
+        let call = |id: Option<&str>, name: Option<&str>, arguments: &str| {
+            Some(vec![ChatCompletionMessageToolCallChunk {
+                index: 0,
+                id: id.map(str::to_string),
+                r#type: Some(ChatCompletionToolType::Function),
+                function: Some(FunctionCallStream {
+                    name: name.map(str::to_string),
+                    arguments: Some(arguments.to_string()),
+                }),
+            }])
+        };
+        let mut generator = ResponseGenerator::new(request().response("resp_1".to_string(), 0));
+        let mut events = generator.process(&chunk(
+            None,
+            call(Some("call_1"), Some("f"), "{\"a\""),
+            None,
+        ));
+        events.extend(generator.process(&chunk(
+            None,
+            call(None, None, ":1}"),
+            Some(FinishReason::ToolCalls),
+        )));
+        events.extend(generator.finish());
+
+        assert_eq!(
+            names(&events),
+            vec![
+                "response.output_item.added",
+                "response.function_call_arguments.delta",
+                "response.function_call_arguments.delta",
+                "response.function_call_arguments.done",
+                "response.output_item.done",
+                "response.completed",
+            ]
+        );
+        let response = generator.into_response();
+        let ResponseItem::FunctionCall(call) = &response.output[0] else {
+            panic!("expected a function call");
+        };
+        assert_eq!(call.call_id, "call_1");
+        assert_eq!(call.arguments, "{\"a\":1}");
+    }
+
+    #[test]
+    fn test_incomplete_and_failed() {
+        let mut generator = ResponseGenerator::new(request().response("resp_1".to_string(), 0));
+        generator.process(&chunk(Some("Hel"), None, Some(FinishReason::Length)));
+        let events = generator.finish();
+        assert_eq!(events.last().unwrap().kind.name(), "response.incomplete");
+        let response = generator.response();
+        assert_eq!(response.status, ResponseStatus::Incomplete);
+        assert_eq!(
+            response.incomplete_details.as_ref().unwrap().reason,
+            "max_output_tokens"
+        );
+
+        let mut generator = ResponseGenerator::new(request().response("resp_2".to_string(), 0));
+        generator.fail("engine failed");
+        let events = generator.finish();
+        assert_eq!(names(&events), vec!["response.failed"]);
+        assert_eq!(
+            generator.response().error.as_ref().unwrap().message,
+            "engine failed"
+        );
+    }
+}
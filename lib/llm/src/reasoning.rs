@@ -0,0 +1,212 @@
+// SPDX-FileCopyrightText: Copyright (c) 2024-2025 NVIDIA CORPORATION & AFFILIATES. All rights reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+//! Thinking models write their reasoning in the output before the answer, between tags such as
+//! `<think>` and `</think>`.
+//!
+//! The [`crate::backend::Backend`] of such a model separates the reasoning from the content of
+//! the output with a [`ReasoningParser`], when the request asks for it with
+//! [`crate::protocols::common::OutputOptions::reasoning`]. Chat completions return it in the
+//! `reasoning_content` of their choices.
+
+use serde::{Deserialize, Serialize};
+
+const THINK_START: &str = "<think>";
+const THINK_END: &str = "</think>";
+
+/// How a model marks the reasoning in its output
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct ReasoningFormat {
+    /// The tag before the reasoning
+    pub start: String,
+    /// The tag after the reasoning
+    pub end: String,
+    /// The output starts with reasoning, its start tag is in the prompt. The start tag is still
+    /// removed if the model writes it.
+    pub starts_in_reasoning: bool,
+}
+
+impl ReasoningFormat {
+    /// The built-in formats: `deepseek_r1` (also QwQ), which starts with the reasoning, and
+    /// `qwen3`, which may start with a reasoning block.
+    pub fn from_name(name: &str) -> Option<Self> {
+        let starts_in_reasoning = match name {
+            "deepseek_r1" => true,
+            "qwen3" => false,
+            _ => return None,
+        };
+        Some(Self {
+            start: THINK_START.to_string(),
+            end: THINK_END.to_string(),
+            starts_in_reasoning,
+        })
+    }
+
+    /// The built-in format of a model, guessed from its name
+    pub fn detect(model_name: &str) -> Option<&'static str> {
+        let model_name = model_name.to_lowercase();
+        if model_name.contains("deepseek-r1") || model_name.contains("qwq") {
+            Some("deepseek_r1")
+        } else if model_name.contains("qwen3") {
+            Some("qwen3")
+        } else {
+            None
+        }
+    }
+}
+
+/// What a [`ReasoningParser`] made of a piece of output
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct ReasoningOutput {
+    pub content: String,
+    pub reasoning: String,
+}
+
+/// Incrementally separates the reasoning of one output from its content
+#[derive(Debug, Clone)]
+pub struct ReasoningParser {
+    format: ReasoningFormat,
+    in_reasoning: bool,
+    /// Whether no text was parsed yet
+    at_start: bool,
+    /// Text which could be the start of a tag
+    pending: String,
+}
+
+impl ReasoningParser {
+    pub fn new(format: ReasoningFormat) -> Self {
+        Self {
+            in_reasoning: format.starts_in_reasoning,
+            format,
+            at_start: true,
+            pending: String::new(),
+        }
+    }
+
+    /// Whether the output is in its reasoning, after the text parsed so far
+    pub fn in_reasoning(&self) -> bool {
+        self.in_reasoning
+    }
+
+    /// Parse the next piece of the output. Text which could be the start of a tag is held back
+    /// until the next pieces tell.
+    pub fn parse(&mut self, text: &str) -> ReasoningOutput {
+        self.pending.push_str(text);
+        let mut output = ReasoningOutput::default();
+        loop {
+            if self.at_start && self.in_reasoning {
+                // the model may repeat the start tag which ended the prompt
+                let trimmed = self.pending.trim_start();
+                if trimmed.is_empty() || self.format.start.starts_with(trimmed) {
+                    return output;
+                }
+                if let Some(after) = trimmed.strip_prefix(self.format.start.as_str()) {
+                    self.pending = after.to_string();
+                }
+            }
+            self.at_start = false;
+
+            let (tag, target) = if self.in_reasoning {
+                (&self.format.end, &mut output.reasoning)
+            } else {
+                (&self.format.start, &mut output.content)
+            };
+            match self.pending.find(tag.as_str()) {
+                Some(start) => {
+                    target.push_str(&self.pending[..start]);
+                    self.pending.drain(..start + tag.len());
+                    self.in_reasoning = !self.in_reasoning;
+                }
+                None => {
+                    let held = held_start(&self.pending, tag);
+                    target.push_str(&self.pending[..held]);
+                    self.pending.drain(..held);
+                    return output;
+                }
+            }
+        }
+    }
+
+    /// The output is complete: return what was held back
+    pub fn finish(&mut self) -> ReasoningOutput {
+        let mut output = ReasoningOutput::default();
+        let pending = std::mem::take(&mut self.pending);
+        if self.in_reasoning {
+            output.reasoning = pending;
+        } else {
+            output.content = pending;
+        }
+        output
+    }
+}
+
+/// Where the end of `text` which could be the start of `tag` begins
+fn held_start(text: &str, tag: &str) -> usize {
+    text.char_indices()
+        .map(|(start, _)| start)
+        .find(|&start| tag.starts_with(&text[start..]))
+        .unwrap_or(text.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_in_pieces(format: &str, output: &str) -> ReasoningOutput {
+        let mut parser = ReasoningParser::new(ReasoningFormat::from_name(format).unwrap());
+        let mut parsed = ReasoningOutput::default();
+        let chars = output.chars().collect::<Vec<_>>();
+        for piece in chars
+            .chunks(3)
+            .map(|piece| piece.iter().collect::<String>())
+        {
+            let output = parser.parse(&piece);
+            parsed.content.push_str(&output.content);
+            parsed.reasoning.push_str(&output.reasoning);
+        }
+        let output = parser.finish();
+        parsed.content.push_str(&output.content);
+        parsed.reasoning.push_str(&output.reasoning);
+        parsed
+    }
+
+    #[test]
+    fn test_starts_in_reasoning() {
+        let parsed = parse_in_pieces("deepseek_r1", "Let me think.</think>The answer is 4.");
+        assert_eq!(parsed.reasoning, "Let me think.");
+        assert_eq!(parsed.content, "The answer is 4.");
+
+        // the model repeats the start tag
+        let parsed = parse_in_pieces("deepseek_r1", "\n<think>\nHmm.</think>4");
+        assert_eq!(parsed.reasoning, "\nHmm.");
+        assert_eq!(parsed.content, "4");
+    }
+
+    #[test]
+    fn test_reasoning_block() {
+        let parsed = parse_in_pieces("qwen3", "<think>2 + 2 < 5</think>\n\nIt is 4.");
+        assert_eq!(parsed.reasoning, "2 + 2 < 5");
+        assert_eq!(parsed.content, "\n\nIt is 4.");
+
+        let parsed = parse_in_pieces("qwen3", "No <thinking> needed");
+        assert_eq!(parsed.reasoning, "");
+        assert_eq!(parsed.content, "No <thinking> needed");
+    }
+
+    #[test]
+    fn test_unfinished_reasoning() {
+        let parsed = parse_in_pieces("deepseek_r1", "Still thinking </thi");
+        assert_eq!(parsed.reasoning, "Still thinking </thi");
+        assert_eq!(parsed.content, "");
+    }
+
+    #[test]
+    fn test_detect() {
+        assert_eq!(
+            ReasoningFormat::detect("deepseek-ai/DeepSeek-R1-Distill-Llama-8B"),
+            Some("deepseek_r1")
+        );
+        assert_eq!(ReasoningFormat::detect("Qwen/Qwen3-8B"), Some("qwen3"));
+        assert_eq!(ReasoningFormat::detect("Qwen/Qwen2.5-7B-Instruct"), None);
+    }
+}
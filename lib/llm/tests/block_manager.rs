@@ -301,6 +301,8 @@ pub mod llm_kvbm {
                             })
                             .collect(),
                         parent_hash: parent_hash.map(ExternalSequenceBlockHash),
+                        lora_id: None,
+                        media_hashes: Vec::new(),
                     };
                     let data = KvCacheEventData::Stored(store_data);
                     let event = KvCacheEvent {
@@ -584,6 +586,8 @@ mod tests {
                         tokens_hash: LocalBlockHash(1),
                     }],
                     parent_hash: None,
+                    lora_id: None,
+                    media_hashes: Vec::new(),
                 }),
             },
         );
@@ -598,6 +602,8 @@ mod tests {
                         tokens_hash: LocalBlockHash(2),
                     }],
                     parent_hash: None,
+                    lora_id: None,
+                    media_hashes: Vec::new(),
                 }),
             },
         );
@@ -641,6 +647,8 @@ mod tests {
                         tokens_hash: LocalBlockHash(3),
                     }],
                     parent_hash: None,
+                    lora_id: None,
+                    media_hashes: Vec::new(),
                 }),
             },
         );
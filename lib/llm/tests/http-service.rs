@@ -16,8 +16,10 @@
 use anyhow::Error;
 use async_stream::stream;
 use dynamo_llm::http::service::{
+    auth::{ApiKey, ApiKeys},
     error::HttpError,
     metrics::{Endpoint, RequestType, Status},
+    rate_limit::{RateLimitConfig, RateLimits},
     service_v2::HttpService,
     Metrics,
 };
@@ -30,13 +32,15 @@ use dynamo_llm::protocols::{
 };
 use dynamo_runtime::{
     pipeline::{
-        async_trait, AsyncEngine, AsyncEngineContextProvider, ManyOut, ResponseStream, SingleIn,
+        async_trait, AsyncEngine, AsyncEngineContext, AsyncEngineContextProvider, ManyOut,
+        ResponseStream, SingleIn,
     },
     CancellationToken,
 };
 use prometheus::{proto::MetricType, Registry};
 use reqwest::StatusCode;
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
 
 struct CounterEngine {}
 
@@ -69,6 +73,7 @@ impl
 
                 let output = NvCreateChatCompletionStreamResponse {
                     inner,
+                    reasoning_content: Default::default(),
                 };
 
                 yield Annotated::from_data(output);
@@ -79,6 +84,31 @@ impl
     }
 }
 
+/// Never answers, like an engine stuck in a long prefill. Keeps the context of its last request.
+#[derive(Default)]
+struct StallEngine {
+    context: Mutex<Option<Arc<dyn AsyncEngineContext>>>,
+}
+
+#[async_trait]
+impl
+    AsyncEngine<
+        SingleIn<NvCreateChatCompletionRequest>,
+        ManyOut<Annotated<NvCreateChatCompletionStreamResponse>>,
+        Error,
+    > for StallEngine
+{
+    async fn generate(
+        &self,
+        request: SingleIn<NvCreateChatCompletionRequest>,
+    ) -> Result<ManyOut<Annotated<NvCreateChatCompletionStreamResponse>>, Error> {
+        let ctx = request.context();
+        *self.context.lock().unwrap() = Some(ctx.clone());
+        let stream = futures::stream::pending();
+        Ok(ResponseStream::new(Box::pin(stream), ctx))
+    }
+}
+
 struct AlwaysFailEngine {}
 
 #[async_trait]
@@ -143,6 +173,7 @@ fn compute_index(endpoint: &Endpoint, request_type: &RequestType, status: &Statu
         Endpoint::Completions => 0,
         Endpoint::ChatCompletions => 1,
         Endpoint::Embeddings => todo!(),
+        Endpoint::Responses => todo!(),
     };
 
     let request_type = match request_type {
@@ -469,3 +500,273 @@ async fn test_http_service() {
     cancel_token.cancel();
     task.await.unwrap().unwrap();
 }
+
+#[tokio::test]
+async fn test_sse_keep_alive_and_disconnect() {
+    let service = HttpService::builder()
+        .port(8990)
+        .sse_keep_alive(Some(Duration::from_millis(100)))
+        .build()
+        .unwrap();
+    let state = service.state_clone();
+    let metrics = state.metrics_clone();
+
+    let token = CancellationToken::new();
+    let cancel_token = token.clone();
+    let task = tokio::spawn(async move { service.run(token.clone()).await });
+
+    let engine = Arc::new(StallEngine::default());
+    state
+        .manager()
+        .add_chat_completions_model("stall", engine.clone())
+        .unwrap();
+
+    let message = async_openai::types::ChatCompletionRequestMessage::User(
+        async_openai::types::ChatCompletionRequestUserMessage {
+            content: async_openai::types::ChatCompletionRequestUserMessageContent::Text(
+                "hi".to_string(),
+            ),
+            name: None,
+        },
+    );
+    let request = async_openai::types::CreateChatCompletionRequestArgs::default()
+        .model("stall")
+        .messages(vec![message])
+        .stream(true)
+        .build()
+        .unwrap();
+
+    let client = reqwest::Client::new();
+    let mut response = client
+        .post("http://localhost:8990/v1/chat/completions")
+        .json(&request)
+        .send()
+        .await
+        .unwrap();
+    assert!(response.status().is_success(), "{:?}", response);
+
+    // the engine sends nothing, so the stream is kept alive with comments
+    let chunk = tokio::time::timeout(Duration::from_secs(5), response.chunk())
+        .await
+        .expect("no keep-alive received")
+        .unwrap()
+        .unwrap();
+    assert!(chunk.starts_with(b":"), "{:?}", chunk);
+
+    let context = engine.context.lock().unwrap().clone().unwrap();
+    assert!(!context.is_stopped());
+
+    // the client goes away before the first token
+    drop(response);
+    tokio::time::timeout(Duration::from_secs(5), context.stopped())
+        .await
+        .expect("generation was not stopped");
+
+    // the monitor task records the disconnect right after stopping generation
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    assert_eq!(
+        metrics.get_client_disconnects("stall", &Endpoint::ChatCompletions),
+        1
+    );
+    assert_eq!(metrics.get_inflight_count("stall"), 0);
+    compare_counter(
+        &metrics,
+        "stall",
+        &Endpoint::ChatCompletions,
+        &RequestType::Stream,
+        &Status::Error,
+        1,
+    );
+
+    cancel_token.cancel();
+    task.await.unwrap().unwrap();
+}
+
+fn chat_request(model: &str) -> async_openai::types::CreateChatCompletionRequest {
+    let message = async_openai::types::ChatCompletionRequestMessage::User(
+        async_openai::types::ChatCompletionRequestUserMessage {
+            content: async_openai::types::ChatCompletionRequestUserMessageContent::Text(
+                "hi".to_string(),
+            ),
+            name: None,
+        },
+    );
+    async_openai::types::CreateChatCompletionRequestArgs::default()
+        .model(model)
+        .messages(vec![message])
+        .stream(true)
+        .build()
+        .unwrap()
+}
+
+#[tokio::test]
+async fn test_rate_limit() {
+    let rate_limit = RateLimitConfig::new(RateLimits {
+        requests_per_second: Some(1.0),
+        tokens_per_minute: None,
+    });
+    let service = HttpService::builder()
+        .port(8991)
+        .rate_limit(Some(rate_limit))
+        .build()
+        .unwrap();
+    let state = service.state_clone();
+
+    let token = CancellationToken::new();
+    let cancel_token = token.clone();
+    let task = tokio::spawn(async move { service.run(token.clone()).await });
+
+    state
+        .manager()
+        .add_chat_completions_model("foo", Arc::new(CounterEngine {}))
+        .unwrap();
+
+    let client = reqwest::Client::new();
+    let request = chat_request("foo");
+    let send = |tenant: &'static str| {
+        client
+            .post("http://localhost:8991/v1/chat/completions")
+            .bearer_auth(tenant)
+            .json(&request)
+            .send()
+    };
+
+    let response = send("sk-a").await.unwrap();
+    assert!(response.status().is_success(), "{:?}", response);
+    let _ = response.bytes().await.unwrap();
+
+    // the second request within a second is over the tenant's limit
+    let response = send("sk-a").await.unwrap();
+    assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+    assert_eq!(response.headers()["retry-after"], "1");
+
+    // other tenants have their own limit
+    let response = send("sk-b").await.unwrap();
+    assert!(response.status().is_success(), "{:?}", response);
+    let _ = response.bytes().await.unwrap();
+
+    // only inference is limited
+    for _ in 0..3 {
+        let response = client
+            .get("http://localhost:8991/v1/models")
+            .bearer_auth("sk-a")
+            .send()
+            .await
+            .unwrap();
+        assert!(response.status().is_success(), "{:?}", response);
+    }
+
+    cancel_token.cancel();
+    task.await.unwrap().unwrap();
+}
+
+#[tokio::test]
+async fn test_api_key_auth() {
+    let api_keys = ApiKeys::new(vec![
+        ApiKey {
+            key: "sk-foo".to_string(),
+            name: "foo-only".to_string(),
+            models: Some(["foo".to_string()].into()),
+        },
+        ApiKey {
+            key: "sk-admin".to_string(),
+            name: "admin".to_string(),
+            models: None,
+        },
+    ]);
+    let service = HttpService::builder()
+        .port(8992)
+        .api_keys(Some(Arc::new(api_keys)))
+        .enable_kv_router_debug_endpoints(true)
+        .build()
+        .unwrap();
+    let state = service.state_clone();
+
+    let token = CancellationToken::new();
+    let cancel_token = token.clone();
+    let task = tokio::spawn(async move { service.run(token.clone()).await });
+
+    state
+        .manager()
+        .add_chat_completions_model("foo", Arc::new(CounterEngine {}))
+        .unwrap();
+    state
+        .manager()
+        .add_chat_completions_model("bar", Arc::new(CounterEngine {}))
+        .unwrap();
+
+    let client = reqwest::Client::new();
+    let chat = |key: Option<&str>, model: &str| {
+        let request = client
+            .post("http://localhost:8992/v1/chat/completions")
+            .json(&chat_request(model));
+        match key {
+            Some(key) => request.bearer_auth(key),
+            None => request,
+        }
+        .send()
+    };
+
+    let response = chat(None, "foo").await.unwrap();
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    let response = chat(Some("sk-unknown"), "foo").await.unwrap();
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+    let response = chat(Some("sk-foo"), "foo").await.unwrap();
+    assert!(response.status().is_success(), "{:?}", response);
+    let _ = response.bytes().await.unwrap();
+
+    // a key cannot use, or discover, models it is not allowed
+    let response = chat(Some("sk-foo"), "bar").await.unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    let response = chat(Some("sk-admin"), "bar").await.unwrap();
+    assert!(response.status().is_success(), "{:?}", response);
+    let _ = response.bytes().await.unwrap();
+
+    let models: serde_json::Value = client
+        .get("http://localhost:8992/v1/models")
+        .bearer_auth("sk-foo")
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    let ids: Vec<_> = models["data"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|model| model["id"].as_str().unwrap())
+        .collect();
+    assert_eq!(ids, ["foo"]);
+
+    // the debug route is behind the same keys and model allow-list
+    let debug = |key: Option<&str>| {
+        let request = client
+            .post("http://localhost:8992/debug/kv_router")
+            .json(&serde_json::json!({"model": "bar", "token_ids": [1, 2, 3]}));
+        match key {
+            Some(key) => request.bearer_auth(key),
+            None => request,
+        }
+        .send()
+    };
+    let response = debug(None).await.unwrap();
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    let response = debug(Some("sk-foo")).await.unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+    // health and metrics need no key
+    for path in ["health", "metrics"] {
+        let response = client
+            .get(format!("http://localhost:8992/{path}"))
+            .send()
+            .await
+            .unwrap();
+        // health is unavailable without model entries, but not unauthorized
+        assert_ne!(response.status(), StatusCode::UNAUTHORIZED, "{path}");
+    }
+
+    cancel_token.cancel();
+    task.await.unwrap().unwrap();
+}
@@ -0,0 +1,115 @@
+// SPDX-FileCopyrightText: Copyright (c) 2024-2025 NVIDIA CORPORATION & AFFILIATES. All rights reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+//! The requests and responses of the LLM pipeline survive both payload encodings of the
+//! request plane, with every optional field set.
+
+use dynamo_llm::protocols::common::{
+    llm_backend::{
+        FinishReason, LLMEngineOutput, MediaInput, MediaKind, PreprocessedRequest, TopLogProb,
+    },
+    preprocessor::PeerBlock,
+    GuidedDecodingOptions, OutputOptions, ReasoningMode, SamplingOptions, StopConditions,
+};
+use dynamo_runtime::{
+    pipeline::network::encoding::PayloadEncoding, protocols::annotated::Annotated,
+};
+use serde::{de::DeserializeOwned, Serialize};
+
+const ENCODINGS: [PayloadEncoding; 2] = [PayloadEncoding::Json, PayloadEncoding::Msgpack];
+
+/// Encodes and decodes `value`, comparing the JSON of both as not every type is `PartialEq`
+fn round_trip<T: Serialize + DeserializeOwned>(encoding: PayloadEncoding, value: &T) {
+    let bytes = encoding.encode(value).unwrap();
+    let decoded: T = encoding.decode(&bytes).unwrap();
+    assert_eq!(
+        serde_json::to_value(&decoded).unwrap(),
+        serde_json::to_value(value).unwrap(),
+        "{encoding:?} round trip"
+    );
+}
+
+fn request() -> PreprocessedRequest {
+    PreprocessedRequest::builder()
+        .token_ids(vec![1, 2, 3, 4])
+        .batch_token_ids(Some(vec![vec![1, 2], vec![3]]))
+        .stop_conditions(StopConditions {
+            max_tokens: Some(16),
+            stop: Some(vec!["\n".to_string()]),
+            ..Default::default()
+        })
+        .sampling_options(SamplingOptions {
+            temperature: Some(0.5),
+            top_p: Some(0.9),
+            seed: Some(-7),
+            ..Default::default()
+        })
+        .output_options(OutputOptions {
+            logprobs: Some(2),
+            reasoning: Some(ReasoningMode::Separate),
+            ..Default::default()
+        })
+        .guided_decoding(Some(GuidedDecodingOptions::JsonSchema(serde_json::json!({
+            "type": "object",
+            "properties": {"answer": {"type": "number"}},
+        }))))
+        .eos_token_ids(vec![2])
+        .mdc_sum(Some("abc".to_string()))
+        .annotations(vec!["formatted_prompt".to_string()])
+        .estimated_prefix_hit_num_blocks(Some(1))
+        .lora_id(Some(3))
+        .media(vec![MediaInput::new(
+            MediaKind::Image,
+            Some("image/png".to_string()),
+            vec![0x89, b'P', b'N', b'G'],
+        )])
+        .priority(Some(-1))
+        .deadline_ms(Some(1_750_000_000_000))
+        .peer_blocks(vec![PeerBlock {
+            worker_id: -5,
+            block_hash: u64::MAX,
+        }])
+        .build()
+        .unwrap()
+}
+
+#[test]
+fn test_preprocessed_request_round_trip() {
+    let full = request();
+    let minimal = PreprocessedRequest::builder()
+        .token_ids(vec![1])
+        .stop_conditions(StopConditions::default())
+        .sampling_options(SamplingOptions::default())
+        .build()
+        .unwrap();
+    for encoding in ENCODINGS {
+        round_trip(encoding, &full);
+        round_trip(encoding, &minimal);
+    }
+}
+
+#[test]
+fn test_engine_output_round_trip() {
+    let mut output = Annotated::from_data(LLMEngineOutput {
+        token_ids: vec![42],
+        tokens: Some(vec![Some(" token".to_string()), None]),
+        text: Some(" token".to_string()),
+        cum_log_probs: Some(-0.5),
+        log_probs: Some(vec![-0.25]),
+        top_logprobs: Some(vec![vec![TopLogProb {
+            token_id: 42,
+            token: Some(" token".to_string()),
+            logprob: -0.25,
+        }]]),
+        finish_reason: Some(FinishReason::Error("failed".to_string())),
+        index: Some(1),
+    });
+    output.id = Some("chatcmpl-1".to_string());
+
+    let event = Annotated::<LLMEngineOutput>::from_annotation("event", &vec![1, 2]).unwrap();
+    for encoding in ENCODINGS {
+        round_trip(encoding, &output);
+        round_trip(encoding, &Annotated::from_data(LLMEngineOutput::stop()));
+        round_trip(encoding, &event);
+    }
+}
@@ -15,14 +15,23 @@
 
 use anyhow::Ok;
 
+use dynamo_llm::http::service::error::HttpError;
 use dynamo_llm::model_card::model::{ModelDeploymentCard, PromptContextMixin};
-use dynamo_llm::preprocessor::prompt::PromptFormatter;
+use dynamo_llm::preprocessor::media::MediaLoader;
+use dynamo_llm::preprocessor::prompt::{OAIChatLikeRequest, PromptFormatter};
+use dynamo_llm::preprocessor::{
+    ContextLengthExceeded, OpenAIPreprocessor, ANNOTATION_FORMATTED_PROMPT,
+};
+use dynamo_llm::protocols::common::llm_backend::MediaKind;
 use dynamo_llm::protocols::openai::chat_completions::NvCreateChatCompletionRequest;
+use dynamo_llm::protocols::openai::completions::NvCreateCompletionRequest;
+use dynamo_llm::protocols::openai::nvext::{NvExt, TruncationMode};
 use serde::{Deserialize, Serialize};
 
 use hf_hub::{api::tokio::ApiBuilder, Cache, Repo, RepoType};
 
 use std::path::PathBuf;
+use std::sync::Arc;
 
 /// ----------------- NOTE ---------------
 /// Currently ModelDeploymentCard does support downloading models using nim-hub.
@@ -471,3 +480,191 @@ async fn test_multi_turn_with_continuation() {
       insta::assert_snapshot!(formatted_prompt);
     });
 }
+
+const MOCK_MODEL_PATH: &str = "tests/data/sample-models/mock-llama-3.1-8b-instruct";
+
+async fn make_preprocessor(context_length: usize) -> Arc<OpenAIPreprocessor> {
+    let mut mdc = ModelDeploymentCard::load(MOCK_MODEL_PATH).await.unwrap();
+    mdc.context_length = context_length;
+    OpenAIPreprocessor::new(mdc).await.unwrap()
+}
+
+fn prompt_tokens(preprocessor: &OpenAIPreprocessor, messages: &str) -> usize {
+    let request = Request::from(messages, None, None, "mock".to_string());
+    let (request, _) = preprocessor.preprocess_request(&request).unwrap();
+    request.token_ids.len()
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_prompt_exceeds_context_length() {
+    let preprocessor = make_preprocessor(8).await;
+    let request = Request::from(SINGLE_CHAT_MESSAGE, None, None, "mock".to_string());
+
+    let err = preprocessor.preprocess_request(&request).unwrap_err();
+    let err = err.downcast_ref::<ContextLengthExceeded>().unwrap();
+    assert_eq!(err.context_length, 8);
+    assert!(err.prompt_tokens >= 8);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_max_tokens_clamped_to_context_length() {
+    let preprocessor = make_preprocessor(4096).await;
+    let num_prompt_tokens = prompt_tokens(&preprocessor, SINGLE_CHAT_MESSAGE);
+
+    let mut request = Request::from(SINGLE_CHAT_MESSAGE, None, None, "mock".to_string());
+    request.inner.max_completion_tokens = Some(100_000);
+    let (request, _) = preprocessor.preprocess_request(&request).unwrap();
+    assert_eq!(
+        request.stop_conditions.max_tokens,
+        Some((4096 - num_prompt_tokens) as u32)
+    );
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_truncate_oldest_turns() {
+    const LAST_TURN_WITH_SYSTEM: &str = r#"
+[
+    {
+      "role": "system",
+      "content": "You are a very helpful assistant!"
+    },
+    {
+      "role": "user",
+      "content": "What if I want to reverse each word in a sentence but keep their order?"
+    }
+]"#;
+    let num_prompt_tokens = prompt_tokens(&make_preprocessor(4096).await, LAST_TURN_WITH_SYSTEM);
+    let preprocessor = make_preprocessor(num_prompt_tokens + 1).await;
+
+    let mut request = Request::from(
+        THREE_TURN_CHAT_MESSAGE_WITH_SYSTEM,
+        None,
+        None,
+        "mock".to_string(),
+    );
+    assert!(preprocessor.preprocess_request(&request).is_err());
+
+    let mut nvext = NvExt::builder();
+    nvext.truncation(TruncationMode::Left);
+    nvext.add_annotation(ANNOTATION_FORMATTED_PROMPT);
+    request.nvext = Some(nvext.build().unwrap());
+    let (preprocessed, annotations) = preprocessor.preprocess_request(&request).unwrap();
+
+    assert_eq!(preprocessed.token_ids.len(), num_prompt_tokens);
+    assert_eq!(preprocessed.stop_conditions.max_tokens, Some(1));
+    let formatted_prompt = &annotations[ANNOTATION_FORMATTED_PROMPT];
+    assert!(formatted_prompt.contains("You are a very helpful assistant!"));
+    assert!(formatted_prompt.contains("reverse each word"));
+    assert!(!formatted_prompt.contains("How do I reverse a string"));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_lora_id_from_model_card() {
+    let request = Request::from(SINGLE_CHAT_MESSAGE, None, None, "mock".to_string());
+
+    let base = make_preprocessor(4096).await;
+    let (preprocessed, _) = base.preprocess_request(&request).unwrap();
+    assert_eq!(preprocessed.lora_id, None);
+
+    let mut mdc = ModelDeploymentCard::load(MOCK_MODEL_PATH).await.unwrap();
+    mdc.lora_id = Some(7);
+    let adapter = OpenAIPreprocessor::new(mdc).await.unwrap();
+    let (preprocessed, _) = adapter.preprocess_request(&request).unwrap();
+    assert_eq!(preprocessed.lora_id, Some(7));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_best_of_requests_logprobs() {
+    let preprocessor = make_preprocessor(4096).await;
+    let completion = |json: serde_json::Value| -> NvCreateCompletionRequest {
+        serde_json::from_value(json).unwrap()
+    };
+
+    // the hidden candidates are ranked by their logprobs
+    let request = completion(serde_json::json!({"model": "mock", "prompt": "hi", "best_of": 3}));
+    let (preprocessed, _) = preprocessor.preprocess_request(&request).unwrap();
+    assert_eq!(preprocessed.output_options.logprobs, Some(0));
+
+    let request = completion(serde_json::json!({
+        "model": "mock", "prompt": "hi", "best_of": 3, "logprobs": 2
+    }));
+    let (preprocessed, _) = preprocessor.preprocess_request(&request).unwrap();
+    assert_eq!(preprocessed.output_options.logprobs, Some(2));
+
+    // every choice is returned, nothing to rank
+    let request = completion(serde_json::json!({
+        "model": "mock", "prompt": "hi", "n": 3, "best_of": 3
+    }));
+    let (preprocessed, _) = preprocessor.preprocess_request(&request).unwrap();
+    assert_eq!(preprocessed.output_options.logprobs, None);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_guided_regex_lookahead() {
+    let preprocessor = make_preprocessor(4096).await;
+    let mut request = Request::from(SINGLE_CHAT_MESSAGE, None, None, "mock".to_string());
+
+    request.nvext = Some(NvExt::builder().guided_regex("[0-9]+").build().unwrap());
+    let (preprocessed, _) = preprocessor.preprocess_request(&request).unwrap();
+    assert!(preprocessed.guided_decoding.is_some());
+
+    // engines support look-around, the regex crate does not
+    request.nvext = Some(
+        NvExt::builder()
+            .guided_regex("(?=[a-z]*[0-9])[a-z0-9]+")
+            .build()
+            .unwrap(),
+    );
+    let (preprocessed, _) = preprocessor.preprocess_request(&request).unwrap();
+    assert!(preprocessed.guided_decoding.is_some());
+}
+
+const IMAGE_CHAT_MESSAGE: &str = r#"
+[
+    {
+      "role": "user",
+      "content": [
+        { "type": "text", "text": "What is in this image?" },
+        { "type": "image_url", "image_url": { "url": "data:image/png;base64,iVBORw0KGgo=" } }
+      ]
+    }
+]"#;
+
+#[tokio::test]
+async fn test_media_placeholders() {
+    let request = Request::from(IMAGE_CHAT_MESSAGE, None, None, "mock".to_string());
+    assert!(request.has_media());
+
+    // the template only sees the kind of the media
+    let messages = serde_json::to_value(request.messages()).unwrap();
+    assert_eq!(
+        messages[0]["content"],
+        serde_json::json!([
+            { "type": "text", "text": "What is in this image?" },
+            { "type": "image" }
+        ])
+    );
+
+    let media = MediaLoader::new()
+        .unwrap()
+        .load(&request.inner.messages)
+        .await
+        .unwrap();
+    assert_eq!(media.len(), 1);
+    assert_eq!(media[0].kind, MediaKind::Image);
+    assert_eq!(media[0].mime_type.as_deref(), Some("image/png"));
+    assert_eq!(media[0].data, b"\x89PNG\r\n\x1a\n");
+}
+
+#[tokio::test]
+async fn test_invalid_media() {
+    let messages = IMAGE_CHAT_MESSAGE.replace("data:image/png;base64,", "data:image/png,");
+    let request = Request::from(&messages, None, None, "mock".to_string());
+
+    let err = MediaLoader::new()
+        .unwrap()
+        .load(&request.inner.messages)
+        .await
+        .unwrap_err();
+    assert_eq!(err.downcast_ref::<HttpError>().unwrap().code, 400);
+}
@@ -29,7 +29,10 @@
 //!
 //! TODO: Top-level Overview of Endpoints/Functions
 
-use crate::{discovery::Lease, service::ServiceSet, transports::etcd::EtcdPath};
+use crate::{
+    discovery::Lease, pipeline::network::request_plane::RequestPlaneMode, service::ServiceSet,
+    transports::etcd::EtcdPath,
+};
 
 use super::{
     error,
@@ -74,7 +77,32 @@ pub const ETCD_ROOT_PATH: &str = "dynamo://";
 #[derive(Debug, Clone, Serialize, Deserialize, Eq, PartialEq)]
 #[serde(rename_all = "snake_case")]
 pub enum TransportType {
+    /// Requests are published to the NATS subject
     NatsTcp(String),
+    /// Requests are sent to the subject on the TCP request server listening on `address`, over
+    /// TLS if `tls` is set
+    Tcp {
+        address: String,
+        subject: String,
+        #[serde(default)]
+        tls: bool,
+    },
+}
+
+impl TransportType {
+    /// Where a router sends the requests for this instance
+    pub fn request_address(&self) -> String {
+        match self {
+            TransportType::NatsTcp(subject) => subject.clone(),
+            TransportType::Tcp {
+                address,
+                subject,
+                tls,
+            } => crate::pipeline::network::tcp::request_plane::request_address(
+                address, subject, *tls,
+            ),
+        }
+    }
 }
 
 #[derive(Default)]
@@ -194,6 +222,17 @@ impl Component {
         &self.namespace
     }
 
+    /// The request plane the endpoints of this component are served on. Static components are not
+    /// registered in etcd, where clients find the address of a TCP request server, so they stay on
+    /// NATS whatever the runtime's request plane.
+    pub fn request_plane(&self) -> RequestPlaneMode {
+        if self.is_static {
+            RequestPlaneMode::Nats
+        } else {
+            self.drt.request_plane()
+        }
+    }
+
     pub fn name(&self) -> String {
         self.name.clone()
     }
@@ -381,6 +420,11 @@ impl Endpoint {
         format!("{}.{}", self.component.service_name(), self.name)
     }
 
+    /// The request plane this endpoint is served on, see [`Component::request_plane`]
+    pub fn request_plane(&self) -> RequestPlaneMode {
+        self.component.request_plane()
+    }
+
     /// Subject to an instance of the [Endpoint] with a specific lease id
     pub fn subject_to(&self, lease_id: i64) -> String {
         format!(
@@ -69,12 +69,18 @@ impl EndpointConfigBuilder {
         // acquire the registry lock
         let registry = endpoint.drt().component_registry.inner.lock().await;
 
-        // get the group
-        let group = registry
-            .services
-            .get(&service_name)
-            .map(|service| service.group(endpoint.component.service_name()))
-            .ok_or(error!("Service not found"))?;
+        // get the group; endpoints on the TCP request plane have no NATS service
+        let group = if endpoint.request_plane().is_tcp() {
+            None
+        } else {
+            Some(
+                registry
+                    .services
+                    .get(&service_name)
+                    .map(|service| service.group(endpoint.component.service_name()))
+                    .ok_or(error!("Service not found"))?,
+            )
+        };
 
         // get the stats handler map
         let handler_map = registry
@@ -93,12 +99,6 @@ impl EndpointConfigBuilder {
                 .insert(endpoint.subject_to(lease_id), stats_handler);
         }
 
-        // creates an endpoint for the service
-        let service_endpoint = group
-            .endpoint(&endpoint.name_with_id(lease_id))
-            .await
-            .map_err(|e| anyhow::anyhow!("Failed to start endpoint: {e}"))?;
-
         let cancel_token = lease
             .map(|l| l.child_token())
             .unwrap_or_else(|| endpoint.drt().child_token());
@@ -109,8 +109,36 @@ impl EndpointConfigBuilder {
             .build()
             .map_err(|e| anyhow::anyhow!("Failed to build push endpoint: {e}"))?;
 
-        // launch in primary runtime
-        let task = tokio::spawn(push_endpoint.start(service_endpoint));
+        let subject = endpoint.subject_to(lease_id);
+        let (task, transport) = match group {
+            Some(group) => {
+                // creates an endpoint for the service
+                let service_endpoint = group
+                    .endpoint(&endpoint.name_with_id(lease_id))
+                    .await
+                    .map_err(|e| anyhow::anyhow!("Failed to start endpoint: {e}"))?;
+
+                // launch in primary runtime
+                let task = tokio::spawn(push_endpoint.start(service_endpoint));
+                (task, TransportType::NatsTcp(subject))
+            }
+            None => {
+                let server = endpoint.drt().tcp_request_server().await?;
+                let requests = server.register(subject.clone());
+                let transport = TransportType::Tcp {
+                    address: server.address().to_string(),
+                    subject: subject.clone(),
+                    tls: server.tls(),
+                };
+
+                let task = tokio::spawn(async move {
+                    let result = push_endpoint.start_tcp(requests).await;
+                    server.deregister(&subject);
+                    result
+                });
+                (task, transport)
+            }
+        };
 
         // make the components service endpoint discovery in etcd
 
@@ -120,7 +148,7 @@ impl EndpointConfigBuilder {
             endpoint: endpoint.name.clone(),
             namespace: endpoint.component.namespace.name.clone(),
             instance_id: lease_id,
-            transport: TransportType::NatsTcp(endpoint.subject_to(lease_id)),
+            transport,
         };
 
         let info = serde_json::to_vec_pretty(&info)?;
@@ -61,10 +61,19 @@ impl ServiceConfigBuilder {
 
         let mut guard = component.drt.component_registry.inner.lock().await;
 
-        if guard.services.contains_key(&service_name) {
+        if guard.stats_handlers.contains_key(&service_name) {
             return Err(anyhow::anyhow!("Service already exists"));
         }
 
+        // endpoints on the TCP request plane are served without a NATS service
+        if component.request_plane().is_tcp() {
+            guard
+                .stats_handlers
+                .insert(service_name, stats_handler_registry_clone);
+            drop(guard);
+            return Ok(component);
+        }
+
         // create service on the secondary runtime
         let builder = component.drt.nats_client.client().service_builder();
 
@@ -14,12 +14,14 @@
 // limitations under the License.
 
 use super::Result;
+use crate::pipeline::network::tcp::tls::{TlsClientOptions, TlsOptions};
 use derive_builder::Builder;
 use figment::{
     providers::{Env, Format, Serialized, Toml},
     Figment,
 };
 use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
 use validator::Validate;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -71,6 +73,35 @@ pub struct RuntimeConfig {
     #[builder(default = "512")]
     #[builder_field_attr(serde(skip_serializing_if = "Option::is_none"))]
     pub max_blocking_threads: usize,
+
+    /// PEM certificate chain the TCP response stream server presents to workers
+    /// Response streams use TLS when both the certificate and the key are set.
+    #[builder(default)]
+    #[builder_field_attr(serde(skip_serializing_if = "Option::is_none"))]
+    pub tcp_tls_cert: Option<PathBuf>,
+
+    /// PEM private key of the TCP response stream server certificate
+    #[builder(default)]
+    #[builder_field_attr(serde(skip_serializing_if = "Option::is_none"))]
+    pub tcp_tls_key: Option<PathBuf>,
+
+    /// PEM certificates of the CAs workers trust when calling home over TLS
+    #[builder(default)]
+    #[builder_field_attr(serde(skip_serializing_if = "Option::is_none"))]
+    pub tcp_tls_ca: Option<PathBuf>,
+
+    /// Name the TCP response stream server certificates are verified against
+    /// Defaults to the IP address the server is reached at.
+    #[builder(default)]
+    #[builder_field_attr(serde(skip_serializing_if = "Option::is_none"))]
+    pub tcp_tls_server_name: Option<String>,
+
+    /// Accept TCP response streams from workers which send no token, as workers from before
+    /// the one-time tokens do. Only meant for rolling upgrades, as it lets any host on the
+    /// network which learns a stream's subject connect to it.
+    #[builder(default)]
+    #[builder_field_attr(serde(skip_serializing_if = "Option::is_none"))]
+    pub tcp_accept_tokenless: bool,
 }
 
 impl RuntimeConfig {
@@ -111,9 +142,26 @@ impl RuntimeConfig {
         RuntimeConfig {
             num_worker_threads: 1,
             max_blocking_threads: 1,
+            ..Default::default()
         }
     }
 
+    /// The certificate of the TCP response stream server, if TLS is configured
+    pub fn tcp_tls(&self) -> Result<Option<TlsOptions>> {
+        match (&self.tcp_tls_cert, &self.tcp_tls_key) {
+            (Some(cert), Some(key)) => Ok(Some(TlsOptions::new(cert.clone(), key.clone()))),
+            (None, None) => Ok(None),
+            _ => anyhow::bail!("tcp_tls_cert and tcp_tls_key must be set together"),
+        }
+    }
+
+    /// How workers verify TCP response stream servers, if a CA is configured
+    pub fn tcp_tls_client(&self) -> Option<TlsClientOptions> {
+        self.tcp_tls_ca
+            .clone()
+            .map(|ca| TlsClientOptions::new(ca).with_server_name(self.tcp_tls_server_name.clone()))
+    }
+
     /// Create a new default runtime configuration
     pub(crate) fn create_runtime(&self) -> Result<tokio::runtime::Runtime> {
         Ok(tokio::runtime::Builder::new_multi_thread()
@@ -129,6 +177,11 @@ impl Default for RuntimeConfig {
         Self {
             num_worker_threads: 16,
             max_blocking_threads: 16,
+            tcp_tls_cert: None,
+            tcp_tls_key: None,
+            tcp_tls_ca: None,
+            tcp_tls_server_name: None,
+            tcp_accept_tokenless: false,
         }
     }
 }
@@ -170,6 +223,12 @@ pub fn disable_ansi_logging() -> bool {
     env_is_truthy("DYN_SDK_DISABLE_ANSI_LOGGING")
 }
 
+/// Check whether spans are exported with OTLP
+/// Set the `DYN_LOGGING_OTLP` environment variable to a [`is_truthy`] value
+pub fn otlp_export_enabled() -> bool {
+    env_is_truthy("DYN_LOGGING_OTLP")
+}
+
 /// Check whether to use local timezone for logging timestamps (default is UTC)
 /// Set the `DYN_LOG_USE_LOCAL_TZ` environment variable to a [`is_truthy`] value
 pub fn use_local_timezone() -> bool {
@@ -217,6 +276,46 @@ mod tests {
         )
     }
 
+    #[test]
+    fn test_runtime_config_tcp_tls() -> Result<()> {
+        temp_env::with_vars(
+            vec![
+                ("DYN_RUNTIME_TCP_TLS_CERT", Some("/certs/tls.crt")),
+                ("DYN_RUNTIME_TCP_TLS_KEY", Some("/certs/tls.key")),
+                ("DYN_RUNTIME_TCP_TLS_CA", Some("/certs/ca.crt")),
+                ("DYN_RUNTIME_TCP_TLS_SERVER_NAME", None),
+            ],
+            || {
+                let config = RuntimeConfig::from_settings()?;
+                assert_eq!(
+                    config.tcp_tls()?,
+                    Some(TlsOptions::new(
+                        "/certs/tls.crt".into(),
+                        "/certs/tls.key".into()
+                    ))
+                );
+                assert_eq!(
+                    config.tcp_tls_client(),
+                    Some(TlsClientOptions::new("/certs/ca.crt".into()))
+                );
+                Ok(())
+            },
+        )?;
+
+        // a certificate without its key is a configuration error, not plaintext
+        temp_env::with_vars(
+            vec![
+                ("DYN_RUNTIME_TCP_TLS_CERT", Some("/certs/tls.crt")),
+                ("DYN_RUNTIME_TCP_TLS_KEY", None),
+            ],
+            || {
+                let config = RuntimeConfig::from_settings()?;
+                assert!(config.tcp_tls().is_err());
+                Ok(())
+            },
+        )
+    }
+
     #[test]
     fn test_runtime_config_rejects_invalid_thread_count() -> Result<()> {
         temp_env::with_vars(
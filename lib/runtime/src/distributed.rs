@@ -17,6 +17,7 @@ pub use crate::component::Component;
 use crate::{
     component::{self, ComponentBuilder, Endpoint, InstanceSource, Namespace},
     discovery::DiscoveryClient,
+    pipeline::network::request_plane::{RequestPlaneClient, RequestPlaneMode},
     service::ServiceClient,
     transports::{etcd, nats, tcp},
     ErrorContext,
@@ -33,7 +34,7 @@ use tokio_util::sync::CancellationToken;
 impl DistributedRuntime {
     pub async fn new(runtime: Runtime, config: DistributedConfig) -> Result<Self> {
         let secondary = runtime.secondary();
-        let (etcd_config, nats_config, is_static) = config.dissolve();
+        let (etcd_config, nats_config, is_static, request_plane) = config.dissolve();
 
         let runtime_clone = runtime.clone();
 
@@ -57,7 +58,13 @@ impl DistributedRuntime {
 
         let nats_client = secondary
             .spawn(async move {
-                let client = nats_config.clone().connect().await.context(format!(
+                // requests do not need NATS on the TCP request plane, don't wait for a server
+                let client = if request_plane.is_tcp() {
+                    nats_config.clone().connect_in_background().await
+                } else {
+                    nats_config.clone().connect().await
+                };
+                let client = client.context(format!(
                     "Failed to connect to NATS server with config {:?}",
                     nats_config
                 ))?;
@@ -70,6 +77,8 @@ impl DistributedRuntime {
             etcd_client,
             nats_client,
             tcp_server: Arc::new(OnceCell::new()),
+            request_plane,
+            tcp_request_server: Arc::new(OnceCell::new()),
             component_registry: component::Registry::new(),
             is_static,
             instance_sources: Arc::new(Mutex::new(HashMap::new())),
@@ -135,11 +144,17 @@ impl DistributedRuntime {
         ServiceClient::new(self.nats_client.clone())
     }
 
+    /// The server workers stream responses back to; serves TLS if the runtime configuration has
+    /// a certificate for it
     pub async fn tcp_server(&self) -> Result<Arc<tcp::server::TcpStreamServer>> {
         Ok(self
             .tcp_server
             .get_or_try_init(async move {
-                let options = tcp::server::ServerOptions::default();
+                let config = crate::RuntimeConfig::from_settings()?;
+                let options = tcp::server::ServerOptions::builder()
+                    .tls(config.tcp_tls()?)
+                    .accept_tokenless(config.tcp_accept_tokenless)
+                    .build()?;
                 let server = tcp::server::TcpStreamServer::new(options).await?;
                 OK(server)
             })
@@ -147,6 +162,38 @@ impl DistributedRuntime {
             .clone())
     }
 
+    /// The TCP server instances take requests on when serving the TCP request plane; serves TLS
+    /// if the runtime configuration has a certificate for it
+    pub async fn tcp_request_server(&self) -> Result<Arc<tcp::request_plane::TcpRequestServer>> {
+        Ok(self
+            .tcp_request_server
+            .get_or_try_init(async move {
+                let config = crate::RuntimeConfig::from_settings()?;
+                let options = tcp::server::ServerOptions::builder()
+                    .tls(config.tcp_tls()?)
+                    .build()?;
+                let server = tcp::request_plane::TcpRequestServer::new(options).await?;
+                OK(server)
+            })
+            .await?
+            .clone())
+    }
+
+    pub fn request_plane(&self) -> RequestPlaneMode {
+        self.request_plane
+    }
+
+    /// Sends requests to endpoint instances over `request_plane`
+    pub fn request_plane_client(
+        &self,
+        request_plane: RequestPlaneMode,
+    ) -> Arc<dyn RequestPlaneClient> {
+        match request_plane {
+            RequestPlaneMode::Nats => Arc::new(self.nats_client.client().clone()),
+            RequestPlaneMode::Tcp => Arc::new(tcp::request_plane::TcpRequestClient::default()),
+        }
+    }
+
     pub fn nats_client(&self) -> nats::Client {
         self.nats_client.clone()
     }
@@ -170,6 +217,7 @@ pub struct DistributedConfig {
     pub etcd_config: etcd::ClientOptions,
     pub nats_config: nats::ClientOptions,
     pub is_static: bool,
+    pub request_plane: RequestPlaneMode,
 }
 
 impl DistributedConfig {
@@ -178,6 +226,7 @@ impl DistributedConfig {
             etcd_config: etcd::ClientOptions::default(),
             nats_config: nats::ClientOptions::default(),
             is_static,
+            request_plane: RequestPlaneMode::from_env(),
         }
     }
 
@@ -186,6 +235,7 @@ impl DistributedConfig {
             etcd_config: etcd::ClientOptions::default(),
             nats_config: nats::ClientOptions::default(),
             is_static: false,
+            request_plane: RequestPlaneMode::from_env(),
         };
 
         config.etcd_config.attach_lease = false;
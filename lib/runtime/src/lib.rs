@@ -85,6 +85,11 @@ pub struct DistributedRuntime {
     nats_client: transports::nats::Client,
     tcp_server: Arc<OnceCell<Arc<transports::tcp::server::TcpStreamServer>>>,
 
+    // how requests reach endpoint instances; the TCP request server is only started by
+    // processes serving endpoints on the TCP request plane
+    request_plane: pipeline::network::request_plane::RequestPlaneMode,
+    tcp_request_server: Arc<OnceCell<Arc<transports::tcp::request_plane::TcpRequestServer>>>,
+
     // local registry for components
     // the registry allows us to use share runtime resources across instances of the same component object.
     // take for example two instances of a client to the same remote component. The registry allows us to use
@@ -37,9 +37,14 @@
 //! "test_logging" = "info"
 //! "test_logging::api" = "trace"
 //! ```
+//!
+//! Spans can also be exported with OTLP over HTTP by setting `DYN_LOGGING_OTLP` to `1`. The
+//! collector is configured with the standard `OTEL_EXPORTER_OTLP_ENDPOINT` (or
+//! `OTEL_EXPORTER_OTLP_TRACES_ENDPOINT`) and `OTEL_SERVICE_NAME` environment variables. A
+//! request's [`TraceContext`] travels with it across components, so their spans join one trace.
 
 use std::collections::{BTreeMap, HashMap};
-use std::sync::Once;
+use std::sync::{Once, OnceLock};
 
 use figment::{
     providers::{Format, Serialized, Toml},
@@ -56,8 +61,14 @@ use tracing_subscriber::fmt::{FmtContext, FormatFields};
 use tracing_subscriber::prelude::*;
 use tracing_subscriber::registry::LookupSpan;
 use tracing_subscriber::EnvFilter;
+use tracing_subscriber::Layer;
 use tracing_subscriber::{filter::Directive, fmt};
 
+use opentelemetry::propagation::TextMapPropagator;
+use opentelemetry::trace::{TraceContextExt, TracerProvider as _};
+use opentelemetry_sdk::{propagation::TraceContextPropagator, trace::SdkTracerProvider, Resource};
+use tracing_opentelemetry::OpenTelemetrySpanExt;
+
 /// ENV used to set the log level
 const FILTER_ENV: &str = "DYN_LOG";
 
@@ -67,9 +78,15 @@ const DEFAULT_FILTER_LEVEL: &str = "info";
 /// ENV used to set the path to the logging configuration file
 const CONFIG_PATH_ENV: &str = "DYN_LOGGING_CONFIG_PATH";
 
+/// Service name of the exported spans when `OTEL_SERVICE_NAME` is not set
+const DEFAULT_SERVICE_NAME: &str = "dynamo";
+
 /// Once instance to ensure the logger is only initialized once
 static INIT: Once = Once::new();
 
+/// The provider of the OTLP exporting tracer, kept to flush the spans on shutdown
+static TRACER_PROVIDER: OnceLock<SdkTracerProvider> = OnceLock::new();
+
 #[derive(Serialize, Deserialize, Debug)]
 struct LoggingConfig {
     log_level: String,
@@ -101,44 +118,163 @@ pub fn init() {
     INIT.call_once(|| {
         let config = load_config();
 
-        // Examples to remove noise
-        // .add_directive("rustls=warn".parse()?)
-        // .add_directive("tokio_util::codec=warn".parse()?)
-        let mut filter_layer = EnvFilter::builder()
-            .with_default_directive(config.log_level.parse().unwrap())
-            .with_env_var(FILTER_ENV)
-            .from_env_lossy();
-
-        // apply the log_filters from the config files
-        for (module, level) in config.log_filters {
-            match format!("{module}={level}").parse::<Directive>() {
-                Ok(d) => {
-                    filter_layer = filter_layer.add_directive(d);
-                }
-                Err(e) => {
-                    eprintln!("Failed parsing filter '{level}' for module '{module}': {e}");
-                }
-            }
-        }
-
         if crate::config::jsonl_logging_enabled() {
             let l = fmt::layer()
                 .with_ansi(false) // ansi terminal escapes and colors always disabled
                 .event_format(CustomJsonFormatter::new())
                 .with_writer(std::io::stderr)
-                .with_filter(filter_layer);
-            tracing_subscriber::registry().with(l).init();
+                .with_filter(filters(&config));
+            tracing_subscriber::registry()
+                .with(otlp_layer(&config))
+                .with(l)
+                .init();
         } else {
             let l = fmt::layer()
                 .with_ansi(!crate::config::disable_ansi_logging())
                 .event_format(fmt::format().compact().with_timer(TimeFormatter::new()))
                 .with_writer(std::io::stderr)
-                .with_filter(filter_layer);
-            tracing_subscriber::registry().with(l).init();
+                .with_filter(filters(&config));
+            tracing_subscriber::registry()
+                .with(otlp_layer(&config))
+                .with(l)
+                .init();
         };
     });
 }
 
+/// Flush the spans not exported yet; call before the process exits
+pub fn shutdown() {
+    if let Some(provider) = TRACER_PROVIDER.get() {
+        if let Err(err) = provider.shutdown() {
+            eprintln!("Failed to flush the exported spans: {err}");
+        }
+    }
+}
+
+fn filters(config: &LoggingConfig) -> EnvFilter {
+    // Examples to remove noise
+    // .add_directive("rustls=warn".parse()?)
+    // .add_directive("tokio_util::codec=warn".parse()?)
+    let mut filter_layer = EnvFilter::builder()
+        .with_default_directive(config.log_level.parse().unwrap())
+        .with_env_var(FILTER_ENV)
+        .from_env_lossy();
+
+    // apply the log_filters from the config files
+    for (module, level) in &config.log_filters {
+        match format!("{module}={level}").parse::<Directive>() {
+            Ok(d) => {
+                filter_layer = filter_layer.add_directive(d);
+            }
+            Err(e) => {
+                eprintln!("Failed parsing filter '{level}' for module '{module}': {e}");
+            }
+        }
+    }
+
+    filter_layer
+}
+
+/// The layer exporting spans with OTLP, if enabled with `DYN_LOGGING_OTLP`
+fn otlp_layer<S>(config: &LoggingConfig) -> Option<impl Layer<S>>
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    if !crate::config::otlp_export_enabled() {
+        return None;
+    }
+
+    // the endpoint comes from the standard OTEL_EXPORTER_OTLP_* environment variables
+    let exporter = match opentelemetry_otlp::SpanExporter::builder()
+        .with_http()
+        .build()
+    {
+        Ok(exporter) => exporter,
+        Err(e) => {
+            eprintln!("Failed creating the OTLP span exporter: {e}");
+            return None;
+        }
+    };
+    let service_name =
+        std::env::var("OTEL_SERVICE_NAME").unwrap_or_else(|_| DEFAULT_SERVICE_NAME.to_string());
+    let provider = SdkTracerProvider::builder()
+        .with_batch_exporter(exporter)
+        .with_resource(Resource::builder().with_service_name(service_name).build())
+        .build();
+    let tracer = provider.tracer("dynamo");
+    let _ = TRACER_PROVIDER.set(provider);
+
+    Some(
+        tracing_opentelemetry::layer()
+            .with_tracer(tracer)
+            .with_filter(filters(config)),
+    )
+}
+
+/// The W3C trace context of a request, from its `traceparent` and `tracestate` headers.
+/// It travels with the request from the frontend to the workers, so that the spans of every
+/// component serving the request are part of the caller's trace.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TraceContext {
+    pub traceparent: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tracestate: Option<String>,
+}
+
+impl TraceContext {
+    pub const TRACEPARENT_HEADER: &'static str = "traceparent";
+    pub const TRACESTATE_HEADER: &'static str = "tracestate";
+
+    /// `None` if `traceparent` is not a valid W3C `traceparent`
+    pub fn new(traceparent: impl Into<String>, tracestate: Option<String>) -> Option<Self> {
+        let trace_context = TraceContext {
+            traceparent: traceparent.into(),
+            tracestate: tracestate.filter(|tracestate| !tracestate.is_empty()),
+        };
+        trace_context
+            .otel_context()
+            .span()
+            .span_context()
+            .is_valid()
+            .then_some(trace_context)
+    }
+
+    /// The trace context of `span`; `None` unless spans are exported and `span` is recorded
+    pub fn from_span(span: &tracing::Span) -> Option<Self> {
+        let context = span.context();
+        if !context.span().span_context().is_valid() {
+            return None;
+        }
+        let mut carrier = HashMap::new();
+        TraceContextPropagator::new().inject_context(&context, &mut carrier);
+        TraceContext::new(
+            carrier.remove(Self::TRACEPARENT_HEADER)?,
+            carrier.remove(Self::TRACESTATE_HEADER),
+        )
+    }
+
+    /// The id of the trace, as 32 hex digits
+    pub fn trace_id(&self) -> &str {
+        self.traceparent.split('-').nth(1).unwrap_or_default()
+    }
+
+    /// Make `span` a child of the span this trace context was taken from
+    pub fn attach(&self, span: &tracing::Span) {
+        span.set_parent(self.otel_context());
+    }
+
+    fn otel_context(&self) -> opentelemetry::Context {
+        let mut carrier = HashMap::from([(
+            Self::TRACEPARENT_HEADER.to_string(),
+            self.traceparent.clone(),
+        )]);
+        if let Some(tracestate) = &self.tracestate {
+            carrier.insert(Self::TRACESTATE_HEADER.to_string(), tracestate.clone());
+        }
+        TraceContextPropagator::new().extract(&carrier)
+    }
+}
+
 /// Log a message with file and line info
 /// Used by Python wrapper
 pub fn log_message(level: &str, message: &str, module: &str, file: &str, line: u32) {
@@ -342,3 +478,43 @@ impl tracing::field::Visit for JsonVisitor {
         );
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TRACEPARENT: &str = "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01";
+
+    #[test]
+    fn test_trace_context() {
+        let trace_context =
+            TraceContext::new(TRACEPARENT, Some("vendor=value".to_string())).unwrap();
+        assert_eq!(trace_context.trace_id(), "4bf92f3577b34da6a3ce929d0e0e4736");
+
+        let json = serde_json::to_string(&trace_context).unwrap();
+        assert_eq!(
+            serde_json::from_str::<TraceContext>(&json).unwrap(),
+            trace_context
+        );
+
+        let trace_context = TraceContext::new(TRACEPARENT, Some(String::new())).unwrap();
+        assert_eq!(trace_context.tracestate, None);
+    }
+
+    #[test]
+    fn test_invalid_trace_context() {
+        assert!(TraceContext::new("not a traceparent", None).is_none());
+        // an all-zero trace id is invalid
+        assert!(TraceContext::new(
+            "00-00000000000000000000000000000000-00f067aa0ba902b7-01",
+            None
+        )
+        .is_none());
+    }
+
+    #[test]
+    fn test_no_span_context_without_exporter() {
+        let span = tracing::info_span!("request");
+        assert!(TraceContext::from_span(&span).is_none());
+    }
+}
@@ -23,9 +23,11 @@
 
 use std::ops::{Deref, DerefMut};
 use std::sync::Arc;
+use std::time::SystemTime;
 
 use super::{AsyncEngineContext, AsyncEngineContextProvider, Data};
 use crate::engine::AsyncEngineController;
+use crate::logging::TraceContext;
 use async_trait::async_trait;
 
 use super::registry::Registry;
@@ -35,6 +37,9 @@ pub struct Context<T: Data> {
     controller: Arc<Controller>, //todo: hold this as an arc
     registry: Registry,
     stages: Vec<String>,
+    priority: Option<i32>,
+    deadline: Option<SystemTime>,
+    trace_context: Option<TraceContext>,
 }
 
 impl<T: Send + Sync + 'static> Context<T> {
@@ -45,6 +50,9 @@ impl<T: Send + Sync + 'static> Context<T> {
             controller: Arc::new(Controller::default()),
             registry: Registry::new(),
             stages: Vec::new(),
+            priority: None,
+            deadline: None,
+            trace_context: None,
         }
     }
 
@@ -54,6 +62,9 @@ impl<T: Send + Sync + 'static> Context<T> {
             controller: Arc::new(controller),
             registry: Registry::new(),
             stages: Vec::new(),
+            priority: None,
+            deadline: None,
+            trace_context: None,
         }
     }
 
@@ -63,6 +74,9 @@ impl<T: Send + Sync + 'static> Context<T> {
             controller: Arc::new(Controller::new(id)),
             registry: Registry::new(),
             stages: Vec::new(),
+            priority: None,
+            deadline: None,
+            trace_context: None,
         }
     }
 
@@ -109,6 +123,9 @@ impl<T: Send + Sync + 'static> Context<T> {
                 controller: self.controller,
                 registry: self.registry,
                 stages: self.stages,
+                priority: self.priority,
+                deadline: self.deadline,
+                trace_context: self.trace_context,
             },
         )
     }
@@ -126,6 +143,61 @@ impl<T: Send + Sync + 'static> Context<T> {
         self.stages.push(stage.to_string());
     }
 
+    /// How urgent the request is, higher is more urgent. `None` is the default priority, 0.
+    pub fn priority(&self) -> Option<i32> {
+        self.priority
+    }
+
+    pub fn set_priority(&mut self, priority: Option<i32>) {
+        self.priority = priority;
+    }
+
+    /// The time by which the request must be complete, if any.
+    pub fn deadline(&self) -> Option<SystemTime> {
+        self.deadline
+    }
+
+    /// Set the deadline of the request, or move it earlier if it already has one. When the
+    /// deadline passes the request is stopped, as if the requester had called `stop_generating`.
+    ///
+    /// Must be called within a tokio runtime, which the deadline is watched on.
+    pub fn set_deadline(&mut self, deadline: Option<SystemTime>) {
+        let Some(deadline) = deadline else {
+            return;
+        };
+        if self.deadline.is_some_and(|current| current <= deadline) {
+            return;
+        }
+        self.deadline = Some(deadline);
+
+        // the watchdog must not keep a finished request alive until its deadline
+        let controller = Arc::downgrade(&self.controller);
+        let timeout = deadline
+            .duration_since(SystemTime::now())
+            .unwrap_or_default();
+        tokio::spawn(async move {
+            tokio::time::sleep(timeout).await;
+            if let Some(controller) = controller.upgrade() {
+                if !controller.is_stopped() {
+                    tracing::debug!(
+                        request_id = controller.id(),
+                        "Deadline passed; stopping generation"
+                    );
+                    controller.stop_generating();
+                }
+            }
+        });
+    }
+
+    /// The W3C trace context of the request, for the spans serving it to join the caller's trace
+    pub fn trace_context(&self) -> Option<&TraceContext> {
+        self.trace_context.as_ref()
+    }
+
+    pub fn set_trace_context(&mut self, trace_context: Option<TraceContext>) {
+        self.trace_context = trace_context;
+    }
+
     /// Transforms the current context to another type using a provided function.
     pub fn map<U: Send + Sync + 'static, F>(self, f: F) -> Context<U>
     where
@@ -353,18 +425,13 @@ impl AsyncEngineContext for Controller {
 
     async fn stopped(&self) {
         let mut rx = self.rx.clone();
-        if *rx.borrow_and_update() != State::Live {
-            return;
-        }
-        let _ = rx.changed().await;
+        let _ = rx.wait_for(|state| *state != State::Live).await;
     }
 
     async fn killed(&self) {
+        // a stop must not wake up tasks waiting for a kill, they would escalate it
         let mut rx = self.rx.clone();
-        if *rx.borrow_and_update() == State::Killed {
-            return;
-        }
-        let _ = rx.changed().await;
+        let _ = rx.wait_for(|state| *state == State::Killed).await;
     }
 
     fn stop_generating(&self) {
@@ -464,4 +531,47 @@ mod tests {
 
         assert_eq!(ctx.current.message, "Processed length: 5");
     }
+
+    #[tokio::test]
+    async fn test_deadline() {
+        let ctx = Context::new(Input {
+            value: "Hello".to_string(),
+        });
+        assert_eq!(ctx.deadline(), None);
+
+        let mut ctx: Context<Processed> = ctx.map(|input| input.into());
+        let deadline = SystemTime::now() + std::time::Duration::from_millis(50);
+        ctx.set_deadline(Some(deadline));
+        // a later deadline does not extend the request
+        ctx.set_deadline(Some(deadline + std::time::Duration::from_secs(60)));
+        ctx.set_priority(Some(1));
+
+        let ctx: Context<Final> = ctx.map(|processed| processed.into());
+        assert_eq!(ctx.deadline(), Some(deadline));
+        assert_eq!(ctx.priority(), Some(1));
+
+        let context = ctx.context();
+        assert!(!context.is_stopped());
+        tokio::time::timeout(std::time::Duration::from_secs(5), context.stopped())
+            .await
+            .expect("the request is stopped at its deadline");
+        assert!(!context.is_killed());
+    }
+
+    #[tokio::test]
+    async fn test_stop_then_kill() {
+        let controller = Controller::default();
+        assert!(!controller.is_stopped());
+
+        controller.stop_generating();
+        assert!(controller.is_stopped() && !controller.is_killed());
+        controller.stopped().await;
+        let killed =
+            tokio::time::timeout(std::time::Duration::from_millis(10), controller.killed());
+        assert!(killed.await.is_err());
+
+        controller.kill();
+        assert!(controller.is_killed());
+        controller.killed().await;
+    }
 }
@@ -17,7 +17,9 @@
 
 pub mod codec;
 pub mod egress;
+pub mod encoding;
 pub mod ingress;
+pub mod request_plane;
 pub mod tcp;
 
 use std::sync::{Arc, OnceLock};
@@ -27,6 +29,7 @@ use async_trait::async_trait;
 use bytes::Bytes;
 use codec::{TwoPartCodec, TwoPartMessage, TwoPartMessageType};
 use derive_builder::Builder;
+use encoding::PayloadEncoding;
 use futures::StreamExt;
 // io::Cursor, TryStreamExt
 use super::{AsyncEngine, AsyncEngineContext, AsyncEngineContextProvider, ResponseStream};
@@ -70,6 +73,10 @@ pub enum ControlMessage {
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
 pub struct ResponseStreamPrologue {
     error: Option<String>,
+
+    /// The encoding of the response frames which follow; absent from workers that only speak JSON
+    #[serde(default, skip_serializing_if = "PayloadEncoding::is_json")]
+    encoding: PayloadEncoding,
 }
 
 pub type StreamProvider<T> = tokio::sync::oneshot::Receiver<Result<T, String>>;
@@ -149,6 +156,13 @@ pub struct StreamSender {
 }
 
 impl StreamSender {
+    /// Declare in the prologue the encoding of the response frames
+    pub fn set_encoding(&mut self, encoding: PayloadEncoding) {
+        if let Some(prologue) = &mut self.prologue {
+            prologue.encoding = encoding;
+        }
+    }
+
     pub async fn send(&self, data: Bytes) -> Result<()> {
         Ok(self.tx.send(TwoPartMessage::from_data(data)).await?)
     }
@@ -185,6 +199,7 @@ impl StreamSender {
 
 pub struct StreamReceiver {
     rx: tokio::sync::mpsc::Receiver<Bytes>,
+    encoding: PayloadEncoding,
 }
 
 /// Connection Info is encoded as JSON and then again serialized has part of the Transport
@@ -274,6 +289,15 @@ struct RequestControlMessage {
     request_type: RequestType,
     response_type: ResponseType,
     connection_info: ConnectionInfo,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    priority: Option<i32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    deadline: Option<std::time::SystemTime>,
+    /// The encoding of the request body, and the one the requester would like the responses in
+    #[serde(default, skip_serializing_if = "PayloadEncoding::is_json")]
+    encoding: PayloadEncoding,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    trace_context: Option<crate::logging::TraceContext>,
 }
 
 pub struct Ingress<Req: PipelineIO, Resp: PipelineIO> {
@@ -13,10 +13,10 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use async_nats::client::Client;
 use tracing as log;
 
 use super::*;
+use crate::pipeline::network::request_plane::RequestPlaneClient;
 use crate::Result;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -39,6 +39,15 @@ struct RequestControlMessage {
     request_type: RequestType,
     response_type: ResponseType,
     connection_info: ConnectionInfo,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    priority: Option<i32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    deadline: Option<std::time::SystemTime>,
+    /// The encoding of the request body, and the one the requester would like the responses in
+    #[serde(default, skip_serializing_if = "PayloadEncoding::is_json")]
+    encoding: PayloadEncoding,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    trace_context: Option<crate::logging::TraceContext>,
 }
 
 pub struct AddressedRequest<T> {
@@ -57,21 +66,37 @@ impl<T> AddressedRequest<T> {
 }
 
 pub struct AddressedPushRouter {
-    // todo: generalize with a generic
-    req_transport: Client,
+    /// Delivers the request to the addressed instance, over NATS or direct TCP
+    req_transport: Arc<dyn RequestPlaneClient>,
 
     // todo: generalize with a generic
     resp_transport: Arc<tcp::server::TcpStreamServer>,
+
+    /// The encoding of request bodies, and the one workers are asked to respond in
+    encoding: PayloadEncoding,
 }
 
 impl AddressedPushRouter {
     pub fn new(
-        req_transport: Client,
+        req_transport: Arc<dyn RequestPlaneClient>,
         resp_transport: Arc<tcp::server::TcpStreamServer>,
     ) -> Result<Arc<Self>> {
         Ok(Arc::new(Self {
             req_transport,
             resp_transport,
+            encoding: PayloadEncoding::from_env(),
+        }))
+    }
+
+    pub fn with_encoding(
+        req_transport: Arc<dyn RequestPlaneClient>,
+        resp_transport: Arc<tcp::server::TcpStreamServer>,
+        encoding: PayloadEncoding,
+    ) -> Result<Arc<Self>> {
+        Ok(Arc::new(Self {
+            req_transport,
+            resp_transport,
+            encoding,
         }))
     }
 }
@@ -120,13 +145,19 @@ where
             request_type: RequestType::SingleIn,
             response_type: ResponseType::ManyOut,
             connection_info,
+            priority: context.priority(),
+            deadline: context.deadline(),
+            encoding: self.encoding,
+            // continue the trace from the span sending the request when spans are exported
+            trace_context: crate::logging::TraceContext::from_span(&tracing::Span::current())
+                .or_else(|| context.trace_context().cloned()),
         };
 
         // next build the two part message where we package the connection info and the request into
         // a single Vec<u8> that can be sent over the wire.
         // --- package this up in the WorkQueuePublisher ---
         let ctrl = serde_json::to_vec(&control_message)?;
-        let data = serde_json::to_vec(&request)?;
+        let data = self.encoding.encode(&request)?;
 
         log::trace!(
             request_id,
@@ -145,14 +176,14 @@ where
 
         // TRANSPORT ABSTRACT REQUIRED - END HERE
 
-        log::trace!(request_id, "enqueueing two-part message to nats");
+        log::trace!(
+            request_id,
+            "enqueueing two-part message on the request plane"
+        );
 
         // we might need to add a timeout on this if there is no subscriber to the subject; however, I think nats
         // will handle this for us
-        let _response = self
-            .req_transport
-            .request(address.to_string(), buffer)
-            .await?;
+        self.req_transport.send_request(&address, buffer).await?;
 
         log::trace!(request_id, "awaiting transport handshake");
         let response_stream = response_stream_provider
@@ -160,14 +191,16 @@ where
             .map_err(|_| PipelineError::DetatchedStreamReceiver)?
             .map_err(PipelineError::ConnectionFailed)?;
 
+        // the worker declares the encoding of its responses in the prologue; workers which
+        // predate the negotiation respond in JSON whatever was asked for
+        let encoding = response_stream.encoding;
         let stream = tokio_stream::wrappers::ReceiverStream::new(response_stream.rx);
 
-        let stream = stream.filter_map(|msg| async move {
-            match serde_json::from_slice::<U>(&msg) {
+        let stream = stream.filter_map(move |msg| async move {
+            match encoding.decode::<U>(&msg) {
                 Ok(r) => Some(r),
                 Err(err) => {
-                    let json_str = String::from_utf8_lossy(&msg);
-                    log::warn!(%err, %json_str, "Failed deserializing JSON to response");
+                    log::warn!(%err, ?encoding, bytes = msg.len(), "Failed deserializing response");
                     None
                 }
             }
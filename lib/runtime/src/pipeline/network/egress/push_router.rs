@@ -32,7 +32,8 @@ use crate::{
     component::{Client, Endpoint, InstanceSource},
     engine::{AsyncEngine, Data},
     pipeline::{
-        error::PipelineErrorExt, AddressedPushRouter, AddressedRequest, Error, ManyOut, SingleIn,
+        error::PipelineErrorExt, network::tcp::request_plane::NoResponders as TcpNoResponders,
+        AddressedPushRouter, AddressedRequest, Error, ManyOut, SingleIn,
     },
     traits::DistributedRuntimeProvider,
 };
@@ -86,7 +87,9 @@ impl RouterMode {
 
 async fn addressed_router(endpoint: &Endpoint) -> anyhow::Result<Arc<AddressedPushRouter>> {
     AddressedPushRouter::new(
-        endpoint.drt().nats_client.client().clone(),
+        endpoint
+            .drt()
+            .request_plane_client(endpoint.request_plane()),
         endpoint.drt().tcp_server().await?,
     )
 }
@@ -201,15 +204,25 @@ where
     {
         let instance_id = routing_algorithm().await?;
 
-        let subject = self.client.endpoint.subject_to(instance_id);
-        let request = request.map(|req| AddressedRequest::new(req, subject));
+        // the instance advertises where it takes requests: its NATS subject, or the address of
+        // its TCP request server
+        let address = self
+            .client
+            .instances()
+            .into_iter()
+            .find(|instance| instance.id() == instance_id)
+            .map(|instance| instance.transport.request_address())
+            .unwrap_or_else(|| self.client.endpoint.subject_to(instance_id));
+        let request = request.map(|req| AddressedRequest::new(req, address));
 
         let stream = self.addressed.generate(request).await;
         if let Some(err) = stream.as_ref().err() {
-            if let Some(req_err) = err.downcast_ref::<NatsRequestError>() {
-                if matches!(req_err.kind(), NatsNoResponders) {
-                    self.client.report_instance_down(instance_id).await;
-                }
+            let no_responders = match err.downcast_ref::<NatsRequestError>() {
+                Some(req_err) => matches!(req_err.kind(), NatsNoResponders),
+                None => err.downcast_ref::<TcpNoResponders>().is_some(),
+            };
+            if no_responders {
+                self.client.report_instance_down(instance_id).await;
             }
         }
         stream
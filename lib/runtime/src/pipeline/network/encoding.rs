@@ -0,0 +1,142 @@
+// SPDX-FileCopyrightText: Copyright (c) 2024-2025 NVIDIA CORPORATION & AFFILIATES. All rights reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+//! Encoding of the request body and the response frames exchanged between a router and a worker.
+//!
+//! The requester declares the encoding in its `RequestControlMessage`; the worker decodes the
+//! request with it and answers in the same encoding, echoing it in the [`super::ResponseStreamPrologue`].
+//! Control messages and prologues are always JSON. A worker that predates this negotiation
+//! ignores the declared encoding and answers in JSON, which the requester still decodes, but it
+//! can only decode JSON requests: MessagePack needs every worker of the endpoint upgraded first.
+
+use anyhow::{Context as _, Result};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+/// Environment variable selecting the encoding a router asks workers to use: `json` or `msgpack`
+pub const PAYLOAD_ENCODING_ENV: &str = "DYN_PAYLOAD_ENCODING";
+
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum PayloadEncoding {
+    #[default]
+    Json,
+    /// MessagePack, with structs encoded as maps so optional and defaulted fields keep working
+    Msgpack,
+}
+
+impl PayloadEncoding {
+    /// The encoding set by [`PAYLOAD_ENCODING_ENV`], JSON if it is unset or not recognized
+    pub fn from_env() -> Self {
+        match std::env::var(PAYLOAD_ENCODING_ENV) {
+            Ok(value) if !value.is_empty() => value.parse().unwrap_or_else(|err| {
+                tracing::warn!(%err, "Falling back to JSON payload encoding");
+                PayloadEncoding::Json
+            }),
+            _ => PayloadEncoding::Json,
+        }
+    }
+
+    pub fn is_json(&self) -> bool {
+        *self == PayloadEncoding::Json
+    }
+
+    pub fn encode<T: Serialize + ?Sized>(&self, value: &T) -> Result<Vec<u8>> {
+        match self {
+            PayloadEncoding::Json => Ok(serde_json::to_vec(value)?),
+            PayloadEncoding::Msgpack => Ok(rmp_serde::to_vec_named(value)?),
+        }
+    }
+
+    pub fn decode<T: DeserializeOwned>(&self, bytes: &[u8]) -> Result<T> {
+        match self {
+            PayloadEncoding::Json => {
+                serde_json::from_slice(bytes).context("Failed to decode JSON payload")
+            }
+            PayloadEncoding::Msgpack => {
+                rmp_serde::from_slice(bytes).context("Failed to decode MessagePack payload")
+            }
+        }
+    }
+}
+
+impl std::str::FromStr for PayloadEncoding {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "json" => Ok(PayloadEncoding::Json),
+            "msgpack" | "messagepack" => Ok(PayloadEncoding::Msgpack),
+            _ => anyhow::bail!("Unknown payload encoding '{s}'; expected 'json' or 'msgpack'"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Item {
+        token_ids: Vec<u32>,
+        text: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        finish_reason: Option<String>,
+        #[serde(default)]
+        cum_log_probs: Option<f64>,
+    }
+
+    fn item() -> Item {
+        Item {
+            token_ids: vec![1, 2, 3],
+            text: Some("hello".to_string()),
+            finish_reason: None,
+            cum_log_probs: Some(-0.5),
+        }
+    }
+
+    #[test]
+    fn test_round_trip() {
+        for encoding in [PayloadEncoding::Json, PayloadEncoding::Msgpack] {
+            let bytes = encoding.encode(&item()).unwrap();
+            let decoded: Item = encoding.decode(&bytes).unwrap();
+            assert_eq!(decoded, item(), "{encoding:?}");
+        }
+    }
+
+    #[test]
+    fn test_msgpack_is_smaller() {
+        let json = PayloadEncoding::Json.encode(&item()).unwrap();
+        let msgpack = PayloadEncoding::Msgpack.encode(&item()).unwrap();
+        assert!(msgpack.len() < json.len());
+    }
+
+    #[test]
+    fn test_mismatched_encoding_fails() {
+        let bytes = PayloadEncoding::Msgpack.encode(&item()).unwrap();
+        assert!(PayloadEncoding::Json.decode::<Item>(&bytes).is_err());
+    }
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(
+            "msgpack".parse::<PayloadEncoding>().unwrap(),
+            PayloadEncoding::Msgpack
+        );
+        assert_eq!(
+            "JSON".parse::<PayloadEncoding>().unwrap(),
+            PayloadEncoding::Json
+        );
+        assert!("bincode".parse::<PayloadEncoding>().is_err());
+    }
+
+    #[test]
+    fn test_missing_encoding_is_json() {
+        #[derive(Deserialize)]
+        struct Header {
+            #[serde(default)]
+            encoding: PayloadEncoding,
+        }
+        let header: Header = serde_json::from_str("{}").unwrap();
+        assert_eq!(header.encoding, PayloadEncoding::Json);
+    }
+}
@@ -19,7 +19,7 @@ use super::*;
 use anyhow::Result;
 use async_nats::service::endpoint::Endpoint;
 use derive_builder::Builder;
-use tokio::sync::Notify;
+use tokio::sync::{mpsc, Notify};
 use tokio_util::sync::CancellationToken;
 
 #[derive(Builder)]
@@ -67,45 +67,88 @@ impl PushEndpoint {
                     tracing::warn!("Failed to respond to request; this may indicate the request has shutdown: {:?}", e);
                 }
 
-                let ingress = self.service_handler.clone();
-                let worker_id = "".to_string();
-
-                // increment the inflight counter
-                inflight.fetch_add(1, Ordering::SeqCst);
-                let inflight_clone = inflight.clone();
-                let notify_clone = notify.clone();
-
-                tokio::spawn(async move {
-                    tracing::trace!(worker_id, "handling new request");
-                    let result = ingress.handle_payload(req.message.payload).await;
-                    match result {
-                        Ok(_) => {
-                            tracing::trace!(worker_id, "request handled successfully");
-                        }
-                        Err(e) => {
-                            tracing::warn!("Failed to handle request: {:?}", e);
-                        }
-                    }
-
-                    // decrease the inflight counter
-                    inflight_clone.fetch_sub(1, Ordering::SeqCst);
-                    notify_clone.notify_one();
-                });
+                self.dispatch(req.message.payload, &inflight, &notify);
             } else {
                 break;
             }
         }
 
-        // await for all inflight requests to complete
-        tracing::info!(
-            "Waiting for {} inflight requests to complete",
-            inflight.load(Ordering::SeqCst)
-        );
-        while inflight.load(Ordering::SeqCst) > 0 {
-            notify.notified().await;
+        wait_for_inflight(&inflight, &notify).await;
+
+        Ok(())
+    }
+
+    /// Serve the requests a [`TcpRequestServer`](crate::pipeline::network::tcp::request_plane::TcpRequestServer)
+    /// receives for this endpoint
+    pub async fn start_tcp(self, mut requests: mpsc::Receiver<Bytes>) -> Result<()> {
+        let inflight = Arc::new(AtomicU64::new(0));
+        let notify = Arc::new(Notify::new());
+        let mut closed = false;
+
+        loop {
+            let payload = tokio::select! {
+                biased;
+
+                payload = requests.recv() => payload,
+
+                // process shutdown
+                _ = self.cancellation_token.cancelled(), if !closed => {
+                    tracing::info!("Shutting down service");
+                    // refuse new requests, but serve the ones already acknowledged
+                    requests.close();
+                    closed = true;
+                    continue;
+                }
+            };
+
+            match payload {
+                Some(payload) => self.dispatch(payload, &inflight, &notify),
+                None => break,
+            }
         }
-        tracing::info!("All inflight requests completed");
+
+        wait_for_inflight(&inflight, &notify).await;
 
         Ok(())
     }
+
+    /// Handle the request on a task of its own, tracked by the inflight counter
+    fn dispatch(&self, payload: Bytes, inflight: &Arc<AtomicU64>, notify: &Arc<Notify>) {
+        let ingress = self.service_handler.clone();
+        let worker_id = "".to_string();
+
+        // increment the inflight counter
+        inflight.fetch_add(1, Ordering::SeqCst);
+        let inflight_clone = inflight.clone();
+        let notify_clone = notify.clone();
+
+        tokio::spawn(async move {
+            tracing::trace!(worker_id, "handling new request");
+            let result = ingress.handle_payload(payload).await;
+            match result {
+                Ok(_) => {
+                    tracing::trace!(worker_id, "request handled successfully");
+                }
+                Err(e) => {
+                    tracing::warn!("Failed to handle request: {:?}", e);
+                }
+            }
+
+            // decrease the inflight counter
+            inflight_clone.fetch_sub(1, Ordering::SeqCst);
+            notify_clone.notify_one();
+        });
+    }
+}
+
+/// await for all inflight requests to complete
+async fn wait_for_inflight(inflight: &AtomicU64, notify: &Notify) {
+    tracing::info!(
+        "Waiting for {} inflight requests to complete",
+        inflight.load(Ordering::SeqCst)
+    );
+    while inflight.load(Ordering::SeqCst) > 0 {
+        notify.notified().await;
+    }
+    tracing::info!("All inflight requests completed");
 }
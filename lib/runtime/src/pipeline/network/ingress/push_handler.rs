@@ -14,7 +14,9 @@
 // limitations under the License.
 
 use super::*;
+use crate::logging::TraceContext;
 use serde::{Deserialize, Serialize};
+use tracing::Instrument;
 
 #[async_trait]
 impl<T: Data, U: Data> PushWorkHandler for Ingress<SingleIn<T>, ManyOut<U>>
@@ -46,7 +48,12 @@ where
                         ));
                     }
                 };
-                let request: T = serde_json::from_slice(&data)?;
+                let request: T = control_msg.encoding.decode(&data).map_err(|err| {
+                    PipelineError::DeserializationError(format!(
+                        "Failed decoding {:?} request: {err:#}",
+                        control_msg.encoding
+                    ))
+                })?;
                 (control_msg, request)
             }
             _ => {
@@ -57,19 +64,53 @@ where
         // extend request with context
         tracing::trace!("received control message: {:?}", control_msg);
         tracing::trace!("received request: {:?}", request);
-        let request: context::Context<T> = Context::with_id(request, control_msg.id);
+        let encoding = control_msg.encoding;
 
+        // the span serving the request continues the requester's trace
+        let span = tracing::info_span!(
+            "handle_payload",
+            request_id = %control_msg.id,
+            trace_id = tracing::field::Empty
+        );
+        if let Some(trace_context) = &control_msg.trace_context {
+            trace_context.attach(&span);
+            span.record("trace_id", trace_context.trace_id());
+        }
+        let trace_context = TraceContext::from_span(&span).or(control_msg.trace_context);
+
+        let mut request: context::Context<T> = Context::with_id(request, control_msg.id);
+        request.set_priority(control_msg.priority);
+        request.set_deadline(control_msg.deadline);
+        request.set_trace_context(trace_context);
+
+        self.respond(request, control_msg.connection_info, encoding)
+            .instrument(span)
+            .await
+    }
+}
+
+impl<T: Data, U: Data> Ingress<SingleIn<T>, ManyOut<U>>
+where
+    T: Data + for<'de> Deserialize<'de> + std::fmt::Debug,
+    U: Data + Serialize + std::fmt::Debug,
+{
+    /// Generate the response to the request and stream it back to the requester
+    async fn respond(
+        &self,
+        request: context::Context<T>,
+        connection_info: ConnectionInfo,
+        encoding: PayloadEncoding,
+    ) -> Result<(), PipelineError> {
         // todo - eventually have a handler class which will returned an abstracted object, but for now,
         // we only support tcp here, so we can just unwrap the connection info
         tracing::trace!("creating tcp response stream");
-        let mut publisher = tcp::client::TcpClient::create_response_stream(
-            request.context(),
-            control_msg.connection_info,
-        )
-        .await
-        .map_err(|e| {
-            PipelineError::Generic(format!("Failed to create response stream: {:?}", e,))
-        })?;
+        let mut publisher =
+            tcp::client::TcpClient::create_response_stream(request.context(), connection_info)
+                .await
+                .map_err(|e| {
+                    PipelineError::Generic(format!("Failed to create response stream: {:?}", e,))
+                })?;
+        publisher.set_encoding(encoding);
 
         tracing::trace!("calling generate");
         let stream = self
@@ -97,9 +138,25 @@ where
 
         let context = stream.context();
 
-        while let Some(resp) = stream.next().await {
+        loop {
+            let resp = tokio::select! {
+                biased;
+
+                // the requester is gone, possibly before the engine produced anything;
+                // dropping the stream lets the engine free the request's resources
+                _ = context.killed() => {
+                    tracing::debug!("Request {} was cancelled by the requester", context.id());
+                    break;
+                }
+
+                resp = stream.next() => match resp {
+                    Some(resp) => resp,
+                    None => break,
+                },
+            };
             tracing::trace!("Sending response: {:?}", resp);
-            let resp_bytes = serde_json::to_vec(&resp)
+            let resp_bytes = encoding
+                .encode(&resp)
                 .expect("fatal error: invalid response object - this should never happen");
             if (publisher.send(resp_bytes.into()).await).is_err() {
                 tracing::error!("Failed to publish response for stream {}", context.id());
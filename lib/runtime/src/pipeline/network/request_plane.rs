@@ -0,0 +1,91 @@
+// SPDX-FileCopyrightText: Copyright (c) 2024-2025 NVIDIA CORPORATION & AFFILIATES. All rights reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+//! The request plane carries the two-part request message from a router to the worker instance
+//! it picked. Responses always come back over the [`super::tcp`] response streams.
+//!
+//! Two transports are available:
+//! - NATS (the default), where each instance subscribes to its endpoint subject.
+//! - Direct TCP, where each instance listens on a [`super::tcp::request_plane::TcpRequestServer`]
+//!   and advertises its address in its [`crate::component::Instance`] record; no NATS server
+//!   is needed to route requests.
+
+use anyhow::Result;
+use async_trait::async_trait;
+use bytes::Bytes;
+use serde::{Deserialize, Serialize};
+
+/// Environment variable selecting the request plane: `nats` or `tcp`
+pub const REQUEST_PLANE_ENV: &str = "DYN_REQUEST_PLANE";
+
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum RequestPlaneMode {
+    #[default]
+    Nats,
+    Tcp,
+}
+
+impl RequestPlaneMode {
+    /// The request plane set by [`REQUEST_PLANE_ENV`], NATS if it is unset or not recognized
+    pub fn from_env() -> Self {
+        match std::env::var(REQUEST_PLANE_ENV) {
+            Ok(value) if !value.is_empty() => value.parse().unwrap_or_else(|err| {
+                tracing::warn!(%err, "Falling back to the NATS request plane");
+                RequestPlaneMode::Nats
+            }),
+            _ => RequestPlaneMode::Nats,
+        }
+    }
+
+    pub fn is_tcp(&self) -> bool {
+        *self == RequestPlaneMode::Tcp
+    }
+}
+
+impl std::str::FromStr for RequestPlaneMode {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "nats" => Ok(RequestPlaneMode::Nats),
+            "tcp" => Ok(RequestPlaneMode::Tcp),
+            _ => anyhow::bail!("Unknown request plane '{s}'; expected 'nats' or 'tcp'"),
+        }
+    }
+}
+
+/// Sends a request to the worker instance at `address` and returns once the instance
+/// acknowledged it; the response stream is set up separately by the instance calling home.
+#[async_trait]
+pub trait RequestPlaneClient: Send + Sync {
+    async fn send_request(&self, address: &str, payload: Bytes) -> Result<()>;
+}
+
+#[async_trait]
+impl RequestPlaneClient for async_nats::Client {
+    async fn send_request(&self, address: &str, payload: Bytes) -> Result<()> {
+        // the reply is an empty acknowledgement; errors keep their type so callers can
+        // recognize `NoResponders`
+        let _response = self.request(address.to_string(), payload).await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(
+            "tcp".parse::<RequestPlaneMode>().unwrap(),
+            RequestPlaneMode::Tcp
+        );
+        assert_eq!(
+            "NATS".parse::<RequestPlaneMode>().unwrap(),
+            RequestPlaneMode::Nats
+        );
+        assert!("zmq".parse::<RequestPlaneMode>().is_err());
+    }
+}
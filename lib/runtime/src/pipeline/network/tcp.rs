@@ -28,12 +28,19 @@
 //! - CallHome stream - the address for the listening socket is forward via some mechanism which then
 //!   connects back to the source of the CallHome stream. To match the socket with an awaiting data
 //!   stream, the CallHomeHandshake is used.
+//!
+//! Each registered stream has a one-time token that is only shared in its connection info; the
+//! server pairs a socket with the stream only if the handshake presents that token. With TLS
+//! configured on the server, the connection info tells the client to call home over TLS.
 
 pub mod client;
+pub mod request_plane;
 pub mod server;
+pub mod tls;
 
 use super::ControlMessage;
 use serde::{Deserialize, Serialize};
+use tokio::io::{AsyncRead, AsyncWrite};
 
 #[allow(unused_imports)]
 use super::{
@@ -49,6 +56,14 @@ pub struct TcpStreamConnectionInfo {
     pub subject: String,
     pub context: String,
     pub stream_type: StreamType,
+
+    /// One-time token the client presents in its [`CallHomeHandshake`]
+    #[serde(default)]
+    pub token: String,
+
+    /// Whether the server expects the client to call home over TLS
+    #[serde(default)]
+    pub tls: bool,
 }
 
 impl From<TcpStreamConnectionInfo> for ConnectionInfo {
@@ -90,6 +105,33 @@ impl TryFrom<ConnectionInfo> for TcpStreamConnectionInfo {
 struct CallHomeHandshake {
     subject: String,
     stream_type: StreamType,
+    #[serde(default)]
+    token: String,
+}
+
+/// A response stream socket, plaintext or TLS
+trait Socket: AsyncRead + AsyncWrite + Send + Unpin {}
+
+impl<T: AsyncRead + AsyncWrite + Send + Unpin> Socket for T {}
+
+type BoxedSocket = Box<dyn Socket>;
+
+/// A random token that is infeasible to guess, hex encoded
+fn one_time_token() -> String {
+    rand::random::<[u8; 32]>()
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect()
+}
+
+/// Compares tokens in time independent of where they differ
+fn tokens_match(expected: &str, presented: &str) -> bool {
+    expected.len() == presented.len()
+        && expected
+            .bytes()
+            .zip(presented.bytes())
+            .fold(0, |diff, (a, b)| diff | (a ^ b))
+            == 0
 }
 
 #[cfg(test)]
@@ -97,7 +139,7 @@ mod tests {
     use crate::engine::AsyncEngineContextProvider;
 
     use super::*;
-    use crate::pipeline::Context;
+    use crate::pipeline::{network::StreamProvider, Context};
 
     #[derive(Debug, Clone, Serialize, Deserialize)]
     struct TestMessage {
@@ -186,4 +228,155 @@ mod tests {
 
         // assert!(data.is_none());
     }
+
+    fn tls_data(file: &str) -> std::path::PathBuf {
+        std::path::PathBuf::from(env!("CARGO_MANIFEST_DIR")).join(format!("tests/data/tls/{file}"))
+    }
+
+    /// Registers a response stream, returning its connection info and the pending receiver
+    async fn register_response_stream(
+        server: &server::TcpStreamServer,
+        context: &Context<()>,
+    ) -> (TcpStreamConnectionInfo, StreamProvider<StreamReceiver>) {
+        let options = StreamOptions::builder()
+            .context(context.context())
+            .enable_request_stream(false)
+            .enable_response_stream(true)
+            .build()
+            .unwrap();
+        let recv_stream = server.register(options).await.recv_stream.unwrap();
+        let info = TcpStreamConnectionInfo::try_from(recv_stream.connection_info).unwrap();
+        (info, recv_stream.stream_provider)
+    }
+
+    #[tokio::test]
+    async fn test_tcp_stream_rejects_wrong_token() {
+        let server = server::TcpStreamServer::new(server::ServerOptions::default())
+            .await
+            .unwrap();
+        let context = Context::new(());
+        let (info, mut stream_provider) = register_response_stream(&server, &context).await;
+        assert_eq!(info.token.len(), 64);
+        assert!(!info.tls);
+
+        // a client that knows the subject but not the token is not paired with the stream
+        let mut forged = info.clone();
+        forged.token = one_time_token();
+        let mut send_stream = client::TcpClient::create_response_stream(
+            Context::with_id((), context.id().to_string()).context(),
+            forged.into(),
+        )
+        .await
+        .unwrap();
+        let _ = send_stream.send_prologue(None).await;
+        let pending =
+            tokio::time::timeout(std::time::Duration::from_millis(200), &mut stream_provider).await;
+        assert!(pending.is_err(), "stream paired with a forged token");
+
+        // the registration survives, so the client holding the token still gets through
+        let mut send_stream = client::TcpClient::create_response_stream(
+            Context::with_id((), context.id().to_string()).context(),
+            info.into(),
+        )
+        .await
+        .unwrap();
+        send_stream.send_prologue(None).await.unwrap();
+        let mut recv_stream = stream_provider.await.unwrap().unwrap();
+
+        send_stream.send(b"token".to_vec().into()).await.unwrap();
+        assert_eq!(recv_stream.rx.recv().await.unwrap(), b"token".as_slice());
+    }
+
+    #[tokio::test]
+    async fn test_tcp_stream_tokenless() {
+        // workers from before the tokens send an empty one, which is a wrong token by default
+        let server = server::TcpStreamServer::new(server::ServerOptions::default())
+            .await
+            .unwrap();
+        let context = Context::new(());
+        let (mut info, mut stream_provider) = register_response_stream(&server, &context).await;
+        info.token = String::new();
+        let mut send_stream = client::TcpClient::create_response_stream(
+            Context::with_id((), context.id().to_string()).context(),
+            info.into(),
+        )
+        .await
+        .unwrap();
+        let _ = send_stream.send_prologue(None).await;
+        let pending =
+            tokio::time::timeout(std::time::Duration::from_millis(200), &mut stream_provider).await;
+        assert!(pending.is_err(), "stream paired without a token");
+
+        // unless the server is told to accept them
+        let options = server::ServerOptions::builder()
+            .accept_tokenless(true)
+            .build()
+            .unwrap();
+        let server = server::TcpStreamServer::new(options).await.unwrap();
+        let (mut info, stream_provider) = register_response_stream(&server, &context).await;
+        info.token = String::new();
+        let mut send_stream = client::TcpClient::create_response_stream(
+            Context::with_id((), context.id().to_string()).context(),
+            info.into(),
+        )
+        .await
+        .unwrap();
+        send_stream.send_prologue(None).await.unwrap();
+        let mut recv_stream = stream_provider.await.unwrap().unwrap();
+
+        send_stream.send(b"legacy".to_vec().into()).await.unwrap();
+        assert_eq!(recv_stream.rx.recv().await.unwrap(), b"legacy".as_slice());
+    }
+
+    #[tokio::test]
+    async fn test_tcp_stream_tls() {
+        let options = server::ServerOptions::builder()
+            .tls(Some(tls::TlsOptions::new(
+                tls_data("server.pem"),
+                tls_data("server.key"),
+            )))
+            .build()
+            .unwrap();
+        let server = server::TcpStreamServer::new(options).await.unwrap();
+        let context = Context::new(());
+        let (info, stream_provider) = register_response_stream(&server, &context).await;
+        assert!(info.tls);
+
+        // the server only speaks TLS
+        let err = client::TcpClient::create_response_stream_with_tls(
+            Context::with_id((), context.id().to_string()).context(),
+            info.clone(),
+            None,
+        )
+        .await;
+        assert!(err.is_err());
+
+        // the server binds to the host address, which is not in the test certificate
+        let tls_client = tls::TlsClient::new(
+            &tls::TlsClientOptions::new(tls_data("ca.pem"))
+                .with_server_name(Some("localhost".to_string())),
+        )
+        .unwrap();
+        let mut send_stream = client::TcpClient::create_response_stream_with_tls(
+            Context::with_id((), context.id().to_string()).context(),
+            info,
+            Some(&tls_client),
+        )
+        .await
+        .unwrap();
+        send_stream.send_prologue(None).await.unwrap();
+        let mut recv_stream = stream_provider.await.unwrap().unwrap();
+
+        send_stream.send(b"token".to_vec().into()).await.unwrap();
+        assert_eq!(recv_stream.rx.recv().await.unwrap(), b"token".as_slice());
+    }
+
+    #[test]
+    fn test_tokens_match() {
+        let token = one_time_token();
+        assert_ne!(token, one_time_token());
+        assert!(tokens_match(&token, &token.clone()));
+        assert!(!tokens_match(&token, &one_time_token()));
+        assert!(!tokens_match(&token, ""));
+    }
 }
@@ -24,7 +24,9 @@ use tokio::{
 };
 use tokio_util::codec::{FramedRead, FramedWrite};
 
-use super::{CallHomeHandshake, ControlMessage, TcpStreamConnectionInfo};
+use super::{
+    tls::TlsClient, BoxedSocket, CallHomeHandshake, ControlMessage, TcpStreamConnectionInfo,
+};
 use crate::engine::AsyncEngineContext;
 use crate::pipeline::network::{
     codec::{TwoPartCodec, TwoPartMessage},
@@ -78,6 +80,21 @@ impl TcpClient {
     ) -> Result<StreamSender> {
         let info =
             TcpStreamConnectionInfo::try_from(info).context("tcp-stream-connection-info-error")?;
+        let tls = if info.tls {
+            Some(TlsClient::from_settings()?)
+        } else {
+            None
+        };
+        Self::create_response_stream_with_tls(context, info, tls).await
+    }
+
+    /// [`TcpClient::create_response_stream`] with the given TLS client instead of the one
+    /// configured by the runtime settings
+    pub(super) async fn create_response_stream_with_tls(
+        context: Arc<dyn AsyncEngineContext>,
+        info: TcpStreamConnectionInfo,
+        tls: Option<&TlsClient>,
+    ) -> Result<StreamSender> {
         tracing::trace!("Creating response stream for {:?}", info);
 
         if info.stream_type != StreamType::Response {
@@ -96,6 +113,15 @@ impl TcpClient {
         }
 
         let stream = TcpClient::connect(&info.address).await?;
+        let stream: BoxedSocket = match (info.tls, tls) {
+            (true, Some(tls)) => Box::new(tls.connect(&info.address, stream).await?),
+            (true, None) => {
+                return Err(error!(
+                    "The response stream requires TLS; however no TLS client was configured"
+                ))
+            }
+            (false, _) => Box::new(stream),
+        };
         let (read_half, write_half) = tokio::io::split(stream);
 
         let framed_reader = FramedRead::new(read_half, TwoPartCodec::default());
@@ -114,6 +140,7 @@ impl TcpClient {
         let handshake = CallHomeHandshake {
             subject: info.subject,
             stream_type: StreamType::Response,
+            token: info.token,
         };
 
         let handshake_bytes = match serde_json::to_vec(&handshake) {
@@ -187,7 +214,10 @@ impl TcpClient {
 
         // set up the prologue for the stream
         // this might have transport specific metadata in the future
-        let prologue = Some(ResponseStreamPrologue { error: None });
+        let prologue = Some(ResponseStreamPrologue {
+            error: None,
+            encoding: Default::default(),
+        });
 
         // create the stream sender
         let stream_sender = StreamSender {
@@ -200,10 +230,10 @@ impl TcpClient {
 }
 
 async fn handle_reader(
-    framed_reader: FramedRead<tokio::io::ReadHalf<tokio::net::TcpStream>, TwoPartCodec>,
+    framed_reader: FramedRead<tokio::io::ReadHalf<BoxedSocket>, TwoPartCodec>,
     context: Arc<dyn AsyncEngineContext>,
     alive_tx: tokio::sync::oneshot::Sender<()>,
-) -> FramedRead<tokio::io::ReadHalf<tokio::net::TcpStream>, TwoPartCodec> {
+) -> FramedRead<tokio::io::ReadHalf<BoxedSocket>, TwoPartCodec> {
     let mut framed_reader = framed_reader;
     let mut alive_tx = alive_tx;
     loop {
@@ -259,11 +289,11 @@ async fn handle_reader(
 }
 
 async fn handle_writer(
-    mut framed_writer: FramedWrite<tokio::io::WriteHalf<tokio::net::TcpStream>, TwoPartCodec>,
+    mut framed_writer: FramedWrite<tokio::io::WriteHalf<BoxedSocket>, TwoPartCodec>,
     mut bytes_rx: tokio::sync::mpsc::Receiver<TwoPartMessage>,
     alive_rx: tokio::sync::oneshot::Receiver<()>,
     context: Arc<dyn AsyncEngineContext>,
-) -> Result<FramedWrite<tokio::io::WriteHalf<tokio::net::TcpStream>, TwoPartCodec>> {
+) -> Result<FramedWrite<tokio::io::WriteHalf<BoxedSocket>, TwoPartCodec>> {
     loop {
         let msg = tokio::select! {
             biased;
@@ -0,0 +1,404 @@
+// SPDX-FileCopyrightText: Copyright (c) 2024-2025 NVIDIA CORPORATION & AFFILIATES. All rights reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+//! Direct TCP request plane.
+//!
+//! Every process serving endpoints runs one [`TcpRequestServer`]; each endpoint instance
+//! registers its subject with it and advertises `tcp://<ip>:<port>/<subject>` in etcd.
+//! A [`TcpRequestClient`] connects to that address, sends a two-part message whose header
+//! names the subject and whose body is the request message, and waits for the server to
+//! acknowledge that the instance has the request queued.
+//!
+//! With a certificate in its [`ServerOptions`] the server only speaks TLS and advertises
+//! `tcps://` addresses, which clients verify with the CA of the runtime settings. The server does
+//! not authenticate clients: anyone who can reach its port can send requests.
+
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use anyhow::Context as _;
+use async_trait::async_trait;
+use bytes::Bytes;
+use futures::{SinkExt, StreamExt};
+use serde::{Deserialize, Serialize};
+use tokio::{net::TcpStream, sync::mpsc, time};
+use tokio_rustls::TlsAcceptor;
+use tokio_util::codec::Framed;
+
+use super::{
+    server::{accept_socket, resolve_local_ip, ServerOptions},
+    tls::{TlsClient, TlsOptions},
+    BoxedSocket,
+};
+use crate::pipeline::{
+    network::{
+        codec::{TwoPartCodec, TwoPartMessage},
+        request_plane::RequestPlaneClient,
+    },
+    PipelineError,
+};
+use crate::Result;
+
+const ADDRESS_SCHEME: &str = "tcp://";
+
+const TLS_ADDRESS_SCHEME: &str = "tcps://";
+
+/// How long a client waits to connect to a server, including the TLS handshake
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// How long a client waits for the server to acknowledge a request once connected
+const ACK_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// The address a router sends requests for `subject` to, served by the [`TcpRequestServer`]
+/// listening on `address`, over TLS if `tls` is set
+pub fn request_address(address: &str, subject: &str, tls: bool) -> String {
+    let scheme = if tls {
+        TLS_ADDRESS_SCHEME
+    } else {
+        ADDRESS_SCHEME
+    };
+    format!("{scheme}{address}/{subject}")
+}
+
+/// Splits a [`request_address`] into the server address, the subject and whether the server
+/// speaks TLS
+fn parse_request_address(address: &str) -> Result<(&str, &str, bool)> {
+    let (rest, tls) = match address.strip_prefix(TLS_ADDRESS_SCHEME) {
+        Some(rest) => (Some(rest), true),
+        None => (address.strip_prefix(ADDRESS_SCHEME), false),
+    };
+    let (server, subject) = rest.and_then(|rest| rest.split_once('/')).ok_or_else(|| {
+        anyhow::anyhow!(
+            "'{address}' is not a TCP request plane address; is the instance serving the NATS request plane?"
+        )
+    })?;
+    Ok((server, subject, tls))
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct RequestHeader {
+    subject: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct RequestAck {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+/// The instance addressed by a request is not served by the server it was sent to, e.g. because
+/// it shut down; the TCP counterpart of NATS' `NoResponders`.
+#[derive(Debug, thiserror::Error)]
+#[error("No responders for subject {subject} at {address}: {reason}")]
+pub struct NoResponders {
+    pub address: String,
+    pub subject: String,
+    pub reason: String,
+}
+
+/// Listens for requests and hands each one to the endpoint registered for its subject
+pub struct TcpRequestServer {
+    address: String,
+    tls: bool,
+    handlers: Arc<Mutex<HashMap<String, mpsc::Sender<Bytes>>>>,
+}
+
+impl TcpRequestServer {
+    pub async fn new(options: ServerOptions) -> Result<Arc<Self>, PipelineError> {
+        let local_ip = resolve_local_ip(options.interface)?;
+
+        let acceptor = options
+            .tls
+            .as_ref()
+            .map(TlsOptions::acceptor)
+            .transpose()
+            .map_err(|e| {
+                PipelineError::Generic(format!("Failed to load TcpRequestServer TLS: {:#}", e))
+            })?;
+        let tls = acceptor.is_some();
+
+        let listener = tokio::net::TcpListener::bind((local_ip.as_str(), options.port))
+            .await
+            .map_err(|e| {
+                PipelineError::Generic(format!("Failed to start TcpRequestServer: {}", e))
+            })?;
+        let local_port = listener
+            .local_addr()
+            .map_err(|e| PipelineError::Generic(format!("Failed get SocketAddr: {:?}", e)))?
+            .port();
+
+        let address = format!("{local_ip}:{local_port}");
+        tracing::debug!(tls, "tcp request plane on {address}");
+
+        let handlers = Arc::new(Mutex::new(HashMap::new()));
+        tokio::spawn(accept_loop(listener, acceptor, handlers.clone()));
+
+        Ok(Arc::new(Self {
+            address,
+            tls,
+            handlers,
+        }))
+    }
+
+    /// The `ip:port` the server listens on
+    pub fn address(&self) -> &str {
+        &self.address
+    }
+
+    /// Whether the server only accepts TLS connections
+    pub fn tls(&self) -> bool {
+        self.tls
+    }
+
+    /// Start accepting requests for `subject`; they are delivered on the returned channel
+    /// until [`TcpRequestServer::deregister`] is called or the receiver is dropped.
+    pub fn register(&self, subject: impl Into<String>) -> mpsc::Receiver<Bytes> {
+        let (tx, rx) = mpsc::channel(64);
+        self.handlers.lock().unwrap().insert(subject.into(), tx);
+        rx
+    }
+
+    pub fn deregister(&self, subject: &str) {
+        self.handlers.lock().unwrap().remove(subject);
+    }
+}
+
+async fn accept_loop(
+    listener: tokio::net::TcpListener,
+    acceptor: Option<TlsAcceptor>,
+    handlers: Arc<Mutex<HashMap<String, mpsc::Sender<Bytes>>>>,
+) {
+    loop {
+        let stream = match listener.accept().await {
+            Ok((stream, _addr)) => stream,
+            Err(e) => {
+                // the client should retry, so we don't need to abort
+                tracing::warn!("failed to accept tcp request connection: {}", e);
+                continue;
+            }
+        };
+        if let Err(e) = stream.set_nodelay(true) {
+            tracing::warn!("failed to set tcp stream to nodelay: {}", e);
+        }
+
+        let acceptor = acceptor.clone();
+        let handlers = handlers.clone();
+        tokio::spawn(async move {
+            let result = match accept_socket(stream, acceptor).await {
+                Ok(socket) => handle_connection(socket, handlers).await,
+                Err(e) => Err(e),
+            };
+            if let Err(e) = result {
+                tracing::warn!("failed to handle tcp request connection: {}", e);
+            }
+        });
+    }
+}
+
+/// Serves the requests of one connection, acknowledging each once it is queued
+async fn handle_connection(
+    stream: BoxedSocket,
+    handlers: Arc<Mutex<HashMap<String, mpsc::Sender<Bytes>>>>,
+) -> Result<()> {
+    let mut framed = Framed::new(stream, TwoPartCodec::default());
+
+    while let Some(message) = framed.next().await {
+        let (header, payload) = message?.into_parts();
+        let header: RequestHeader =
+            serde_json::from_slice(&header).context("Invalid request plane header")?;
+
+        let handler = handlers.lock().unwrap().get(&header.subject).cloned();
+        let error = match handler {
+            Some(handler) => match handler.send(payload).await {
+                Ok(()) => None,
+                Err(_) => Some("endpoint is shutting down".to_string()),
+            },
+            None => Some("no endpoint registered".to_string()),
+        };
+
+        let ack = serde_json::to_vec(&RequestAck { error })?;
+        framed.send(TwoPartMessage::from_header(ack.into())).await?;
+    }
+
+    Ok(())
+}
+
+/// Sends requests to [`TcpRequestServer`]s, one connection per request like the response
+/// streams.
+#[derive(Default, Clone)]
+pub struct TcpRequestClient {
+    tls: Option<TlsClient>,
+}
+
+impl TcpRequestClient {
+    /// A client verifying TLS servers with `tls` instead of the client configured by the runtime
+    /// settings
+    pub fn with_tls(tls: TlsClient) -> Self {
+        Self { tls: Some(tls) }
+    }
+
+    async fn connect(&self, server: &str, tls: Option<&TlsClient>) -> Result<BoxedSocket> {
+        let stream = TcpStream::connect(server).await?;
+        stream.set_nodelay(true)?;
+        Ok(match tls {
+            Some(tls) => Box::new(tls.connect(server, stream).await?),
+            None => Box::new(stream),
+        })
+    }
+}
+
+#[async_trait]
+impl RequestPlaneClient for TcpRequestClient {
+    async fn send_request(&self, address: &str, payload: Bytes) -> Result<()> {
+        let (server, subject, tls) = parse_request_address(address)?;
+        let no_responders = |reason: String| NoResponders {
+            address: server.to_string(),
+            subject: subject.to_string(),
+            reason,
+        };
+
+        let tls = match (tls, &self.tls) {
+            (true, Some(client)) => Some(client),
+            (true, None) => Some(TlsClient::from_settings()?),
+            (false, _) => None,
+        };
+        let stream = time::timeout(CONNECT_TIMEOUT, self.connect(server, tls))
+            .await
+            .map_err(|_| no_responders("timed out connecting".to_string()))?
+            .map_err(|e| no_responders(format!("{e:#}")))?;
+        let mut framed = Framed::new(stream, TwoPartCodec::default());
+
+        let header = serde_json::to_vec(&RequestHeader {
+            subject: subject.to_string(),
+        })?;
+        let ack = time::timeout(ACK_TIMEOUT, async {
+            framed
+                .send(TwoPartMessage::from_parts(header.into(), payload))
+                .await?;
+            anyhow::Ok(framed.next().await.transpose()?)
+        })
+        .await
+        .map_err(|_| {
+            anyhow::anyhow!(
+                "Timed out waiting for {server} to acknowledge the request for {subject}"
+            )
+        })??
+        .ok_or_else(|| {
+            no_responders("connection closed before the request was acknowledged".to_string())
+        })?;
+        let ack: RequestAck = match ack.header() {
+            Some(header) => serde_json::from_slice(header)?,
+            None => anyhow::bail!("Expected a request plane acknowledgement, got data"),
+        };
+
+        match ack.error {
+            None => Ok(()),
+            Some(reason) => Err(no_responders(reason).into()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pipeline::network::tcp::tls::TlsClientOptions;
+    use std::path::PathBuf;
+
+    fn tls_data(file: &str) -> PathBuf {
+        PathBuf::from(env!("CARGO_MANIFEST_DIR")).join(format!("tests/data/tls/{file}"))
+    }
+
+    #[test]
+    fn test_request_address() {
+        let address = request_address("10.0.0.1:4000", "ns|comp.generate-1", false);
+        assert_eq!(
+            parse_request_address(&address).unwrap(),
+            ("10.0.0.1:4000", "ns|comp.generate-1", false)
+        );
+        let address = request_address("10.0.0.1:4000", "ns|comp.generate-1", true);
+        assert_eq!(
+            parse_request_address(&address).unwrap(),
+            ("10.0.0.1:4000", "ns|comp.generate-1", true)
+        );
+        assert!(parse_request_address("ns|comp.generate-1").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_send_request() {
+        let server = TcpRequestServer::new(ServerOptions::default())
+            .await
+            .unwrap();
+        assert!(!server.tls());
+        let mut requests = server.register("ns.comp.generate-1");
+        let client = TcpRequestClient::default();
+
+        let address = request_address(server.address(), "ns.comp.generate-1", false);
+        client
+            .send_request(&address, Bytes::from_static(b"request"))
+            .await
+            .unwrap();
+        assert_eq!(
+            requests.recv().await.unwrap(),
+            Bytes::from_static(b"request")
+        );
+
+        // requests for unknown or deregistered subjects have no responders
+        let address = request_address(server.address(), "ns.comp.generate-2", false);
+        let err = client
+            .send_request(&address, Bytes::from_static(b"request"))
+            .await
+            .unwrap_err();
+        assert!(err.downcast_ref::<NoResponders>().is_some());
+
+        server.deregister("ns.comp.generate-1");
+        let address = request_address(server.address(), "ns.comp.generate-1", false);
+        let err = client
+            .send_request(&address, Bytes::from_static(b"request"))
+            .await
+            .unwrap_err();
+        assert!(err.downcast_ref::<NoResponders>().is_some());
+    }
+
+    #[tokio::test]
+    async fn test_send_request_tls() {
+        let options = ServerOptions::builder()
+            .tls(Some(TlsOptions::new(
+                tls_data("server.pem"),
+                tls_data("server.key"),
+            )))
+            .build()
+            .unwrap();
+        let server = TcpRequestServer::new(options).await.unwrap();
+        assert!(server.tls());
+        let mut requests = server.register("ns.comp.generate-1");
+
+        // the server binds to the host address, which is not in the test certificate
+        let client = TcpRequestClient::with_tls(
+            TlsClient::new(
+                &TlsClientOptions::new(tls_data("ca.pem"))
+                    .with_server_name(Some("localhost".to_string())),
+            )
+            .unwrap(),
+        );
+        let address = request_address(server.address(), "ns.comp.generate-1", true);
+        client
+            .send_request(&address, Bytes::from_static(b"request"))
+            .await
+            .unwrap();
+        assert_eq!(
+            requests.recv().await.unwrap(),
+            Bytes::from_static(b"request")
+        );
+
+        // the server only speaks TLS
+        let address = request_address(server.address(), "ns.comp.generate-1", false);
+        let err = client
+            .send_request(&address, Bytes::from_static(b"request"))
+            .await
+            .unwrap_err();
+        assert!(err.downcast_ref::<NoResponders>().is_some());
+    }
+}
@@ -33,11 +33,13 @@ use tokio::{
     sync::{mpsc, oneshot},
     time,
 };
+use tokio_rustls::TlsAcceptor;
 use tokio_util::codec::{FramedRead, FramedWrite};
 
 use super::{
-    CallHomeHandshake, ControlMessage, PendingConnections, RegisteredStream, StreamOptions,
-    StreamReceiver, StreamSender, TcpStreamConnectionInfo, TwoPartCodec,
+    one_time_token, tls::TlsOptions, tokens_match, BoxedSocket, CallHomeHandshake, ControlMessage,
+    PendingConnections, RegisteredStream, StreamOptions, StreamReceiver, StreamSender,
+    TcpStreamConnectionInfo, TwoPartCodec,
 };
 use crate::engine::AsyncEngineContext;
 use crate::pipeline::{
@@ -53,6 +55,9 @@ use crate::{error, ErrorContext, Result};
 #[allow(dead_code)]
 type ResponseType = TwoPartMessage;
 
+/// Connections that have not completed the TLS handshake by then are closed
+const TLS_HANDSHAKE_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(10);
+
 #[derive(Debug, Serialize, Deserialize, Clone, Builder, Default)]
 pub struct ServerOptions {
     #[builder(default = "0")]
@@ -60,6 +65,15 @@ pub struct ServerOptions {
 
     #[builder(default)]
     pub interface: Option<String>,
+
+    /// Serve response streams over TLS with this certificate
+    #[builder(default)]
+    pub tls: Option<TlsOptions>,
+
+    /// Pair response streams with workers which present no token in their handshake, as
+    /// workers from before the one-time tokens do. Only meant for rolling upgrades.
+    #[builder(default)]
+    pub accept_tokenless: bool,
 }
 
 impl ServerOptions {
@@ -74,6 +88,7 @@ impl ServerOptions {
 pub struct TcpStreamServer {
     local_ip: String,
     local_port: u16,
+    tls: bool,
     state: Arc<Mutex<State>>,
 }
 
@@ -86,11 +101,13 @@ pub struct TcpStreamServer {
 #[allow(dead_code)]
 struct RequestedSendConnection {
     context: Arc<dyn AsyncEngineContext>,
+    token: String,
     connection: oneshot::Sender<Result<StreamSender, String>>,
 }
 
 struct RequestedRecvConnection {
     context: Arc<dyn AsyncEngineContext>,
+    token: String,
     connection: oneshot::Sender<Result<StreamReceiver, String>>,
 }
 
@@ -115,6 +132,7 @@ struct State {
     tx_subjects: HashMap<String, RequestedSendConnection>,
     rx_subjects: HashMap<String, RequestedRecvConnection>,
     handle: Option<tokio::task::JoinHandle<Result<()>>>,
+    accept_tokenless: bool,
 }
 
 impl TcpStreamServer {
@@ -123,50 +141,46 @@ impl TcpStreamServer {
     }
 
     pub async fn new(options: ServerOptions) -> Result<Arc<Self>, PipelineError> {
-        let local_ip = match options.interface {
-            Some(interface) => {
-                let interfaces: HashMap<String, std::net::IpAddr> =
-                    list_afinet_netifas()?.into_iter().collect();
-
-                interfaces
-                    .get(&interface)
-                    .ok_or(PipelineError::Generic(format!(
-                        "Interface not found: {}",
-                        interface
-                    )))?
-                    .to_string()
-            }
-            None => local_ip()
-                .or_else(|err| match err {
-                    Error::LocalIpAddressNotFound => {
-                        // Fall back to IPv6 if no IPv4 addresses are found
-                        local_ipv6()
-                    }
-                    _ => Err(err),
-                })
-                .unwrap()
-                .to_string(),
-        };
+        let local_ip = resolve_local_ip(options.interface)?;
 
-        let state = Arc::new(Mutex::new(State::default()));
+        let acceptor = options
+            .tls
+            .as_ref()
+            .map(TlsOptions::acceptor)
+            .transpose()
+            .map_err(|e| {
+                PipelineError::Generic(format!("Failed to load TcpStreamServer TLS: {:#}", e))
+            })?;
+        let tls = acceptor.is_some();
 
-        let local_port = Self::start(local_ip.clone(), options.port, state.clone())
+        let state = Arc::new(Mutex::new(State {
+            accept_tokenless: options.accept_tokenless,
+            ..Default::default()
+        }));
+
+        let local_port = Self::start(local_ip.clone(), options.port, acceptor, state.clone())
             .await
             .map_err(|e| {
                 PipelineError::Generic(format!("Failed to start TcpStreamServer: {}", e))
             })?;
 
-        tracing::debug!("tcp transport service on {local_ip}:{local_port}");
+        tracing::debug!(tls, "tcp transport service on {local_ip}:{local_port}");
 
         Ok(Arc::new(Self {
             local_ip,
             local_port,
+            tls,
             state,
         }))
     }
 
     #[allow(clippy::await_holding_lock)]
-    async fn start(local_ip: String, local_port: u16, state: Arc<Mutex<State>>) -> Result<u16> {
+    async fn start(
+        local_ip: String,
+        local_port: u16,
+        acceptor: Option<TlsAcceptor>,
+        state: Arc<Mutex<State>>,
+    ) -> Result<u16> {
         let addr = format!("{}:{}", local_ip, local_port);
         let state_clone = state.clone();
         let mut guard = state.lock().await;
@@ -174,7 +188,7 @@ impl TcpStreamServer {
             panic!("TcpStreamServer already started");
         }
         let (ready_tx, ready_rx) = tokio::sync::oneshot::channel::<Result<u16>>();
-        let handle = tokio::spawn(tcp_listener(addr, state_clone, ready_tx));
+        let handle = tokio::spawn(tcp_listener(addr, acceptor, state_clone, ready_tx));
         guard.handle = Some(handle);
         drop(guard);
         let local_port = ready_rx.await??;
@@ -182,6 +196,49 @@ impl TcpStreamServer {
     }
 }
 
+/// Completes the TLS handshake of an accepted connection, if the server is configured for TLS
+pub(super) async fn accept_socket(
+    stream: tokio::net::TcpStream,
+    acceptor: Option<TlsAcceptor>,
+) -> Result<BoxedSocket> {
+    let Some(acceptor) = acceptor else {
+        return Ok(Box::new(stream));
+    };
+    let stream = time::timeout(TLS_HANDSHAKE_TIMEOUT, acceptor.accept(stream))
+        .await
+        .map_err(|_| error!("TLS handshake timed out"))?
+        .map_err(|e| error!("TLS handshake failed: {}", e))?;
+    Ok(Box::new(stream))
+}
+
+/// The IP address of the named interface, or of the host if none is given
+pub(crate) fn resolve_local_ip(interface: Option<String>) -> Result<String, PipelineError> {
+    Ok(match interface {
+        Some(interface) => {
+            let interfaces: HashMap<String, std::net::IpAddr> =
+                list_afinet_netifas()?.into_iter().collect();
+
+            interfaces
+                .get(&interface)
+                .ok_or(PipelineError::Generic(format!(
+                    "Interface not found: {}",
+                    interface
+                )))?
+                .to_string()
+        }
+        None => local_ip()
+            .or_else(|err| match err {
+                Error::LocalIpAddressNotFound => {
+                    // Fall back to IPv6 if no IPv4 addresses are found
+                    local_ipv6()
+                }
+                _ => Err(err),
+            })
+            .unwrap()
+            .to_string(),
+    })
+}
+
 // todo - possible rename ResponseService to ResponseServer
 #[async_trait::async_trait]
 impl ResponseService for TcpStreamServer {
@@ -213,11 +270,13 @@ impl ResponseService for TcpStreamServer {
 
         let send_stream = if options.enable_request_stream {
             let sender_subject = uuid::Uuid::new_v4().to_string();
+            let token = one_time_token();
 
             let (pending_sender_tx, pending_sender_rx) = oneshot::channel();
 
             let connection_info = RequestedSendConnection {
                 context: options.context.clone(),
+                token: token.clone(),
                 connection: pending_sender_tx,
             };
 
@@ -232,6 +291,8 @@ impl ResponseService for TcpStreamServer {
                     subject: sender_subject.clone(),
                     context: options.context.id().to_string(),
                     stream_type: StreamType::Request,
+                    token,
+                    tls: self.tls,
                 }
                 .into(),
                 stream_provider: pending_sender_rx,
@@ -245,9 +306,11 @@ impl ResponseService for TcpStreamServer {
         let recv_stream = if options.enable_response_stream {
             let (pending_recver_tx, pending_recver_rx) = oneshot::channel();
             let receiver_subject = uuid::Uuid::new_v4().to_string();
+            let token = one_time_token();
 
             let connection_info = RequestedRecvConnection {
                 context: options.context.clone(),
+                token: token.clone(),
                 connection: pending_recver_tx,
             };
 
@@ -262,6 +325,8 @@ impl ResponseService for TcpStreamServer {
                     subject: receiver_subject.clone(),
                     context: options.context.id().to_string(),
                     stream_type: StreamType::Response,
+                    token,
+                    tls: self.tls,
                 }
                 .into(),
                 stream_provider: pending_recver_rx,
@@ -287,6 +352,7 @@ impl ResponseService for TcpStreamServer {
 // to the sender
 async fn tcp_listener(
     addr: String,
+    acceptor: Option<TlsAcceptor>,
     state: Arc<Mutex<State>>,
     read_tx: tokio::sync::oneshot::Sender<Result<u16>>,
 ) -> Result<()> {
@@ -343,13 +409,20 @@ async fn tcp_listener(
             }
         }
 
-        tokio::spawn(handle_connection(stream, state.clone()));
+        tokio::spawn(handle_connection(stream, acceptor.clone(), state.clone()));
     }
 
     // #[instrument(level = "trace"), skip(state)]
     // todo - clone before spawn and trace process_stream
-    async fn handle_connection(stream: tokio::net::TcpStream, state: Arc<Mutex<State>>) {
-        let result = process_stream(stream, state).await;
+    async fn handle_connection(
+        stream: tokio::net::TcpStream,
+        acceptor: Option<TlsAcceptor>,
+        state: Arc<Mutex<State>>,
+    ) {
+        let result = match accept_socket(stream, acceptor).await {
+            Ok(socket) => process_stream(socket, state).await,
+            Err(e) => Err(e),
+        };
         match result {
             Ok(_) => tracing::trace!("successfully processed tcp connection"),
             Err(e) => {
@@ -362,7 +435,7 @@ async fn tcp_listener(
 
     /// This method is responsible for the internal tcp stream handshake
     /// The handshake will specialize the stream as a request/sender or response/receiver stream
-    async fn process_stream(stream: tokio::net::TcpStream, state: Arc<Mutex<State>>) -> Result<()> {
+    async fn process_stream(stream: BoxedSocket, state: Arc<Mutex<State>>) -> Result<()> {
         // split the socket in to a reader and writer
         let (read_half, write_half) = tokio::io::split(stream);
 
@@ -394,8 +467,7 @@ async fn tcp_listener(
         match handshake.stream_type {
             StreamType::Request => process_request_stream().await,
             StreamType::Response => {
-                process_response_stream(handshake.subject, state, framed_reader, framed_writer)
-                    .await
+                process_response_stream(handshake, state, framed_reader, framed_writer).await
             }
         }
     }
@@ -405,21 +477,48 @@ async fn tcp_listener(
     }
 
     async fn process_response_stream(
-        subject: String,
+        handshake: CallHomeHandshake,
         state: Arc<Mutex<State>>,
-        mut reader: FramedRead<tokio::io::ReadHalf<tokio::net::TcpStream>, TwoPartCodec>,
-        writer: FramedWrite<tokio::io::WriteHalf<tokio::net::TcpStream>, TwoPartCodec>,
+        mut reader: FramedRead<tokio::io::ReadHalf<BoxedSocket>, TwoPartCodec>,
+        writer: FramedWrite<tokio::io::WriteHalf<BoxedSocket>, TwoPartCodec>,
     ) -> Result<()> {
-        let response_stream = state
-            .lock().await
-            .rx_subjects
-            .remove(&subject)
-            .ok_or(error!("Subject not found: {}; upstream publisher specified a subject unknown to the downsteam subscriber", subject))?;
+        let subject = handshake.subject;
+        let response_stream = {
+            let mut state = state.lock().await;
+            let requested = state
+                .rx_subjects
+                .get(&subject)
+                .ok_or(error!("Subject not found: {}; upstream publisher specified a subject unknown to the downsteam subscriber", subject))?;
+
+            // workers from before the one-time tokens send none, they are only paired when the
+            // server was told to accept them
+            let tokenless = handshake.token.is_empty() && state.accept_tokenless;
+            if tokenless {
+                tracing::warn!(
+                    "Accepting a response stream for subject {} without a token",
+                    subject
+                );
+            }
+
+            // a wrong token leaves the registration in place for the client it was issued to
+            if !tokenless && !tokens_match(&requested.token, &handshake.token) {
+                return Err(error!(
+                    "Invalid token for subject {}; rejecting the connection",
+                    subject
+                ));
+            }
+
+            state
+                .rx_subjects
+                .remove(&subject)
+                .expect("subject is registered")
+        };
 
         // unwrap response_stream
         let RequestedRecvConnection {
             context,
             connection,
+            ..
         } = response_stream;
 
         // the [`Prologue`]
@@ -458,6 +557,7 @@ async fn tcp_listener(
         if connection
             .send(Ok(crate::pipeline::network::StreamReceiver {
                 rx: response_rx,
+                encoding: prologue.encoding,
             }))
             .is_err()
         {
@@ -489,7 +589,7 @@ async fn tcp_listener(
     }
 
     async fn network_receive_handler(
-        mut framed_reader: FramedRead<tokio::io::ReadHalf<tokio::net::TcpStream>, TwoPartCodec>,
+        mut framed_reader: FramedRead<tokio::io::ReadHalf<BoxedSocket>, TwoPartCodec>,
         response_tx: mpsc::Sender<Bytes>,
         control_tx: mpsc::Sender<ControlMessage>,
         context: Arc<dyn AsyncEngineContext>,
@@ -568,7 +668,7 @@ async fn tcp_listener(
     }
 
     async fn network_send_handler(
-        socket_tx: FramedWrite<tokio::io::WriteHalf<tokio::net::TcpStream>, TwoPartCodec>,
+        socket_tx: FramedWrite<tokio::io::WriteHalf<BoxedSocket>, TwoPartCodec>,
         control_rx: mpsc::Receiver<ControlMessage>,
     ) {
         let mut socket_tx = socket_tx;
@@ -0,0 +1,217 @@
+// SPDX-FileCopyrightText: Copyright (c) 2024-2025 NVIDIA CORPORATION & AFFILIATES. All rights reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+//! TLS for the response streams.
+//!
+//! The [`super::server::TcpStreamServer`] of the requester presents a certificate; workers
+//! calling home verify it against a CA before sending the handshake, so neither the one-time
+//! token nor the responses are visible on the network. Certificates and keys are PEM files and
+//! are loaded once, when the server or the first TLS client starts.
+
+use std::{
+    fs::File,
+    io::BufReader,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use anyhow::Context as _;
+use once_cell::sync::OnceCell;
+use serde::{Deserialize, Serialize};
+use tokio::net::TcpStream;
+use tokio_rustls::{
+    client::TlsStream,
+    rustls::{
+        crypto::ring,
+        pki_types::{CertificateDer, PrivateKeyDer, ServerName},
+        ClientConfig, RootCertStore, ServerConfig,
+    },
+    TlsAcceptor, TlsConnector,
+};
+
+use crate::{config::RuntimeConfig, Result};
+
+/// Certificate the [`super::server::TcpStreamServer`] presents to workers calling home.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct TlsOptions {
+    /// PEM certificate chain, the server certificate first
+    pub cert_path: PathBuf,
+
+    /// PEM private key of the server certificate
+    pub key_path: PathBuf,
+}
+
+impl TlsOptions {
+    pub fn new(cert_path: PathBuf, key_path: PathBuf) -> Self {
+        Self {
+            cert_path,
+            key_path,
+        }
+    }
+
+    /// Load the files into an acceptor for incoming connections.
+    pub fn acceptor(&self) -> Result<TlsAcceptor> {
+        let certs = load_certs(&self.cert_path)?;
+        let key = load_key(&self.key_path)?;
+
+        let config = ServerConfig::builder_with_provider(Arc::new(ring::default_provider()))
+            .with_safe_default_protocol_versions()?
+            .with_no_client_auth()
+            .with_single_cert(certs, key)
+            .with_context(|| {
+                format!(
+                    "Invalid certificate {:?} or key {:?}",
+                    self.cert_path, self.key_path
+                )
+            })?;
+        Ok(TlsAcceptor::from(Arc::new(config)))
+    }
+}
+
+/// How workers verify the certificate of the servers they call home to.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct TlsClientOptions {
+    /// PEM certificates of the CAs server certificates must be signed by
+    pub ca_path: PathBuf,
+
+    /// Name the server certificates must be valid for. `None` uses the IP address the server is
+    /// reached at.
+    pub server_name: Option<String>,
+}
+
+impl TlsClientOptions {
+    pub fn new(ca_path: PathBuf) -> Self {
+        Self {
+            ca_path,
+            server_name: None,
+        }
+    }
+
+    pub fn with_server_name(mut self, server_name: Option<String>) -> Self {
+        self.server_name = server_name;
+        self
+    }
+}
+
+/// Opens TLS connections to response stream servers.
+#[derive(Clone)]
+pub struct TlsClient {
+    connector: TlsConnector,
+    server_name: Option<ServerName<'static>>,
+}
+
+impl TlsClient {
+    pub fn new(options: &TlsClientOptions) -> Result<Self> {
+        let mut roots = RootCertStore::empty();
+        for cert in load_certs(&options.ca_path)? {
+            roots
+                .add(cert)
+                .with_context(|| format!("Invalid CA certificate in {:?}", options.ca_path))?;
+        }
+
+        let config = ClientConfig::builder_with_provider(Arc::new(ring::default_provider()))
+            .with_safe_default_protocol_versions()?
+            .with_root_certificates(roots)
+            .with_no_client_auth();
+
+        let server_name = options
+            .server_name
+            .clone()
+            .map(ServerName::try_from)
+            .transpose()
+            .context("Invalid TLS server name")?;
+
+        Ok(Self {
+            connector: TlsConnector::from(Arc::new(config)),
+            server_name,
+        })
+    }
+
+    /// The client configured by [`RuntimeConfig`], loaded on first use and shared by the process.
+    pub fn from_settings() -> Result<&'static Self> {
+        static CLIENT: OnceCell<TlsClient> = OnceCell::new();
+        CLIENT.get_or_try_init(|| {
+            let options = RuntimeConfig::from_settings()?
+                .tcp_tls_client()
+                .context("The response stream requires TLS; set DYN_RUNTIME_TCP_TLS_CA to the CA of the server certificate")?;
+            TlsClient::new(&options)
+        })
+    }
+
+    /// Complete the TLS handshake on `stream`, which is connected to `address`.
+    pub async fn connect(&self, address: &str, stream: TcpStream) -> Result<TlsStream<TcpStream>> {
+        let server_name = match &self.server_name {
+            Some(server_name) => server_name.clone(),
+            None => address_host(address)?,
+        };
+        Ok(self.connector.connect(server_name, stream).await?)
+    }
+}
+
+/// The host of an `ip:port` or `[ipv6]:port` address.
+fn address_host(address: &str) -> Result<ServerName<'static>> {
+    let (host, _port) = address
+        .rsplit_once(':')
+        .with_context(|| format!("Invalid address {address}"))?;
+    let host = host.trim_start_matches('[').trim_end_matches(']');
+    ServerName::try_from(host.to_string()).with_context(|| format!("Invalid address {address}"))
+}
+
+/// Loads the PEM certificates in `path`, failing if there are none.
+pub fn load_certs(path: &Path) -> Result<Vec<CertificateDer<'static>>> {
+    let file = File::open(path).with_context(|| format!("Failed opening {path:?}"))?;
+    let certs = rustls_pemfile::certs(&mut BufReader::new(file))
+        .collect::<Result<Vec<_>, _>>()
+        .with_context(|| format!("Failed parsing certificates in {path:?}"))?;
+    if certs.is_empty() {
+        anyhow::bail!("No certificates found in {path:?}");
+    }
+    Ok(certs)
+}
+
+/// Loads the first PEM private key in `path`.
+pub fn load_key(path: &Path) -> Result<PrivateKeyDer<'static>> {
+    let file = File::open(path).with_context(|| format!("Failed opening {path:?}"))?;
+    rustls_pemfile::private_key(&mut BufReader::new(file))
+        .with_context(|| format!("Failed parsing private key in {path:?}"))?
+        .with_context(|| format!("No private key found in {path:?}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn data(file: &str) -> PathBuf {
+        PathBuf::from(env!("CARGO_MANIFEST_DIR")).join(format!("tests/data/tls/{file}"))
+    }
+
+    #[test]
+    fn test_address_host() {
+        assert_eq!(
+            address_host("10.0.0.1:4000").unwrap(),
+            ServerName::try_from("10.0.0.1").unwrap()
+        );
+        assert_eq!(
+            address_host("[::1]:4000").unwrap(),
+            ServerName::try_from("::1").unwrap()
+        );
+        assert!(address_host("10.0.0.1").is_err());
+    }
+
+    #[test]
+    fn test_load_errors() {
+        TlsOptions::new(data("server.pem"), data("server.key"))
+            .acceptor()
+            .unwrap();
+        TlsClient::new(&TlsClientOptions::new(data("ca.pem"))).unwrap();
+
+        // a key is not a certificate, and the other way around
+        assert!(TlsOptions::new(data("server.key"), data("server.key"))
+            .acceptor()
+            .is_err());
+        assert!(TlsOptions::new(data("server.pem"), data("server.pem"))
+            .acceptor()
+            .is_err());
+        assert!(TlsClient::new(&TlsClientOptions::new(data("missing.pem"))).is_err());
+    }
+}
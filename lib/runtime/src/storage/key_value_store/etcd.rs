@@ -98,9 +98,12 @@ impl KeyValueBucket for EtcdBucket {
     }
 
     async fn delete(&self, key: &str) -> Result<(), StorageError> {
+        let k = make_key(&self.bucket_name, key);
+        tracing::trace!("etcd delete: {k}");
+
         let _ = self
             .client
-            .kv_delete(key, None)
+            .kv_delete(k, None)
             .await
             .map_err(|e| StorageError::EtcdError(e.to_string()))?;
         Ok(())
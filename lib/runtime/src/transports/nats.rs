@@ -245,6 +245,17 @@ impl ClientOptions {
 
     /// Validate the config and attempt to connection to the NATS server
     pub async fn connect(self) -> Result<Client> {
+        self.connect_with(false).await
+    }
+
+    /// Validate the config and return a client which connects to the NATS server in the
+    /// background, so processes which can run without NATS start when no server is up.
+    /// Operations wait until the connection is established.
+    pub async fn connect_in_background(self) -> Result<Client> {
+        self.connect_with(true).await
+    }
+
+    async fn connect_with(self, in_background: bool) -> Result<Client> {
         self.validate()?;
 
         let client = match self.auth {
@@ -258,6 +269,11 @@ impl ClientOptions {
             }
         };
 
+        let client = if in_background {
+            client.retry_on_initial_connect()
+        } else {
+            client
+        };
         let client = client.connect(self.server).await?;
         let js_ctx = jetstream::new(client.clone());
 
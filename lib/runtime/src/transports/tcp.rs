@@ -13,4 +13,4 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-pub use crate::pipeline::network::tcp::{client, server};
+pub use crate::pipeline::network::tcp::{client, request_plane, server, tls};
@@ -101,9 +101,13 @@ impl Worker {
         Fut: Future<Output = Result<()>> + Send + 'static,
     {
         let runtime = self.runtime.clone();
-        runtime.secondary().block_on(self.execute_internal(f))??;
-        runtime.shutdown();
-        Ok(())
+        let result = runtime.secondary().block_on(self.execute_internal(f));
+        if matches!(result, Ok(Ok(()))) {
+            runtime.shutdown();
+        }
+        // flush the exported spans of failed applications too
+        crate::logging::shutdown();
+        result?
     }
 
     pub async fn execute_async<F, Fut>(self, f: F) -> Result<()>
@@ -113,9 +117,12 @@ impl Worker {
     {
         let runtime = self.runtime.clone();
         let task = self.execute_internal(f);
-        task.await??;
-        runtime.shutdown();
-        Ok(())
+        let result = task.await;
+        if matches!(result, Ok(Ok(()))) {
+            runtime.shutdown();
+        }
+        crate::logging::shutdown();
+        result?
     }
 
     /// Executes the provided application/closure on the [`Runtime`].
@@ -0,0 +1,102 @@
+// SPDX-FileCopyrightText: Copyright (c) 2024-2025 NVIDIA CORPORATION & AFFILIATES. All rights reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+//! Throughput of the payload encodings on a stream of token-sized response items.
+//!
+//! A benchmark rather than a check, so it is ignored by default. Run it with
+//! `cargo test --release --test payload_encoding -- --ignored --nocapture` to see the numbers.
+
+use std::time::{Duration, Instant};
+
+use dynamo_runtime::{
+    pipeline::network::encoding::PayloadEncoding, protocols::annotated::Annotated,
+};
+use serde::{Deserialize, Serialize};
+
+const ITEMS: usize = 20_000;
+
+/// Shaped like the output a backend streams for every generated token
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct TokenOutput {
+    token_ids: Vec<u32>,
+    tokens: Option<Vec<Option<String>>>,
+    text: Option<String>,
+    cum_log_probs: Option<f64>,
+    log_probs: Option<Vec<f64>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    finish_reason: Option<String>,
+}
+
+fn items() -> Vec<Annotated<TokenOutput>> {
+    (0..ITEMS)
+        .map(|i| {
+            let mut item = Annotated::from_data(TokenOutput {
+                token_ids: vec![i as u32 % 32_000],
+                tokens: None,
+                text: Some(" token".to_string()),
+                cum_log_probs: Some(-(i as f64) / 100.0),
+                log_probs: Some(vec![-0.25]),
+                finish_reason: None,
+            });
+            item.id = Some("chatcmpl-3f2a5b9c".to_string());
+            item
+        })
+        .collect()
+}
+
+struct Measurement {
+    bytes: usize,
+    encode: Duration,
+    decode: Duration,
+}
+
+fn measure(encoding: PayloadEncoding, items: &[Annotated<TokenOutput>]) -> Measurement {
+    let start = Instant::now();
+    let frames: Vec<Vec<u8>> = items
+        .iter()
+        .map(|item| encoding.encode(item).unwrap())
+        .collect();
+    let encode = start.elapsed();
+
+    let start = Instant::now();
+    let decoded: Vec<Annotated<TokenOutput>> = frames
+        .iter()
+        .map(|frame| encoding.decode(frame).unwrap())
+        .collect();
+    let decode = start.elapsed();
+
+    assert_eq!(decoded.len(), items.len());
+    assert_eq!(decoded[ITEMS - 1].data, items[ITEMS - 1].data);
+    assert_eq!(decoded[ITEMS - 1].id, items[ITEMS - 1].id);
+
+    Measurement {
+        bytes: frames.iter().map(Vec::len).sum(),
+        encode,
+        decode,
+    }
+}
+
+fn per_sec(duration: Duration) -> f64 {
+    ITEMS as f64 / duration.as_secs_f64().max(f64::EPSILON)
+}
+
+#[test]
+#[ignore]
+fn payload_encoding_throughput() {
+    let items = items();
+
+    let json = measure(PayloadEncoding::Json, &items);
+    let msgpack = measure(PayloadEncoding::Msgpack, &items);
+
+    for (name, m) in [("json", &json), ("msgpack", &msgpack)] {
+        println!(
+            "{name:>8}: {:>6} bytes/item, encode {:>10.0} items/s, decode {:>10.0} items/s",
+            m.bytes / ITEMS,
+            per_sec(m.encode),
+            per_sec(m.decode),
+        );
+    }
+
+    // timings depend on the machine and build profile, the size of the frames does not
+    assert!(msgpack.bytes < json.bytes);
+}
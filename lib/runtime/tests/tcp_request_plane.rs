@@ -0,0 +1,91 @@
+// SPDX-FileCopyrightText: Copyright (c) 2024-2025 NVIDIA CORPORATION & AFFILIATES. All rights reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+//! Serves an endpoint and calls it over the TCP request plane, with no NATS server to fall back
+//! on. Needs etcd for discovery.
+
+#[cfg(feature = "integration")]
+mod integration {
+    use dynamo_runtime::{
+        distributed::DistributedConfig,
+        pipeline::{
+            async_trait,
+            network::{request_plane::RequestPlaneMode, Ingress},
+            AsyncEngine, AsyncEngineContextProvider, Error, ManyOut, PushRouter, ResponseStream,
+            SingleIn,
+        },
+        protocols::annotated::Annotated,
+        transports::{etcd, nats},
+        DistributedRuntime, Result, Runtime,
+    };
+    use futures::StreamExt;
+    use std::{sync::Arc, time::Duration};
+
+    const NAMESPACE: &str = "test-tcp-request-plane";
+
+    struct Echo;
+
+    #[async_trait]
+    impl AsyncEngine<SingleIn<String>, ManyOut<Annotated<String>>, Error> for Echo {
+        async fn generate(&self, input: SingleIn<String>) -> Result<ManyOut<Annotated<String>>> {
+            let (data, ctx) = input.into_parts();
+            let chars = data
+                .chars()
+                .map(|c| Annotated::from_data(c.to_string()))
+                .collect::<Vec<_>>();
+            let stream = futures::stream::iter(chars);
+            Ok(ResponseStream::new(Box::pin(stream), ctx.context()))
+        }
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_tcp_request_plane_without_nats() -> Result<()> {
+        let runtime = Runtime::from_current()?;
+        let config = DistributedConfig {
+            etcd_config: etcd::ClientOptions::default(),
+            // nothing listens here, the TCP request plane must not need it
+            nats_config: nats::ClientOptions::builder()
+                .server("nats://127.0.0.1:1")
+                .build()?,
+            is_static: false,
+            request_plane: RequestPlaneMode::Tcp,
+        };
+        let distributed = DistributedRuntime::new(runtime, config).await?;
+        let component = distributed.namespace(NAMESPACE)?.component("backend")?;
+
+        let service = component.service_builder().create().await?;
+        let server = tokio::spawn(
+            service
+                .endpoint("generate")
+                .endpoint_builder()
+                .handler(Ingress::for_engine(Arc::new(Echo))?)
+                .start(),
+        );
+
+        let client = component.endpoint("generate").client().await?;
+        tokio::time::timeout(Duration::from_secs(10), client.wait_for_instances()).await??;
+        let router =
+            PushRouter::<String, Annotated<String>>::from_client(client, Default::default())
+                .await?;
+
+        for _ in 0..3 {
+            let stream = tokio::time::timeout(
+                Duration::from_secs(10),
+                router.round_robin("hello".to_string().into()),
+            )
+            .await??;
+            let responses = tokio::time::timeout(
+                Duration::from_secs(10),
+                stream
+                    .filter_map(|resp| async move { resp.data })
+                    .collect::<Vec<_>>(),
+            )
+            .await?;
+            assert_eq!(responses.concat(), "hello");
+        }
+
+        distributed.shutdown();
+        server.await??;
+        Ok(())
+    }
+}